@@ -0,0 +1,203 @@
+// ─────────────────────────────────────────────────────────────────
+//  alerts.rs — Email alerts for critical events
+//
+//  For schools without chat-webhook infrastructure. Three events are
+//  mailed to `[alerts.smtp]`: tamper findings, an agent that keeps
+//  crashing (see `tamper::CRASH_LOOP`), and the same high-severity
+//  violation repeating within a window. Every alert is rate limited
+//  by a Redis key shared with the watchdog (falling back to memory
+//  while Redis is down) plus an hourly cap.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, Utc};
+use tracing::{info, warn};
+
+use crate::config::{AlertsConfig, SmtpConfig};
+use crate::mail;
+use crate::models::{Violation, ViolationKind};
+use crate::store::Store;
+use crate::tamper;
+
+/// Don't hold up shutdown / the watchdog on an unreachable mail server.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AlertEvent {
+    Tamper,
+    CrashLoop,
+    RepeatedViolations,
+}
+
+impl AlertEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            AlertEvent::Tamper             => "tamper",
+            AlertEvent::CrashLoop          => "crash_loop",
+            AlertEvent::RepeatedViolations => "repeated_violations",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            AlertEvent::Tamper             => "Вмешательство в работу агента",
+            AlertEvent::CrashLoop          => "Агент постоянно перезапускается",
+            AlertEvent::RepeatedViolations => "Повторные серьёзные нарушения",
+        }
+    }
+}
+
+/// An alert that passed detection and is waiting to be sent.
+#[derive(Debug, Clone)]
+pub struct Alert {
+    event: AlertEvent,
+    /// Rate-limit key: event plus what it's about.
+    key: String,
+    username: String,
+    detail: String,
+    at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// High-severity violations per target within the repeat window.
+    high: HashMap<String, VecDeque<Instant>>,
+    /// Last send per alert key.
+    sent: HashMap<String, Instant>,
+    /// All sends during the last hour.
+    sent_hour: VecDeque<Instant>,
+    /// Alerts dropped by rate limiting since the last one that went out.
+    suppressed: usize,
+}
+
+#[derive(Clone)]
+pub struct Alerter {
+    cfg: Arc<AlertsConfig>,
+    smtp: Arc<SmtpConfig>,
+    hostname: String,
+    store: Store,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Alerter {
+    /// None unless alerts are enabled and SMTP is configured.
+    pub fn new(cfg: &AlertsConfig, hostname: String, store: Store) -> Option<Self> {
+        if !cfg.enabled {
+            return None;
+        }
+        let Some(smtp) = cfg.smtp.clone() else {
+            warn!("Email alerts are enabled but [alerts.smtp] is missing — alerts disabled");
+            return None;
+        };
+        Some(Self {
+            cfg: Arc::new(cfg.clone()),
+            smtp: Arc::new(smtp),
+            hostname,
+            store,
+            inner: Arc::new(Mutex::new(Inner::default())),
+        })
+    }
+
+    /// The alert `v` triggers, if any. Cheap; call for every violation.
+    pub fn observe(&self, v: &Violation) -> Option<Alert> {
+        let detail = v.detail.clone().unwrap_or_else(|| v.target.clone());
+        let (event, key, detail) = if v.kind == ViolationKind::Tamper {
+            let event = if detail.starts_with(tamper::CRASH_LOOP) {
+                AlertEvent::CrashLoop
+            } else {
+                AlertEvent::Tamper
+            };
+            (event, event.name().to_string(), detail)
+        } else if v.kind.severity() == "high" {
+            let count = self.repeated(&v.target)?;
+            let event = AlertEvent::RepeatedViolations;
+            let detail = format!(
+                "{}: {} ({count} раз за {} мин). Последнее: {detail}",
+                v.kind.label(),
+                v.target,
+                self.cfg.repeat_window_secs / 60
+            );
+            (event, format!("{}:{}", event.name(), v.target), detail)
+        } else {
+            return None;
+        };
+        Some(Alert { event, key, username: v.username.clone(), detail, at: v.timestamp })
+    }
+
+    /// Count a high-severity violation of `target`; Some(count) once it
+    /// reaches the threshold within the window (the count then restarts).
+    fn repeated(&self, target: &str) -> Option<usize> {
+        let window = Duration::from_secs(self.cfg.repeat_window_secs);
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let seen = inner.high.entry(target.to_string()).or_default();
+        seen.retain(|t| t.elapsed() < window);
+        seen.push_back(Instant::now());
+        if seen.len() < self.cfg.repeat_threshold.max(1) {
+            return None;
+        }
+        let count = seen.len();
+        seen.clear();
+        Some(count)
+    }
+
+    /// Send `alert` unless rate limited. Failures are logged.
+    pub async fn send(self, alert: Alert) {
+        if !self.allowed(&alert).await {
+            info!("📧 Alert {} rate limited", alert.key);
+            self.inner.lock().unwrap_or_else(|e| e.into_inner()).suppressed += 1;
+            return;
+        }
+        let suppressed = std::mem::take(&mut self.inner.lock().unwrap_or_else(|e| e.into_inner()).suppressed);
+
+        let time = alert.at.with_timezone(&Local).format("%d.%m.%Y %H:%M:%S").to_string();
+        let fill = |template: &str| {
+            template
+                .replace("{event}", alert.event.label())
+                .replace("{hostname}", &self.hostname)
+                .replace("{username}", &alert.username)
+                .replace("{detail}", &alert.detail)
+                .replace("{time}", &time)
+        };
+        let subject = fill(&self.cfg.subject);
+        let mut body = fill(&self.cfg.body);
+        if suppressed > 0 {
+            body.push_str(&format!("\n(ещё {suppressed} оповещений подавлено ограничением частоты)\n"));
+        }
+
+        match tokio::time::timeout(SEND_TIMEOUT, mail::send(&self.smtp, &subject, mail::Body::Text(body))).await {
+            Ok(Ok(())) => info!("📧 Alert sent: {subject}"),
+            Ok(Err(e)) => warn!("Failed to send alert email: {e}"),
+            Err(_) => warn!("Failed to send alert email: SMTP server timed out"),
+        }
+    }
+
+    /// Per-key interval (in memory, and through Redis when reachable so
+    /// the watchdog and restarted agents share it) and hourly cap.
+    async fn allowed(&self, alert: &Alert) -> bool {
+        let interval = Duration::from_secs(self.cfg.min_interval_secs);
+        {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            inner.sent_hour.retain(|t| t.elapsed() < Duration::from_secs(3600));
+            if inner.sent_hour.len() >= self.cfg.max_per_hour {
+                return false;
+            }
+        }
+        let recent = |inner: &Inner| inner.sent.get(&alert.key).is_some_and(|t| t.elapsed() < interval);
+        if recent(&self.inner.lock().unwrap_or_else(|e| e.into_inner())) {
+            return false;
+        }
+        let claimed = self.store.claim_alert(&self.hostname, &alert.key, self.cfg.min_interval_secs).await;
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        // Re-check: another send may have passed while we waited on Redis
+        let allowed = claimed.unwrap_or(true) && !recent(&inner);
+        if allowed {
+            inner.sent.insert(alert.key.clone(), Instant::now());
+            inner.sent_hour.push_back(Instant::now());
+        }
+        allowed
+    }
+}
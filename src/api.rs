@@ -1,32 +1,39 @@
-use std::sync::Arc;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use axum::{
-    extract::{Query, State},
-    response::IntoResponse,
+    extract::{ws::WebSocketUpgrade, ConnectInfo, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sysinfo::System;
 use tower_http::cors::CorsLayer;
 
-use crate::config::AppConfig;
-use crate::models::{HealthResponse, SystemSnapshot, ViolationsResponse};
+use crate::audio;
+use crate::audit::Audit;
+use crate::config::{AppConfig, FocusAction};
+use crate::desktop::Desktop;
+use crate::diagnostics::{self, Diagnostics};
+use crate::doctor::ToolMatrix;
+use crate::exam::ExamMode;
+use crate::focus_mode::FocusMode;
+use crate::models::{
+    Capabilities, HealthResponse, SystemHistoryResponse, SystemSnapshot, TimelineResponse, ViolationsResponse,
+};
+use crate::monitor::{silent_cmd, Monitor};
+use crate::report;
+use crate::self_view;
+use crate::selfstat::SelfMonitor;
+use crate::shell;
+use crate::softlock::SoftLock;
 use crate::store::Store;
-
-/// Create a `Command` that will NOT pop up a console window on Windows.
-#[cfg(target_os = "windows")]
-fn silent_cmd(program: &str) -> std::process::Command {
-    use std::os::windows::process::CommandExt;
-    let mut cmd = std::process::Command::new(program);
-    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
-    cmd
-}
-
-#[cfg(not(target_os = "windows"))]
-fn silent_cmd(program: &str) -> std::process::Command {
-    std::process::Command::new(program)
-}
+use crate::unlock::{Redeemed, UnlockCodes};
 
 // ── Shared state ────────────────────────────────────────────────
 
@@ -37,35 +44,111 @@ pub struct AppState {
     pub hostname: String,
     pub ip: String,
     pub start_time: std::time::Instant,
+    pub self_monitor: Arc<SelfMonitor>,
+    pub monitor: Arc<Mutex<Monitor>>,
+    pub audit: Audit,
+    pub exam: Arc<ExamMode>,
+    pub desktop: Arc<Desktop>,
+    pub soft_lock: Arc<SoftLock>,
+    pub focus_mode: Arc<FocusMode>,
+    pub capabilities: Arc<Capabilities>,
+    /// OS tools found at startup (see `doctor.rs`).
+    pub tools: Arc<ToolMatrix>,
+    pub diagnostics: Arc<Diagnostics>,
+    /// None unless `[lock.unlock_codes]` is enabled.
+    pub unlock_codes: Option<Arc<UnlockCodes>>,
+    #[cfg(feature = "screenshots")]
+    pub screenshot_sink: Arc<crate::screenshot_sink::ScreenshotSink>,
 }
 
 // ── Router ──────────────────────────────────────────────────────
 
 pub fn build_router(state: AppState) -> Router {
+    let state = Arc::new(state);
     Router::new()
         .route("/health", get(health))
+        .route("/metrics", get(metrics))
         .route("/info", get(system_info))
+        .route("/info/history", get(system_history))
+        .route("/capabilities", get(capabilities))
+        .route("/doctor", get(doctor))
         .route("/violations", get(violations))
+        .route("/timeline", get(timeline))
+        .route("/me", get(self_view_page))
         .route("/config", get(show_config))
         .route("/screenshot", get(get_screenshot))
+        .route("/screenshot/history", get(screenshot_history))
+        .route("/snapshot", post(snapshot_handler))
         .route("/apps", get(list_apps))
+        .route("/room", get(room_overview))
+        .route("/lock", get(lock_status))
         .route("/lock/:mode", post(lock_handler))
+        .route("/unlock/code", post(unlock_code_handler))
+        .route("/focus", get(focus_status))
         .route("/open-url", post(open_url_handler))
+        .route("/message", post(message_handler))
+        .route("/capture", post(capture_handler))
+        .route("/exam", get(exam_status))
+        .route("/exam/start", post(exam_start))
+        .route("/audio/mute", post(audio_mute))
+        .route("/audio/unmute", post(audio_unmute))
+        .route("/audio/volume", post(audio_volume))
+        .route("/wallpaper", post(wallpaper_set))
+        .route("/wallpaper/restore", post(wallpaper_restore))
+        .route("/lock-message", post(lock_message_set))
+        .route("/lock-message/clear", post(lock_message_clear))
+        .merge(admin_router(&state))
         .layer(CorsLayer::permissive())
-        .with_state(Arc::new(state))
+        .with_state(state)
+}
+
+/// Control endpoints behind the admin token (see `admin_only`).
+fn admin_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/unlock-code", get(unlock_code_issue))
+        .route("/diagnostics/bundle", get(diagnostics_download).post(diagnostics_bundle))
+        .route("/report/weekly", post(report_weekly))
+        .route("/ws/shell", get(ws_shell))
+        .route("/unlock", post(unlock_handler))
+        .route("/exam/stop", post(exam_stop))
+        .route("/focus", post(focus_start))
+        .route("/focus/stop", post(focus_stop))
+        .route("/kill", post(kill_handler))
+        .route_layer(middleware::from_fn_with_state(Arc::clone(state), admin_only))
 }
 
 // ── Handlers ────────────────────────────────────────────────────
 
+/// GET /capabilities — what this agent supports and has switched on
+/// (see `capabilities.rs`)
+async fn capabilities(State(s): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(Capabilities::clone(&s.capabilities))
+}
+
+/// GET /doctor — the OS tools each feature shells out to, which were
+/// found and what is unavailable without them (see `doctor.rs`)
+async fn doctor(State(s): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(ToolMatrix::clone(&s.tools))
+}
+
 async fn health(State(s): State<Arc<AppState>>) -> impl IntoResponse {
     Json(HealthResponse {
         status: "ok",
         version: env!("CARGO_PKG_VERSION"),
         hostname: s.hostname.clone(),
         uptime_secs: s.start_time.elapsed().as_secs(),
+        redis_circuit_open: s.store.circuit_open(),
+        redis_server: s.store.redis_server(),
+        resources: s.self_monitor.latest(),
     })
 }
 
+/// GET /metrics — Redis operation counters and latencies in the
+/// Prometheus text format (see `store_metrics.rs`)
+async fn metrics(State(s): State<Arc<AppState>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], s.store.metrics_text())
+}
+
 async fn system_info(State(s): State<Arc<AppState>>) -> impl IntoResponse {
     // Build a quick snapshot — runs in a blocking task because sysinfo
     // does synchronous work.
@@ -116,6 +199,75 @@ async fn violations(
     })
 }
 
+#[derive(Deserialize)]
+struct TimelineQuery {
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// GET /timeline?from=&to=   RFC 3339 bounds; defaults to the last 24 hours
+async fn timeline(
+    State(s): State<Arc<AppState>>,
+    Query(q): Query<TimelineQuery>,
+) -> impl IntoResponse {
+    let to = q.to.unwrap_or_else(chrono::Utc::now);
+    let from = q.from.unwrap_or(to - chrono::Duration::hours(24));
+    let events = s.store.timeline(&s.hostname, from, to).await;
+    Json(TimelineResponse { from, to, total: events.len(), events })
+}
+
+/// GET /info/history?from=&to=   CPU / RAM samples (see `timeseries.rs`),
+/// oldest first; defaults to the last hour
+async fn system_history(
+    State(s): State<Arc<AppState>>,
+    Query(q): Query<TimelineQuery>,
+) -> impl IntoResponse {
+    let to = q.to.unwrap_or_else(chrono::Utc::now);
+    let from = q.from.unwrap_or(to - chrono::Duration::hours(1));
+    let samples = s.store.timeseries(&s.hostname, from, to).await;
+    Json(SystemHistoryResponse { from, to, total: samples.len(), samples })
+}
+
+/// GET /me — read-only page for the student at this machine: what is
+/// monitored, what was done here lately and how long it is kept
+/// (see `self_view.rs`)
+async fn self_view_page(State(s): State<Arc<AppState>>) -> Response {
+    if !s.config.self_view.enabled {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let to = chrono::Utc::now();
+    let from = to - chrono::Duration::days(s.config.self_view.days);
+    let ctx = self_view::Context {
+        hostname: s.hostname.clone(),
+        capabilities: Capabilities::clone(&s.capabilities),
+        exam: s.exam.is_active(),
+        soft_lock: s.soft_lock.is_active(),
+        focus_app: s.focus_mode.app(),
+        events: s.store.timeline(&s.hostname, from, to).await,
+    };
+    Html(self_view::html(&s.config, &ctx)).into_response()
+}
+
+/// POST /report/weekly   generate the weekly report now (stored in Redis,
+/// emailed when SMTP is configured) and return it; admin only
+async fn report_weekly(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let report = report::weekly(&s.store, &s.hostname, s.config.redis.heartbeat_interval).await;
+    let emailed = report::deliver(&s.config.report, &s.store, &report).await;
+    let detail = format!("{} student(s), emailed: {}", report.students.len(), matches!(emailed, Ok(true)));
+    s.audit.record("report_generated", &addr.to_string(), Some(detail)).await;
+    match emailed {
+        Ok(emailed) => Json(serde_json::json!({ "status": "ok", "emailed": emailed, "report": report })),
+        Err(e) => Json(serde_json::json!({
+            "status": "error",
+            "error": format!("report stored but email failed: {e}"),
+            "report": report,
+        })),
+    }
+}
+
 async fn show_config(State(s): State<Arc<AppState>>) -> impl IntoResponse {
     // Return the ban lists only — not secrets
     let cfg = &s.config.monitor;
@@ -126,19 +278,159 @@ async fn show_config(State(s): State<Arc<AppState>>) -> impl IntoResponse {
     }))
 }
 
-async fn get_screenshot(State(s): State<Arc<AppState>>) -> impl IntoResponse {
-    match s.store.latest_screenshot(&s.hostname).await {
-        Some(data) => Json(serde_json::json!({
-            "success": true,
-            "screenshot": data,
-        })),
-        None => Json(serde_json::json!({
-            "success": false,
-            "error": "No screenshot available",
-        })),
+#[derive(Deserialize)]
+struct ScreenshotQuery {
+    token: Option<String>,
+    /// "json" for the old `{ success, screenshot }` form with base64 data.
+    format: Option<String>,
+}
+
+/// GET /screenshot — latest screenshot as `image/jpeg`, its timestamp in
+/// `X-Screenshot-Timestamp` (a redirect to the presigned URL with the S3
+/// sink); `?format=json` or `Accept: application/json` for the JSON form
+/// (audited as `screenshot_viewed`)
+async fn get_screenshot(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(q): Query<ScreenshotQuery>,
+) -> Response {
+    let shot = s.store.latest_screenshot(&s.hostname).await;
+    #[cfg(feature = "screenshots")]
+    let shot = match shot {
+        Some(mut shot) if shot.jpeg.is_none() => {
+            let sink = Arc::clone(&s.screenshot_sink);
+            tokio::task::spawn_blocking(move || {
+                sink.refresh_url(&mut shot.meta);
+                shot.jpeg = sink.image(&mut shot.meta);
+                shot
+            })
+            .await
+            .ok()
+        }
+        Some(mut shot) => {
+            shot.jpeg = shot.jpeg.take().and_then(|jpeg| s.screenshot_sink.unseal(&mut shot.meta, jpeg));
+            Some(shot)
+        }
+        None => None,
+    };
+    let detail = format!("{}, found: {}", token_identity(&s, &headers, q.token.as_deref()), shot.is_some());
+    s.audit.record("screenshot_viewed", &addr.to_string(), Some(detail)).await;
+
+    let wants_json = q.format.as_deref() == Some("json")
+        || headers
+            .get(header::ACCEPT)
+            .and_then(|a| a.to_str().ok())
+            .is_some_and(|a| a.contains("application/json"));
+    let Some(shot) = shot else {
+        let error = Json(serde_json::json!({ "success": false, "error": "No screenshot available" }));
+        return if wants_json { error.into_response() } else { (StatusCode::NOT_FOUND, error).into_response() };
+    };
+    if wants_json {
+        return Json(serde_json::json!({ "success": true, "screenshot": shot.to_json().to_string() })).into_response();
+    }
+
+    let timestamp = shot.meta.get("timestamp").and_then(|t| t.as_str()).unwrap_or_default().to_string();
+    if let Some(jpeg) = shot.jpeg {
+        let headers = [
+            (header::CONTENT_TYPE, "image/jpeg".to_string()),
+            (header::HeaderName::from_static("x-screenshot-timestamp"), timestamp),
+        ];
+        return (headers, jpeg).into_response();
+    }
+    match shot.meta.get("url").and_then(|u| u.as_str()) {
+        Some(url) => (StatusCode::FOUND, [(header::LOCATION, url.to_string())]).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "success": false, "error": "Screenshot image unavailable" })))
+            .into_response(),
     }
 }
 
+/// GET /screenshot/history — the stored screenshots, newest first
+/// (audited as `screenshot_history_viewed`)
+async fn screenshot_history(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(q): Query<TokenQuery>,
+) -> impl IntoResponse {
+    let shots = s.store.screenshot_history(&s.hostname).await;
+    #[cfg(feature = "screenshots")]
+    let shots: Vec<serde_json::Value> = {
+        let sink = Arc::clone(&s.screenshot_sink);
+        tokio::task::spawn_blocking(move || shots.into_iter().map(|shot| sink.resolve(shot)).collect())
+            .await
+            .unwrap_or_default()
+    };
+    let detail = format!("{}, {} screenshot(s)", token_identity(&s, &headers, q.token.as_deref()), shots.len());
+    s.audit.record("screenshot_history_viewed", &addr.to_string(), Some(detail)).await;
+    Json(serde_json::json!({
+        "total": shots.len(),
+        "screenshots": shots,
+    }))
+}
+
+#[derive(Deserialize)]
+struct SnapshotBody {
+    /// Instant to capture at, on the Redis server's clock (RFC 3339).
+    at: chrono::DateTime<chrono::Utc>,
+    /// Hash the room's captures are collected in; defaults to `at` in
+    /// Unix milliseconds.
+    #[serde(default)]
+    id: Option<String>,
+}
+
+/// POST /snapshot — capture the screen at `at`, in step with the rest of
+/// the room (see `snapshot.rs`)
+async fn snapshot_handler(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<SnapshotBody>,
+) -> impl IntoResponse {
+    let id = body.id.filter(|id| !id.is_empty()).unwrap_or_else(|| body.at.timestamp_millis().to_string());
+    if !s.config.screenshots.enabled {
+        return Json(serde_json::json!({ "status": "error", "error": "screenshots are disabled" }));
+    }
+    s.audit.record("snapshot_scheduled", &addr.to_string(), Some(format!("{id} at {}", body.at.to_rfc3339()))).await;
+    #[cfg(feature = "screenshots")]
+    {
+        let scheduled = crate::snapshot::schedule(
+            s.config.screenshots.clone(),
+            s.store.clone(),
+            Arc::clone(&s.screenshot_sink),
+            s.hostname.clone(),
+            id.clone(),
+            body.at,
+        )
+        .await;
+        match scheduled {
+            Ok(in_ms) => Json(serde_json::json!({ "status": "ok", "id": id, "in_ms": in_ms })),
+            Err(e) => Json(serde_json::json!({ "status": "error", "id": id, "error": e })),
+        }
+    }
+    #[cfg(not(feature = "screenshots"))]
+    Json(serde_json::json!({ "status": "error", "id": id, "error": "built without screenshot support" }))
+}
+
+/// GET /room — heartbeats of every agent in the same site/room namespace.
+/// They carry screen thumbnails, so reads are audited as `room_viewed`.
+async fn room_overview(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(q): Query<TokenQuery>,
+) -> impl IntoResponse {
+    let agents = s.store.room_heartbeats().await;
+    let detail = format!("{}, {} agent(s)", token_identity(&s, &headers, q.token.as_deref()), agents.len());
+    s.audit.record("room_viewed", &addr.to_string(), Some(detail)).await;
+    Json(serde_json::json!({
+        "namespace": s.store.namespace(),
+        "site": s.config.tags.site,
+        "room": s.config.tags.room,
+        "total": agents.len(),
+        "agents": agents,
+    }))
+}
+
 // ── Apps handler ────────────────────────────────────────────────
 
 #[derive(Serialize)]
@@ -202,7 +494,7 @@ async fn list_apps(State(s): State<Arc<AppState>>) -> impl IntoResponse {
             .collect();
 
         // Sort by memory descending, take top 30
-        procs.sort_by(|a, b| b.memory_mb.cmp(&a.memory_mb));
+        procs.sort_by_key(|p| std::cmp::Reverse(p.memory_mb));
         procs.truncate(30);
         procs
     })
@@ -229,25 +521,32 @@ fn whoami() -> String {
 
 /// POST /lock/{mode}  where mode = "soft" | "hard"
 async fn lock_handler(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     axum::extract::Path(mode): axum::extract::Path<String>,
 ) -> impl IntoResponse {
+    if matches!(mode.as_str(), "soft" | "hard") {
+        s.audit.record("lock", &addr.to_string(), Some(mode.clone())).await;
+    }
     match mode.as_str() {
         "soft" => {
-            tracing::info!("🔒 Soft-lock: minimising all windows");
-            let ok = tokio::task::spawn_blocking(soft_lock).await.unwrap_or(false);
-            if ok {
-                Json(serde_json::json!({ "status": "ok" }))
-            } else {
-                Json(serde_json::json!({ "status": "error", "error": "soft lock failed" }))
+            tracing::info!("🔒 Soft-lock: minimising all windows until unlocked");
+            let lock = Arc::clone(&s.soft_lock);
+            match tokio::task::spawn_blocking(move || lock.start()).await.ok().flatten() {
+                Some(status) => Json(serde_json::json!({ "status": "ok", "soft_lock": status })),
+                None => Json(serde_json::json!({ "status": "error", "error": "soft lock failed" })),
             }
         }
         "hard" => {
             tracing::info!("🔒 Hard-lock: locking workstation");
-            let ok = tokio::task::spawn_blocking(hard_lock).await.unwrap_or(false);
-            if ok {
-                Json(serde_json::json!({ "status": "ok" }))
-            } else {
-                Json(serde_json::json!({ "status": "error", "error": "hard lock failed" }))
+            match tokio::task::spawn_blocking(hard_lock).await.ok().flatten() {
+                Some(lock) => Json(serde_json::json!({
+                    // A locker ran, but nothing confirmed the session locked
+                    "status": if lock.verified { "ok" } else { "unverified" },
+                    "method": lock.method,
+                    "verified": lock.verified,
+                })),
+                None => Json(serde_json::json!({ "status": "error", "error": "hard lock failed" })),
             }
         }
         _ => {
@@ -256,19 +555,215 @@ async fn lock_handler(
     }
 }
 
+/// GET /lock — soft-lock status (`active`, `since`, `circumventions`)
+async fn lock_status(State(s): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(s.soft_lock.status())
+}
+
+/// POST /unlock — end a soft lock
+async fn unlock_handler(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let status = s.soft_lock.stop();
+    let detail = format!("soft, {} circumvention attempt(s)", status.circumventions);
+    s.audit.record("unlock", &addr.to_string(), Some(detail)).await;
+    Json(serde_json::json!({ "status": "ok", "was_active": status.active }))
+}
+
+#[derive(Deserialize)]
+struct FocusBody {
+    /// Process name of the allowed application (e.g. "winword", "firefox").
+    app: String,
+    /// Lift focus mode after this long; without it, only `/focus/stop` does.
+    #[serde(default)]
+    minutes: Option<u64>,
+    /// Defaults to `[focus_mode] action`.
+    #[serde(default)]
+    action: Option<FocusAction>,
+}
+
+/// POST /focus — allow only one application until `/focus/stop` or the timer
+async fn focus_start(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<FocusBody>,
+) -> impl IntoResponse {
+    let app = body.app.trim();
+    if app.is_empty() {
+        return Json(serde_json::json!({ "status": "error", "error": "app is required" }));
+    }
+    let action = body.action.unwrap_or(s.config.focus_mode.action);
+    let until = body.minutes.filter(|&m| m > 0).map(|m| chrono::Utc::now() + chrono::Duration::minutes(m as i64));
+    let status = s.focus_mode.start(app, action, until);
+    let detail = match until {
+        Some(until) => format!("{app} ({action:?}) until {}", until.to_rfc3339()),
+        None => format!("{app} ({action:?})"),
+    };
+    s.audit.record("focus_started", &addr.to_string(), Some(detail)).await;
+    Json(serde_json::json!({ "status": "ok", "focus": status }))
+}
+
+/// POST /focus/stop — lift focus mode
+async fn focus_stop(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let status = s.focus_mode.stop();
+    if status.active {
+        let detail = format!("{}, {} escape(s)", status.app.as_deref().unwrap_or_default(), status.escapes);
+        s.audit.record("focus_stopped", &addr.to_string(), Some(detail)).await;
+    }
+    Json(serde_json::json!({ "status": "ok", "was_active": status.active, "escapes": status.escapes }))
+}
+
+/// GET /focus — focus-mode status (`active`, `app`, `action`, `since`, `until`, `escapes`)
+async fn focus_status(State(s): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(s.focus_mode.status())
+}
+
+#[derive(Deserialize)]
+struct UnlockCodeBody {
+    code: String,
+}
+
+/// POST /unlock/code — end a soft lock with a one-time unlock code
+async fn unlock_code_handler(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<UnlockCodeBody>,
+) -> impl IntoResponse {
+    let Some(codes) = s.unlock_codes.as_ref() else {
+        return Json(serde_json::json!({ "status": "error", "error": "unlock codes are disabled" }));
+    };
+    let outcome = codes.redeem(&body.code, s.soft_lock.status().since).await;
+    codes.audit_rejection(outcome, &s.audit, &addr.to_string()).await;
+    match outcome {
+        Redeemed::Accepted => {}
+        Redeemed::Rejected { attempts_left } => {
+            return Json(serde_json::json!({
+                "status": "error",
+                "error": "invalid or used code",
+                "attempts_left": attempts_left,
+            }));
+        }
+        Redeemed::LockedOut { retry_in_secs } => {
+            return Json(serde_json::json!({
+                "status": "error",
+                "error": "too many wrong codes",
+                "retry_in_secs": retry_in_secs,
+            }));
+        }
+    }
+    let status = s.soft_lock.stop();
+    let detail = format!("soft, by unlock code, {} circumvention attempt(s)", status.circumventions);
+    s.audit.record("unlock", &addr.to_string(), Some(detail)).await;
+    Json(serde_json::json!({ "status": "ok", "was_active": status.active }))
+}
+
 #[derive(Deserialize)]
 struct OpenUrlBody {
     url: String,
+    /// "chrome" | "edge" | "firefox" | "default" (the OS default browser)
+    #[serde(default)]
+    browser: Option<String>,
+    /// Locked-down full-screen window without browser UI.
+    #[serde(default)]
+    kiosk: bool,
+    /// Full-screen window (browser UI still reachable).
+    #[serde(default)]
+    fullscreen: bool,
+    /// Open the URL even though it matches a banned domain.
+    #[serde(default, rename = "override")]
+    override_ban: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Browser {
+    Default,
+    Chrome,
+    Edge,
+    Firefox,
+}
+
+impl Browser {
+    fn parse(name: Option<&str>) -> Option<Self> {
+        match name.map(str::to_lowercase).as_deref() {
+            None | Some("default") => Some(Browser::Default),
+            Some("chrome") => Some(Browser::Chrome),
+            Some("edge") => Some(Browser::Edge),
+            Some("firefox") => Some(Browser::Firefox),
+            _ => None,
+        }
+    }
+
+    /// Command-line flags for the requested window mode.
+    fn flags(self, kiosk: bool, fullscreen: bool) -> Vec<String> {
+        match self {
+            Browser::Default => Vec::new(),
+            Browser::Chrome | Browser::Edge => {
+                let mut flags = vec!["--new-window".to_string()];
+                if kiosk {
+                    // Kiosk only applies to a fresh browser process, so use a
+                    // dedicated profile that no student window shares.
+                    let dir = std::env::temp_dir().join("nishack-kiosk");
+                    flags.push("--kiosk".into());
+                    flags.push(format!("--user-data-dir={}", dir.display()));
+                    if self == Browser::Edge {
+                        flags.push("--edge-kiosk-type=fullscreen".into());
+                    }
+                } else if fullscreen {
+                    flags.push("--start-fullscreen".into());
+                }
+                flags
+            }
+            // Firefox has no fullscreen flag; kiosk is the full-screen mode.
+            Browser::Firefox if kiosk || fullscreen => vec!["--kiosk".into()],
+            Browser::Firefox => vec!["--new-window".into()],
+        }
+    }
 }
 
-/// POST /open-url   body: { "url": "https://..." }
-async fn open_url_handler(Json(body): Json<OpenUrlBody>) -> impl IntoResponse {
+/// POST /open-url   body: { "url": "https://...", "browser": "chrome", "kiosk": true }
+/// URLs on the active ban list are refused unless `"override": true`.
+async fn open_url_handler(
+    State(s): State<Arc<AppState>>,
+    Json(body): Json<OpenUrlBody>,
+) -> impl IntoResponse {
     let url = body.url.clone();
     if !url.starts_with("http://") && !url.starts_with("https://") {
         return Json(serde_json::json!({ "status": "error", "error": "URL must start with http(s)://" }));
     }
-    tracing::info!("🌐 Opening URL: {url}");
-    let ok = tokio::task::spawn_blocking(move || open_url_in_browser(&url))
+    let Some(browser) = Browser::parse(body.browser.as_deref()) else {
+        return Json(serde_json::json!({ "status": "error", "error": "browser must be chrome, edge, firefox or default" }));
+    };
+    if browser == Browser::Default && (body.kiosk || body.fullscreen) {
+        return Json(serde_json::json!({ "status": "error", "error": "kiosk / fullscreen need an explicit browser" }));
+    }
+
+    let monitor = Arc::clone(&s.monitor);
+    let check = url.clone();
+    let banned = tokio::task::spawn_blocking(move || {
+        monitor.lock().unwrap_or_else(PoisonError::into_inner).banned_domain_for(&check)
+    })
+    .await
+    .ok()
+    .flatten();
+    if let Some(domain) = banned {
+        if !body.override_ban {
+            tracing::warn!("🚫 Refusing to open {url}: matches banned domain {domain}");
+            return Json(serde_json::json!({
+                "status": "error",
+                "error": format!("URL matches banned domain {domain}; resend with \"override\": true to open it anyway"),
+                "banned_domain": domain,
+            }));
+        }
+        tracing::warn!("⚠️  Opening {url} despite banned domain {domain} (override)");
+    }
+
+    let flags = browser.flags(body.kiosk, body.fullscreen);
+    tracing::info!("🌐 Opening URL: {url} ({browser:?} {flags:?})");
+    let ok = tokio::task::spawn_blocking(move || open_url_in_browser(&url, browser, &flags))
         .await
         .unwrap_or(false);
     if ok {
@@ -278,93 +773,602 @@ async fn open_url_handler(Json(body): Json<OpenUrlBody>) -> impl IntoResponse {
     }
 }
 
-// ── Platform-specific implementations ───────────────────────────
+// ── Exam mode ───────────────────────────────────────────────────
 
-/// Soft lock: minimize all windows (Win: Win+D, macOS: AppleScript, Linux: wmctrl)
-fn soft_lock() -> bool {
-    #[cfg(target_os = "windows")]
-    {
-        // Use the Shell.Application COM object to toggle desktop (minimise all)
-        silent_cmd("powershell")
-            .args(["-WindowStyle", "Hidden", "-Command",
-                   "(New-Object -ComObject Shell.Application).MinimizeAll()"])
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+/// GET /exam
+async fn exam_status(State(s): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(s.exam.status())
+}
+
+/// POST /exam/start
+async fn exam_start(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let exam = Arc::clone(&s.exam);
+    let status = tokio::task::spawn_blocking(move || exam.start()).await.unwrap_or_else(|_| s.exam.status());
+    s.audit.record("exam_started", &addr.to_string(), None).await;
+    Json(status)
+}
+
+/// POST /exam/stop
+async fn exam_stop(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let exam = Arc::clone(&s.exam);
+    let status = tokio::task::spawn_blocking(move || exam.stop()).await.unwrap_or_else(|_| s.exam.status());
+    s.audit.record("exam_stopped", &addr.to_string(), None).await;
+    Json(status)
+}
+
+// ── Audio handlers ──────────────────────────────────────────────
+
+/// POST /audio/mute
+async fn audio_mute() -> impl IntoResponse {
+    tracing::info!("🔇 Muting audio");
+    audio_result(tokio::task::spawn_blocking(|| audio::set_muted(true)).await.unwrap_or(false))
+}
+
+/// POST /audio/unmute
+async fn audio_unmute() -> impl IntoResponse {
+    tracing::info!("🔈 Unmuting audio");
+    audio_result(tokio::task::spawn_blocking(|| audio::set_muted(false)).await.unwrap_or(false))
+}
+
+#[derive(Deserialize)]
+struct VolumeBody {
+    /// 0-100
+    level: u8,
+}
+
+/// POST /audio/volume   body: { "level": 40 }  (also unmutes)
+async fn audio_volume(Json(body): Json<VolumeBody>) -> impl IntoResponse {
+    if body.level > 100 {
+        return Json(serde_json::json!({ "status": "error", "error": "level must be 0-100" }));
     }
+    tracing::info!("🔉 Setting volume to {}%", body.level);
+    let level = body.level;
+    audio_result(tokio::task::spawn_blocking(move || audio::set_volume(level)).await.unwrap_or(false))
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("osascript")
-            .args(["-e", r#"tell application "System Events" to keystroke "m" using {command down, option down}"#])
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+fn audio_result(ok: bool) -> Json<serde_json::Value> {
+    if ok {
+        Json(serde_json::json!({ "status": "ok" }))
+    } else {
+        Json(serde_json::json!({ "status": "error", "error": "audio control failed" }))
     }
+}
 
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("wmctrl")
-            .args(["-k", "on"])
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+// ── Wallpaper / lock-screen message ─────────────────────────────
+
+#[derive(Deserialize)]
+struct WallpaperBody {
+    /// Base64 PNG / JPEG / BMP.
+    #[serde(default)]
+    image: Option<String>,
+    /// Or an image already on this PC.
+    #[serde(default)]
+    path: Option<String>,
+    /// Restore the original automatically after this many seconds.
+    #[serde(default)]
+    duration_secs: Option<u64>,
+}
+
+/// POST /wallpaper   body: { "image": "<base64>", "duration_secs": 2700 }
+///                     or: { "path": "C:\\exam\\rules.png" }
+async fn wallpaper_set(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<WallpaperBody>,
+) -> impl IntoResponse {
+    let image = match &body.image {
+        Some(b64) => match general_purpose::STANDARD.decode(b64.trim()) {
+            Ok(bytes) => Some(bytes),
+            Err(_) => return Json(serde_json::json!({ "status": "error", "error": "image is not valid base64" })),
+        },
+        None => None,
+    };
+    let path = body.path.clone();
+    if image.is_none() && path.is_none() {
+        return Json(serde_json::json!({ "status": "error", "error": "send \"image\" (base64) or \"path\"" }));
+    }
+
+    let desktop = Arc::clone(&s.desktop);
+    let result = tokio::task::spawn_blocking(move || match image {
+        Some(bytes) => desktop.set_wallpaper(&bytes),
+        None => desktop.set_wallpaper_file(std::path::Path::new(&path.unwrap_or_default())),
+    })
+    .await
+    .unwrap_or_else(|e| Err(anyhow::anyhow!("{e}")));
+
+    match result {
+        Ok(generation) => {
+            s.audit.record("wallpaper_set", &addr.to_string(), body.path.clone()).await;
+            if let Some(secs) = body.duration_secs {
+                let desktop = Arc::clone(&s.desktop);
+                tokio::spawn(async move {
+                    tokio::time::sleep(std::time::Duration::from_secs(secs)).await;
+                    let _ = tokio::task::spawn_blocking(move || desktop.restore_wallpaper(Some(generation))).await;
+                });
+            }
+            Json(serde_json::json!({ "status": "ok", "restores_in_secs": body.duration_secs }))
+        }
+        Err(e) => Json(serde_json::json!({ "status": "error", "error": e.to_string() })),
     }
 }
 
-/// Hard lock: lock the workstation
-fn hard_lock() -> bool {
-    #[cfg(target_os = "windows")]
-    {
-        // LockWorkStation via rundll32
-        silent_cmd("rundll32.exe")
-            .args(["user32.dll,LockWorkStation"])
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+/// POST /wallpaper/restore
+async fn wallpaper_restore(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let desktop = Arc::clone(&s.desktop);
+    let restored = tokio::task::spawn_blocking(move || desktop.restore_wallpaper(None)).await.unwrap_or(false);
+    s.audit.record("wallpaper_restored", &addr.to_string(), None).await;
+    Json(serde_json::json!({ "status": "ok", "restored": restored }))
+}
+
+#[derive(Deserialize)]
+struct MessageBody {
+    text: String,
+    #[serde(default)]
+    title: Option<String>,
+    /// How long the pop-up stays up.
+    #[serde(default = "message_default_secs")]
+    secs: u64,
+}
+
+fn message_default_secs() -> u64 {
+    60
+}
+
+/// POST /message   body: { "text": "Save your work", "title": "Teacher", "secs": 60 }
+/// — a pop-up for the student (see `notify.rs`)
+async fn message_handler(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<MessageBody>,
+) -> impl IntoResponse {
+    if body.text.trim().is_empty() {
+        return Json(serde_json::json!({ "status": "error", "error": "text must not be empty" }));
+    }
+    s.audit.record("message_shown", &addr.to_string(), Some(body.text.clone())).await;
+    let title = body.title.unwrap_or_else(|| "Учитель".into());
+    let _ = tokio::task::spawn_blocking(move || crate::notify::warn_user(&title, &body.text, body.secs)).await;
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+#[derive(Deserialize)]
+struct KillBody {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    pid: Option<u32>,
+}
+
+/// POST /kill   body: { "name": "steam" } or { "pid": 4242 } — kill the
+/// process(es) now, confirmed like banned ones
+async fn kill_handler(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<KillBody>,
+) -> impl IntoResponse {
+    if body.name.as_deref().is_none_or(|n| n.trim().is_empty()) && body.pid.is_none() {
+        return Json(serde_json::json!({ "status": "error", "error": "name or pid required" }));
+    }
+    let monitor = Arc::clone(&s.monitor);
+    let (name, pid) = (body.name.clone(), body.pid);
+    let killed = tokio::task::spawn_blocking(move || {
+        monitor.lock().unwrap_or_else(PoisonError::into_inner).kill_matching(name.as_deref(), pid)
+    })
+    .await
+    .unwrap_or_default();
+    let detail = killed.iter().map(|(pid, name, stopped)| format!("{name} (PID {pid}){}", if *stopped { "" } else { " survived" }));
+    s.audit.record("process_killed", &addr.to_string(), Some(detail.collect::<Vec<_>>().join(", "))).await;
+    if killed.is_empty() {
+        return Json(serde_json::json!({ "status": "error", "error": "no such process" }));
     }
+    let processes: Vec<_> = killed
+        .iter()
+        .map(|(pid, name, stopped)| serde_json::json!({ "pid": pid, "name": name, "killed": stopped }))
+        .collect();
+    let status = if killed.iter().all(|(_, _, stopped)| *stopped) { "ok" } else { "error" };
+    Json(serde_json::json!({ "status": status, "processes": processes }))
+}
 
-    #[cfg(target_os = "macos")]
+/// POST /capture — take a screenshot now and store it like the periodic ones
+async fn capture_handler(State(s): State<Arc<AppState>>, ConnectInfo(addr): ConnectInfo<SocketAddr>) -> impl IntoResponse {
+    if !s.config.screenshots.enabled {
+        return Json(serde_json::json!({ "status": "error", "error": "screenshots are disabled" }));
+    }
+    s.audit.record("screenshot_requested", &addr.to_string(), None).await;
+    #[cfg(feature = "screenshots")]
     {
-        std::process::Command::new("pmset")
-            .args(["displaysleepnow"])
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+        let (quality, dim) = (s.config.screenshots.quality, s.config.screenshots.max_dimension);
+        let capture = tokio::task::spawn_blocking(move || crate::screenshot::try_capture_screenshot(quality, dim));
+        let Ok(Ok(Some(data))) = tokio::time::timeout(std::time::Duration::from_secs(15), capture).await else {
+            return Json(serde_json::json!({ "status": "error", "error": "capture failed" }));
+        };
+        match s.screenshot_sink.put(&s.hostname, &data).await {
+            Some(fields) => {
+                s.store.push_screenshot(&s.hostname, fields).await;
+                Json(serde_json::json!({ "status": "ok" }))
+            }
+            None => Json(serde_json::json!({ "status": "error", "error": "screenshot sink failed" })),
+        }
     }
+    #[cfg(not(feature = "screenshots"))]
+    Json(serde_json::json!({ "status": "error", "error": "built without screenshot support" }))
+}
 
-    #[cfg(target_os = "linux")]
-    {
-        // Try loginctl first (systemd), fallback to xdg-screensaver
-        std::process::Command::new("loginctl")
-            .args(["lock-session"])
+#[derive(Deserialize)]
+struct LockMessageBody {
+    text: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    duration_secs: Option<u64>,
+}
+
+/// POST /lock-message   body: { "title": "Exam", "text": "Machine reserved", "duration_secs": 3600 }
+async fn lock_message_set(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<LockMessageBody>,
+) -> impl IntoResponse {
+    if body.text.trim().is_empty() {
+        return Json(serde_json::json!({ "status": "error", "error": "text must not be empty" }));
+    }
+    let desktop = Arc::clone(&s.desktop);
+    let (title, text) = (body.title.clone(), body.text.clone());
+    let result = tokio::task::spawn_blocking(move || desktop.set_lock_message(&title, &text))
+        .await
+        .unwrap_or_else(|e| Err(anyhow::anyhow!("{e}")));
+
+    match result {
+        Ok(generation) => {
+            s.audit.record("lock_message_set", &addr.to_string(), Some(body.text.clone())).await;
+            if let Some(secs) = body.duration_secs {
+                let desktop = Arc::clone(&s.desktop);
+                tokio::spawn(async move {
+                    tokio::time::sleep(std::time::Duration::from_secs(secs)).await;
+                    let _ = tokio::task::spawn_blocking(move || desktop.clear_lock_message(Some(generation))).await;
+                });
+            }
+            Json(serde_json::json!({ "status": "ok", "restores_in_secs": body.duration_secs }))
+        }
+        Err(e) => Json(serde_json::json!({ "status": "error", "error": e.to_string() })),
+    }
+}
+
+/// POST /lock-message/clear
+async fn lock_message_clear(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> impl IntoResponse {
+    let desktop = Arc::clone(&s.desktop);
+    let restored = tokio::task::spawn_blocking(move || desktop.clear_lock_message(None)).await.unwrap_or(false);
+    s.audit.record("lock_message_cleared", &addr.to_string(), None).await;
+    Json(serde_json::json!({ "status": "ok", "restored": restored }))
+}
+
+// ── Admin endpoints ─────────────────────────────────────────────
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Marks a request made by the Redis command channel (see `commands.rs`),
+/// which the admin gate lets through: only the teacher can publish there.
+/// HTTP clients can't set request extensions.
+#[derive(Clone, Copy)]
+pub struct CommandChannel;
+
+/// Middleware of the admin router: see `require_admin`.
+async fn admin_only(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(q): Query<TokenQuery>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if req.extensions().get::<CommandChannel>().is_none() {
+        let path = req.uri().path().to_string();
+        if let Err(denied) = require_admin(&s, req.headers(), q.token.as_deref(), addr, &path).await {
+            return denied;
+        }
+    }
+    next.run(req).await
+}
+
+/// Admin endpoints need `Authorization: Bearer <api.admin_token>`, or
+/// `?token=` for WebSocket clients (browsers can't set headers there).
+/// They're disabled outright while no token is configured. Failed
+/// attempts are audited.
+async fn require_admin(
+    s: &AppState,
+    headers: &HeaderMap,
+    query_token: Option<&str>,
+    addr: SocketAddr,
+    endpoint: &str,
+) -> Result<(), Response> {
+    let Some(expected) = s.config.api.admin_token.as_deref().filter(|t| !t.is_empty()) else {
+        return Err((StatusCode::FORBIDDEN, "admin endpoints are disabled (no api.admin_token)").into_response());
+    };
+    let supplied = supplied_token(headers, query_token);
+    if supplied.is_some_and(|t| constant_time_eq(t.as_bytes(), expected.as_bytes())) {
+        return Ok(());
+    }
+    s.audit.record("admin_auth_failed", &addr.to_string(), Some(endpoint.to_string())).await;
+    Err((StatusCode::UNAUTHORIZED, "invalid admin token").into_response())
+}
+
+/// The bearer token from the header, else `?token=`.
+fn supplied_token<'a>(headers: &'a HeaderMap, query_token: Option<&'a str>) -> Option<&'a str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or(query_token)
+}
+
+/// Which token a screen viewer presented, for the audit trail: the admin
+/// token, another token by SHA-256 prefix (never the token itself), or none.
+fn token_identity(s: &AppState, headers: &HeaderMap, query_token: Option<&str>) -> String {
+    let Some(token) = supplied_token(headers, query_token).filter(|t| !t.is_empty()) else {
+        return "no token".to_string();
+    };
+    let admin = s.config.api.admin_token.as_deref().filter(|t| !t.is_empty());
+    if admin.is_some_and(|a| constant_time_eq(token.as_bytes(), a.as_bytes())) {
+        return "admin token".to_string();
+    }
+    let digest = format!("{:x}", Sha256::digest(token.as_bytes()));
+    format!("token sha256:{}", &digest[..8])
+}
+
+/// Compare without leaking the position of the first mismatch.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// GET /unlock-code — this machine's current derived unlock code (admin only, audited)
+async fn unlock_code_issue(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Response {
+    let Some((code, valid_for_secs)) = s.unlock_codes.as_ref().and_then(|c| c.current()) else {
+        return (StatusCode::NOT_FOUND, "unlock codes are disabled or have no secret").into_response();
+    };
+    s.audit.record("unlock_code_issued", &addr.to_string(), None).await;
+    Json(serde_json::json!({ "code": code, "valid_for_secs": valid_for_secs })).into_response()
+}
+
+/// POST /diagnostics/bundle — collect a support bundle (see
+/// `diagnostics.rs`; admin only, audited)
+async fn diagnostics_bundle(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+) -> Response {
+    let ctx = diagnostics::Context {
+        hostname: s.hostname.clone(),
+        config_path: AppConfig::path(None),
+        capabilities: Capabilities::clone(&s.capabilities),
+        agent_uptime_secs: s.start_time.elapsed().as_secs(),
+        last_scan: s.monitor.lock().unwrap_or_else(PoisonError::into_inner).last_scan(),
+        capture: (s.config.screenshots.quality, s.config.screenshots.max_dimension),
+    };
+    let diagnostics = Arc::clone(&s.diagnostics);
+    let bundle = match tokio::task::spawn_blocking(move || diagnostics.build(ctx)).await {
+        Ok(bundle) => bundle,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("bundle failed: {e}")).into_response(),
+    };
+    s.audit
+        .record("diagnostics_bundle", &addr.to_string(), Some(format!("{} ({} bytes)", bundle.name, bundle.data.len())))
+        .await;
+    Json(serde_json::json!({
+        "status": "ok",
+        "name": bundle.name,
+        "created_at": bundle.created_at,
+        "size": bundle.data.len(),
+        "files": bundle.files,
+        "download": "/diagnostics/bundle",
+    }))
+    .into_response()
+}
+
+/// GET /diagnostics/bundle — the latest bundle as a zip (admin only)
+async fn diagnostics_download(State(s): State<Arc<AppState>>) -> Response {
+    let Some(bundle) = s.diagnostics.latest() else {
+        return (StatusCode::NOT_FOUND, "no bundle yet (POST /diagnostics/bundle)").into_response();
+    };
+    let disposition = format!("attachment; filename=\"{}\"", bundle.name);
+    ([(header::CONTENT_TYPE, "application/zip".to_string()), (header::CONTENT_DISPOSITION, disposition)], bundle.data.clone())
+        .into_response()
+}
+
+/// GET /ws/shell — interactive shell over WebSocket (admin only, audited)
+async fn ws_shell(
+    State(s): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    tracing::info!("🖥️  Remote shell opened by {addr}");
+    let audit = s.audit.clone();
+    ws.on_upgrade(move |socket| shell::run_session(socket, audit, addr.to_string()))
+}
+
+// ── Platform-specific implementations ───────────────────────────
+
+/// Lock mechanisms in order of preference: (program, args).
+#[cfg(target_os = "windows")]
+const HARD_LOCK_METHODS: &[(&str, &[&str])] = &[
+    ("rundll32.exe", &["user32.dll,LockWorkStation"]),
+    // Disconnecting the console session also leaves it at the lock screen.
+    ("tsdiscon", &[]),
+];
+
+#[cfg(target_os = "macos")]
+const HARD_LOCK_METHODS: &[(&str, &[&str])] = &[
+    // ⌃⌘Q — the system "Lock Screen" shortcut.
+    ("osascript", &["-e", r#"tell application "System Events" to keystroke "q" using {control down, command down}"#]),
+    // Pre-Big Sur fast-user-switch lock.
+    ("/System/Library/CoreServices/Menu Extras/User.menu/Contents/Resources/CGSession", &["-suspend"]),
+    // Only locks if "require password after sleep" is immediate.
+    ("pmset", &["displaysleepnow"]),
+];
+
+#[cfg(target_os = "linux")]
+const HARD_LOCK_METHODS: &[(&str, &[&str])] = &[
+    ("loginctl", &["lock-session"]),
+    // Every session — works when the agent runs outside the user's session.
+    ("loginctl", &["lock-sessions"]),
+    ("xdg-screensaver", &["lock"]),
+    ("gnome-screensaver-command", &["--lock"]),
+    ("dm-tool", &["lock"]),
+];
+
+/// How long to wait for the session to report itself locked.
+const LOCK_VERIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+struct HardLock {
+    method: &'static str,
+    /// False when the OS can't tell us the session state.
+    verified: bool,
+}
+
+/// Hard lock: lock the workstation, then confirm the session really is
+/// locked and fall through to the next mechanism if it isn't — or if
+/// the OS can't say, so every mechanism gets its chance.
+fn hard_lock() -> Option<HardLock> {
+    // First mechanism that ran, in case no check ever confirms a lock
+    // (some lockers never report their state).
+    let mut unconfirmed = None;
+    for &(program, args) in HARD_LOCK_METHODS {
+        let ran = silent_cmd(program)
+            .args(args)
             .status()
             .map(|s| s.success())
-            .unwrap_or_else(|_| {
-                std::process::Command::new("xdg-screensaver")
-                    .args(["lock"])
-                    .status()
-                    .map(|s| s.success())
-                    .unwrap_or(false)
-            })
+            .unwrap_or(false);
+        if !ran {
+            continue;
+        }
+        unconfirmed.get_or_insert(program);
+
+        let deadline = std::time::Instant::now() + LOCK_VERIFY_TIMEOUT;
+        let locked = loop {
+            match session_locked() {
+                Some(false) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(250))
+                }
+                state => break state,
+            }
+        };
+        match locked {
+            Some(true) => return Some(HardLock { method: program, verified: true }),
+            // Can't check — run the rest too, in case this one didn't take.
+            None => tracing::warn!("Hard-lock via {program} ran but the session state is unknown"),
+            Some(false) => tracing::warn!("Hard-lock via {program} did not lock the session, trying next method"),
+        }
+    }
+    unconfirmed.map(|method| HardLock { method, verified: false })
+}
+
+/// Whether the interactive session is currently locked, if the OS says.
+fn session_locked() -> Option<bool> {
+    if cfg!(target_os = "windows") {
+        // LogonUI.exe runs exactly while the lock / logon screen is shown.
+        let out = silent_cmd("tasklist")
+            .args(["/FI", "IMAGENAME eq LogonUI.exe", "/NH"])
+            .output()
+            .ok()?;
+        Some(String::from_utf8_lossy(&out.stdout).contains("LogonUI.exe"))
+    } else if cfg!(target_os = "macos") {
+        let out = silent_cmd("ioreg").args(["-n", "Root", "-d1"]).output().ok()?;
+        Some(String::from_utf8_lossy(&out.stdout).contains("\"CGSSessionScreenIsLocked\"=Yes"))
+    } else {
+        // logind's LockedHint, over every graphical session.
+        let out = silent_cmd("loginctl").args(["list-sessions", "--no-legend"]).output().ok()?;
+        let mut any_session = false;
+        for id in String::from_utf8_lossy(&out.stdout).lines().filter_map(|l| l.split_whitespace().next()) {
+            let Ok(show) = silent_cmd("loginctl")
+                .args(["show-session", id, "-p", "Type", "-p", "LockedHint"])
+                .output()
+            else {
+                continue;
+            };
+            let props = String::from_utf8_lossy(&show.stdout);
+            if !props.contains("Type=x11") && !props.contains("Type=wayland") {
+                continue;
+            }
+            any_session = true;
+            if props.contains("LockedHint=yes") {
+                return Some(true);
+            }
+        }
+        // Not every locker sets LockedHint; without a graphical session
+        // the answer is meaningless either way.
+        any_session.then_some(false)
     }
 }
 
-/// Open a URL in the OS default browser
-fn open_url_in_browser(url: &str) -> bool {
+/// Where `exe` is installed according to its App Paths registration
+/// (per user first), which is how `start` and Win+R find browsers.
+#[cfg(target_os = "windows")]
+fn app_path(exe: &str) -> Option<String> {
+    ["HKCU", "HKLM"].iter().find_map(|hive| {
+        let key = format!(r"{hive}\SOFTWARE\Microsoft\Windows\CurrentVersion\App Paths\{exe}");
+        let out = silent_cmd("reg").args(["query", &key, "/ve"]).output().ok()?;
+        // "    (Default)    REG_SZ    C:\...\chrome.exe"; the value name
+        // is localised, the type isn't
+        String::from_utf8_lossy(&out.stdout).lines().find_map(|l| {
+            let (_, value) = l.split_once("REG_SZ")?;
+            let value = value.trim().trim_matches('"');
+            (!value.is_empty()).then(|| value.to_string())
+        })
+    })
+}
+
+/// Open a URL in the OS default browser, or in `browser` with `flags`.
+/// Browsers are launched detached; we don't wait for them to exit.
+fn open_url_in_browser(url: &str, browser: Browser, flags: &[String]) -> bool {
     #[cfg(target_os = "windows")]
     {
-        silent_cmd("cmd")
-            .args(["/C", "start", "", url])
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+        // No cmd.exe in between: it would act on `&`, `|` and `^` in the URL
+        let exe = match browser {
+            Browser::Default => {
+                return std::process::Command::new("rundll32")
+                    .arg("url.dll,FileProtocolHandler")
+                    .arg(url)
+                    .spawn()
+                    .is_ok()
+            }
+            Browser::Chrome => "chrome.exe",
+            Browser::Edge => "msedge.exe",
+            Browser::Firefox => "firefox.exe",
+        };
+        let program = app_path(exe).unwrap_or_else(|| exe.to_string());
+        std::process::Command::new(program)
+            .args(flags)
+            .arg(url)
+            .spawn()
+            .is_ok()
     }
 
     #[cfg(target_os = "macos")]
     {
-        std::process::Command::new("open")
-            .arg(url)
+        let mut cmd = std::process::Command::new("open");
+        if browser != Browser::Default {
+            let app = match browser {
+                Browser::Chrome => "Google Chrome",
+                Browser::Edge => "Microsoft Edge",
+                _ => "Firefox",
+            };
+            // -n: new instance so the flags are honoured
+            cmd.args(["-na", app, "--args"]).args(flags);
+        }
+        cmd.arg(url)
             .status()
             .map(|s| s.success())
             .unwrap_or(false)
@@ -372,10 +1376,26 @@ fn open_url_in_browser(url: &str) -> bool {
 
     #[cfg(target_os = "linux")]
     {
-        std::process::Command::new("xdg-open")
-            .arg(url)
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
+        let candidates: &[&str] = match browser {
+            Browser::Default => {
+                return std::process::Command::new("xdg-open")
+                    .arg(url)
+                    .status()
+                    .map(|s| s.success())
+                    .unwrap_or(false)
+            }
+            Browser::Chrome => &["google-chrome", "google-chrome-stable", "chromium", "chromium-browser"],
+            Browser::Edge => &["microsoft-edge", "microsoft-edge-stable"],
+            Browser::Firefox => &["firefox"],
+        };
+        candidates.iter().any(|program| {
+            std::process::Command::new(program)
+                .args(flags)
+                .arg(url)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()
+                .is_ok()
+        })
     }
 }
@@ -0,0 +1,107 @@
+// ─────────────────────────────────────────────────────────────────
+//  audio.rs — Master volume / mute of the default output device
+//
+//    Windows: Core Audio IAudioEndpointVolume via a PowerShell type
+//    macOS:   osascript "set volume"
+//    Linux:   pactl (PulseAudio / PipeWire), falling back to amixer
+// ─────────────────────────────────────────────────────────────────
+
+#[cfg(target_os = "windows")]
+use crate::monitor::silent_cmd;
+
+/// Mute or unmute the default output device.
+pub fn set_muted(muted: bool) -> bool {
+    set(Change::Mute(muted))
+}
+
+/// Set the default output device to `level` percent (0-100) and unmute it.
+pub fn set_volume(level: u8) -> bool {
+    set(Change::Volume(level.min(100)))
+}
+
+#[derive(Clone, Copy)]
+enum Change {
+    Mute(bool),
+    Volume(u8),
+}
+
+#[cfg(target_os = "windows")]
+const CORE_AUDIO: &str = r#"
+Add-Type -TypeDefinition @'
+using System;
+using System.Runtime.InteropServices;
+[Guid("5CDF2C82-841E-4546-9722-0CF74078229A"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IAudioEndpointVolume {
+    int _0(); int _1(); int _2(); int _3();
+    int SetMasterVolumeLevelScalar(float level, Guid ctx);
+    int _5(); int _6(); int _7(); int _8(); int _9(); int _10();
+    int SetMute([MarshalAs(UnmanagedType.Bool)] bool mute, Guid ctx);
+}
+[Guid("D666063F-1587-4E43-81F1-B948E807363F"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IMMDevice { int Activate(ref Guid iid, int ctx, IntPtr p, out IAudioEndpointVolume v); }
+[Guid("A95664D2-9614-4F35-A746-DE8DB63617E6"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IMMDeviceEnumerator { int _0(); int GetDefaultAudioEndpoint(int flow, int role, out IMMDevice d); }
+[ComImport, Guid("BCDE0395-E52F-467C-8E3D-C4579291692E")] class MMDeviceEnumerator { }
+public static class NishackAudio {
+    static IAudioEndpointVolume Endpoint() {
+        var e = (IMMDeviceEnumerator)new MMDeviceEnumerator();
+        IMMDevice d; Marshal.ThrowExceptionForHR(e.GetDefaultAudioEndpoint(0, 1, out d));
+        var iid = typeof(IAudioEndpointVolume).GUID;
+        IAudioEndpointVolume v; Marshal.ThrowExceptionForHR(d.Activate(ref iid, 23, IntPtr.Zero, out v));
+        return v;
+    }
+    public static void Mute(bool m) { Marshal.ThrowExceptionForHR(Endpoint().SetMute(m, Guid.Empty)); }
+    public static void Volume(float l) { Marshal.ThrowExceptionForHR(Endpoint().SetMasterVolumeLevelScalar(l, Guid.Empty)); }
+}
+'@
+"#;
+
+#[cfg(target_os = "windows")]
+fn set(change: Change) -> bool {
+    let call = match change {
+        Change::Mute(m) => format!("[NishackAudio]::Mute(${m})"),
+        Change::Volume(l) => format!("[NishackAudio]::Volume({}); [NishackAudio]::Mute($false)", l as f32 / 100.0),
+    };
+    silent_cmd("powershell")
+        .args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &format!("{CORE_AUDIO}\n{call}")])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "macos")]
+fn set(change: Change) -> bool {
+    let script = match change {
+        Change::Mute(m) => format!("set volume output muted {m}"),
+        Change::Volume(l) => format!("set volume output volume {l} without output muted"),
+    };
+    std::process::Command::new("osascript")
+        .args(["-e", &script])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+fn set(change: Change) -> bool {
+    let run = |program: &str, args: &[&str]| {
+        std::process::Command::new(program)
+            .args(args)
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+    };
+    match change {
+        Change::Mute(m) => {
+            let flag = if m { "1" } else { "0" };
+            run("pactl", &["set-sink-mute", "@DEFAULT_SINK@", flag])
+                || run("amixer", &["-q", "sset", "Master", if m { "mute" } else { "unmute" }])
+        }
+        Change::Volume(l) => {
+            let pct = format!("{l}%");
+            (run("pactl", &["set-sink-volume", "@DEFAULT_SINK@", &pct])
+                && run("pactl", &["set-sink-mute", "@DEFAULT_SINK@", "0"]))
+                || run("amixer", &["-q", "sset", "Master", &pct, "unmute"])
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// ─────────────────────────────────────────────────────────────────
+//  audio_sessions.rs — Which programs are playing or recording audio
+//
+//  Tells the teacher who is in a voice call or watching a video, and
+//  lets `[monitor.audio]` flag banned apps using the microphone:
+//    Windows: active WASAPI sessions on the default output and input
+//             devices (IAudioSessionManager2 via a PowerShell type)
+//    Linux:   uncorked `pactl` sink inputs (playing) and source
+//             outputs (recording), PulseAudio or PipeWire
+//  macOS has no per-app session API outside the audio HAL and isn't
+//  covered.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeSet;
+
+use crate::models::AudioSession;
+use crate::monitor::silent_cmd;
+
+/// Programs with an active audio stream, one entry per (process,
+/// direction). Blocking.
+pub fn active() -> Vec<AudioSession> {
+    let sessions = if cfg!(target_os = "windows") {
+        windows_sessions()
+    } else if cfg!(target_os = "linux") {
+        [("sink-inputs", "playback"), ("source-outputs", "capture")]
+            .into_iter()
+            .flat_map(|(list, direction)| pactl_streams(list, direction))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let unique: BTreeSet<(String, &'static str)> = sessions.into_iter().collect();
+    unique.into_iter().map(|(process, direction)| AudioSession { process, direction: direction.into() }).collect()
+}
+
+const WASAPI_SESSIONS: &str = r#"
+Add-Type -TypeDefinition @'
+using System;
+using System.Runtime.InteropServices;
+[Guid("bfb7ff88-7239-4fc9-8fa2-07c950be9c6d"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IAudioSessionControl2 {
+    int GetState(out int state);
+    int _1(); int _2(); int _3(); int _4(); int _5(); int _6(); int _7(); int _8(); int _9(); int _10();
+    int GetProcessId(out uint pid);
+}
+[Guid("E2F5BB11-0570-40CA-ACDD-3AA01277DEE8"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IAudioSessionEnumerator { int GetCount(out int n); int GetSession(int i, out IAudioSessionControl2 s); }
+[Guid("77AA99A0-1BD6-484F-8BC7-2C654C9A9B6F"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IAudioSessionManager2 { int _0(); int _1(); int GetSessionEnumerator(out IAudioSessionEnumerator e); }
+[Guid("D666063F-1587-4E43-81F1-B948E807363F"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IMMDevice { int Activate(ref Guid iid, int ctx, IntPtr p, out IAudioSessionManager2 m); }
+[Guid("A95664D2-9614-4F35-A746-DE8DB63617E6"), InterfaceType(ComInterfaceType.InterfaceIsIUnknown)]
+interface IMMDeviceEnumerator { int _0(); int GetDefaultAudioEndpoint(int flow, int role, out IMMDevice d); }
+[ComImport, Guid("BCDE0395-E52F-467C-8E3D-C4579291692E")] class MMDeviceEnumerator { }
+public static class NishackSessions {
+    public static void Print() {
+        var en = (IMMDeviceEnumerator)new MMDeviceEnumerator();
+        string[] flows = { "playback", "capture" };
+        for (int flow = 0; flow < 2; flow++) {
+            IMMDevice d;
+            if (en.GetDefaultAudioEndpoint(flow, 1, out d) != 0) continue;
+            var iid = typeof(IAudioSessionManager2).GUID;
+            IAudioSessionManager2 m; IAudioSessionEnumerator e; int n;
+            if (d.Activate(ref iid, 23, IntPtr.Zero, out m) != 0 || m.GetSessionEnumerator(out e) != 0) continue;
+            e.GetCount(out n);
+            for (int i = 0; i < n; i++) {
+                IAudioSessionControl2 s; int state; uint pid;
+                if (e.GetSession(i, out s) != 0 || s.GetState(out state) != 0 || state != 1) continue;
+                if (s.GetProcessId(out pid) != 0 || pid == 0) continue;
+                try { Console.WriteLine(System.Diagnostics.Process.GetProcessById((int)pid).ProcessName + "\t" + flows[flow]); }
+                catch (ArgumentException) { }
+            }
+        }
+    }
+}
+'@
+[NishackSessions]::Print()
+"#;
+
+fn windows_sessions() -> Vec<(String, &'static str)> {
+    let Ok(out) = silent_cmd("powershell").args(["-NoProfile", "-Command", WASAPI_SESSIONS]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| line.trim().split_once('\t'))
+        .map(|(process, flow)| (process.to_lowercase(), if flow == "capture" { "capture" } else { "playback" }))
+        .collect()
+}
+
+//   Sink Input #42
+//       Corked: no
+//       Properties:
+//           application.name = "Firefox"
+//           application.process.binary = "firefox"
+fn pactl_streams(list: &str, direction: &'static str) -> Vec<(String, &'static str)> {
+    let Ok(out) = silent_cmd("pactl").args(["list", list]).output() else {
+        return Vec::new();
+    };
+    let text = String::from_utf8_lossy(&out.stdout);
+    let mut streams = Vec::new();
+    let mut block: Vec<&str> = Vec::new();
+    // A trailing empty line closes the last block
+    for line in text.lines().chain([""]) {
+        if !line.trim().is_empty() {
+            block.push(line.trim());
+            continue;
+        }
+        let corked = block.contains(&"Corked: yes");
+        let property = |key: &str| {
+            block.iter().find_map(|l| {
+                let value = l.strip_prefix(key)?.trim_start().strip_prefix('=')?;
+                Some(value.trim().trim_matches('"').to_string())
+            })
+        };
+        if !block.is_empty() && !corked {
+            if let Some(process) = property("application.process.binary").or_else(|| property("application.name")) {
+                streams.push((process.to_lowercase(), direction));
+            }
+        }
+        block.clear();
+    }
+    streams
+}
@@ -0,0 +1,107 @@
+// ─────────────────────────────────────────────────────────────────
+//  audit.rs — Audit trail for admin actions
+//
+//  Every admin action is appended to `<dir>/audit.log` (JSON lines)
+//  and mirrored to Redis. Interactive sessions additionally get a
+//  full transcript in asciicast v2 format, replayable with
+//  `asciinema play <file>`.
+// ─────────────────────────────────────────────────────────────────
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::config::AuditConfig;
+use crate::models::AuditEvent;
+use crate::store::Store;
+
+#[derive(Clone)]
+pub struct Audit {
+    dir: PathBuf,
+    hostname: String,
+    store: Store,
+}
+
+impl Audit {
+    pub fn new(cfg: &AuditConfig, hostname: String, store: Store) -> Self {
+        Self { dir: PathBuf::from(&cfg.dir), hostname, store }
+    }
+
+    /// Record `action` by `actor` locally and in Redis.
+    pub async fn record(&self, action: &str, actor: &str, detail: Option<String>) {
+        let event = AuditEvent {
+            hostname: self.hostname.clone(),
+            action: action.to_string(),
+            actor: actor.to_string(),
+            detail,
+            timestamp: Utc::now(),
+        };
+        info!("📝 Audit: {action} by {actor}");
+
+        let line = serde_json::to_string(&event).unwrap_or_default();
+        let appended = std::fs::create_dir_all(&self.dir).and_then(|_| {
+            let mut f = OpenOptions::new().create(true).append(true).open(self.dir.join("audit.log"))?;
+            writeln!(f, "{line}")
+        });
+        if let Err(e) = appended {
+            warn!("Failed to write audit log: {e}");
+        }
+        self.store.record_audit(&event).await;
+    }
+
+    /// Start a transcript file named after `kind` and the current time.
+    pub fn transcript(&self, kind: &str, cols: u16, rows: u16) -> anyhow::Result<Transcript> {
+        std::fs::create_dir_all(&self.dir)?;
+        let now = Utc::now();
+        let path = self.dir.join(format!("{kind}-{}.cast", now.format("%Y%m%d-%H%M%S%3f")));
+        let mut out = BufWriter::new(File::create(&path)?);
+        let header = serde_json::json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": now.timestamp(),
+            "title": format!("{kind} on {}", self.hostname),
+        });
+        writeln!(out, "{header}")?;
+        Ok(Transcript { path, started: Instant::now(), out: Mutex::new(out) })
+    }
+}
+
+/// asciicast v2 recording of one session. Shared by the threads that
+/// pump input and output, hence the interior mutex.
+pub struct Transcript {
+    pub path: PathBuf,
+    started: Instant,
+    out: Mutex<BufWriter<File>>,
+}
+
+impl Transcript {
+    /// Bytes the terminal printed.
+    pub fn output(&self, data: &[u8]) {
+        self.event("o", &String::from_utf8_lossy(data));
+    }
+
+    /// Bytes the remote user typed.
+    pub fn input(&self, data: &[u8]) {
+        self.event("i", &String::from_utf8_lossy(data));
+    }
+
+    /// Terminal resized to `cols`x`rows`.
+    pub fn resize(&self, cols: u16, rows: u16) {
+        self.event("r", &format!("{cols}x{rows}"));
+    }
+
+    fn event(&self, code: &str, data: &str) {
+        let t = self.started.elapsed().as_secs_f64();
+        let line = serde_json::json!([t, code, data]);
+        if let Ok(mut out) = self.out.lock() {
+            // Flush each event so a crash never loses the tail of a session
+            let _ = writeln!(out, "{line}").and_then(|_| out.flush());
+        }
+    }
+}
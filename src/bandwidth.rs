@@ -0,0 +1,186 @@
+// ─────────────────────────────────────────────────────────────────
+//  bandwidth.rs — Per-process network usage
+//
+//  Samples cumulative byte counters from the OS and turns the
+//  difference between two samples into per-app throughput:
+//    Linux:  ss -tinp (tcp_info bytes_acked / bytes_received per socket)
+//            — TCP only: QUIC / UDP traffic (YouTube, most video calls)
+//            has no per-socket byte counters and isn't counted
+//    macOS:  nettop -P (per-process bytes_in / bytes_out, any protocol)
+//  Windows has no per-process counters without ETW, so sampling is
+//  unavailable there and the feature isn't advertised (see
+//  capabilities.rs).
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use tracing::{info, warn};
+
+use crate::models::AppTraffic;
+use crate::monitor::silent_cmd;
+
+/// Whether this platform has per-process counters to sample.
+pub const SUPPORTED: bool = cfg!(any(target_os = "linux", target_os = "macos"));
+
+/// Cumulative counters of one socket (Linux) or process (macOS).
+#[derive(Debug, Clone)]
+struct Counter {
+    app: String,
+    sent: u64,
+    recv: u64,
+}
+
+struct Inner {
+    /// Counters from the previous sample, keyed by socket / pid.
+    prev: HashMap<String, Counter>,
+    prev_at: Option<Instant>,
+    top: Vec<AppTraffic>,
+}
+
+/// Samples per-app throughput. Shared between the monitor (which
+/// samples and checks limits) and the heartbeat (which reads `top_talkers`).
+pub struct BandwidthMonitor {
+    top_n: usize,
+    inner: Mutex<Inner>,
+}
+
+impl BandwidthMonitor {
+    pub fn new(top_n: usize) -> Self {
+        if !SUPPORTED {
+            info!("Per-process bandwidth accounting is not available on this platform");
+        }
+        Self {
+            top_n,
+            inner: Mutex::new(Inner { prev: HashMap::new(), prev_at: None, top: Vec::new() }),
+        }
+    }
+
+    /// Busiest apps from the latest sample.
+    pub fn top_talkers(&self) -> Vec<AppTraffic> {
+        self.inner.lock().map(|i| i.top.clone()).unwrap_or_default()
+    }
+
+    /// Take a sample and return every app's throughput since the previous
+    /// one, busiest first. The first call only records a baseline.
+    /// Blocking: shells out.
+    pub fn sample(&self) -> Vec<AppTraffic> {
+        let Some(current) = read_counters() else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+
+        let previous = std::mem::replace(&mut inner.prev, current);
+        let Some(prev_at) = inner.prev_at.replace(now) else {
+            return Vec::new();
+        };
+
+        let mut per_app: HashMap<String, (u64, u64)> = HashMap::new();
+        for (key, c) in &inner.prev {
+            // Sockets opened since the last sample count from zero
+            let (sent, recv) = previous.get(key).map(|p| (p.sent, p.recv)).unwrap_or((0, 0));
+            let entry = per_app.entry(c.app.clone()).or_default();
+            entry.0 += c.sent.saturating_sub(sent);
+            entry.1 += c.recv.saturating_sub(recv);
+        }
+
+        let secs = now.duration_since(prev_at).as_secs_f64().max(1.0);
+        let mut apps: Vec<AppTraffic> = per_app
+            .into_iter()
+            .filter(|(_, (sent, recv))| sent + recv > 0)
+            .map(|(app, (sent, recv))| AppTraffic {
+                app,
+                sent_bytes_per_sec: (sent as f64 / secs) as u64,
+                recv_bytes_per_sec: (recv as f64 / secs) as u64,
+            })
+            .collect();
+        apps.sort_by_key(|a| std::cmp::Reverse(a.sent_bytes_per_sec + a.recv_bytes_per_sec));
+        inner.top = apps.iter().take(self.top_n).cloned().collect();
+        apps
+    }
+}
+
+/// Current cumulative counters, or None where they're unavailable.
+fn read_counters() -> Option<HashMap<String, Counter>> {
+    let result = if cfg!(target_os = "linux") {
+        silent_cmd("ss")
+            .args(["-Htinp", "state", "established"])
+            .output()
+            .map(|o| parse_ss(&String::from_utf8_lossy(&o.stdout)))
+    } else if cfg!(target_os = "macos") {
+        silent_cmd("nettop")
+            .args(["-P", "-L", "1", "-x", "-J", "bytes_in,bytes_out"])
+            .output()
+            .map(|o| parse_nettop(&String::from_utf8_lossy(&o.stdout)))
+    } else {
+        return None;
+    };
+
+    match result {
+        Ok(counters) => Some(counters),
+        Err(e) => {
+            warn!("Could not sample network usage: {e}");
+            None
+        }
+    }
+}
+
+// Each socket is a header line followed by an indented tcp_info line:
+//   0  0  10.0.0.5:51234  142.250.74.46:443  users:(("chrome",pid=1234,fd=42))
+//        cubic wscale:7,7 ... bytes_acked:1234 bytes_received:5678 ...
+fn parse_ss(out: &str) -> HashMap<String, Counter> {
+    let mut counters = HashMap::new();
+    let mut socket: Option<(String, String)> = None;
+    for line in out.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            // Loopback traffic never leaves the machine
+            socket = (cols.len() >= 5 && !is_loopback(cols[3]))
+                .then(|| {
+                    let app = cols[4].split('"').nth(1)?.to_lowercase();
+                    Some((format!("{}-{}", cols[2], cols[3]), app))
+                })
+                .flatten();
+            continue;
+        }
+        let Some((key, app)) = socket.take() else {
+            continue;
+        };
+        let field = |name: &str| {
+            line.split_whitespace()
+                .find_map(|f| f.strip_prefix(name))
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        counters.insert(key, Counter { app, sent: field("bytes_acked:"), recv: field("bytes_received:") });
+    }
+    counters
+}
+
+fn is_loopback(addr: &str) -> bool {
+    addr.rsplit_once(':')
+        .and_then(|(ip, _)| ip.trim_matches(|c| c == '[' || c == ']').parse::<std::net::IpAddr>().ok())
+        .is_some_and(|ip| ip.is_loopback())
+}
+
+//   time,,bytes_in,bytes_out,
+//   12:00:00.123456,Google Chrome H.1234,5678,1234,
+fn parse_nettop(out: &str) -> HashMap<String, Counter> {
+    let mut counters = HashMap::new();
+    for line in out.lines().skip(1) {
+        let cols: Vec<&str> = line.split(',').collect();
+        if cols.len() < 4 {
+            continue;
+        }
+        let Some((app, pid)) = cols[1].rsplit_once('.') else {
+            continue;
+        };
+        let (Ok(recv), Ok(sent)) = (cols[2].parse(), cols[3].parse()) else {
+            continue;
+        };
+        counters.insert(pid.to_string(), Counter { app: app.to_lowercase(), sent, recv });
+    }
+    counters
+}
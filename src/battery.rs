@@ -0,0 +1,57 @@
+// ─────────────────────────────────────────────────────────────────
+//  battery.rs — Laptop battery charge for heartbeats
+//
+//  Lets the dashboard warn before a laptop dies mid-test:
+//    Windows: Win32_Battery (EstimatedChargeRemaining, BatteryStatus)
+//    macOS:   pmset -g batt
+//    Linux:   /sys/class/power_supply/BAT*/{capacity,status}
+//  Desktops report no battery.
+// ─────────────────────────────────────────────────────────────────
+
+use crate::models::Battery;
+use crate::monitor::silent_cmd;
+
+/// The (first) battery's state, None without one. Blocking.
+pub fn status() -> Option<Battery> {
+    if cfg!(target_os = "windows") {
+        windows_status()
+    } else if cfg!(target_os = "macos") {
+        mac_status()
+    } else {
+        linux_status()
+    }
+}
+
+// BatteryStatus 1 = discharging; 2 = on AC, 3 = full, 6-9 = charging
+fn windows_status() -> Option<Battery> {
+    let script = "Get-CimInstance Win32_Battery | Select-Object -First 1 | ForEach-Object { \"$($_.EstimatedChargeRemaining) $($_.BatteryStatus)\" }";
+    let out = silent_cmd("powershell").args(["-NoProfile", "-Command", script]).output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let (percent, status) = text.trim().split_once(' ')?;
+    Some(Battery { percent: percent.parse::<u8>().ok()?.min(100), charging: status.trim() != "1" })
+}
+
+//  -InternalBattery-0 (id=4653155)	87%; discharging; 3:12 remaining present: true
+fn mac_status() -> Option<Battery> {
+    let out = silent_cmd("pmset").args(["-g", "batt"]).output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let line = text.lines().find(|l| l.contains("InternalBattery"))?;
+    let (_, rest) = line.split_once('\t')?;
+    let mut fields = rest.split(';').map(str::trim);
+    let percent = fields.next()?.strip_suffix('%')?.parse::<u8>().ok()?;
+    Some(Battery { percent: percent.min(100), charging: fields.next() != Some("discharging") })
+}
+
+fn linux_status() -> Option<Battery> {
+    let mut supplies: Vec<_> = std::fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|e| e.file_name().to_string_lossy().starts_with("BAT"))
+        .map(|e| e.path())
+        .collect();
+    supplies.sort();
+    let dir = supplies.first()?;
+    let percent = std::fs::read_to_string(dir.join("capacity")).ok()?.trim().parse::<u8>().ok()?;
+    let status = std::fs::read_to_string(dir.join("status")).unwrap_or_default();
+    Some(Battery { percent: percent.min(100), charging: status.trim() != "Discharging" })
+}
@@ -0,0 +1,179 @@
+// ─────────────────────────────────────────────────────────────────
+//  blocker.rs — OS firewall rules for banned domains
+//
+//  Resolves banned domains to IP addresses (through the upstream
+//  resolvers, past our own hosts-file block) and installs outbound
+//  block rules so the sites are unreachable, not just detected:
+//    Windows: netsh advfirewall (rules named "nishack-block"); netsh
+//             programs WFP filters itself, the agent doesn't call the
+//             WFP API directly
+//    Linux:   nftables table `inet nishack`
+//    macOS:   pf anchor `com.apple/nishack` (evaluated by the stock pf.conf)
+//  Everything we add lives under our own name and is removed on clear().
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::process::Stdio;
+
+use tracing::{info, warn};
+
+use crate::monitor::silent_cmd;
+use crate::upstream_dns::DomainResolver;
+
+#[cfg(target_os = "windows")]
+const RULE_NAME: &str = "nishack-block";
+/// netsh limits the length of one `remoteip=` list.
+#[cfg(target_os = "windows")]
+const IPS_PER_RULE: usize = 100;
+#[cfg(target_os = "macos")]
+const PF_ANCHOR: &str = "com.apple/nishack";
+
+/// Tracks the set of IPs currently blocked by our firewall rules.
+pub struct FirewallBlocker {
+    blocked: BTreeSet<IpAddr>,
+    resolver: DomainResolver,
+}
+
+impl FirewallBlocker {
+    /// `resolvers`: DNS servers banned domains are looked up with.
+    pub fn new(resolvers: Vec<SocketAddr>) -> Self {
+        Self { blocked: BTreeSet::new(), resolver: DomainResolver::start(resolvers) }
+    }
+
+    /// Start resolving `domains` (plus `www.` variants) in the background;
+    /// `poll` installs the rules once that's done.
+    pub fn request<'a>(&mut self, domains: impl IntoIterator<Item = &'a String>) {
+        self.resolver.request(domains);
+    }
+
+    /// Once the last request is resolved, replace our rules so they block
+    /// exactly the resulting addresses. Never waits for DNS.
+    pub fn poll(&mut self) -> anyhow::Result<()> {
+        let Some(resolved) = self.resolver.finished() else {
+            return Ok(());
+        };
+        let ips: BTreeSet<IpAddr> = resolved.into_keys().collect();
+        if ips == self.blocked {
+            return Ok(());
+        }
+
+        if ips.is_empty() {
+            self.clear();
+            return Ok(());
+        }
+
+        install_rules(&ips)?;
+        info!("🧱 Firewall: blocking {} address(es) of banned domains", ips.len());
+        self.blocked = ips;
+        Ok(())
+    }
+
+    /// Remove every rule we installed (and drop a lookup still running).
+    pub fn clear(&mut self) {
+        self.resolver.discard();
+        match remove_rules() {
+            Ok(()) => {
+                if !self.blocked.is_empty() {
+                    info!("Firewall: block rules removed");
+                }
+                self.blocked.clear();
+            }
+            Err(e) => warn!("Failed to remove firewall rules: {e}"),
+        }
+    }
+}
+
+/// Run a command feeding `script` on stdin; errors carry stderr.
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+fn run_with_stdin(program: &str, args: &[&str], script: &str) -> anyhow::Result<()> {
+    let mut child = silent_cmd(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("{program} not available: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(script.as_bytes())?;
+    }
+    let out = child.wait_with_output()?;
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("{program} failed: {}", String::from_utf8_lossy(&out.stderr).trim()))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn install_rules(ips: &BTreeSet<IpAddr>) -> anyhow::Result<()> {
+    remove_rules()?;
+    let ips: Vec<String> = ips.iter().map(IpAddr::to_string).collect();
+    for chunk in ips.chunks(IPS_PER_RULE) {
+        let status = silent_cmd("netsh")
+            .args([
+                "advfirewall", "firewall", "add", "rule",
+                &format!("name={RULE_NAME}"),
+                "dir=out", "action=block", "enable=yes",
+                &format!("remoteip={}", chunk.join(",")),
+            ])
+            .output()?;
+        if !status.status.success() {
+            return Err(anyhow::anyhow!("netsh add rule failed: {}", String::from_utf8_lossy(&status.stdout).trim()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn remove_rules() -> anyhow::Result<()> {
+    // Deletes every rule with our name; "no rules match" is not an error.
+    silent_cmd("netsh")
+        .args(["advfirewall", "firewall", "delete", "rule", &format!("name={RULE_NAME}")])
+        .output()?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn install_rules(ips: &BTreeSet<IpAddr>) -> anyhow::Result<()> {
+    let v4: Vec<String> = ips.iter().filter(|ip| ip.is_ipv4()).map(IpAddr::to_string).collect();
+    let v6: Vec<String> = ips.iter().filter(|ip| ip.is_ipv6()).map(IpAddr::to_string).collect();
+
+    // Recreate the whole table in one atomic nft transaction.
+    let _ = remove_rules();
+    let mut script = String::from(
+        "table inet nishack {\n  chain output {\n    type filter hook output priority 0; policy accept;\n",
+    );
+    if !v4.is_empty() {
+        script.push_str(&format!("    ip daddr {{ {} }} drop\n", v4.join(", ")));
+    }
+    if !v6.is_empty() {
+        script.push_str(&format!("    ip6 daddr {{ {} }} drop\n", v6.join(", ")));
+    }
+    script.push_str("  }\n}\n");
+    run_with_stdin("nft", &["-f", "-"], &script)
+}
+
+#[cfg(target_os = "linux")]
+fn remove_rules() -> anyhow::Result<()> {
+    // Ignore "table does not exist".
+    let _ = silent_cmd("nft").args(["delete", "table", "inet", "nishack"]).output()?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn install_rules(ips: &BTreeSet<IpAddr>) -> anyhow::Result<()> {
+    let list: Vec<String> = ips.iter().map(IpAddr::to_string).collect();
+    let rules = format!("block drop out quick to {{ {} }}\n", list.join(", "));
+    run_with_stdin("pfctl", &["-a", PF_ANCHOR, "-f", "-"], &rules)?;
+    // Make sure pf is enabled (no-op if it already is).
+    let _ = silent_cmd("pfctl").arg("-E").output();
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn remove_rules() -> anyhow::Result<()> {
+    silent_cmd("pfctl").args(["-a", PF_ANCHOR, "-F", "all"]).output()?;
+    Ok(())
+}
@@ -0,0 +1,335 @@
+// ─────────────────────────────────────────────────────────────────
+//  browser.rs — Browser profile discovery, history and extensions
+//
+//  Locates Chrome / Edge / Firefox profiles for the current user,
+//  reads their SQLite history databases and lists installed
+//  extensions. Browsers keep the history database locked while
+//  running, so we always work on a temporary copy.
+// ─────────────────────────────────────────────────────────────────
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{Connection, OpenFlags};
+
+/// Seconds between 1601-01-01 (Chromium/WebKit epoch) and 1970-01-01.
+const WEBKIT_EPOCH_OFFSET_SECS: i64 = 11_644_473_600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowserFamily {
+    Chromium,
+    Firefox,
+}
+
+/// One browser profile directory on disk.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// "chrome" | "edge" | "chromium" | "firefox"
+    pub browser: &'static str,
+    pub family: BrowserFamily,
+    pub dir: PathBuf,
+}
+
+impl Profile {
+    /// Path of the history database inside this profile.
+    pub fn history_db(&self) -> PathBuf {
+        match self.family {
+            BrowserFamily::Chromium => self.dir.join("History"),
+            BrowserFamily::Firefox => self.dir.join("places.sqlite"),
+        }
+    }
+}
+
+/// A single page visit read from a history database.
+#[derive(Debug, Clone)]
+pub struct Visit {
+    pub url: String,
+    pub visited_at: DateTime<Utc>,
+}
+
+// ── Profile discovery ───────────────────────────────────────────
+
+fn env_dir(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var).map(PathBuf::from)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env_dir("USERPROFILE").or_else(|| env_dir("HOME"))
+}
+
+/// Root directories holding the profiles of each supported browser.
+fn browser_roots() -> Vec<(&'static str, BrowserFamily, PathBuf)> {
+    let mut roots = Vec::new();
+
+    if cfg!(target_os = "windows") {
+        if let Some(local) = env_dir("LOCALAPPDATA") {
+            roots.push(("chrome", BrowserFamily::Chromium, local.join(r"Google\Chrome\User Data")));
+            roots.push(("edge", BrowserFamily::Chromium, local.join(r"Microsoft\Edge\User Data")));
+        }
+        if let Some(roaming) = env_dir("APPDATA") {
+            roots.push(("firefox", BrowserFamily::Firefox, roaming.join(r"Mozilla\Firefox\Profiles")));
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(home) = home_dir() {
+            let support = home.join("Library/Application Support");
+            roots.push(("chrome", BrowserFamily::Chromium, support.join("Google/Chrome")));
+            roots.push(("edge", BrowserFamily::Chromium, support.join("Microsoft Edge")));
+            roots.push(("firefox", BrowserFamily::Firefox, support.join("Firefox/Profiles")));
+        }
+    } else if let Some(home) = home_dir() {
+        let config = home.join(".config");
+        roots.push(("chrome", BrowserFamily::Chromium, config.join("google-chrome")));
+        roots.push(("chromium", BrowserFamily::Chromium, config.join("chromium")));
+        roots.push(("edge", BrowserFamily::Chromium, config.join("microsoft-edge")));
+        roots.push(("firefox", BrowserFamily::Firefox, home.join(".mozilla/firefox")));
+    }
+
+    roots
+}
+
+/// Enumerate every browser profile found for the current user.
+///
+/// Chromium keeps profiles as `Default`, `Profile 1`, ... under its user-data
+/// dir; Firefox uses randomly-named directories under `Profiles`. In both
+/// cases a profile is any sub-directory that contains a history database.
+pub fn discover_profiles() -> Vec<Profile> {
+    let mut profiles = Vec::new();
+
+    for (browser, family, root) in browser_roots() {
+        let Ok(entries) = std::fs::read_dir(&root) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            if !dir.is_dir() {
+                continue;
+            }
+            let profile = Profile { browser, family, dir };
+            if profile.history_db().exists() {
+                profiles.push(profile);
+            }
+        }
+    }
+
+    profiles
+}
+
+// ── History reading ─────────────────────────────────────────────
+
+/// Copy a (possibly locked) SQLite database plus its WAL file into the temp
+/// dir so we can open it without fighting the browser for the lock.
+fn copy_database(src: &Path, tag: &str) -> anyhow::Result<PathBuf> {
+    let dst = std::env::temp_dir().join(format!("nishack-{}-{tag}.sqlite", std::process::id()));
+    std::fs::copy(src, &dst)?;
+
+    let wal = PathBuf::from(format!("{}-wal", src.display()));
+    if wal.exists() {
+        let _ = std::fs::copy(&wal, format!("{}-wal", dst.display()));
+    }
+    Ok(dst)
+}
+
+fn remove_database(path: &Path) {
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(format!("{}-wal", path.display()));
+    let _ = std::fs::remove_file(format!("{}-shm", path.display()));
+}
+
+/// Read all visits in `profile` that happened strictly after `since`,
+/// oldest first.
+pub fn read_history_since(profile: &Profile, since: DateTime<Utc>) -> anyhow::Result<Vec<Visit>> {
+    let tag = format!(
+        "{}-{}",
+        profile.browser,
+        profile.dir.file_name().map(|n| n.to_string_lossy().replace(' ', "_")).unwrap_or_default()
+    );
+    let copy = copy_database(&profile.history_db(), &tag)?;
+    let result = query_visits(&copy, profile.family, since);
+    remove_database(&copy);
+    result
+}
+
+fn query_visits(db: &Path, family: BrowserFamily, since: DateTime<Utc>) -> anyhow::Result<Vec<Visit>> {
+    let conn = Connection::open_with_flags(
+        db,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+
+    // Both browsers store microseconds, but from different epochs.
+    let since_unix_us = since.timestamp_micros();
+    let (sql, offset_us) = match family {
+        BrowserFamily::Chromium => (
+            "SELECT urls.url, visits.visit_time FROM visits \
+             JOIN urls ON urls.id = visits.url \
+             WHERE visits.visit_time > ?1 ORDER BY visits.visit_time",
+            WEBKIT_EPOCH_OFFSET_SECS * 1_000_000,
+        ),
+        BrowserFamily::Firefox => (
+            "SELECT moz_places.url, moz_historyvisits.visit_date FROM moz_historyvisits \
+             JOIN moz_places ON moz_places.id = moz_historyvisits.place_id \
+             WHERE moz_historyvisits.visit_date > ?1 ORDER BY moz_historyvisits.visit_date",
+            0,
+        ),
+    };
+
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([since_unix_us + offset_us], |row| {
+        let url: String = row.get(0)?;
+        let time: i64 = row.get(1)?;
+        Ok((url, time - offset_us))
+    })?;
+
+    let mut visits = Vec::new();
+    for row in rows.flatten() {
+        let (url, unix_us) = row;
+        if let Some(visited_at) = Utc.timestamp_micros(unix_us).single() {
+            visits.push(Visit { url, visited_at });
+        }
+    }
+    Ok(visits)
+}
+
+/// The browser (as in `Profile::browser`) a process name belongs to.
+#[cfg_attr(not(feature = "streaming"), allow(dead_code))]
+pub fn browser_of_process(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    Some(match name.strip_suffix(".exe").unwrap_or(&name) {
+        "msedge" | "microsoft edge" => "edge",
+        "chromium" | "chromium-browser" => "chromium",
+        "chrome" | "google chrome" | "google-chrome" => "chrome",
+        "firefox" | "firefox-esr" | "firefox-bin" => "firefox",
+        _ => return None,
+    })
+}
+
+/// Host part of a URL, lowercased. Returns `None` for non-web URLs
+/// (`chrome://`, `about:`, `file://` ...).
+pub fn url_host(raw: &str) -> Option<String> {
+    let parsed = url::Url::parse(raw).ok()?;
+    match parsed.scheme() {
+        "http" | "https" => parsed.host_str().map(str::to_lowercase),
+        _ => None,
+    }
+}
+
+/// Does `host` belong to `domain` (exact match or any sub-domain)?
+pub fn host_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.'))
+}
+
+// ── Extension enumeration ───────────────────────────────────────
+
+/// An extension installed in a browser profile.
+#[derive(Debug, Clone)]
+pub struct Extension {
+    pub browser: &'static str,
+    /// Store id (Chromium: 32-char id, Firefox: add-on id / e-mail style)
+    pub id: String,
+    pub name: String,
+    /// Directory (Chromium) or .xpi file (Firefox) to delete on removal.
+    pub path: PathBuf,
+}
+
+/// List all extensions installed in `profile`.
+pub fn list_extensions(profile: &Profile) -> Vec<Extension> {
+    match profile.family {
+        BrowserFamily::Chromium => chromium_extensions(profile),
+        BrowserFamily::Firefox => firefox_extensions(profile),
+    }
+}
+
+/// Chromium layout: `<profile>/Extensions/<id>/<version>/manifest.json`
+fn chromium_extensions(profile: &Profile) -> Vec<Extension> {
+    let Ok(entries) = std::fs::read_dir(profile.dir.join("Extensions")) else {
+        return Vec::new();
+    };
+
+    let mut out = Vec::new();
+    for entry in entries.flatten() {
+        let ext_dir = entry.path();
+        let id = entry.file_name().to_string_lossy().to_string();
+
+        // Any version directory will do — they share the same name.
+        let Some(version_dir) = std::fs::read_dir(&ext_dir)
+            .ok()
+            .and_then(|mut d| d.find_map(|e| e.ok().map(|e| e.path()).filter(|p| p.is_dir())))
+        else {
+            continue;
+        };
+        let Ok(raw) = std::fs::read_to_string(version_dir.join("manifest.json")) else {
+            continue;
+        };
+        let Ok(manifest) = serde_json::from_str::<serde_json::Value>(&raw) else {
+            continue;
+        };
+
+        let name = manifest["name"].as_str().unwrap_or_default();
+        let name = resolve_chromium_message(&version_dir, &manifest, name);
+        out.push(Extension {
+            browser: profile.browser,
+            id,
+            name,
+            path: ext_dir,
+        });
+    }
+    out
+}
+
+/// Chromium manifests often use `__MSG_appName__` placeholders; look the
+/// real name up in `_locales/<default_locale>/messages.json`.
+fn resolve_chromium_message(version_dir: &Path, manifest: &serde_json::Value, name: &str) -> String {
+    let Some(key) = name.strip_prefix("__MSG_").and_then(|n| n.strip_suffix("__")) else {
+        return name.to_string();
+    };
+    let locale = manifest["default_locale"].as_str().unwrap_or("en");
+    let messages = std::fs::read_to_string(version_dir.join("_locales").join(locale).join("messages.json"))
+        .ok()
+        .and_then(|raw| serde_json::from_str::<serde_json::Value>(&raw).ok());
+
+    messages
+        .and_then(|m| {
+            m.as_object()?
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .and_then(|(_, v)| v["message"].as_str().map(String::from))
+        })
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Firefox keeps its add-on registry in `<profile>/extensions.json`.
+fn firefox_extensions(profile: &Profile) -> Vec<Extension> {
+    let Ok(raw) = std::fs::read_to_string(profile.dir.join("extensions.json")) else {
+        return Vec::new();
+    };
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(&raw) else {
+        return Vec::new();
+    };
+
+    json["addons"]
+        .as_array()
+        .map(|addons| {
+            addons
+                .iter()
+                // Skip built-in system add-ons and themes
+                .filter(|a| a["location"].as_str() == Some("app-profile") && a["type"].as_str() == Some("extension"))
+                .filter_map(|a| {
+                    Some(Extension {
+                        browser: profile.browser,
+                        id: a["id"].as_str()?.to_string(),
+                        name: a["defaultLocale"]["name"].as_str().unwrap_or_default().to_string(),
+                        path: PathBuf::from(a["path"].as_str()?),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Delete an extension from disk. The browser drops it on next start.
+pub fn remove_extension(ext: &Extension) -> std::io::Result<()> {
+    if ext.path.is_dir() {
+        std::fs::remove_dir_all(&ext.path)
+    } else {
+        std::fs::remove_file(&ext.path)
+    }
+}
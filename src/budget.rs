@@ -0,0 +1,57 @@
+// ─────────────────────────────────────────────────────────────────
+//  budget.rs — Keeping the agent out of the student's way
+//
+//  With `[monitor.budget] lower_priority` the agent drops to
+//  below-normal scheduling priority at startup; the PowerShell,
+//  ipconfig and ss runs it starts inherit it:
+//    Windows: PriorityClass BelowNormal
+//    macOS:   renice +10
+//    Linux:   nice +10 on every thread (Linux priorities are per
+//             thread; threads started later inherit it)
+//  Staggering the detector groups and skipping the expensive ones
+//  under load happen in the monitor (`Monitor::scan_phase`).
+// ─────────────────────────────────────────────────────────────────
+
+use tracing::{info, warn};
+
+use crate::monitor::silent_cmd;
+
+/// Niceness the agent runs at on macOS / Linux.
+#[cfg_attr(target_os = "windows", allow(dead_code))]
+const NICE: i32 = 10;
+
+/// Lower the agent's own priority. Blocking.
+pub fn lower_priority() {
+    let pid = std::process::id();
+    let lowered = if cfg!(target_os = "windows") {
+        let script = format!("(Get-Process -Id {pid}).PriorityClass = 'BelowNormal'");
+        silent_cmd("powershell").args(["-NoProfile", "-Command", &script]).status().is_ok_and(|s| s.success())
+    } else if cfg!(target_os = "macos") {
+        silent_cmd("renice").args(["-n", &NICE.to_string(), "-p", &pid.to_string()]).status().is_ok_and(|s| s.success())
+    } else {
+        renice_threads()
+    };
+    if lowered {
+        info!("🐢 Running at below-normal priority");
+    } else {
+        warn!("Could not lower the agent's priority");
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn renice_threads() -> bool {
+    let Ok(tasks) = std::fs::read_dir("/proc/self/task") else {
+        return false;
+    };
+    let mut all = true;
+    for tid in tasks.filter_map(Result::ok).filter_map(|t| t.file_name().to_string_lossy().parse::<libc::id_t>().ok()) {
+        // SAFETY: plain syscall on a thread id of this process
+        all &= unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, NICE) } == 0;
+    }
+    all
+}
+
+#[cfg(not(target_os = "linux"))]
+fn renice_threads() -> bool {
+    false
+}
@@ -0,0 +1,129 @@
+// ─────────────────────────────────────────────────────────────────
+//  capabilities.rs — What this agent can do on this machine
+//
+//  Advertised when registering in Redis, in the streaming handshake
+//  and at `GET /capabilities`, so the teacher server can hide
+//  controls and skip commands an agent would only fail at. Screenshots,
+//  streaming and webcam-use reporting also depend on the Cargo features
+//  the binary was built with.
+//  Webcam capture, H.264 encoding and OCR aren't built into the agent
+//  yet and are always reported as unavailable.
+// ─────────────────────────────────────────────────────────────────
+
+use crate::bandwidth;
+use crate::commands;
+use crate::config::AppConfig;
+use crate::models::Capabilities;
+use crate::platform;
+
+/// Version of the API / Redis / command protocol. Bumped on changes
+/// older teacher servers can't handle.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Probe the machine and config. Enumerates displays, so call it off
+/// the async runtime.
+pub fn detect(cfg: &AppConfig) -> Capabilities {
+    let monitors = monitor_count();
+    let screenshots = cfg!(feature = "screenshots") && cfg.screenshots.enabled;
+    let streaming = cfg!(feature = "streaming") && cfg.streaming.enabled;
+
+    let mut degraded: Vec<String> = platform::build_mismatch().into_iter().collect();
+    if cfg.screenshots.enabled && !cfg!(feature = "screenshots") {
+        degraded.push("screenshots enabled but not built in".into());
+    }
+    if cfg.streaming.enabled && !cfg!(feature = "streaming") {
+        degraded.push("streaming enabled but not built in".into());
+    }
+    if cfg.monitor.bandwidth.enabled && !bandwidth::SUPPORTED {
+        degraded.push("bandwidth accounting not available on this platform".into());
+    }
+    if cfg.monitor.webcam.enabled && !cfg!(feature = "webcam") {
+        degraded.push("webcam use enabled but not built in".into());
+    }
+    if (screenshots || streaming) && monitors == 0 {
+        degraded.push("no display found for screen capture".into());
+    }
+
+    Capabilities {
+        protocol_version: PROTOCOL_VERSION,
+        platform: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        native_arch: platform::native_arch(),
+        version: env!("CARGO_PKG_VERSION"),
+        monitors,
+        multi_monitor: monitors > 1,
+        exam_mode: true,
+        webcam: false,
+        h264: false,
+        ocr: false,
+        screenshots,
+        streaming,
+        shell: cfg.api.admin_token.as_deref().is_some_and(|t| !t.is_empty()),
+        commands: if cfg.commands.enabled { commands::actions().collect() } else { Vec::new() },
+        build_features: build_features(),
+        features: features(cfg),
+        config_hash: cfg.source_hash.clone(),
+        degraded,
+    }
+}
+
+fn build_features() -> Vec<&'static str> {
+    [
+        ("screenshots", cfg!(feature = "screenshots")),
+        ("streaming", cfg!(feature = "streaming")),
+        ("webcam", cfg!(feature = "webcam")),
+    ]
+    .into_iter()
+    .filter_map(|(name, on)| on.then_some(name))
+    .collect()
+}
+
+/// Config sections that are switched on.
+fn features(cfg: &AppConfig) -> Vec<&'static str> {
+    let m = &cfg.monitor;
+    [
+        ("browser_history", m.browser_history.enabled),
+        ("downloads", m.downloads.enabled),
+        ("installed_apps", m.installed_apps.enabled),
+        ("documents", m.documents.enabled),
+        ("banned_keywords", !m.banned_keywords.is_empty()),
+        ("categories", !m.categories.is_empty()),
+        ("dns_sniffer", m.dns_sniffer.enabled),
+        ("dns_bypass", m.dns_bypass.enabled),
+        ("connections", m.connections.enabled),
+        ("bandwidth", m.bandwidth.enabled && bandwidth::SUPPORTED),
+        ("resource_abuse", m.resource_abuse.enabled),
+        ("remote_access", m.remote_access.enabled),
+        ("screen_share", m.screen_share.enabled),
+        ("audio", m.audio.enabled),
+        ("webcam_use", m.webcam.enabled && cfg!(feature = "webcam")),
+        ("vpn_proxy", m.vpn_proxy.enabled),
+        ("tamper", m.tamper.enabled),
+        ("hosts_file", m.enforcement.hosts_file),
+        ("firewall", m.enforcement.firewall),
+        ("unlock_codes", cfg.lock.unlock_codes.enabled),
+        ("inbox", cfg.commands.inbox),
+        ("alerts", cfg.alerts.enabled),
+        ("weekly_report", cfg.report.enabled),
+        ("schedule", !cfg.schedule.is_empty()),
+    ]
+    .into_iter()
+    .filter_map(|(name, on)| on.then_some(name))
+    .collect()
+}
+
+#[cfg(feature = "streaming")]
+fn monitor_count() -> usize {
+    xcap::Monitor::all().map(|m| m.len()).unwrap_or(0)
+}
+
+#[cfg(all(feature = "screenshots", not(feature = "streaming")))]
+fn monitor_count() -> usize {
+    screenshots::Screen::all().map(|s| s.len()).unwrap_or(0)
+}
+
+/// Built without capture code, so displays can't be enumerated.
+#[cfg(not(any(feature = "screenshots", feature = "streaming")))]
+fn monitor_count() -> usize {
+    0
+}
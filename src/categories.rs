@@ -0,0 +1,108 @@
+// ─────────────────────────────────────────────────────────────────
+//  categories.rs — Shared ban-list categories
+//
+//  Instead of every school maintaining hundreds of domains, admins
+//  enable named categories (`categories = ["games", "social"]`) whose
+//  lists are published once — at `[monitor.category_source] url` or
+//  in Redis at `{prefix}:category:{name}` — and refreshed
+//  periodically (and, for Redis-hosted lists, as soon as they change;
+//  see config_watch.rs). Category entries are added to the machine's lists
+//  (config.toml or the teacher's layers) before per-user profiles
+//  apply, and take the lists' default actions.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::config::CategorySourceConfig;
+use crate::models::{BanConfig, BanLayer, CategoryList};
+use crate::monitor::Monitor;
+use crate::store::Store;
+
+/// Refresh the enabled categories every `refresh_secs` and on `changes`,
+/// and hand the combined lists to the monitor whenever they change. Runs
+/// forever.
+pub async fn run(
+    names: Vec<String>,
+    source: CategorySourceConfig,
+    store: Store,
+    monitor: Arc<Mutex<Monitor>>,
+    mut changes: watch::Receiver<u64>,
+) {
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(30)).build() {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Ban-list categories disabled: failed to create HTTP client: {e}");
+            return;
+        }
+    };
+    info!("Ban-list categories enabled: {}", names.join(", "));
+
+    // Last good list per category, kept when a refresh fails
+    let mut lists: BTreeMap<String, CategoryList> = BTreeMap::new();
+    let mut applied = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(source.refresh_secs.max(60)));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            Ok(()) = changes.changed() => interval.reset(),
+        }
+        for name in &names {
+            match fetch(&client, &source, &store, name).await {
+                Some(list) => {
+                    lists.insert(name.clone(), list);
+                }
+                None => warn!("Could not refresh ban-list category {name:?}"),
+            }
+        }
+
+        let bans = combine(lists.values());
+        if applied.as_ref() == Some(&bans) {
+            continue;
+        }
+        info!(
+            "🗂️  Ban-list categories: {} processes, {} domains from {}/{} categories",
+            bans.banned_processes.len(),
+            bans.banned_domains.len(),
+            lists.len(),
+            names.len()
+        );
+        monitor.lock().unwrap_or_else(PoisonError::into_inner).set_category_bans(&bans);
+        applied = Some(bans);
+    }
+}
+
+async fn fetch(
+    client: &reqwest::Client,
+    source: &CategorySourceConfig,
+    store: &Store,
+    name: &str,
+) -> Option<CategoryList> {
+    let Some(url) = &source.url else {
+        return store.fetch_category(name).await;
+    };
+    let url = url.replace("{category}", name);
+    let resp = client.get(&url).send().await.and_then(|r| r.error_for_status());
+    match resp {
+        Ok(resp) => resp.json().await.map_err(|e| warn!("Malformed category list at {url}: {e}")).ok(),
+        Err(e) => {
+            warn!("Failed to fetch category list {url}: {e}");
+            None
+        }
+    }
+}
+
+fn combine<'a>(lists: impl Iterator<Item = &'a CategoryList>) -> BanConfig {
+    let layers: Vec<BanLayer> = lists
+        .map(|l| BanLayer {
+            banned_processes: l.processes.clone(),
+            banned_domains: l.domains.clone(),
+            ..Default::default()
+        })
+        .collect();
+    BanConfig::merge(&layers)
+}
@@ -0,0 +1,162 @@
+// ─────────────────────────────────────────────────────────────────
+//  commands.rs — Teacher commands over Redis pub/sub
+//
+//  The agent subscribes to its own host channel, the broadcast
+//  `{prefix}:commands:all` and one channel per location tag (see
+//  `Store::command_channels`), so a single publish on
+//  `{prefix}:commands:room:lab-204` locks the whole room. Each
+//  message is a JSON object with an "action"; the remaining fields
+//  are the body of the matching local API route, and the command is
+//  run through the same router as an HTTP request would be:
+//
+//    PUBLISH nishack:commands:room:lab-204 '{"action":"lock","mode":"hard"}'
+//    PUBLISH nishack:commands:pc-07 '{"action":"kill","name":"steam"}'
+//
+//  With `[redis] fallback_urls`, the subscription moves along whenever
+//  the agent switches servers.
+// ─────────────────────────────────────────────────────────────────
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{header, Request};
+use axum::Router;
+use futures_util::StreamExt;
+use tower::ServiceExt;
+use tracing::{info, warn};
+
+use crate::api::CommandChannel;
+use crate::audit::Audit;
+use crate::store::Store;
+
+/// Command actions and the API route each one runs. `{mode}` is taken from
+/// the command's "mode" field.
+const ACTIONS: &[(&str, &str)] = &[
+    ("lock", "/lock/{mode}"),
+    ("unlock", "/unlock"),
+    ("open_url", "/open-url"),
+    ("exam_start", "/exam/start"),
+    ("exam_stop", "/exam/stop"),
+    ("mute", "/audio/mute"),
+    ("unmute", "/audio/unmute"),
+    ("volume", "/audio/volume"),
+    ("wallpaper", "/wallpaper"),
+    ("wallpaper_restore", "/wallpaper/restore"),
+    ("lock_message", "/lock-message"),
+    ("lock_message_clear", "/lock-message/clear"),
+    ("snapshot", "/snapshot"),
+    ("focus", "/focus"),
+    ("focus_stop", "/focus/stop"),
+    ("message", "/message"),
+    ("kill", "/kill"),
+    ("capture", "/capture"),
+];
+
+/// Names of the supported command actions.
+pub fn actions() -> impl Iterator<Item = &'static str> {
+    ACTIONS.iter().map(|(name, _)| *name)
+}
+
+/// Largest API response we read back for the log.
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// Subscribe to `channels` and run every command received, resubscribing
+/// whenever the connection drops or the Redis server changes. Runs forever.
+pub async fn run(store: Store, router: Router, channels: Vec<String>, audit: Audit) {
+    let mut switches = store.server_switches();
+    loop {
+        if let Some(switches) = &mut switches {
+            switches.borrow_and_update();
+        }
+        let Some(mut pubsub) = store.subscribe(&channels).await else {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            continue;
+        };
+        info!("📡 Listening for commands on {}", channels.join(", "));
+
+        let mut messages = pubsub.on_message();
+        let switched = loop {
+            let switch = async {
+                match &mut switches {
+                    Some(switches) => switches.changed().await.is_ok(),
+                    None => std::future::pending().await,
+                }
+            };
+            let msg = tokio::select! {
+                msg = messages.next() => msg,
+                true = switch => break true,
+            };
+            let Some(msg) = msg else {
+                break false;
+            };
+            let channel = msg.get_channel_name().to_string();
+            let Ok(payload) = msg.get_payload::<String>() else {
+                warn!("Ignoring non-text command on {channel}");
+                continue;
+            };
+            // A slow command (e.g. a verified hard lock) mustn't hold up the next
+            tokio::spawn(dispatch(router.clone(), audit.clone(), channel, payload));
+        };
+        if switched {
+            info!("Redis server changed, resubscribing to commands");
+            continue;
+        }
+        warn!("Command subscription dropped, resubscribing");
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
+
+/// Run the command in `payload`, received on `channel` (or from the
+/// message inbox, see `inbox.rs`).
+pub async fn dispatch(router: Router, audit: Audit, channel: String, payload: String) {
+    let mut command: serde_json::Value = match serde_json::from_str(&payload) {
+        Ok(v @ serde_json::Value::Object(_)) => v,
+        _ => {
+            warn!("Ignoring malformed command on {channel}: {payload}");
+            return;
+        }
+    };
+    let action = command
+        .as_object_mut()
+        .and_then(|o| o.remove("action"))
+        .and_then(|a| a.as_str().map(String::from))
+        .unwrap_or_default();
+    let Some((_, route)) = ACTIONS.iter().find(|(name, _)| *name == action) else {
+        warn!("Ignoring unknown command {action:?} on {channel}");
+        return;
+    };
+    let mode = command.get("mode").and_then(|m| m.as_str()).unwrap_or("hard");
+    let path = route.replace("{mode}", mode);
+
+    info!("📡 Command {action} from {channel}");
+    audit.record("command", &format!("redis:{channel}"), Some(payload.clone())).await;
+
+    // Commands come through Redis, not a socket; handlers see a local peer.
+    let peer = ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0)));
+    let request = Request::post(&path)
+        .header(header::CONTENT_TYPE, "application/json")
+        .extension(peer)
+        .extension(CommandChannel)
+        .body(Body::from(command.to_string()));
+    let Ok(request) = request else {
+        warn!("Could not build request for command {action}");
+        return;
+    };
+
+    let response = match router.oneshot(request).await {
+        Ok(r) => r,
+        Err(never) => match never {},
+    };
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), MAX_RESPONSE_BYTES)
+        .await
+        .map(|b| String::from_utf8_lossy(&b).into_owned())
+        .unwrap_or_default();
+    if status.is_success() && !body.contains("\"error\"") {
+        info!("   ✅ {action}: {body}");
+    } else {
+        warn!("   ❌ {action} failed ({status}): {body}");
+    }
+}
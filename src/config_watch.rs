@@ -0,0 +1,158 @@
+// ─────────────────────────────────────────────────────────────────
+//  config_watch.rs — Instant pickup of the teacher's config changes
+//
+//  The ban config layers, their confirmations and Redis-hosted
+//  categories are polled; with `[monitor.ban_sync] listen` the agent
+//  also subscribes to `{prefix}:config_changed` and to the keyspace
+//  notifications of those keys (see `Store::config_channels`), and
+//  every message wakes the ban sync and category loops at once. A
+//  burst of writes (three layers set in a row) makes one sync. The
+//  explicit channel works on any server, keyspace notifications only
+//  where `notify-keyspace-events` has them on (and, in a cluster, only
+//  for keys on the node the subscription is on).
+//
+//  Schedules and `[streaming]` get the same layering
+//  (`{prefix}:schedule`, `{namespace}:schedule`,
+//  `{namespace}:schedule:{hostname}`, likewise `streaming`), re-read by
+//  `sync_overrides` on the ban sync period and on every signal. The
+//  most specific schedule layer replaces config.toml's blocks outright;
+//  streaming layers are merged key by key over config.toml's section.
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde_json::Value;
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::config::{ScheduleBlock, StreamingConfig};
+use crate::schedule::Schedule;
+use crate::store::Store;
+
+/// `[streaming]` keys only config.toml sets: turning streaming or
+/// recording on and off needs tasks started at launch.
+const FIXED_STREAMING_KEYS: &[&str] = &["enabled", "record", "record_interval_secs", "record_keep"];
+
+/// Subscribe to `channels` and bump `changes` on every message,
+/// resubscribing whenever the connection drops or the Redis server
+/// changes. Runs forever.
+pub async fn run(store: Store, channels: Vec<String>, changes: watch::Sender<u64>) {
+    let mut switches = store.server_switches();
+    loop {
+        if let Some(switches) = &mut switches {
+            switches.borrow_and_update();
+        }
+        let Some(mut pubsub) = store.subscribe(&channels).await else {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            continue;
+        };
+        info!("👂 Listening for config changes on {} channel(s)", channels.len());
+        // Anything changed while nobody listened is picked up now
+        changes.send_modify(|n| *n += 1);
+
+        let mut messages = pubsub.on_message();
+        let switched = loop {
+            let switch = async {
+                match &mut switches {
+                    Some(switches) => switches.changed().await.is_ok(),
+                    None => std::future::pending().await,
+                }
+            };
+            let msg = tokio::select! {
+                msg = messages.next() => msg,
+                true = switch => break true,
+            };
+            let Some(msg) = msg else {
+                break false;
+            };
+            debug!("Config change signalled on {}", msg.get_channel_name());
+            changes.send_modify(|n| *n += 1);
+        };
+        if !switched {
+            warn!("Config change subscription dropped, resubscribing");
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+}
+
+/// Apply the Redis-side schedule and streaming overrides every `every`
+/// and whenever `changes` ticks. An override that doesn't parse is
+/// logged once and the one in force stays; a removed override hands
+/// back to config.toml. Runs forever.
+#[allow(clippy::too_many_arguments)]
+pub async fn sync_overrides(
+    store: Store,
+    hostname: String,
+    schedule: Arc<Schedule>,
+    base_schedule: Vec<ScheduleBlock>,
+    base_streaming: StreamingConfig,
+    streaming: Option<watch::Sender<StreamingConfig>>,
+    mut changes: watch::Receiver<u64>,
+    every: Duration,
+) {
+    let mut interval = tokio::time::interval(every);
+    let mut last_schedule: Option<Value> = None;
+    let mut last_streaming: Vec<Value> = Vec::new();
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            Ok(()) = changes.changed() => interval.reset(),
+        }
+
+        if let Some(layers) = store.fetch_overrides("schedule", &hostname).await {
+            let layer = layers.into_iter().last();
+            if layer != last_schedule {
+                match &layer {
+                    None => {
+                        info!("📅 Schedule override removed, back to config.toml");
+                        schedule.replace(&base_schedule);
+                    }
+                    Some(value) => match serde_json::from_value::<Vec<ScheduleBlock>>(value.clone()) {
+                        Ok(blocks) => {
+                            info!("📅 Schedule override applied ({} block(s))", blocks.len());
+                            schedule.replace(&blocks);
+                        }
+                        Err(e) => warn!("Schedule override is not a list of blocks, keeping the current one: {e}"),
+                    },
+                }
+                last_schedule = layer;
+            }
+        }
+
+        let Some(streaming) = &streaming else { continue };
+        if let Some(layers) = store.fetch_overrides("streaming", &hostname).await {
+            if layers != last_streaming {
+                match merge_streaming(&base_streaming, &layers) {
+                    Ok(cfg) => {
+                        info!("📺 Streaming settings override applied ({} layer(s))", layers.len());
+                        streaming.send_replace(cfg);
+                    }
+                    Err(e) => warn!("Streaming override is invalid, keeping the current settings: {e}"),
+                }
+                last_streaming = layers;
+            }
+        }
+    }
+}
+
+/// `base` with the keys of each object layer laid over it in order.
+fn merge_streaming(base: &StreamingConfig, layers: &[Value]) -> Result<StreamingConfig, String> {
+    let mut merged = serde_json::to_value(base).map_err(|e| e.to_string())?;
+    let target = merged.as_object_mut().ok_or("streaming config is not an object")?;
+    for layer in layers {
+        let layer = layer.as_object().ok_or("layer is not a JSON object")?;
+        for (key, value) in layer {
+            if FIXED_STREAMING_KEYS.contains(&key.as_str()) {
+                warn!("Ignoring streaming override of {key:?}: only config.toml sets it");
+                continue;
+            }
+            if !target.contains_key(key) {
+                return Err(format!("unknown key {key:?}"));
+            }
+            target.insert(key.clone(), value.clone());
+        }
+    }
+    serde_json::from_value(merged).map_err(|e| e.to_string())
+}
@@ -0,0 +1,324 @@
+// ─────────────────────────────────────────────────────────────────
+//  desktop.rs — Temporary wallpaper and lock-screen message
+//
+//  Teachers put exam instructions or "machine reserved" on a PC's
+//  wallpaper / sign-in screen. The original value is remembered the
+//  first time we change it and put back on restore or shutdown:
+//    Windows: SystemParametersInfo(SPI_SETDESKWALLPAPER); logon
+//             notice (legalnoticecaption / legalnoticetext, HKLM)
+//    macOS:   System Events desktop picture; loginwindow
+//             LoginwindowText
+//    Linux:   GNOME picture-uri; GDM banner via a dconf keyfile
+// ─────────────────────────────────────────────────────────────────
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use tracing::{info, warn};
+
+use crate::monitor::silent_cmd;
+
+const WIN_DESKTOP_KEY: &str = r"HKCU\Control Panel\Desktop";
+const WIN_NOTICE_KEY: &str = r"HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\Policies\System";
+const GNOME_BACKGROUND: &str = "org.gnome.desktop.background";
+const GDM_BANNER_FILE: &str = "/etc/dconf/db/gdm.d/90-nishack-banner";
+
+/// Values as they were before we changed them; None = was unset.
+#[derive(Debug, Clone, Default)]
+struct Saved {
+    values: Vec<(&'static str, Option<String>)>,
+}
+
+impl Saved {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values.iter().find(|(n, _)| *n == name).and_then(|(_, v)| v.as_deref())
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// (generation of the latest change, original values)
+    wallpaper: Option<(u64, Saved)>,
+    lock_message: Option<(u64, Saved)>,
+    generation: u64,
+}
+
+/// Shared wallpaper / lock-message state (API and shutdown use it).
+#[derive(Default)]
+pub struct Desktop {
+    inner: Mutex<Inner>,
+}
+
+impl Desktop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show `image` (PNG / JPEG / BMP bytes) as the wallpaper. Returns the
+    /// change's generation for `restore_wallpaper`. Blocking.
+    pub fn set_wallpaper(&self, image: &[u8]) -> anyhow::Result<u64> {
+        let format = image::guess_format(image).map_err(|_| anyhow::anyhow!("not a recognised image"))?;
+        let ext = format.extensions_str().first().copied().unwrap_or("img");
+        let path = std::env::temp_dir().join(format!("nishack-wallpaper.{ext}"));
+        std::fs::write(&path, image)?;
+        self.set_wallpaper_file(&path)
+    }
+
+    /// Show an image that already exists on this PC as the wallpaper.
+    pub fn set_wallpaper_file(&self, path: &Path) -> anyhow::Result<u64> {
+        if !path.is_file() {
+            return Err(anyhow::anyhow!("{} does not exist", path.display()));
+        }
+        let path = std::path::absolute(path)?;
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        // Only the first change records the original
+        let saved = match &inner.wallpaper {
+            Some((_, saved)) => saved.clone(),
+            None => current_wallpaper(),
+        };
+        if !apply_wallpaper(&path.to_string_lossy()) {
+            return Err(anyhow::anyhow!("could not set the wallpaper"));
+        }
+        inner.generation += 1;
+        inner.wallpaper = Some((inner.generation, saved));
+        info!("🖼️  Wallpaper set to {}", path.display());
+        Ok(inner.generation)
+    }
+
+    /// Put the original wallpaper back. With `only`, restore only if no
+    /// newer change was made since (used by timed changes).
+    pub fn restore_wallpaper(&self, only: Option<u64>) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if only.is_some_and(|g| inner.wallpaper.as_ref().is_some_and(|(cur, _)| *cur != g)) {
+            return false;
+        }
+        let Some((_, saved)) = inner.wallpaper.take() else {
+            return false;
+        };
+        let ok = restore_wallpaper(&saved);
+        if ok {
+            info!("🖼️  Wallpaper restored");
+        } else {
+            warn!("Could not restore the original wallpaper");
+        }
+        ok
+    }
+
+    /// Show `text` (with an optional `title`) on the sign-in / lock screen.
+    pub fn set_lock_message(&self, title: &str, text: &str) -> anyhow::Result<u64> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let saved = match &inner.lock_message {
+            Some((_, saved)) => saved.clone(),
+            None => current_lock_message(),
+        };
+        if !apply_lock_message(title, text) {
+            return Err(anyhow::anyhow!("could not set the lock-screen message (agent needs admin rights)"));
+        }
+        inner.generation += 1;
+        inner.lock_message = Some((inner.generation, saved));
+        info!("🔒 Lock-screen message set");
+        Ok(inner.generation)
+    }
+
+    /// Put the original lock-screen message back (see `restore_wallpaper`).
+    pub fn clear_lock_message(&self, only: Option<u64>) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if only.is_some_and(|g| inner.lock_message.as_ref().is_some_and(|(cur, _)| *cur != g)) {
+            return false;
+        }
+        let Some((_, saved)) = inner.lock_message.take() else {
+            return false;
+        };
+        let ok = restore_lock_message(&saved);
+        if ok {
+            info!("🔒 Lock-screen message restored");
+        } else {
+            warn!("Could not restore the original lock-screen message");
+        }
+        ok
+    }
+
+    /// Undo every change before the agent exits. Blocking.
+    pub fn restore_all(&self) {
+        self.restore_wallpaper(None);
+        self.clear_lock_message(None);
+    }
+}
+
+// ── Wallpaper ───────────────────────────────────────────────────
+
+fn current_wallpaper() -> Saved {
+    let values = if cfg!(target_os = "windows") {
+        vec![("WallPaper", reg_get(WIN_DESKTOP_KEY, "WallPaper"))]
+    } else if cfg!(target_os = "macos") {
+        let pic = silent_cmd("osascript")
+            .args(["-e", r#"tell application "System Events" to get picture of desktop 1"#])
+            .output()
+            .ok()
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .filter(|p| !p.is_empty());
+        vec![("picture", pic)]
+    } else {
+        vec![
+            ("picture-uri", gsettings_get(GNOME_BACKGROUND, "picture-uri")),
+            ("picture-uri-dark", gsettings_get(GNOME_BACKGROUND, "picture-uri-dark")),
+        ]
+    };
+    Saved { values }
+}
+
+fn apply_wallpaper(path: &str) -> bool {
+    if cfg!(target_os = "windows") {
+        windows_set_wallpaper(path)
+    } else if cfg!(target_os = "macos") {
+        let script = format!(
+            r#"tell application "System Events" to set picture of every desktop to "{}""#,
+            applescript_escape(path)
+        );
+        silent_cmd("osascript").args(["-e", &script]).status().is_ok_and(|s| s.success())
+    } else {
+        let uri = format!("'file://{path}'");
+        // picture-uri-dark only exists on GNOME 42+, so it may fail harmlessly
+        let _ = gsettings_set(GNOME_BACKGROUND, "picture-uri-dark", &uri);
+        gsettings_set(GNOME_BACKGROUND, "picture-uri", &uri)
+    }
+}
+
+fn restore_wallpaper(saved: &Saved) -> bool {
+    if cfg!(target_os = "windows") {
+        // An empty path means "no wallpaper"
+        windows_set_wallpaper(saved.get("WallPaper").unwrap_or_default())
+    } else if cfg!(target_os = "macos") {
+        saved.get("picture").is_some_and(apply_wallpaper)
+    } else {
+        // gsettings prints values in GVariant form, which `set` accepts back
+        let put_back = |key| match saved.get(key) {
+            Some(v) => gsettings_set(GNOME_BACKGROUND, key, v),
+            None => silent_cmd("gsettings").args(["reset", GNOME_BACKGROUND, key]).status().is_ok(),
+        };
+        let _ = put_back("picture-uri-dark");
+        put_back("picture-uri")
+    }
+}
+
+fn windows_set_wallpaper(path: &str) -> bool {
+    let script = format!(
+        "Add-Type -TypeDefinition 'using System.Runtime.InteropServices; public static class NishackWallpaper {{ \
+         [DllImport(\"user32.dll\", CharSet = CharSet.Unicode)] \
+         public static extern bool SystemParametersInfo(int a, int b, string c, int d); }}'\n\
+         # SPI_SETDESKWALLPAPER, SPIF_UPDATEINIFILE | SPIF_SENDCHANGE\n\
+         if (-not [NishackWallpaper]::SystemParametersInfo(20, 0, '{}', 3)) {{ exit 1 }}",
+        path.replace('\'', "''")
+    );
+    silent_cmd("powershell")
+        .args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script])
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+// ── Lock-screen message ─────────────────────────────────────────
+
+fn current_lock_message() -> Saved {
+    let values = if cfg!(target_os = "windows") {
+        vec![
+            ("legalnoticecaption", reg_get(WIN_NOTICE_KEY, "legalnoticecaption")),
+            ("legalnoticetext", reg_get(WIN_NOTICE_KEY, "legalnoticetext")),
+        ]
+    } else if cfg!(target_os = "macos") {
+        let text = silent_cmd("defaults")
+            .args(["read", "/Library/Preferences/com.apple.loginwindow", "LoginwindowText"])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim_end().to_string());
+        vec![("LoginwindowText", text)]
+    } else {
+        // Our banner lives in its own dconf keyfile; removing it restores GDM
+        Vec::new()
+    };
+    Saved { values }
+}
+
+fn apply_lock_message(title: &str, text: &str) -> bool {
+    if cfg!(target_os = "windows") {
+        // Shown as a notice the user has to acknowledge before signing in
+        reg_set(WIN_NOTICE_KEY, "legalnoticecaption", title) && reg_set(WIN_NOTICE_KEY, "legalnoticetext", text)
+    } else if cfg!(target_os = "macos") {
+        let message = if title.is_empty() { text.to_string() } else { format!("{title}\n{text}") };
+        silent_cmd("defaults")
+            .args(["write", "/Library/Preferences/com.apple.loginwindow", "LoginwindowText", &message])
+            .status()
+            .is_ok_and(|s| s.success())
+    } else {
+        let message = if title.is_empty() { text.to_string() } else { format!("{title}\n{text}") };
+        let keyfile = format!(
+            "[org/gnome/login-screen]\nbanner-message-enable=true\nbanner-message-text='{}'\n",
+            message.replace('\\', "\\\\").replace('\'', "\\'").replace('\n', "\\n")
+        );
+        let written = Path::new(GDM_BANNER_FILE)
+            .parent()
+            .is_some_and(|dir| std::fs::create_dir_all(dir).is_ok())
+            && std::fs::write(GDM_BANNER_FILE, keyfile).is_ok();
+        written && dconf_update()
+    }
+}
+
+fn restore_lock_message(saved: &Saved) -> bool {
+    if cfg!(target_os = "windows") {
+        ["legalnoticecaption", "legalnoticetext"]
+            .iter()
+            .all(|name| reg_set(WIN_NOTICE_KEY, name, saved.get(name).unwrap_or_default()))
+    } else if cfg!(target_os = "macos") {
+        let mut cmd = silent_cmd("defaults");
+        match saved.get("LoginwindowText") {
+            Some(old) => cmd.args(["write", "/Library/Preferences/com.apple.loginwindow", "LoginwindowText", old]),
+            None => cmd.args(["delete", "/Library/Preferences/com.apple.loginwindow", "LoginwindowText"]),
+        };
+        cmd.status().is_ok_and(|s| s.success())
+    } else {
+        let removed = match std::fs::remove_file(GDM_BANNER_FILE) {
+            Ok(()) => true,
+            Err(e) => e.kind() == std::io::ErrorKind::NotFound,
+        };
+        removed && dconf_update()
+    }
+}
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+fn reg_get(key: &str, name: &str) -> Option<String> {
+    let out = silent_cmd("reg").args(["query", key, "/v", name]).output().ok()?;
+    // "    WallPaper    REG_SZ    C:\Users\me\My Pictures\a.jpg" (values may contain spaces)
+    String::from_utf8_lossy(&out.stdout).lines().find_map(|l| {
+        let (head, value) = l.split_once("REG_SZ")?;
+        (head.trim().eq_ignore_ascii_case(name)).then(|| value.trim().to_string())
+    })
+}
+
+fn reg_set(key: &str, name: &str, value: &str) -> bool {
+    silent_cmd("reg")
+        .args(["add", key, "/v", name, "/t", "REG_SZ", "/d", value, "/f"])
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+fn gsettings_get(schema: &str, key: &str) -> Option<String> {
+    silent_cmd("gsettings")
+        .args(["get", schema, key])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+}
+
+fn gsettings_set(schema: &str, key: &str, value: &str) -> bool {
+    silent_cmd("gsettings").args(["set", schema, key, value]).status().is_ok_and(|s| s.success())
+}
+
+fn dconf_update() -> bool {
+    silent_cmd("dconf").arg("update").status().is_ok_and(|s| s.success())
+}
+
+pub(crate) fn applescript_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
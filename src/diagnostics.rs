@@ -0,0 +1,303 @@
+// ─────────────────────────────────────────────────────────────────
+//  diagnostics.rs — Support bundle for field issues
+//
+//  `POST /diagnostics/bundle` zips up what support usually asks for:
+//    config.toml       the config as loaded, secrets redacted
+//    agent.log         the last `LOG_LINES` log lines (kept in memory
+//                      by a second tracing layer; the agent writes no
+//                      log file of its own)
+//    last_scan.json    the last full scan's violations and timing
+//    capture_test.jpg  a fresh screen capture, or capture_test.txt
+//                      with the error
+//    environment.json  OS, build, paths, resources and capabilities
+//  The latest bundle is kept in memory for `GET /diagnostics/bundle`.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Datelike, Local, Timelike, Utc};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::models::{Capabilities, ScanReport};
+use crate::profiles;
+
+/// Log lines kept for bundles.
+const LOG_LINES: usize = 2000;
+
+/// What a bundle needs from the rest of the agent.
+pub struct Context {
+    pub hostname: String,
+    pub config_path: PathBuf,
+    pub capabilities: Capabilities,
+    pub agent_uptime_secs: u64,
+    pub last_scan: Option<ScanReport>,
+    /// JPEG quality and max dimension for the capture test.
+    pub capture: (u8, u32),
+}
+
+pub struct Bundle {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub files: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+/// Recent log lines and the latest bundle.
+#[derive(Default)]
+pub struct Diagnostics {
+    logs: LogTail,
+    latest: Mutex<Option<Arc<Bundle>>>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writer for a tracing layer feeding `agent.log`.
+    pub fn log_writer(&self) -> LogTail {
+        self.logs.clone()
+    }
+
+    pub fn latest(&self) -> Option<Arc<Bundle>> {
+        self.latest.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Collect a bundle and keep it as the latest. Blocking (captures
+    /// the screen).
+    pub fn build(&self, ctx: Context) -> Arc<Bundle> {
+        let created_at = Utc::now();
+        let mut zip = ZipWriter::default();
+
+        zip.add("config.toml", redacted_config(&ctx.config_path).as_bytes());
+        let logs = self.logs.0.lock().unwrap_or_else(|e| e.into_inner());
+        let log: String = logs.iter().map(String::as_str).collect();
+        drop(logs);
+        zip.add("agent.log", log.as_bytes());
+        let last_scan = match &ctx.last_scan {
+            Some(scan) => serde_json::to_vec_pretty(scan).unwrap_or_default(),
+            None => b"null\n".to_vec(),
+        };
+        zip.add("last_scan.json", &last_scan);
+        match capture_test(ctx.capture) {
+            Ok(jpeg) => zip.add("capture_test.jpg", &jpeg),
+            Err(e) => zip.add("capture_test.txt", format!("capture failed: {e}\n").as_bytes()),
+        }
+        zip.add("environment.json", &serde_json::to_vec_pretty(&environment(&ctx, created_at)).unwrap_or_default());
+
+        let bundle = Arc::new(Bundle {
+            name: format!("nishack-{}-{}.zip", ctx.hostname, created_at.format("%Y%m%d-%H%M%S")),
+            created_at,
+            files: zip.names.clone(),
+            data: zip.finish(),
+        });
+        *self.latest.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::clone(&bundle));
+        bundle
+    }
+}
+
+// ── Log tail ────────────────────────────────────────────────────
+
+/// Ring buffer of formatted log lines, as a tracing `MakeWriter`.
+#[derive(Clone, Default)]
+pub struct LogTail(Arc<Mutex<VecDeque<String>>>);
+
+impl<'a> MakeWriter<'a> for LogTail {
+    type Writer = TailWriter;
+
+    fn make_writer(&'a self) -> TailWriter {
+        TailWriter { lines: Arc::clone(&self.0), buf: Vec::new() }
+    }
+}
+
+/// One event's output; appended to the tail when dropped.
+pub struct TailWriter {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    buf: Vec<u8>,
+}
+
+impl Write for TailWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for TailWriter {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        lines.push_back(String::from_utf8_lossy(&self.buf).into_owned());
+        while lines.len() > LOG_LINES {
+            lines.pop_front();
+        }
+    }
+}
+
+// ── Bundle parts ────────────────────────────────────────────────
+
+/// The config file with tokens, passwords, keys and URL credentials
+/// replaced by `***`. Comments don't survive the round trip.
+fn redacted_config(path: &std::path::Path) -> String {
+    let raw = match std::fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(e) => return format!("# could not read {}: {e}\n", path.display()),
+    };
+    match raw.parse::<toml::Table>() {
+        Ok(mut table) => {
+            redact_table(&mut table);
+            format!("# {} (secrets redacted)\n{}", path.display(), toml::to_string_pretty(&table).unwrap_or_default())
+        }
+        Err(e) => format!("# {} does not parse: {e}\n", path.display()),
+    }
+}
+
+fn redact_table(table: &mut toml::Table) {
+    for (key, value) in table.iter_mut() {
+        redact(key, value);
+    }
+}
+
+fn redact(key: &str, value: &mut toml::Value) {
+    let key = key.to_lowercase();
+    let secret = ["token", "secret", "password", "passwd", "credential", "webhook"].iter().any(|s| key.contains(s))
+        || key == "key"
+        || key.ends_with("_key");
+    match value {
+        toml::Value::String(s) if secret && !s.is_empty() => *s = "***".into(),
+        toml::Value::String(s) => {
+            if let Ok(mut url) = url::Url::parse(s) {
+                if url.password().is_some() {
+                    let _ = url.set_password(Some("***"));
+                    *s = url.to_string();
+                }
+            }
+        }
+        toml::Value::Table(t) => redact_table(t),
+        toml::Value::Array(items) => items.iter_mut().for_each(|item| redact(&key, item)),
+        _ => {}
+    }
+}
+
+#[cfg(feature = "screenshots")]
+fn capture_test((quality, max_dimension): (u8, u32)) -> anyhow::Result<Vec<u8>> {
+    use base64::Engine as _;
+    let b64 = crate::screenshot::capture_screenshot(quality, max_dimension)?;
+    Ok(base64::engine::general_purpose::STANDARD.decode(b64)?)
+}
+
+#[cfg(not(feature = "screenshots"))]
+fn capture_test(_: (u8, u32)) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("this build has no capture code (feature \"screenshots\")")
+}
+
+fn environment(ctx: &Context, now: DateTime<Utc>) -> serde_json::Value {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    sys.refresh_cpu_list(sysinfo::CpuRefreshKind::new());
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All);
+    serde_json::json!({
+        "generated_at": now,
+        "hostname": ctx.hostname,
+        "console_user": profiles::logged_in_user(),
+        "os": sysinfo::System::long_os_version(),
+        "kernel": sysinfo::System::kernel_version(),
+        "cpus": sys.cpus().len(),
+        "total_memory_mb": sys.total_memory() / 1_048_576,
+        "used_memory_mb": sys.used_memory() / 1_048_576,
+        "processes": sys.processes().len(),
+        "system_uptime_secs": sysinfo::System::uptime(),
+        "agent_uptime_secs": ctx.agent_uptime_secs,
+        "agent_pid": std::process::id(),
+        "exe": std::env::current_exe().ok(),
+        "cwd": std::env::current_dir().ok(),
+        "config_path": ctx.config_path,
+        "capabilities": ctx.capabilities,
+    })
+}
+
+// ── Zip ─────────────────────────────────────────────────────────
+
+/// Minimal zip archive (deflate, UTF-8 names, no zip64 — bundles are
+/// far below 4 GiB).
+#[derive(Default)]
+struct ZipWriter {
+    out: Vec<u8>,
+    central: Vec<u8>,
+    names: Vec<String>,
+}
+
+impl ZipWriter {
+    fn add(&mut self, name: &str, data: &[u8]) {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        let compressed = match encoder.write_all(data) {
+            Ok(()) => encoder.finish().unwrap_or_default(),
+            Err(_) => return,
+        };
+        let crc = crc32fast::hash(data);
+        let (time, date) = dos_time(Local::now());
+        let offset = self.out.len() as u32;
+
+        // Fields shared by the local and the central header, from "version
+        // needed" on: version, flags (bit 11 = UTF-8), deflate, time,
+        // date, CRC, sizes, name length
+        let mut common = Vec::with_capacity(26);
+        for v in [20u16, 0x0800, 8, time, date] {
+            common.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in [crc, compressed.len() as u32, data.len() as u32] {
+            common.extend_from_slice(&v.to_le_bytes());
+        }
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+
+        self.out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        self.out.extend_from_slice(&common);
+        self.out.extend_from_slice(&0u16.to_le_bytes()); // extra
+        self.out.extend_from_slice(name.as_bytes());
+        self.out.extend_from_slice(&compressed);
+
+        self.central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.central.extend_from_slice(&20u16.to_le_bytes()); // made by
+        self.central.extend_from_slice(&common);
+        for v in [0u16, 0, 0, 0] {
+            // extra, comment, disk, internal attributes
+            self.central.extend_from_slice(&v.to_le_bytes());
+        }
+        self.central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        self.names.push(name.to_string());
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.out.len() as u32;
+        let count = self.names.len() as u16;
+        self.out.extend_from_slice(&self.central);
+        self.out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        for v in [0u16, 0, count, count] {
+            self.out.extend_from_slice(&v.to_le_bytes());
+        }
+        self.out.extend_from_slice(&(self.central.len() as u32).to_le_bytes());
+        self.out.extend_from_slice(&offset.to_le_bytes());
+        self.out.extend_from_slice(&0u16.to_le_bytes()); // comment
+        self.out
+    }
+}
+
+/// MS-DOS (time, date) as zip headers store them.
+fn dos_time(t: DateTime<Local>) -> (u16, u16) {
+    let time = ((t.hour() << 11) | (t.minute() << 5) | (t.second() / 2)) as u16;
+    let date = ((((t.year() - 1980).max(0) as u32) << 9) | (t.month() << 5) | t.day()) as u16;
+    (time, date)
+}
@@ -0,0 +1,236 @@
+// ─────────────────────────────────────────────────────────────────
+//  displays.rs — Extra screens during exam mode
+//
+//  A second monitor (extended or mirrored) or a remote display
+//  session lets someone else watch the exam, so while exam mode is
+//  on the attached outputs are checked every `[exam]
+//  display_check_secs` and anything beyond one local screen becomes
+//  an `extra_display` violation:
+//    Windows: active monitors (WMI, mirrored ones count too) and
+//             RDP sessions (qwinsta)
+//    macOS:   system_profiler displays; AirPlay / Sidecar are remote
+//    Linux:   xrandr active monitors
+//  With `disable_extra_displays` the extra outputs are switched off
+//  (DisplaySwitch /internal, xrandr --off) and back on when the exam
+//  ends; macOS has no command for it and remote sessions are only
+//  reported.
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::config::ExamConfig;
+use crate::exam::ExamMode;
+use crate::models::{Violation, ViolationKind};
+use crate::monitor::silent_cmd;
+use crate::violation_sinks::ViolationSinks;
+
+/// Displays and remote display sessions attached right now.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub outputs: Vec<Output>,
+    pub remote_sessions: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub name: String,
+    pub primary: bool,
+}
+
+impl Layout {
+    fn has_extras(&self) -> bool {
+        self.outputs.len() > 1 || !self.remote_sessions.is_empty()
+    }
+
+    /// "2 displays (eDP-1, HDMI-1), remote: rdp-tcp#3"
+    fn describe(&self) -> String {
+        let names: Vec<&str> = self.outputs.iter().map(|o| o.name.as_str()).collect();
+        let mut text = format!("{} display(s) ({})", names.len(), names.join(", "));
+        if !self.remote_sessions.is_empty() {
+            text.push_str(&format!(", remote: {}", self.remote_sessions.join(", ")));
+        }
+        text
+    }
+}
+
+/// What `disable_extra` switched off, for `restore`.
+pub enum Disabled {
+    /// Windows projection mode set to "PC screen only".
+    DisplaySwitch,
+    /// xrandr outputs turned off.
+    Outputs(Vec<String>),
+}
+
+/// Check the displays while exam mode is on. Runs forever.
+pub async fn run(cfg: ExamConfig, exam: Arc<ExamMode>, sinks: ViolationSinks, hostname: String, username: String) {
+    let interval = Duration::from_secs(cfg.display_check_secs.max(1));
+    // Layout last reported, so an unchanged one isn't reported every check
+    let mut reported: Option<String> = None;
+    loop {
+        tokio::time::sleep(interval).await;
+        if !exam.is_active() {
+            reported = None;
+            continue;
+        }
+        let Some(layout) = tokio::task::spawn_blocking(layout).await.ok().flatten() else {
+            continue;
+        };
+        if !layout.has_extras() {
+            reported = None;
+            continue;
+        }
+        let description = layout.describe();
+        if reported.as_deref() == Some(description.as_str()) {
+            continue;
+        }
+        warn!("🖥️  Extra display during exam: {description}");
+
+        let mut switched_off = false;
+        if cfg.disable_extra_displays && layout.outputs.len() > 1 {
+            let exam = Arc::clone(&exam);
+            let outputs = layout.clone();
+            switched_off = tokio::task::spawn_blocking(move || exam.disable_extra_displays(&outputs))
+                .await
+                .unwrap_or(false);
+        }
+        let v = Violation {
+            hostname: hostname.clone(),
+            target: if layout.remote_sessions.is_empty() { "second_display".into() } else { "remote_display".into() },
+            kind: ViolationKind::ExtraDisplay,
+            action_taken: switched_off,
+            username: username.clone(),
+            timestamp: Utc::now(),
+            url: None,
+            visited_at: None,
+            detail: Some(format!("{description}{}", if switched_off { "; extra outputs switched off" } else { "" })),
+            process: None,
+            occurrences: 1,
+            last_seen: None,
+            usage: None,
+            exe: None,
+        };
+        sinks.deliver(&v).await;
+        reported = Some(description);
+    }
+}
+
+/// The attached displays. None when they can't be listed. Blocking.
+pub fn layout() -> Option<Layout> {
+    if cfg!(target_os = "windows") {
+        windows_layout()
+    } else if cfg!(target_os = "macos") {
+        macos_layout()
+    } else {
+        linux_layout()
+    }
+}
+
+/// Switch every output but the primary one off. Blocking.
+pub fn disable_extra(layout: &Layout) -> Option<Disabled> {
+    if cfg!(target_os = "windows") {
+        let ok = silent_cmd("DisplaySwitch.exe").arg("/internal").status().is_ok_and(|s| s.success());
+        return ok.then_some(Disabled::DisplaySwitch);
+    }
+    if cfg!(target_os = "macos") {
+        warn!("Extra displays can't be switched off on macOS; reporting only");
+        return None;
+    }
+    let primary = layout.outputs.iter().position(|o| o.primary).unwrap_or(0);
+    let off: Vec<String> = layout
+        .outputs
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != primary)
+        .map(|(_, o)| o.name.clone())
+        .filter(|name| silent_cmd("xrandr").args(["--output", name, "--off"]).status().is_ok_and(|s| s.success()))
+        .collect();
+    info!("Switched off display output(s): {}", off.join(", "));
+    (!off.is_empty()).then_some(Disabled::Outputs(off))
+}
+
+/// Switch back on what `disable_extra` turned off. Blocking.
+pub fn restore(disabled: Disabled) {
+    match disabled {
+        Disabled::DisplaySwitch => {
+            let _ = silent_cmd("DisplaySwitch.exe").arg("/extend").status();
+        }
+        Disabled::Outputs(names) => {
+            for name in names {
+                let _ = silent_cmd("xrandr").args(["--output", &name, "--auto"]).status();
+            }
+        }
+    }
+    info!("Extra displays switched back on");
+}
+
+fn windows_layout() -> Option<Layout> {
+    let script = r#"Get-CimInstance -Namespace root\wmi -ClassName WmiMonitorBasicDisplayParams | Where-Object { $_.Active } | ForEach-Object { $_.InstanceName }"#;
+    let out = silent_cmd("powershell").args(["-NoProfile", "-Command", script]).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    // DISPLAY\SAM0F3A\5&2a4d1ba2&0&UID4352_0
+    let outputs = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .enumerate()
+        .map(|(i, line)| Output { name: line.split('\\').nth(1).unwrap_or(line).to_string(), primary: i == 0 })
+        .collect();
+
+    // Connected RDP sessions are "rdp-tcp#N"; the bare "rdp-tcp" is the listener
+    let remote_sessions = silent_cmd("qwinsta")
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .split_whitespace()
+                .map(|w| w.trim_start_matches('>'))
+                .filter(|w| w.to_lowercase().starts_with("rdp-tcp#"))
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(Layout { outputs, remote_sessions })
+}
+
+fn macos_layout() -> Option<Layout> {
+    let out = silent_cmd("system_profiler").args(["SPDisplaysDataType", "-json"]).output().ok()?;
+    let json: serde_json::Value = serde_json::from_slice(&out.stdout).ok()?;
+    let mut layout = Layout::default();
+    let gpus = json.get("SPDisplaysDataType")?.as_array()?;
+    for display in gpus.iter().filter_map(|g| g.get("spdisplays_ndrvs")?.as_array()).flatten() {
+        let name = display.get("_name").and_then(|n| n.as_str()).unwrap_or("display").to_string();
+        let connection = display.to_string().to_lowercase();
+        if connection.contains("airplay") || connection.contains("sidecar") {
+            layout.remote_sessions.push(name);
+        } else {
+            let primary = display.get("spdisplays_main").and_then(|m| m.as_str()) == Some("spdisplays_yes");
+            layout.outputs.push(Output { name, primary });
+        }
+    }
+    Some(layout)
+}
+
+//   Monitors: 2
+//    0: +*eDP-1 1920/344x1080/194+0+0  eDP-1
+//    1: +HDMI-1 1920/527x1080/296+1920+0  HDMI-1
+fn linux_layout() -> Option<Layout> {
+    let out = silent_cmd("xrandr").arg("--listactivemonitors").output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let outputs = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(':')?;
+            let name = rest.split_whitespace().last()?.to_string();
+            Some(Output { name, primary: rest.trim_start().trim_start_matches('+').starts_with('*') })
+        })
+        .collect();
+    Some(Layout { outputs, ..Default::default() })
+}
@@ -0,0 +1,73 @@
+// ─────────────────────────────────────────────────────────────────
+//  dns_cache.rs — Linux resolver caches
+//
+//  Linux has no system-wide DNS cache command like ipconfig or
+//  dscacheutil, so each caching resolver is asked in turn:
+//    systemd-resolved  `resolvectl show-cache` (systemd 254+), else
+//                      SIGUSR1, which dumps the cache to the journal
+//    nscd              the hosts cache file (/var/cache/nscd/hosts)
+//  Both need root, like the rest of enforcement.
+// ─────────────────────────────────────────────────────────────────
+
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::debug;
+
+use crate::monitor::silent_cmd;
+
+/// nscd's persistent hosts cache, by distribution.
+const NSCD_HOSTS_CACHES: &[&str] = &["/var/cache/nscd/hosts", "/var/db/nscd/hosts"];
+
+/// How long systemd-resolved gets to write its cache dump to the journal.
+const JOURNAL_DUMP_WAIT: Duration = Duration::from_millis(500);
+
+/// Shortest run of name characters taken from the binary nscd cache.
+const MIN_NAME_LEN: usize = 4;
+
+/// Cached names from systemd-resolved and nscd, one text blob to match
+/// banned domains in. None when neither resolver could be read.
+pub fn dump() -> Option<String> {
+    let parts: Vec<String> = [resolved_cache(), nscd_cache()].into_iter().flatten().collect();
+    (!parts.is_empty()).then(|| parts.join("\n"))
+}
+
+/// Whether the nscd hosts cache exists (and so is worth invalidating).
+pub fn nscd_present() -> bool {
+    NSCD_HOSTS_CACHES.iter().any(|p| std::path::Path::new(p).exists())
+}
+
+fn resolved_cache() -> Option<String> {
+    if let Ok(out) = silent_cmd("resolvectl").arg("show-cache").output() {
+        if out.status.success() {
+            return Some(String::from_utf8_lossy(&out.stdout).into_owned());
+        }
+    }
+    // Older systemd: SIGUSR1 logs the cache contents to the journal
+    let since = format!("@{}", Utc::now().timestamp());
+    let signalled = silent_cmd("systemctl")
+        .args(["kill", "--signal=USR1", "systemd-resolved"])
+        .status()
+        .is_ok_and(|s| s.success());
+    if !signalled {
+        debug!("systemd-resolved not available for a cache dump");
+        return None;
+    }
+    std::thread::sleep(JOURNAL_DUMP_WAIT);
+    let out = silent_cmd("journalctl")
+        .args(["-u", "systemd-resolved", "--since", &since, "-o", "cat", "--no-pager"])
+        .output()
+        .ok()?;
+    out.status.success().then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// Host names in the binary nscd cache: runs of name characters.
+fn nscd_cache() -> Option<String> {
+    let data = NSCD_HOSTS_CACHES.iter().find_map(|p| std::fs::read(p).ok())?;
+    let names: Vec<String> = data
+        .split(|b| !(b.is_ascii_alphanumeric() || *b == b'.' || *b == b'-'))
+        .filter(|run| run.len() >= MIN_NAME_LEN && run.contains(&b'.'))
+        .map(|run| String::from_utf8_lossy(run).into_owned())
+        .collect();
+    Some(names.join("\n"))
+}
@@ -0,0 +1,225 @@
+// ─────────────────────────────────────────────────────────────────
+//  dns_sniffer.rs — Live DNS query / TLS SNI capture
+//
+//  Cache parsing misses queries that expire between scans. When
+//  enabled, a background thread runs a packet-capture tool and
+//  forwards every observed query name:
+//    tshark  — DNS queries and TLS ClientHello SNI (covers DoH / DoT
+//              targets too); works anywhere Wireshark/Npcap is installed
+//    tcpdump — DNS queries only (Linux / macOS fallback)
+//  Both need capture privileges, which the agent has when run as a
+//  service.
+// ─────────────────────────────────────────────────────────────────
+
+use std::io::{BufRead, BufReader};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+
+use crate::config::DnsSnifferConfig;
+use crate::monitor::silent_cmd;
+
+/// How a name was observed on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuerySource {
+    Dns,
+    TlsSni,
+}
+
+/// One observed lookup / connection target.
+#[derive(Debug, Clone)]
+pub struct SniffedQuery {
+    pub name: String,
+    pub source: QuerySource,
+    pub seen_at: DateTime<Utc>,
+}
+
+/// Handle to the background capture thread.
+pub struct DnsSniffer {
+    rx: Receiver<SniffedQuery>,
+    /// Cleared when the capture thread gives up.
+    alive: Arc<AtomicBool>,
+    /// Set once the running tool has printed a line, cleared when it exits.
+    heard: Arc<AtomicBool>,
+}
+
+/// Where a capture run sends what it sees.
+struct Feed {
+    tx: Sender<SniffedQuery>,
+    heard: Arc<AtomicBool>,
+}
+
+impl DnsSniffer {
+    /// Spawn the capture thread. It restarts the tool if it exits and gives
+    /// up only when no supported tool is installed.
+    pub fn start(cfg: &DnsSnifferConfig) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cfg = cfg.clone();
+        let alive = Arc::new(AtomicBool::new(true));
+        let heard = Arc::new(AtomicBool::new(false));
+        let running = Arc::clone(&alive);
+        let feed = Feed { tx, heard: Arc::clone(&heard) };
+        std::thread::Builder::new()
+            .name("dns-sniffer".into())
+            .spawn(move || {
+                capture_loop(cfg, feed);
+                running.store(false, Ordering::Relaxed);
+            })
+            .expect("failed to spawn dns-sniffer thread");
+        Self { rx, alive, heard }
+    }
+
+    /// Whether a capture tool is running and has printed anything yet;
+    /// false while it's silent (e.g. a filter that matches nothing),
+    /// restarting, or after no tool could be started.
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Relaxed) && self.heard.load(Ordering::Relaxed)
+    }
+
+    /// Take everything captured since the last call.
+    pub fn drain(&self) -> Vec<SniffedQuery> {
+        self.rx.try_iter().collect()
+    }
+}
+
+/// Why a capture run ended.
+enum Exit {
+    /// The tool isn't installed — retrying won't help.
+    Missing(String),
+    /// The tool exited (interface went away, killed, ...).
+    Ended,
+    /// Nobody is listening any more (Monitor dropped).
+    Closed,
+}
+
+fn capture_loop(cfg: DnsSnifferConfig, feed: Feed) {
+    loop {
+        let exit = match cfg.tool.as_deref() {
+            Some("tcpdump") => run_tcpdump(&cfg, &feed),
+            Some("tshark") => run_tshark(&cfg, &feed),
+            Some(other) => Exit::Missing(format!("unsupported capture tool {other:?}")),
+            // Auto: prefer tshark (adds SNI), fall back to tcpdump off Windows
+            None => match run_tshark(&cfg, &feed) {
+                Exit::Missing(e) if !cfg!(target_os = "windows") => {
+                    info!("{e}, falling back to tcpdump (DNS only)");
+                    run_tcpdump(&cfg, &feed)
+                }
+                other => other,
+            },
+        };
+
+        match exit {
+            Exit::Missing(e) => {
+                warn!("DNS sniffer disabled: {e} — falling back to DNS cache parsing");
+                return;
+            }
+            Exit::Closed => return,
+            Exit::Ended => warn!("DNS sniffer exited, restarting in 30s"),
+        }
+        std::thread::sleep(Duration::from_secs(30));
+    }
+}
+
+/// Spawn `program` and feed each stdout line to `parse`.
+fn run_capture(
+    program: &str,
+    args: &[String],
+    feed: &Feed,
+    parse: impl Fn(&str) -> Vec<(String, QuerySource)>,
+) -> Exit {
+    let mut child = match silent_cmd(program)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(e) => return Exit::Missing(format!("{program} not available: {e}")),
+    };
+    info!("🕵️  DNS sniffer running ({program})");
+
+    let Some(stdout) = child.stdout.take() else {
+        return Exit::Ended;
+    };
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        feed.heard.store(true, Ordering::Relaxed);
+        for (name, source) in parse(&line) {
+            let q = SniffedQuery { name, source, seen_at: Utc::now() };
+            if feed.tx.send(q).is_err() {
+                let _ = child.kill();
+                return Exit::Closed;
+            }
+        }
+    }
+    feed.heard.store(false, Ordering::Relaxed);
+    let _ = child.wait();
+    Exit::Ended
+}
+
+fn run_tshark(cfg: &DnsSnifferConfig, feed: &Feed) -> Exit {
+    // No -q / -Q: without -w they silence the `-T fields` lines too.
+    // Status chatter goes to stderr, which run_capture discards.
+    let mut args: Vec<String> = vec!["-l".into(), "-n".into()];
+    if let Some(iface) = &cfg.interface {
+        args.extend(["-i".into(), iface.clone()]);
+    }
+    args.extend(
+        [
+            "-f", "udp port 53 or tcp port 53 or tcp port 443 or tcp port 853",
+            "-Y", "(dns.flags.response == 0) or (tls.handshake.type == 1)",
+            "-T", "fields", "-E", "separator=|",
+            "-e", "dns.qry.name", "-e", "tls.handshake.extensions_server_name",
+        ]
+        .map(String::from),
+    );
+
+    run_capture("tshark", &args, feed, |line| {
+        let mut out = Vec::new();
+        let mut cols = line.split('|');
+        for (col, source) in [(cols.next(), QuerySource::Dns), (cols.next(), QuerySource::TlsSni)] {
+            // tshark joins multiple values in one packet with ','
+            for name in col.unwrap_or_default().split(',') {
+                let name = clean_name(name);
+                if !name.is_empty() {
+                    out.push((name, source));
+                }
+            }
+        }
+        out
+    })
+}
+
+fn run_tcpdump(cfg: &DnsSnifferConfig, feed: &Feed) -> Exit {
+    let iface = cfg.interface.clone().unwrap_or_else(|| {
+        if cfg!(target_os = "linux") { "any".into() } else { "en0".into() }
+    });
+    let args: Vec<String> = ["-l", "-n", "-i", &iface, "udp port 53 or tcp port 53"]
+        .map(String::from)
+        .to_vec();
+
+    // e.g. "12:00:00.1 IP 10.0.0.2.5353 > 8.8.8.8.53: 1234+ A? example.com. (29)"
+    run_capture("tcpdump", &args, feed, |line| {
+        let mut tokens = line.split_whitespace();
+        while let Some(tok) = tokens.next() {
+            if tok.ends_with('?') && tok.len() > 1 {
+                if let Some(name) = tokens.next() {
+                    let name = clean_name(name);
+                    if !name.is_empty() {
+                        return vec![(name, QuerySource::Dns)];
+                    }
+                }
+            }
+        }
+        Vec::new()
+    })
+}
+
+fn clean_name(raw: &str) -> String {
+    raw.trim().trim_end_matches('.').to_lowercase()
+}
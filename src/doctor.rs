@@ -0,0 +1,146 @@
+// ─────────────────────────────────────────────────────────────────
+//  doctor.rs — Which OS tools the agent found
+//
+//  Most detectors shell out (`REQUIREMENTS` lists to what, per OS), and
+//  a stripped-down image often lacks some of them: no wmctrl on a
+//  Wayland desktop, no ipconfig on a locked-down Windows build, no
+//  loginctl without systemd. PATH is searched once at startup; a
+//  feature none of whose tools is there is marked unavailable, its
+//  monitor detectors don't run (rather than warning every cycle) and
+//  the heartbeat helpers that need it are skipped. The full matrix is
+//  at `GET /doctor`, the unavailable features go out with heartbeats.
+//  Tools are looked up only, never run.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::models::ToolCheck;
+
+/// What one feature shells out to. Any one of a platform's tools is
+/// enough; an empty list means it needs none there (or isn't covered).
+struct Requirement {
+    feature: &'static str,
+    /// Monitor detectors switched off without a tool.
+    detectors: &'static [&'static str],
+    windows: &'static [&'static str],
+    macos: &'static [&'static str],
+    linux: &'static [&'static str],
+}
+
+const REQUIREMENTS: &[Requirement] = &[
+    Requirement { feature: "user_profile", detectors: &["user_profile"], windows: &["powershell"], macos: &["stat"], linux: &["loginctl", "who"] },
+    Requirement { feature: "firewall", detectors: &["firewall"], windows: &["netsh"], macos: &["pfctl"], linux: &["nft"] },
+    Requirement { feature: "dns_cache", detectors: &["dns_cache"], windows: &["ipconfig"], macos: &["dscacheutil"], linux: &["resolvectl", "journalctl", "nscd"] },
+    Requirement { feature: "window_titles", detectors: &["window_titles"], windows: &["powershell"], macos: &["osascript"], linux: &["wmctrl"] },
+    Requirement { feature: "connections", detectors: &["connections"], windows: &["netstat"], macos: &["lsof"], linux: &["ss"] },
+    Requirement { feature: "bandwidth", detectors: &["bandwidth"], windows: &[], macos: &["nettop"], linux: &["ss"] },
+    Requirement { feature: "audio", detectors: &["microphone"], windows: &["powershell"], macos: &[], linux: &["pactl"] },
+    Requirement { feature: "webcam", detectors: &["webcam"], windows: &["powershell"], macos: &[], linux: &[] },
+    Requirement { feature: "vpn_proxy", detectors: &["vpn_proxy"], windows: &["powershell"], macos: &["scutil"], linux: &["ip"] },
+    Requirement { feature: "remote_access_services", detectors: &[], windows: &["sc"], macos: &["launchctl"], linux: &["systemctl"] },
+    Requirement { feature: "gpu_usage", detectors: &[], windows: &["powershell"], macos: &["nvidia-smi"], linux: &["nvidia-smi"] },
+    Requirement { feature: "documents", detectors: &[], windows: &["powershell"], macos: &["osascript"], linux: &["wmctrl"] },
+    Requirement { feature: "soft_lock", detectors: &[], windows: &["powershell"], macos: &["osascript"], linux: &["wmctrl"] },
+    Requirement { feature: "focus_mode", detectors: &[], windows: &["powershell"], macos: &["osascript"], linux: &["xprop"] },
+    Requirement { feature: "notifications", detectors: &[], windows: &["msg"], macos: &["osascript"], linux: &["notify-send"] },
+    Requirement { feature: "unlock_prompt", detectors: &[], windows: &["powershell"], macos: &["osascript"], linux: &["zenity"] },
+    Requirement { feature: "exam_displays", detectors: &[], windows: &["powershell"], macos: &["system_profiler"], linux: &["xrandr"] },
+    Requirement { feature: "network", detectors: &[], windows: &["powershell"], macos: &["route"], linux: &["ip"] },
+    Requirement { feature: "wifi_ssid", detectors: &[], windows: &["netsh"], macos: &["networksetup"], linux: &["iwgetid", "nmcli"] },
+];
+
+/// Result of the startup probe, served at `GET /doctor`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolMatrix {
+    pub platform: &'static str,
+    pub checked_at: DateTime<Utc>,
+    /// Every tool looked for and whether it is on PATH.
+    pub tools: BTreeMap<String, bool>,
+    pub features: Vec<ToolCheck>,
+}
+
+impl ToolMatrix {
+    /// Search PATH for every tool this platform's features need and log
+    /// the features that can't work. Blocking (a few hundred stats).
+    pub fn probe() -> Self {
+        let mut tools = BTreeMap::new();
+        let mut features = Vec::new();
+        for req in REQUIREMENTS {
+            let needs = if cfg!(target_os = "windows") {
+                req.windows
+            } else if cfg!(target_os = "macos") {
+                req.macos
+            } else {
+                req.linux
+            };
+            if needs.is_empty() {
+                continue;
+            }
+            for tool in needs {
+                tools.entry(tool.to_string()).or_insert_with(|| on_path(tool));
+            }
+            let using = needs.iter().find(|tool| tools.get(**tool) == Some(&true)).map(|tool| tool.to_string());
+            let disables: Vec<String> = match using {
+                Some(_) => Vec::new(),
+                None => req.detectors.iter().map(|d| d.to_string()).collect(),
+            };
+            if using.is_none() && disables.is_empty() {
+                info!("🩺 {} unavailable: none of {} found on PATH", req.feature, needs.join(" / "));
+            } else if using.is_none() {
+                warn!(
+                    "🩺 {} unavailable: none of {} found on PATH — {} disabled",
+                    req.feature,
+                    needs.join(" / "),
+                    disables.join(", ")
+                );
+            }
+            features.push(ToolCheck {
+                feature: req.feature.into(),
+                status: if using.is_some() { "ok" } else { "unavailable" }.into(),
+                needs: needs.iter().map(|t| t.to_string()).collect(),
+                using,
+                disables,
+            });
+        }
+        Self { platform: std::env::consts::OS, checked_at: Utc::now(), tools, features }
+    }
+
+    /// False only for a feature found unavailable; features with nothing
+    /// to check on this platform count as available.
+    pub fn available(&self, feature: &str) -> bool {
+        !self.features.iter().any(|f| f.feature == feature && f.using.is_none())
+    }
+
+    pub fn unavailable(&self) -> Vec<ToolCheck> {
+        self.features.iter().filter(|f| f.using.is_none()).cloned().collect()
+    }
+
+    /// Monitor detectors to switch off.
+    pub fn disabled_detectors(&self) -> Vec<String> {
+        self.features.iter().flat_map(|f| f.disables.iter().cloned()).collect()
+    }
+}
+
+/// Whether `tool` is an existing file in a PATH directory (with one of
+/// PATHEXT's extensions on Windows, unless it has its own).
+fn on_path(tool: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    let extensions: Vec<String> = if cfg!(target_os = "windows") && Path::new(tool).extension().is_none() {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into())
+            .split(';')
+            .filter(|e| !e.is_empty())
+            .map(str::to_string)
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    std::env::split_paths(&path).any(|dir| extensions.iter().any(|ext| dir.join(format!("{tool}{ext}")).is_file()))
+}
@@ -0,0 +1,167 @@
+// ─────────────────────────────────────────────────────────────────
+//  documents.rs — Documents open in office apps and editors
+//
+//  Read from window titles of known applications, so the teacher can
+//  see in usage samples and the weekly report whether a student is
+//  working on the assigned file:
+//    "Essay.docx - Word", "essay.odt - LibreOffice Writer",
+//    "● main.py - project - Visual Studio Code", "Essay - Google Docs"
+//  Windows lists each process's main window, macOS every window of
+//  the visible apps, Linux the windows known to `wmctrl -lp`.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::config::DocumentsConfig;
+use crate::models::OpenDocument;
+use crate::monitor::silent_cmd;
+
+/// Process name (lowercase, without `.exe`) → application shown.
+const APPS: &[(&str, &str)] = &[
+    ("winword", "Word"),
+    ("microsoft word", "Word"),
+    ("excel", "Excel"),
+    ("microsoft excel", "Excel"),
+    ("powerpnt", "PowerPoint"),
+    ("microsoft powerpoint", "PowerPoint"),
+    ("soffice", "LibreOffice"),
+    ("soffice.bin", "LibreOffice"),
+    ("libreoffice", "LibreOffice"),
+    ("wps", "WPS Office"),
+    ("notepad", "Notepad"),
+    ("notepad++", "Notepad++"),
+    ("code", "VS Code"),
+    ("sublime_text", "Sublime Text"),
+    ("gedit", "gedit"),
+    ("gnome-text-editor", "Text Editor"),
+    ("kate", "Kate"),
+    ("pycharm64", "PyCharm"),
+    ("pycharm", "PyCharm"),
+    ("idle", "IDLE"),
+    ("pages", "Pages"),
+    ("numbers", "Numbers"),
+    ("keynote", "Keynote"),
+    ("textedit", "TextEdit"),
+];
+
+/// Online editors, recognised in any (browser) window title.
+const WEB_EDITORS: &[(&str, &str)] = &[
+    (" - Google Docs", "Google Docs"),
+    (" - Google Sheets", "Google Sheets"),
+    (" - Google Slides", "Google Slides"),
+    (" - Google Документы", "Google Docs"),
+    (" - Google Таблицы", "Google Sheets"),
+    (" - Google Презентации", "Google Slides"),
+];
+
+/// Documents open right now, one entry per (app, file). Blocking.
+pub fn open_documents(cfg: &DocumentsConfig) -> Vec<OpenDocument> {
+    let mut found = BTreeSet::new();
+    for (process, title) in window_titles() {
+        if let Some(doc) = document(cfg, &process, &title) {
+            found.insert((doc.app, doc.file));
+        }
+    }
+    found.into_iter().map(|(app, file)| OpenDocument { app, file }).collect()
+}
+
+fn document(cfg: &DocumentsConfig, process: &str, title: &str) -> Option<OpenDocument> {
+    let process = process.trim().to_lowercase();
+    let process = process.strip_suffix(".exe").unwrap_or(&process);
+    let app = APPS
+        .iter()
+        .find(|(name, _)| *name == process)
+        .map(|(_, app)| app.to_string())
+        .or_else(|| cfg.apps.iter().any(|a| a.to_lowercase() == process).then(|| process.to_string()));
+
+    let (app, name) = match app {
+        Some(app) => (app, title),
+        None => {
+            let (suffix, app) = WEB_EDITORS.iter().find(|(suffix, _)| title.contains(suffix))?;
+            (app.to_string(), &title[..title.find(suffix)?])
+        }
+    };
+    let file = file_name(name, &app)?;
+    Some(OpenDocument { app, file })
+}
+
+/// The document in a window title such as "* C:\work\essay.txt - Notepad++"
+/// or "notes.txt (~/school) - gedit". None for start screens titled only
+/// with the application.
+fn file_name(title: &str, app: &str) -> Option<String> {
+    let mut name = title.split(" - ").next().unwrap_or_default().trim();
+    // Unsaved-changes markers
+    name = name.trim_start_matches(['●', '•', '*']).trim_end_matches('*').trim();
+    // "[Compatibility Mode]", "(~/school)" and similar annotations
+    for (open, close) in [('[', ']'), ('(', ')')] {
+        if name.ends_with(close) {
+            if let Some(at) = name.rfind(open) {
+                name = name[..at].trim();
+            }
+        }
+    }
+    let name = if name.contains(['/', '\\']) {
+        let path = name.replace('\\', "/");
+        Path::new(&path).file_name()?.to_string_lossy().into_owned()
+    } else {
+        name.to_string()
+    };
+    let generic = name.eq_ignore_ascii_case(app) || APPS.iter().any(|(_, a)| name.eq_ignore_ascii_case(a));
+    (!name.is_empty() && !generic).then_some(name)
+}
+
+/// (process name, window title) of every titled window.
+pub(crate) fn window_titles() -> Vec<(String, String)> {
+    if cfg!(target_os = "windows") {
+        let script = r#"Get-Process | Where-Object {$_.MainWindowTitle -ne ''} | ForEach-Object { "$($_.ProcessName)`t$($_.MainWindowTitle)" }"#;
+        let Ok(out) = silent_cmd("powershell").args(["-NoProfile", "-Command", script]).output() else {
+            return Vec::new();
+        };
+        tab_separated(&String::from_utf8_lossy(&out.stdout))
+    } else if cfg!(target_os = "macos") {
+        let script = r#"
+            set out to ""
+            tell application "System Events"
+                repeat with p in (every process whose background only is false)
+                    try
+                        repeat with w in windows of p
+                            set out to out & (name of p) & tab & (name of w) & linefeed
+                        end repeat
+                    end try
+                end repeat
+            end tell
+            return out
+        "#;
+        let Ok(out) = silent_cmd("osascript").args(["-e", script]).output() else {
+            return Vec::new();
+        };
+        tab_separated(&String::from_utf8_lossy(&out.stdout))
+    } else {
+        let Ok(out) = silent_cmd("wmctrl").arg("-lp").output() else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&out.stdout).lines().filter_map(wmctrl_window).collect()
+    }
+}
+
+fn tab_separated(out: &str) -> Vec<(String, String)> {
+    out.lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(process, title)| (process.to_string(), title.trim().to_string()))
+        .collect()
+}
+
+//   0x03a00004  0 4242   lab-pc essay.odt - LibreOffice Writer
+fn wmctrl_window(line: &str) -> Option<(String, String)> {
+    // id, desktop, pid, host; the title is the rest of the line
+    let mut rest = line.trim_start();
+    let mut cols = Vec::new();
+    for _ in 0..4 {
+        let end = rest.find(char::is_whitespace)?;
+        cols.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", cols[2])).ok()?;
+    Some((comm.trim().to_string(), rest.trim().to_string()))
+}
@@ -0,0 +1,176 @@
+// ─────────────────────────────────────────────────────────────────
+//  doh.rs — DNS-over-HTTPS / DNS-over-TLS bypass detection
+//
+//  Encrypted DNS skips the OS resolver, so neither the DNS cache nor
+//  the hosts file sees what a student looks up. We catch it two ways:
+//    • connections to known DoH resolvers (port 443) or any DoT (853)
+//    • browser settings with secure DNS switched on
+//  and can push browser policies that turn DoH off.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::PathBuf;
+
+use tracing::{info, warn};
+
+use crate::browser::{self, BrowserFamily};
+#[cfg(target_os = "windows")]
+use crate::monitor::silent_cmd;
+use crate::netstat::TcpConnection;
+
+/// Standard DNS-over-TLS port.
+pub const DOT_PORT: u16 = 853;
+
+/// Resolved addresses of the configured DoH endpoints.
+pub struct DohResolvers {
+    by_ip: HashMap<IpAddr, String>,
+}
+
+impl DohResolvers {
+    /// Endpoints may be IP literals or hostnames (resolved now, blocking).
+    pub fn resolve(endpoints: &[String]) -> Self {
+        let mut by_ip = HashMap::new();
+        for ep in endpoints {
+            if let Ok(ip) = ep.parse::<IpAddr>() {
+                by_ip.insert(ip, ep.clone());
+                continue;
+            }
+            match (ep.as_str(), 443).to_socket_addrs() {
+                Ok(addrs) => {
+                    for a in addrs {
+                        by_ip.entry(a.ip()).or_insert_with(|| ep.clone());
+                    }
+                }
+                Err(e) => warn!("Could not resolve DoH endpoint {ep}: {e}"),
+            }
+        }
+        Self { by_ip }
+    }
+
+    /// Name of the resolver / protocol this connection bypasses DNS with.
+    pub fn classify(&self, conn: &TcpConnection) -> Option<String> {
+        if conn.remote.port() == DOT_PORT {
+            return Some(format!("DoT {}", conn.remote.ip()));
+        }
+        if conn.remote.port() == 443 {
+            return self.by_ip.get(&conn.remote.ip()).map(|name| format!("DoH {name}"));
+        }
+        None
+    }
+}
+
+// ── Browser settings ────────────────────────────────────────────
+
+/// Browsers (by name) whose own settings have secure DNS switched on.
+pub fn browsers_with_doh() -> Vec<&'static str> {
+    let mut found = HashSet::new();
+    let mut checked_roots = HashSet::new();
+
+    for profile in browser::discover_profiles() {
+        let enabled = match profile.family {
+            // Chromium stores the choice browser-wide in "<User Data>/Local State"
+            BrowserFamily::Chromium => {
+                let Some(root) = profile.dir.parent().map(PathBuf::from) else {
+                    continue;
+                };
+                if !checked_roots.insert(root.clone()) {
+                    continue;
+                }
+                std::fs::read_to_string(root.join("Local State"))
+                    .ok()
+                    .and_then(|raw| serde_json::from_str::<serde_json::Value>(&raw).ok())
+                    .and_then(|json| json["dns_over_https"]["mode"].as_str().map(String::from))
+                    .is_some_and(|mode| mode == "secure" || mode == "automatic")
+            }
+            // Firefox: network.trr.mode 2 (DoH first) or 3 (DoH only)
+            BrowserFamily::Firefox => std::fs::read_to_string(profile.dir.join("prefs.js"))
+                .map(|prefs| {
+                    prefs.lines().any(|l| {
+                        l.contains("\"network.trr.mode\"") && (l.contains(", 2)") || l.contains(", 3)"))
+                    })
+                })
+                .unwrap_or(false),
+        };
+        if enabled {
+            found.insert(profile.browser);
+        }
+    }
+
+    let mut out: Vec<_> = found.into_iter().collect();
+    out.sort();
+    out
+}
+
+/// Push machine-wide browser policies that switch secure DNS off
+/// (Chrome, Edge, Firefox). Returns true if every policy was written.
+pub fn disable_browser_doh() -> bool {
+    let ok = write_policies();
+    if ok {
+        info!("🔒 Browser DoH disabled via policy");
+    } else {
+        warn!("Could not write browser DoH policies (agent needs admin rights)");
+    }
+    ok
+}
+
+#[cfg(target_os = "windows")]
+fn write_policies() -> bool {
+    let reg = |key: &str, name: &str, kind: &str, data: &str| {
+        silent_cmd("reg")
+            .args(["add", key, "/v", name, "/t", kind, "/d", data, "/f"])
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    };
+    let chrome = reg(r"HKLM\SOFTWARE\Policies\Google\Chrome", "DnsOverHttpsMode", "REG_SZ", "off");
+    let edge = reg(r"HKLM\SOFTWARE\Policies\Microsoft\Edge", "DnsOverHttpsMode", "REG_SZ", "off");
+    let ff_key = r"HKLM\SOFTWARE\Policies\Mozilla\Firefox\DNSOverHTTPS";
+    let firefox = reg(ff_key, "Enabled", "REG_DWORD", "0") && reg(ff_key, "Locked", "REG_DWORD", "1");
+    chrome && edge && firefox
+}
+
+#[cfg(target_os = "macos")]
+fn write_policies() -> bool {
+    let defaults = |domain: &str, args: &[&str]| {
+        std::process::Command::new("defaults")
+            .arg("write")
+            .arg(format!("/Library/Preferences/{domain}"))
+            .args(args)
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+    };
+    let chrome = defaults("com.google.Chrome", &["DnsOverHttpsMode", "off"]);
+    let edge = defaults("com.microsoft.Edge", &["DnsOverHttpsMode", "off"]);
+    let firefox = defaults(
+        "org.mozilla.firefox",
+        &["DNSOverHTTPS", "-dict", "Enabled", "-bool", "false", "Locked", "-bool", "true"],
+    );
+    chrome && edge && firefox
+}
+
+#[cfg(target_os = "linux")]
+fn write_policies() -> bool {
+    // Chromium-family browsers merge every JSON file in their managed dir,
+    // so a dedicated file never clobbers other policies.
+    let chromium = serde_json::json!({ "DnsOverHttpsMode": "off" }).to_string();
+    let mut ok = true;
+    for dir in ["/etc/opt/chrome/policies/managed", "/etc/opt/edge/policies/managed", "/etc/chromium/policies/managed"] {
+        ok &= std::fs::create_dir_all(dir)
+            .and_then(|_| std::fs::write(format!("{dir}/nishack-doh.json"), &chromium))
+            .is_ok();
+    }
+
+    // Firefox reads a single policies.json — merge into it instead of replacing.
+    let path = "/etc/firefox/policies/policies.json";
+    let mut policies: serde_json::Value = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_else(|| serde_json::json!({ "policies": {} }));
+    policies["policies"]["DNSOverHTTPS"] = serde_json::json!({ "Enabled": false, "Locked": true });
+    ok &= std::fs::create_dir_all("/etc/firefox/policies")
+        .and_then(|_| std::fs::write(path, policies.to_string()))
+        .is_ok();
+    ok
+}
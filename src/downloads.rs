@@ -0,0 +1,105 @@
+// ─────────────────────────────────────────────────────────────────
+//  downloads.rs — Files landing in users' Downloads / Desktop
+//
+//  The agent usually runs as SYSTEM / root, so every local user's
+//  folders are watched, not just its own:
+//    Windows: C:\Users\<name>\{Downloads,Desktop}
+//    macOS:   /Users/<name>/{Downloads,Desktop}
+//    Linux:   /home/<name>/{Downloads,Desktop} (+ Russian XDG names)
+//  Only the top level of each folder is listed, and files still being
+//  downloaded (.crdownload, .part, …) are skipped until they finish.
+// ─────────────────────────────────────────────────────────────────
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Suffixes browsers use while a download is in progress.
+const PARTIAL: &[&str] = &["crdownload", "part", "partial", "download", "opdownload", "tmp"];
+
+/// Profile directories that don't belong to a real user.
+const NOT_USERS: &[&str] = &["public", "default", "default user", "all users", "shared", "guest"];
+
+const FOLDERS: &[&str] = &["Downloads", "Desktop", "Загрузки", "Рабочий стол"];
+
+pub struct FoundFile {
+    pub path: PathBuf,
+    /// File name, lowercased.
+    pub name: String,
+    pub size: u64,
+    /// Later of creation and modification (copies keep the source's mtime).
+    pub changed: SystemTime,
+}
+
+/// Downloads / Desktop folders of every local user that exist.
+pub fn watched_dirs() -> Vec<PathBuf> {
+    let users_root: PathBuf = if cfg!(target_os = "windows") {
+        let drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".into());
+        PathBuf::from(format!("{drive}\\Users"))
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from("/Users")
+    } else {
+        PathBuf::from("/home")
+    };
+
+    let mut homes: Vec<PathBuf> = std::fs::read_dir(&users_root)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+                .filter(|e| !NOT_USERS.contains(&e.file_name().to_string_lossy().to_lowercase().as_str()))
+                .map(|e| e.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(home) = std::env::var_os("USERPROFILE").or_else(|| std::env::var_os("HOME")).map(PathBuf::from) {
+        if !homes.contains(&home) {
+            homes.push(home);
+        }
+    }
+
+    homes
+        .iter()
+        .flat_map(|home| FOLDERS.iter().map(move |f| home.join(f)))
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Completed files directly inside `dir`.
+pub fn files_in(dir: &Path) -> Vec<FoundFile> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            if extension(&name).is_some_and(|ext| PARTIAL.contains(&ext)) {
+                return None;
+            }
+            let modified = meta.modified().ok()?;
+            let changed = meta.created().map_or(modified, |created| created.max(modified));
+            Some(FoundFile { path: entry.path(), name, size: meta.len(), changed })
+        })
+        .collect()
+}
+
+/// Extension of a file name, without the dot.
+pub fn extension(name: &str) -> Option<&str> {
+    name.rsplit_once('.').map(|(_, ext)| ext).filter(|ext| !ext.is_empty())
+}
+
+/// "12.4 MB"-style size.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{bytes} B") } else { format!("{size:.1} {}", UNITS[unit]) }
+}
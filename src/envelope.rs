@@ -0,0 +1,138 @@
+// ─────────────────────────────────────────────────────────────────
+//  envelope.rs — AES-256-GCM sealing of screen images
+//
+//  With an `[encryption] key` (or NISHACK_ENCRYPTION_KEY), screenshots
+//  and heartbeat thumbnails are sealed before they reach Redis or the
+//  disk sink, and so are live stream frames and magnifier crops. A sealed payload is
+//    "NGC1" ‖ u8 key-id length ‖ key id ‖ 12-byte nonce ‖
+//    ciphertext ‖ 16-byte tag
+//  with everything before the nonce as associated data, so the key id
+//  can't be swapped. The key is either given as base64, or derived with
+//  HKDF-SHA256 from a `key_file` of random bytes (which can be kept
+//  readable by administrators only, unlike config.toml). The key id says which shared key the teacher
+//  backend should open it with, which lets keys be rotated one room at
+//  a time. Payloads without the magic are plain JPEGs (which start with
+//  0xFF 0xD8), written before encryption was turned on.
+// ─────────────────────────────────────────────────────────────────
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{bail, Context};
+use base64::Engine;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use tracing::info;
+
+use crate::config::EncryptionConfig;
+
+const MAGIC: &[u8; 4] = b"NGC1";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// Key files hold at least a key's worth of random bytes.
+const MIN_KEY_FILE_LEN: usize = 32;
+const KEY_FILE_INFO: &[u8] = b"nishack envelope aes-256-gcm";
+/// What the payloads are marked with in screenshot metadata and the
+/// stream handshake.
+pub const ALGORITHM: &str = "aes-256-gcm";
+
+pub struct Sealer {
+    cipher: Aes256Gcm,
+    key_id: String,
+}
+
+impl Sealer {
+    /// The sealer for `cfg`, None when no key is set.
+    pub fn from_config(cfg: &EncryptionConfig) -> anyhow::Result<Option<Self>> {
+        let key = std::env::var("NISHACK_ENCRYPTION_KEY")
+            .ok()
+            .filter(|k| !k.is_empty())
+            .or_else(|| cfg.key.clone().filter(|k| !k.is_empty()));
+        let key = match (key, cfg.key_file.as_deref().filter(|f| !f.is_empty())) {
+            (Some(key), _) => {
+                let key = base64::engine::general_purpose::STANDARD
+                    .decode(key.trim())
+                    .context("[encryption] key isn't valid base64")?;
+                if key.len() != 32 {
+                    bail!("[encryption] key is {} bytes, expected 32 (base64 of a 256-bit key)", key.len());
+                }
+                key
+            }
+            (None, Some(path)) => key_from_file(path)?.to_vec(),
+            (None, None) => return Ok(None),
+        };
+        if cfg.key_id.is_empty() || cfg.key_id.len() > u8::MAX as usize {
+            bail!("[encryption] key_id must be 1 to 255 bytes");
+        }
+        info!("🔐 Screen images are encrypted (AES-256-GCM, key id \"{}\")", cfg.key_id);
+        Ok(Some(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+            key_id: cfg.key_id.clone(),
+        }))
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn header(key_id: &str) -> Vec<u8> {
+        let mut header = Vec::with_capacity(MAGIC.len() + 1 + key_id.len());
+        header.extend_from_slice(MAGIC);
+        header.push(key_id.len() as u8);
+        header.extend_from_slice(key_id.as_bytes());
+        header
+    }
+
+    pub fn seal(&self, plain: &[u8]) -> Vec<u8> {
+        let mut out = Self::header(&self.key_id);
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let aad = out.clone();
+        // Only fails for inputs beyond what GCM allows (64 GiB)
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plain, aad: &aad })
+            .expect("AES-GCM encryption of a screen image");
+        out.reserve(NONCE_LEN + sealed.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&sealed);
+        out
+    }
+
+    pub fn open(&self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if !is_sealed(bytes) {
+            bail!("not a sealed payload");
+        }
+        let id_len = bytes[MAGIC.len()] as usize;
+        let header_len = MAGIC.len() + 1 + id_len;
+        if bytes.len() < header_len + NONCE_LEN + TAG_LEN {
+            bail!("sealed payload is truncated");
+        }
+        let key_id = String::from_utf8_lossy(&bytes[MAGIC.len() + 1..header_len]);
+        if key_id != self.key_id {
+            bail!("sealed with key id \"{key_id}\", this agent has \"{}\"", self.key_id);
+        }
+        let (header, rest) = bytes.split_at(header_len);
+        let (nonce, sealed) = rest.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: sealed, aad: header })
+            .map_err(|_| anyhow::anyhow!("authentication failed (wrong key or damaged payload)"))
+    }
+}
+
+/// The AES key for a key file: HKDF-SHA256 (no salt, info `KEY_FILE_INFO`)
+/// of its contents, at least `MIN_KEY_FILE_LEN` random bytes.
+fn key_from_file(path: &str) -> anyhow::Result<[u8; 32]> {
+    let material = std::fs::read(path).with_context(|| format!("reading [encryption] key_file {path}"))?;
+    if material.len() < MIN_KEY_FILE_LEN {
+        bail!("[encryption] key_file {path} has {} bytes, expected at least {MIN_KEY_FILE_LEN} random ones", material.len());
+    }
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, &material)
+        .expand(KEY_FILE_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    Ok(key)
+}
+
+/// Whether `bytes` is a sealed payload rather than a plain image.
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.len() > MAGIC.len() && bytes.starts_with(MAGIC)
+}
@@ -0,0 +1,126 @@
+// ─────────────────────────────────────────────────────────────────
+//  exam.rs — Exam mode
+//
+//  A machine-wide switch the teacher flips for the duration of a
+//  test. Entering exam mode applies the configured OS changes (e.g.
+//  do-not-disturb) and leaving it — or shutting the agent down —
+//  restores them, extra displays switched off during the exam and
+//  the shortcut lockdown included (see `displays.rs`, `lockdown.rs`).
+// ─────────────────────────────────────────────────────────────────
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::info;
+
+use crate::config::ExamConfig;
+use crate::displays::{self, Disabled, Layout};
+use crate::focus::{self, SavedFocus};
+use crate::lockdown::{self, Lockdown};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExamStatus {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    /// Whether OS do-not-disturb is currently held on by exam mode.
+    pub do_not_disturb: bool,
+    /// Whether shortcuts and the task manager are blocked right now.
+    pub lockdown: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    since: Option<DateTime<Utc>>,
+    focus: Option<SavedFocus>,
+    /// Extra displays switched off during this exam.
+    displays: Option<Disabled>,
+    lockdown: Option<Lockdown>,
+}
+
+/// Shared exam-mode state (API, scans and shutdown all consult it).
+pub struct ExamMode {
+    cfg: ExamConfig,
+    state_dir: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+impl ExamMode {
+    /// Undoes a lockdown a crashed run left in `state_dir`. Blocking.
+    pub fn new(cfg: &ExamConfig, state_dir: &Path) -> Self {
+        lockdown::recover(state_dir);
+        Self { cfg: cfg.clone(), state_dir: state_dir.to_path_buf(), inner: Mutex::new(Inner::default()) }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.inner.lock().is_ok_and(|i| i.since.is_some())
+    }
+
+    pub fn status(&self) -> ExamStatus {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        ExamStatus {
+            active: inner.since.is_some(),
+            since: inner.since,
+            do_not_disturb: inner.focus.is_some(),
+            lockdown: inner.lockdown.is_some(),
+        }
+    }
+
+    /// Enter exam mode (no-op if already active). Blocking.
+    pub fn start(&self) -> ExamStatus {
+        {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            if inner.since.is_none() {
+                info!("📝 Exam mode started");
+                inner.since = Some(Utc::now());
+                if self.cfg.do_not_disturb {
+                    inner.focus = focus::enable(&self.cfg);
+                }
+                if self.cfg.lockdown {
+                    inner.lockdown = lockdown::engage(&self.state_dir);
+                }
+            }
+        }
+        self.status()
+    }
+
+    /// Leave exam mode and restore everything it changed. Blocking.
+    pub fn stop(&self) -> ExamStatus {
+        {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            if inner.since.take().is_some() {
+                info!("📝 Exam mode ended");
+                if let Some(saved) = inner.focus.take() {
+                    focus::restore(&self.cfg, saved);
+                }
+                if let Some(disabled) = inner.displays.take() {
+                    displays::restore(disabled);
+                }
+                if let Some(locked) = inner.lockdown.take() {
+                    lockdown::release(locked);
+                }
+            }
+        }
+        self.status()
+    }
+
+    /// Switch the outputs beyond the primary one off until the exam ends.
+    /// Returns whether any were switched off. Blocking.
+    pub fn disable_extra_displays(&self, layout: &Layout) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.since.is_none() {
+            return false;
+        }
+        let Some(disabled) = displays::disable_extra(layout) else {
+            return false;
+        };
+        // Re-attached after an earlier switch-off: still restored once
+        match (inner.displays.as_mut(), disabled) {
+            (Some(Disabled::Outputs(names)), Disabled::Outputs(more)) => names.extend(more),
+            (_, disabled) => inner.displays = Some(disabled),
+        }
+        true
+    }
+}
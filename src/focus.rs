@@ -0,0 +1,139 @@
+// ─────────────────────────────────────────────────────────────────
+//  focus.rs — OS do-not-disturb during exams
+//
+//  Turns notification banners off and remembers the previous
+//  settings so they can be put back exactly:
+//    Windows: toast notifications off (PushNotifications\ToastEnabled
+//             and the global Notifications toggle, HKCU)
+//    macOS:   a user-provided Shortcuts pair for Focus (Monterey+),
+//             else the legacy notificationcenterui doNotDisturb flag
+//    Linux:   GNOME show-banners
+//  Chat apps that draw their own popups (instead of OS notifications)
+//  are not covered.
+// ─────────────────────────────────────────────────────────────────
+
+use tracing::{info, warn};
+
+use crate::config::ExamConfig;
+use crate::monitor::silent_cmd;
+
+/// Settings as they were before do-not-disturb was switched on.
+#[derive(Debug, Clone)]
+pub struct SavedFocus {
+    /// (registry key / setting, previous value); None = was unset.
+    values: Vec<(String, Option<String>)>,
+}
+
+const WIN_TOASTS: &[(&str, &str)] = &[
+    (r"HKCU\Software\Microsoft\Windows\CurrentVersion\PushNotifications", "ToastEnabled"),
+    (r"HKCU\Software\Microsoft\Windows\CurrentVersion\Notifications\Settings", "NOC_GLOBAL_SETTING_TOASTS_ENABLED"),
+];
+
+/// Switch do-not-disturb on. Returns what to restore, or None on failure.
+pub fn enable(cfg: &ExamConfig) -> Option<SavedFocus> {
+    let saved = if cfg!(target_os = "windows") {
+        enable_windows()
+    } else if cfg!(target_os = "macos") {
+        enable_macos(cfg)
+    } else {
+        enable_gnome()
+    };
+    match &saved {
+        Some(_) => info!("🔕 Do-not-disturb on"),
+        None => warn!("Could not switch do-not-disturb on"),
+    }
+    saved
+}
+
+/// Put the settings back as `saved` recorded them.
+pub fn restore(cfg: &ExamConfig, saved: SavedFocus) {
+    if cfg!(target_os = "windows") {
+        for ((key, name), (_, old)) in WIN_TOASTS.iter().zip(&saved.values) {
+            match old {
+                Some(v) => {
+                    reg_set(key, name, v);
+                }
+                None => {
+                    let _ = silent_cmd("reg").args(["delete", key, "/v", name, "/f"]).output();
+                }
+            }
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(off) = &cfg.macos_focus_off_shortcut {
+            let _ = silent_cmd("shortcuts").args(["run", off]).status();
+        } else {
+            let old = saved.values.first().and_then(|(_, v)| v.clone()).unwrap_or_else(|| "0".into());
+            let _ = silent_cmd("defaults")
+                .args(["-currentHost", "write", "com.apple.notificationcenterui", "doNotDisturb", "-bool", &old])
+                .status();
+            let _ = silent_cmd("killall").arg("NotificationCenter").status();
+        }
+    } else {
+        let old = saved.values.first().and_then(|(_, v)| v.clone()).unwrap_or_else(|| "true".into());
+        let _ = silent_cmd("gsettings")
+            .args(["set", "org.gnome.desktop.notifications", "show-banners", &old])
+            .status();
+    }
+    info!("🔔 Notification settings restored");
+}
+
+fn reg_get(key: &str, name: &str) -> Option<String> {
+    let out = silent_cmd("reg").args(["query", key, "/v", name]).output().ok()?;
+    String::from_utf8_lossy(&out.stdout).lines().find_map(|l| {
+        let cols: Vec<&str> = l.split_whitespace().collect();
+        (cols.len() == 3 && cols[0] == name).then(|| cols[2].to_string())
+    })
+}
+
+fn reg_set(key: &str, name: &str, value: &str) -> bool {
+    // `reg query` prints DWORDs as hex; `reg add /d` accepts that form
+    silent_cmd("reg")
+        .args(["add", key, "/v", name, "/t", "REG_DWORD", "/d", value, "/f"])
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+fn enable_windows() -> Option<SavedFocus> {
+    let values: Vec<_> = WIN_TOASTS
+        .iter()
+        .map(|(key, name)| (format!(r"{key}\{name}"), reg_get(key, name)))
+        .collect();
+    let mut ok = true;
+    for (key, name) in WIN_TOASTS {
+        ok &= reg_set(key, name, "0");
+    }
+    ok.then_some(SavedFocus { values })
+}
+
+fn enable_macos(cfg: &ExamConfig) -> Option<SavedFocus> {
+    if let Some(on) = &cfg.macos_focus_on_shortcut {
+        let ran = silent_cmd("shortcuts").args(["run", on]).status().is_ok_and(|s| s.success());
+        return ran.then(|| SavedFocus { values: Vec::new() });
+    }
+    let old = silent_cmd("defaults")
+        .args(["-currentHost", "read", "com.apple.notificationcenterui", "doNotDisturb"])
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|v| !v.is_empty());
+    let ok = silent_cmd("defaults")
+        .args(["-currentHost", "write", "com.apple.notificationcenterui", "doNotDisturb", "-bool", "true"])
+        .status()
+        .is_ok_and(|s| s.success());
+    let _ = silent_cmd("killall").arg("NotificationCenter").status();
+    ok.then(|| SavedFocus { values: vec![("doNotDisturb".into(), old)] })
+}
+
+fn enable_gnome() -> Option<SavedFocus> {
+    let old = silent_cmd("gsettings")
+        .args(["get", "org.gnome.desktop.notifications", "show-banners"])
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|v| !v.is_empty());
+    let ok = silent_cmd("gsettings")
+        .args(["set", "org.gnome.desktop.notifications", "show-banners", "false"])
+        .status()
+        .is_ok_and(|s| s.success());
+    ok.then(|| SavedFocus { values: vec![("show-banners".into(), old)] })
+}
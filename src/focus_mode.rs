@@ -0,0 +1,327 @@
+// ─────────────────────────────────────────────────────────────────
+//  focus_mode.rs — Pin the student to one application
+//
+//  `POST /focus {"app": "winword"}` (or the `focus` command) allows a
+//  single program: every `[focus_mode] check_secs` the foreground
+//  window is looked up, and one belonging to anything else is
+//  minimised or its process killed (`action`). Desktop shell parts
+//  listed in `always_allowed` and the agent itself are left alone.
+//  Lifted by `POST /focus/stop` or when `minutes` run out. Switches
+//  are reported as one `focus_escape` violation per session, its
+//  occurrences counting them (as for soft locks). Not to be confused
+//  with the exam do-not-disturb in `focus.rs`.
+//    Windows: GetForegroundWindow / ShowWindow (user32)
+//    macOS:   frontmost process (System Events), hidden to minimise
+//    Linux:   _NET_ACTIVE_WINDOW / _NET_WM_PID (xprop), xdotool
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tracing::{info, warn};
+
+use crate::audit::Audit;
+use crate::config::{FocusAction, FocusModeConfig};
+use crate::models::{Violation, ViolationKind};
+use crate::monitor::silent_cmd;
+use crate::violation_sinks::ViolationSinks;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FocusStatus {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<FocusAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    /// When the timer lifts focus mode; None = until `POST /focus/stop`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    /// Times another application was brought to the front.
+    pub escapes: u32,
+}
+
+struct Pinned {
+    app: String,
+    action: FocusAction,
+    since: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    pinned: Option<Pinned>,
+    /// The session's escape violation, once one was reported.
+    violation: Option<Violation>,
+}
+
+/// Shared focus-mode state (API, the enforcement loop and heartbeats).
+#[derive(Default)]
+pub struct FocusMode {
+    inner: Mutex<Inner>,
+}
+
+impl FocusMode {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The allowed application while focus mode is on.
+    pub fn app(&self) -> Option<String> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.pinned.as_ref().map(|p| p.app.clone())
+    }
+
+    pub fn status(&self) -> FocusStatus {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let pinned = inner.pinned.as_ref();
+        FocusStatus {
+            active: pinned.is_some(),
+            app: pinned.map(|p| p.app.clone()),
+            action: pinned.map(|p| p.action),
+            since: pinned.map(|p| p.since),
+            until: pinned.and_then(|p| p.until),
+            escapes: inner.violation.as_ref().map_or(0, |v| v.occurrences),
+        }
+    }
+
+    /// Allow only `app` until `stop` or `until`. Replaces a running
+    /// session, whose escape count starts over.
+    pub fn start(&self, app: &str, action: FocusAction, until: Option<DateTime<Utc>>) -> FocusStatus {
+        {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            info!("🎯 Focus mode on: only {app} ({action:?})");
+            *inner = Inner {
+                pinned: Some(Pinned { app: normalize(app), action, since: Utc::now(), until }),
+                violation: None,
+            };
+        }
+        self.status()
+    }
+
+    /// Leave focus mode.
+    pub fn stop(&self) -> FocusStatus {
+        let status = self.status();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.pinned.take().is_some() {
+            info!("🎯 Focus mode off ({} escape(s))", status.escapes);
+        }
+        status
+    }
+
+    /// End focus mode if its timer ran out; the final status if so.
+    fn expire(&self) -> Option<FocusStatus> {
+        let expired = {
+            let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            inner.pinned.as_ref().and_then(|p| p.until).is_some_and(|until| Utc::now() >= until)
+        };
+        expired.then(|| self.stop())
+    }
+
+    fn pinned(&self) -> Option<(String, FocusAction)> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.pinned.as_ref().map(|p| (p.app.clone(), p.action))
+    }
+
+    /// Count a switch to `target`. Returns the session's violation, new
+    /// (`occurrences == 1`) or with the count bumped; None if focus mode
+    /// was lifted meanwhile.
+    fn escaped(&self, hostname: &str, username: &str, target: &str, enforced: bool) -> Option<Violation> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let app = inner.pinned.as_ref()?.app.clone();
+        let detail = format!("switched to {target} while only {app} is allowed");
+        let v = match inner.violation.as_mut() {
+            Some(v) => {
+                v.occurrences += 1;
+                v.last_seen = Some(Utc::now());
+                v.detail = Some(detail);
+                v.action_taken |= enforced;
+                v
+            }
+            None => inner.violation.insert(Violation {
+                hostname: hostname.to_string(),
+                target: target.to_string(),
+                kind: ViolationKind::FocusEscape,
+                action_taken: enforced,
+                username: username.to_string(),
+                timestamp: Utc::now(),
+                url: None,
+                visited_at: None,
+                detail: Some(detail),
+                process: None,
+                occurrences: 1,
+                last_seen: None,
+                usage: None,
+                exe: None,
+            }),
+        };
+        Some(v.clone())
+    }
+}
+
+/// Enforce focus mode while it is on and end it when its timer runs
+/// out. Runs forever.
+pub async fn run(
+    cfg: FocusModeConfig,
+    focus: Arc<FocusMode>,
+    sinks: ViolationSinks,
+    audit: Audit,
+    hostname: String,
+    username: String,
+) {
+    let interval = Duration::from_secs(cfg.check_secs.max(1));
+    let always_allowed: Vec<String> = cfg.always_allowed.iter().map(|n| normalize(n)).collect();
+    let own_pid = std::process::id();
+    loop {
+        tokio::time::sleep(interval).await;
+        if let Some(status) = focus.expire() {
+            let detail = format!("{}, {} escape(s)", status.app.unwrap_or_default(), status.escapes);
+            audit.record("focus_stopped", "timer", Some(detail)).await;
+            continue;
+        }
+        let Some((app, action)) = focus.pinned() else {
+            continue;
+        };
+        let Some(front) = tokio::task::spawn_blocking(foreground).await.ok().flatten() else {
+            continue;
+        };
+        let name = normalize(&front.name);
+        if front.pid == own_pid || name == app || always_allowed.contains(&name) {
+            continue;
+        }
+
+        let enforced = {
+            let front = front.clone();
+            tokio::task::spawn_blocking(move || match action {
+                FocusAction::Minimize => minimize(&front),
+                FocusAction::Kill => kill(front.pid),
+            })
+            .await
+            .unwrap_or(false)
+        };
+        if !enforced {
+            warn!("Focus mode: could not {} {name} (PID {})", if action == FocusAction::Kill { "kill" } else { "minimise" }, front.pid);
+        }
+        let Some(v) = focus.escaped(&hostname, &username, &name, enforced) else {
+            continue;
+        };
+        warn!("🎯 Focus mode escape to {name} (attempt {})", v.occurrences);
+        if v.occurrences > 1 {
+            sinks.update(&v).await;
+        } else {
+            sinks.deliver(&v).await;
+        }
+    }
+}
+
+/// Lower case without `.exe`, as process names are compared.
+fn normalize(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    name.strip_suffix(".exe").unwrap_or(&name).to_string()
+}
+
+/// The window in front and the process it belongs to.
+#[derive(Debug, Clone)]
+pub(crate) struct Foreground {
+    /// HWND / X window id; empty on macOS, where the process is hidden.
+    pub window: String,
+    pub pid: u32,
+    pub name: String,
+    /// Window title; empty when it can't be read (macOS without
+    /// accessibility access).
+    #[cfg_attr(not(feature = "streaming"), allow(dead_code))]
+    pub title: String,
+}
+
+const WIN_FOREGROUND_SCRIPT: &str = r#"
+Add-Type -Namespace NisHack -Name Fg -MemberDefinition '
+[DllImport("user32.dll")] public static extern IntPtr GetForegroundWindow();
+[DllImport("user32.dll")] public static extern uint GetWindowThreadProcessId(IntPtr h, out uint pid);'
+$h = [NisHack.Fg]::GetForegroundWindow()
+$p = 0
+[void][NisHack.Fg]::GetWindowThreadProcessId($h, [ref]$p)
+$proc = Get-Process -Id $p
+"$h`t$p`t$($proc.ProcessName)`t$($proc.MainWindowTitle)"
+"#;
+
+const MAC_FOREGROUND_SCRIPT: &str = r#"
+tell application "System Events"
+    set p to first application process whose frontmost is true
+    set t to ""
+    try
+        set t to name of front window of p
+    end try
+    return (unix id of p as text) & tab & name of p & tab & t
+end tell
+"#;
+
+/// The foreground window. None when there is none or it can't be told.
+/// Blocking.
+pub(crate) fn foreground() -> Option<Foreground> {
+    if cfg!(target_os = "windows") {
+        let out = silent_cmd("powershell").args(["-NoProfile", "-Command", WIN_FOREGROUND_SCRIPT]).output().ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+        let mut cols = text.trim_end_matches(['\r', '\n']).splitn(4, '\t');
+        let window = cols.next()?.to_string();
+        let pid = cols.next()?.parse().ok().filter(|&p| p != 0)?;
+        let name = cols.next()?.to_string();
+        return Some(Foreground { window, pid, name, title: cols.next().unwrap_or_default().to_string() });
+    }
+    if cfg!(target_os = "macos") {
+        let out = silent_cmd("osascript").args(["-e", MAC_FOREGROUND_SCRIPT]).output().ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+        let mut cols = text.trim_end_matches('\n').splitn(3, '\t');
+        let pid = cols.next()?.parse().ok()?;
+        let name = cols.next()?.to_string();
+        return Some(Foreground { window: String::new(), pid, name, title: cols.next().unwrap_or_default().to_string() });
+    }
+    // _NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007
+    let out = silent_cmd("xprop").args(["-root", "_NET_ACTIVE_WINDOW"]).output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let window = text.split('#').nth(1)?.split(',').next()?.trim().to_string();
+    if window == "0x0" {
+        return None;
+    }
+    // _NET_WM_PID(CARDINAL) = 4242
+    // _NET_WM_NAME(UTF8_STRING) = "Essay.odt - LibreOffice Writer"
+    let out = silent_cmd("xprop").args(["-id", &window, "_NET_WM_PID", "_NET_WM_NAME"]).output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let value = |prop: &str| text.lines().find(|l| l.starts_with(prop))?.split_once(" = ").map(|(_, v)| v.trim());
+    let pid: u32 = value("_NET_WM_PID")?.parse().ok()?;
+    let title = value("_NET_WM_NAME").map(|t| t.trim_matches('"').replace("\\\"", "\"")).unwrap_or_default();
+    let name = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?.trim().to_string();
+    Some(Foreground { window, pid, name, title })
+}
+
+/// Minimise (macOS: hide) the foreground window. Blocking.
+fn minimize(front: &Foreground) -> bool {
+    let status = if cfg!(target_os = "windows") {
+        let script = format!(
+            "Add-Type -Namespace NisHack -Name Show -MemberDefinition '[DllImport(\"user32.dll\")] public static extern bool ShowWindow(IntPtr h, int cmd);'; \
+             [void][NisHack.Show]::ShowWindow([IntPtr]{}, 6)",
+            front.window
+        );
+        silent_cmd("powershell").args(["-NoProfile", "-Command", &script]).status()
+    } else if cfg!(target_os = "macos") {
+        let script = format!(
+            "tell application \"System Events\" to set visible of (first process whose unix id is {}) to false",
+            front.pid
+        );
+        silent_cmd("osascript").args(["-e", &script]).status()
+    } else {
+        silent_cmd("xdotool").args(["windowminimize", &front.window]).status()
+    };
+    status.is_ok_and(|s| s.success())
+}
+
+/// Kill the process owning the foreground window. Blocking.
+fn kill(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut sys = System::new();
+    sys.refresh_processes(ProcessesToUpdate::Some(&[pid]));
+    sys.process(pid).is_some_and(|p| p.kill())
+}
@@ -0,0 +1,67 @@
+// ─────────────────────────────────────────────────────────────────
+//  gpu.rs — Per-process GPU utilisation
+//
+//    Windows: "GPU Engine" performance counters (any GPU vendor), read
+//             through their WMI class: Get-Counter paths are localised
+//             and don't exist on e.g. a Russian Windows
+//    Others:  nvidia-smi pmon (NVIDIA only)
+//  Machines without either simply report no GPU usage.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::HashMap;
+
+use crate::monitor::silent_cmd;
+
+/// GPU utilisation (0-100) per PID. Empty when unavailable. Blocking.
+pub fn usage_by_pid() -> HashMap<u32, f32> {
+    if cfg!(target_os = "windows") {
+        let script = r#"Get-CimInstance Win32_PerfFormattedData_GPUPerformanceCounters_GPUEngine -ErrorAction SilentlyContinue | Where-Object { $_.Name -like '*engtype_3D' } | ForEach-Object { "$($_.Name) $($_.UtilizationPercentage)" }"#;
+        silent_cmd("powershell")
+            .args(["-NoProfile", "-Command", script])
+            .output()
+            .map(|o| parse_gpu_engine(&String::from_utf8_lossy(&o.stdout)))
+            .unwrap_or_default()
+    } else {
+        silent_cmd("nvidia-smi")
+            .args(["pmon", "-c", "1", "-s", "u"])
+            .output()
+            .map(|o| parse_pmon(&String::from_utf8_lossy(&o.stdout)))
+            .unwrap_or_default()
+    }
+}
+
+//   pid_1234_luid_0x00000000_0x0000c6b1_phys_0_eng_0_engtype_3d 42.5
+fn parse_gpu_engine(out: &str) -> HashMap<u32, f32> {
+    let mut usage = HashMap::new();
+    for line in out.lines() {
+        let Some((instance, value)) = line.trim().rsplit_once(' ') else {
+            continue;
+        };
+        let pid = instance
+            .strip_prefix("pid_")
+            .and_then(|rest| rest.split('_').next())
+            .and_then(|p| p.parse::<u32>().ok());
+        if let (Some(pid), Ok(value)) = (pid, value.replace(',', ".").parse::<f32>()) {
+            // One instance per engine; a process may use several
+            *usage.entry(pid).or_insert(0.0) += value;
+        }
+    }
+    usage.values_mut().for_each(|v| *v = v.min(100.0));
+    usage
+}
+
+//   # gpu        pid  type    sm   mem   enc   dec   command
+//       0       1234     C    95    40     -     -   python
+fn parse_pmon(out: &str) -> HashMap<u32, f32> {
+    let mut usage = HashMap::new();
+    for line in out.lines().filter(|l| !l.trim_start().starts_with('#')) {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 4 {
+            continue;
+        }
+        if let (Ok(pid), Ok(sm)) = (cols[1].parse::<u32>(), cols[3].parse::<f32>()) {
+            *usage.entry(pid).or_insert(0.0) += sm;
+        }
+    }
+    usage
+}
@@ -0,0 +1,148 @@
+// ─────────────────────────────────────────────────────────────────
+//  hosts.rs — hosts-file based domain blocking
+//
+//  Points banned domains at 0.0.0.0 inside a clearly marked block at
+//  the top of the OS hosts file. Only lines inside our block are ever
+//  touched and the rest is kept byte for byte (line endings, a missing
+//  final newline, trailing blank lines); domains the admin already
+//  mapped elsewhere in the file are left alone, so removing the block
+//  restores the file exactly.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use tracing::{info, warn};
+
+const BEGIN_MARKER: &str = "# >>> nishack managed block — do not edit >>>";
+const END_MARKER: &str = "# <<< nishack managed block <<<";
+const SINK_ADDR: &str = "0.0.0.0";
+
+/// Default location of the hosts file on this platform.
+pub fn default_hosts_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        let root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".into());
+        PathBuf::from(root).join(r"System32\drivers\etc\hosts")
+    } else {
+        PathBuf::from("/etc/hosts")
+    }
+}
+
+/// Owns the managed block in the hosts file.
+pub struct HostsBlocker {
+    path: PathBuf,
+    /// Hostnames currently written into our block.
+    managed: BTreeSet<String>,
+}
+
+impl HostsBlocker {
+    pub fn new(path: Option<&str>) -> Self {
+        Self {
+            path: path.map(PathBuf::from).unwrap_or_else(default_hosts_path),
+            managed: BTreeSet::new(),
+        }
+    }
+
+    /// Rewrite the managed block so it blocks exactly `domains`
+    /// (plus their `www.` variants). Returns the number of managed entries.
+    pub fn apply<'a>(&mut self, domains: impl IntoIterator<Item = &'a String>) -> anyhow::Result<usize> {
+        let raw = std::fs::read_to_string(&self.path)?;
+        let (outside, newline) = split_managed(&raw);
+
+        // Hostnames the admin already mapped themselves — never shadow them.
+        let existing: BTreeSet<String> = outside.lines().flat_map(hosts_line_names).collect();
+
+        let mut wanted = BTreeSet::new();
+        for d in domains {
+            let d = d.trim().to_lowercase();
+            if d.is_empty() {
+                continue;
+            }
+            if !d.starts_with("www.") {
+                wanted.insert(format!("www.{d}"));
+            }
+            wanted.insert(d);
+        }
+        wanted.retain(|d| !existing.contains(d));
+
+        if wanted == self.managed && raw.contains(BEGIN_MARKER) != wanted.is_empty() {
+            return Ok(wanted.len());
+        }
+
+        self.write(&outside, newline, &wanted)?;
+        info!("🛑 hosts file: {} domain(s) blocked in {}", wanted.len(), self.path.display());
+        self.managed = wanted;
+        Ok(self.managed.len())
+    }
+
+    /// Remove our block entirely, leaving every other line untouched.
+    pub fn clear(&mut self) {
+        let raw = match std::fs::read_to_string(&self.path) {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Could not read hosts file to clean up: {e}");
+                return;
+            }
+        };
+        if !raw.contains(BEGIN_MARKER) {
+            self.managed.clear();
+            return;
+        }
+        let (outside, newline) = split_managed(&raw);
+        match self.write(&outside, newline, &BTreeSet::new()) {
+            Ok(()) => {
+                info!("hosts file: managed block removed");
+                self.managed.clear();
+            }
+            Err(e) => warn!("Failed to remove managed hosts block: {e}"),
+        }
+    }
+
+    fn write(&self, outside: &str, newline: &str, entries: &BTreeSet<String>) -> anyhow::Result<()> {
+        // Our block goes first, so the user's content follows it untouched
+        // however it ends.
+        let mut out = String::with_capacity(outside.len());
+        if !entries.is_empty() {
+            out.push_str(BEGIN_MARKER);
+            out.push_str(newline);
+            for e in entries {
+                out.push_str(&format!("{SINK_ADDR} {e}{newline}"));
+            }
+            out.push_str(END_MARKER);
+            out.push_str(newline);
+        }
+        out.push_str(outside);
+
+        std::fs::write(&self.path, out)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", self.path.display()))
+    }
+}
+
+/// Split off the content outside our managed block, byte for byte, and
+/// detect the line ending in use (Windows hosts files are CRLF).
+fn split_managed(raw: &str) -> (String, &'static str) {
+    let newline = if raw.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut outside = String::with_capacity(raw.len());
+    let mut inside = false;
+    for line in raw.split_inclusive('\n') {
+        let bare = line.trim_end_matches(['\r', '\n']);
+        if bare == BEGIN_MARKER {
+            inside = true;
+        } else if bare == END_MARKER {
+            inside = false;
+        } else if !inside {
+            outside.push_str(line);
+        }
+    }
+    (outside, newline)
+}
+
+/// Hostnames mapped by one hosts-file line (`addr name [alias...] # comment`).
+fn hosts_line_names(line: &str) -> Vec<String> {
+    let content = line.split('#').next().unwrap_or_default();
+    content
+        .split_whitespace()
+        .skip(1)
+        .map(str::to_lowercase)
+        .collect()
+}
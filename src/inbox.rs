@@ -0,0 +1,71 @@
+// ─────────────────────────────────────────────────────────────────
+//  inbox.rs — Teacher messages that wait for an offline PC
+//
+//  Pub/sub only reaches agents that are listening at that moment. For
+//  anything that should still arrive after a reboot or a network
+//  outage, the teacher adds it to the Stream `{prefix}:messages:{hostname}`
+//  instead — a command JSON in `data`, or its fields as they are:
+//
+//    XADD nishack:messages:pc-07 * action message text "See me after class"
+//    XADD nishack:messages:pc-07 * data '{"action":"lock","mode":"soft"}'
+//
+//  Every `[commands] inbox_poll_secs` (and at once after a Redis server
+//  switch) the agent drains the inbox through the consumer group
+//  "nishack", oldest first, runs each entry like a pub/sub command and
+//  acknowledges its ID (XACK). An entry whose acknowledgement didn't
+//  get through is read again, so it may run twice; `XPENDING` shows
+//  what a PC has taken but not finished. The Stream isn't trimmed by
+//  the agent — add with `MAXLEN ~ <n>` to keep it short.
+// ─────────────────────────────────────────────────────────────────
+
+use std::time::Duration;
+
+use axum::Router;
+use chrono::Utc;
+use tracing::info;
+
+use crate::audit::Audit;
+use crate::commands;
+use crate::store::Store;
+
+/// Messages read per round trip.
+const BATCH: usize = 20;
+
+/// Poll the inbox every `every`. Runs forever.
+pub async fn run(store: Store, router: Router, audit: Audit, hostname: String, every: Duration) {
+    let mut switches = store.server_switches();
+    let mut interval = tokio::time::interval(every);
+    loop {
+        let switch = async {
+            match &mut switches {
+                Some(switches) => switches.changed().await.is_ok(),
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = interval.tick() => {}
+            true = switch => interval.reset(),
+        }
+        drain(&store, &router, &audit, &hostname).await;
+    }
+}
+
+/// Run and acknowledge everything in the inbox, until it's empty or
+/// Redis can't be reached.
+async fn drain(store: &Store, router: &Router, audit: &Audit, hostname: &str) {
+    while let Some(batch) = store.read_inbox(hostname, BATCH).await {
+        if batch.is_empty() {
+            return;
+        }
+        for (id, message) in batch {
+            // Stream IDs start with the ms timestamp they were added at
+            let added = id.split('-').next().and_then(|ms| ms.parse::<i64>().ok()).unwrap_or_default();
+            let waited = (Utc::now().timestamp_millis() - added).max(0) / 1000;
+            info!("📬 Inbox message {id}, left {waited}s ago");
+            commands::dispatch(router.clone(), audit.clone(), format!("inbox:{id}"), message).await;
+            if !store.ack_inbox(hostname, &id).await {
+                return;
+            }
+        }
+    }
+}
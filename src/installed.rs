@@ -0,0 +1,185 @@
+// ─────────────────────────────────────────────────────────────────
+//  installed.rs — Installed-application inventory
+//
+//  Software is audited when it is installed, not only once it runs:
+//  the inventory is re-read periodically, diffed against the previous
+//  one (kept in Redis, so changes made while the agent was off are
+//  caught too) and every install / uninstall is pushed as an event.
+//    Windows: registry Uninstall keys (machine, 32-bit and per-user)
+//    macOS:   *.app bundles in /Applications and ~/Applications
+//    Linux:   .desktop entries (system, Flatpak, Snap, per-user)
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::config::InstalledAppsConfig;
+use crate::models::{AppChange, InstalledApp};
+use crate::monitor::silent_cmd;
+use crate::store::Store;
+
+/// Re-read the inventory every `interval` and record what changed.
+/// Runs forever.
+pub async fn run(cfg: InstalledAppsConfig, store: Store, hostname: String) {
+    let mut previous = store.installed_apps(&hostname).await;
+    loop {
+        match tokio::task::spawn_blocking(inventory).await {
+            Ok(Some(current)) => {
+                let changes = previous.as_deref().map(|p| diff(&hostname, p, &current)).unwrap_or_default();
+                for c in &changes {
+                    info!("📦 Application {}: {} {}", c.action, c.name, c.version.as_deref().unwrap_or_default());
+                }
+                if previous.is_none() || !changes.is_empty() {
+                    store.push_installed_apps(&hostname, &current, &changes).await;
+                }
+                previous = Some(current);
+            }
+            Ok(None) => warn!("Could not read the installed-application inventory"),
+            Err(e) => warn!("Application inventory task panicked: {e}"),
+        }
+        tokio::time::sleep(Duration::from_secs(cfg.interval)).await;
+    }
+}
+
+/// Installs and uninstalls between two inventories, by name.
+fn diff(hostname: &str, old: &[InstalledApp], new: &[InstalledApp]) -> Vec<AppChange> {
+    let index = |apps: &[InstalledApp]| -> BTreeMap<String, InstalledApp> {
+        apps.iter().map(|a| (a.name.to_lowercase(), a.clone())).collect()
+    };
+    let (old, new) = (index(old), index(new));
+    let change = |action: &str, app: &InstalledApp| AppChange {
+        hostname: hostname.to_string(),
+        action: action.to_string(),
+        name: app.name.clone(),
+        version: app.version.clone(),
+        publisher: app.publisher.clone(),
+        timestamp: Utc::now(),
+    };
+    let installed = new.iter().filter(|(k, _)| !old.contains_key(*k)).map(|(_, a)| change("installed", a));
+    let removed = old.iter().filter(|(k, _)| !new.contains_key(*k)).map(|(_, a)| change("uninstalled", a));
+    installed.chain(removed).collect()
+}
+
+/// Everything installed, sorted by name. None when the platform query
+/// fails (so a broken lookup doesn't read as "everything uninstalled").
+pub fn inventory() -> Option<Vec<InstalledApp>> {
+    let mut apps = if cfg!(target_os = "windows") {
+        registry_apps()?
+    } else if cfg!(target_os = "macos") {
+        bundle_apps()
+    } else {
+        desktop_entries()
+    };
+    apps.sort_by_key(|a| a.name.to_lowercase());
+    apps.dedup_by_key(|a| a.name.to_lowercase());
+    Some(apps)
+}
+
+fn registry_apps() -> Option<Vec<InstalledApp>> {
+    const SCRIPT: &str = r"Get-ItemProperty -ErrorAction SilentlyContinue `
+        'HKLM:\Software\Microsoft\Windows\CurrentVersion\Uninstall\*',
+        'HKLM:\Software\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\*',
+        'Registry::HKEY_USERS\*\Software\Microsoft\Windows\CurrentVersion\Uninstall\*' |
+      Where-Object { $_.DisplayName -and -not $_.SystemComponent } |
+      Select-Object DisplayName, DisplayVersion, Publisher | ConvertTo-Json -Compress";
+    let out = silent_cmd("powershell").args(["-NoProfile", "-Command", SCRIPT]).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_slice(&out.stdout).ok()?;
+    // A single result comes back as an object rather than an array
+    let entries = match json {
+        serde_json::Value::Array(entries) => entries,
+        other => vec![other],
+    };
+    let text = |e: &serde_json::Value, k: &str| {
+        e.get(k).and_then(|v| v.as_str()).map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+    };
+    Some(
+        entries
+            .iter()
+            .filter_map(|e| {
+                Some(InstalledApp {
+                    name: text(e, "DisplayName")?,
+                    version: text(e, "DisplayVersion"),
+                    publisher: text(e, "Publisher"),
+                })
+            })
+            .collect(),
+    )
+}
+
+fn bundle_apps() -> Vec<InstalledApp> {
+    let mut dirs = vec![std::path::PathBuf::from("/Applications")];
+    if let Ok(users) = std::fs::read_dir("/Users") {
+        dirs.extend(users.flatten().map(|u| u.path().join("Applications")));
+    }
+    dirs.iter()
+        .flat_map(|dir| std::fs::read_dir(dir).into_iter().flatten().flatten())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().strip_suffix(".app")?.to_string();
+            let version = bundle_version(&entry.path());
+            Some(InstalledApp { name, version, publisher: None })
+        })
+        .collect()
+}
+
+fn bundle_version(bundle: &Path) -> Option<String> {
+    let plist = bundle.join("Contents/Info.plist");
+    let out = silent_cmd("plutil")
+        .args(["-extract", "CFBundleShortVersionString", "raw", "-o", "-"])
+        .arg(&plist)
+        .output()
+        .ok()?;
+    let version = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !version.is_empty()).then_some(version)
+}
+
+fn desktop_entries() -> Vec<InstalledApp> {
+    let mut dirs: Vec<std::path::PathBuf> = [
+        "/usr/share/applications",
+        "/usr/local/share/applications",
+        "/var/lib/flatpak/exports/share/applications",
+        "/var/lib/snapd/desktop/applications",
+    ]
+    .iter()
+    .map(Into::into)
+    .collect();
+    if let Ok(homes) = std::fs::read_dir("/home") {
+        dirs.extend(homes.flatten().map(|h| h.path().join(".local/share/applications")));
+    }
+    dirs.iter()
+        .flat_map(|dir| std::fs::read_dir(dir).into_iter().flatten().flatten())
+        .filter(|e| e.path().extension().is_some_and(|x| x == "desktop"))
+        .filter_map(|e| parse_desktop_entry(&std::fs::read_to_string(e.path()).ok()?))
+        .collect()
+}
+
+//   [Desktop Entry]
+//   Name=Firefox
+//   X-AppVersion=128.0
+//   NoDisplay=false
+fn parse_desktop_entry(text: &str) -> Option<InstalledApp> {
+    let mut in_entry = false;
+    let (mut name, mut version) = (None, None);
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+        match line.split_once('=') {
+            Some(("Name", v)) if name.is_none() => name = Some(v.trim().to_string()),
+            Some(("X-AppVersion", v)) if version.is_none() => version = Some(v.trim().to_string()),
+            Some(("NoDisplay" | "Hidden", "true")) => return None,
+            _ => {}
+        }
+    }
+    Some(InstalledApp { name: name.filter(|n| !n.is_empty())?, version, publisher: None })
+}
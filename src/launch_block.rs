@@ -0,0 +1,419 @@
+// ─────────────────────────────────────────────────────────────────
+//  launch_block.rs — Stop banned programs before they start
+//
+//  Killing after start still lets a game flash up (and installers
+//  run for a second); with `[monitor.enforcement] block_launch` the
+//  launch itself is refused:
+//    Windows: Image File Execution Options. Each banned exe gets
+//             `Debugger` = this binary with `--launch-blocked`, so
+//             Windows starts us instead; the stub tells the student
+//             and leaves a record in %ProgramData%\nishack for the
+//             agent. Keys are tagged `NishackManaged` and an admin's
+//             own Debugger entries are never replaced.
+//    Linux:   fanotify exec permission events on every mounted disk
+//             file system; exec of a banned file name is denied.
+//             (seccomp only filters the calling process and
+//             execsnoop only observes, so neither can refuse
+//             another program's exec.)
+//  macOS has no equivalent without an Endpoint Security entitlement;
+//  kill-after-start stays in place there (and everywhere as backup).
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// First argument of the stub Windows runs in place of a blocked exe.
+pub const STUB_FLAG: &str = "--launch-blocked";
+
+#[cfg(target_os = "windows")]
+const IFEO_KEY: &str = r"HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion\Image File Execution Options";
+#[cfg(target_os = "windows")]
+const MANAGED_VALUE: &str = "NishackManaged";
+
+/// A launch that was refused.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedLaunch {
+    /// Banned name it matched (lowercase, without `.exe`).
+    pub name: String,
+    pub path: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Owns the OS launch blocks for the current set of banned processes.
+pub struct LaunchBlocker {
+    /// Banned names currently blocked (lowercase, without `.exe`).
+    blocked: Arc<Mutex<BTreeSet<String>>>,
+    /// Refused launches not yet collected by `drain`.
+    attempts: Arc<Mutex<Vec<BlockedLaunch>>>,
+    #[cfg(target_os = "linux")]
+    fanotify: Option<linux::Fanotify>,
+}
+
+impl LaunchBlocker {
+    /// None where launches can't be blocked (macOS).
+    pub fn new() -> Option<Self> {
+        if cfg!(target_os = "macos") {
+            warn!("Launch blocking is not available on macOS — banned programs are killed after start instead");
+            return None;
+        }
+        let blocker = Self {
+            blocked: Arc::default(),
+            attempts: Arc::default(),
+            #[cfg(target_os = "linux")]
+            fanotify: None,
+        };
+        // Entries left behind by an agent that didn't shut down cleanly
+        #[cfg(target_os = "windows")]
+        blocker.blocked.lock().unwrap_or_else(|e| e.into_inner()).extend(windows::managed());
+        Some(blocker)
+    }
+
+    /// Block launching exactly `names` (process names as in the ban list).
+    /// Returns the number blocked.
+    pub fn apply<'a>(&mut self, names: impl IntoIterator<Item = &'a String>) -> anyhow::Result<usize> {
+        let wanted: BTreeSet<String> = names
+            .into_iter()
+            .map(|n| {
+                let n = n.trim().to_lowercase();
+                n.strip_suffix(".exe").map(String::from).unwrap_or(n)
+            })
+            .filter(|n| !n.is_empty())
+            .collect();
+        if *self.blocked.lock().unwrap_or_else(|e| e.into_inner()) == wanted {
+            return Ok(wanted.len());
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let current = self.blocked.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            let applied = windows::apply(&current, &wanted)?;
+            *self.blocked.lock().unwrap_or_else(|e| e.into_inner()) = applied;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            *self.blocked.lock().unwrap_or_else(|e| e.into_inner()) = wanted.clone();
+            if wanted.is_empty() {
+                if let Some(f) = &self.fanotify {
+                    f.unmark();
+                }
+            } else {
+                if self.fanotify.is_none() {
+                    self.fanotify =
+                        Some(linux::Fanotify::start(Arc::clone(&self.blocked), Arc::clone(&self.attempts))?);
+                }
+                if let Some(f) = &self.fanotify {
+                    f.mark()?;
+                }
+            }
+        }
+        let count = self.blocked.lock().unwrap_or_else(|e| e.into_inner()).len();
+        info!("⛔ Launch blocking: {count} program(s)");
+        Ok(count)
+    }
+
+    /// Lift every block.
+    pub fn clear(&mut self) {
+        let none: Vec<String> = Vec::new();
+        if let Err(e) = self.apply(&none) {
+            warn!("Failed to lift launch blocking: {e}");
+        }
+    }
+
+    /// Launches refused since the last call.
+    pub fn drain(&self) -> Vec<BlockedLaunch> {
+        #[cfg(target_os = "windows")]
+        self.attempts.lock().unwrap_or_else(|e| e.into_inner()).extend(windows::take_spool());
+        std::mem::take(&mut *self.attempts.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Run as the stub Windows starts in place of a blocked program, if this
+/// process is one: warn the student, record the attempt and return true
+/// (the caller exits without starting the agent).
+pub fn run_stub_from_args() -> bool {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) != Some(STUB_FLAG) {
+        return false;
+    }
+    let path = args.get(2).cloned().unwrap_or_default();
+    let file = path.rsplit(['\\', '/']).next().unwrap_or_default().to_lowercase();
+    let name = file.strip_suffix(".exe").unwrap_or(&file).to_string();
+    crate::notify::warn_user("nishack", &format!("Запуск программы {file} запрещён на уроке."), 10);
+    #[cfg(target_os = "windows")]
+    windows::spool(&BlockedLaunch { name, path, at: Utc::now() });
+    #[cfg(not(target_os = "windows"))]
+    let _ = (name, path);
+    true
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::collections::BTreeSet;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use tracing::{info, warn};
+
+    use super::{BlockedLaunch, IFEO_KEY, MANAGED_VALUE, STUB_FLAG};
+    use crate::monitor::silent_cmd;
+
+    fn key(name: &str) -> String {
+        format!(r"{IFEO_KEY}\{name}.exe")
+    }
+
+    /// Names of the IFEO entries we own.
+    pub fn managed() -> BTreeSet<String> {
+        let Ok(out) = silent_cmd("reg").args(["query", IFEO_KEY, "/s", "/v", MANAGED_VALUE]).output() else {
+            return BTreeSet::new();
+        };
+        String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .filter(|l| l.starts_with("HKEY_"))
+            .filter_map(|l| l.rsplit('\\').next())
+            .map(|exe| {
+                let exe = exe.trim().to_lowercase();
+                exe.strip_suffix(".exe").map(String::from).unwrap_or(exe)
+            })
+            .collect()
+    }
+
+    /// Move the IFEO entries from `current` to `wanted`; returns what is
+    /// blocked afterwards.
+    pub fn apply(current: &BTreeSet<String>, wanted: &BTreeSet<String>) -> anyhow::Result<BTreeSet<String>> {
+        let agent = std::env::current_exe()?;
+        let debugger = format!("\"{}\" {STUB_FLAG}", agent.display());
+        let mut blocked = current.clone();
+
+        for name in current.difference(wanted) {
+            let key = key(name);
+            for value in ["Debugger", MANAGED_VALUE] {
+                let _ = silent_cmd("reg").args(["delete", &key, "/v", value, "/f"]).output();
+            }
+            blocked.remove(name);
+        }
+        for name in wanted.difference(current) {
+            let key = key(name);
+            let taken = silent_cmd("reg")
+                .args(["query", &key, "/v", "Debugger"])
+                .output()
+                .is_ok_and(|o| o.status.success());
+            if taken {
+                warn!("{name}.exe already has an IFEO Debugger set by someone else — not blocking its launch");
+                continue;
+            }
+            let set = |value: &str, kind: &str, data: &str| {
+                silent_cmd("reg")
+                    .args(["add", &key, "/v", value, "/t", kind, "/d", data, "/f"])
+                    .output()
+                    .is_ok_and(|o| o.status.success())
+            };
+            if !(set(MANAGED_VALUE, "REG_DWORD", "1") && set("Debugger", "REG_SZ", &debugger)) {
+                anyhow::bail!("could not write {key}");
+            }
+            blocked.insert(name.clone());
+        }
+        info!("IFEO launch blocks: {}", blocked.iter().cloned().collect::<Vec<_>>().join(", "));
+        Ok(blocked)
+    }
+
+    /// Where stubs leave their records. %ProgramData% lets ordinary users
+    /// create files, so the stub can write it from the student's session.
+    fn spool_path() -> PathBuf {
+        let root = std::env::var("ProgramData").unwrap_or_else(|_| r"C:\ProgramData".into());
+        PathBuf::from(root).join("nishack").join("launch_blocked.log")
+    }
+
+    pub fn spool(launch: &BlockedLaunch) {
+        let path = spool_path();
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let Ok(line) = serde_json::to_string(launch) else {
+            return;
+        };
+        let written = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| writeln!(f, "{line}"));
+        if let Err(e) = written {
+            warn!("Could not record blocked launch in {}: {e}", path.display());
+        }
+    }
+
+    /// Records left by stubs since the last call (the spool is emptied).
+    pub fn take_spool() -> Vec<BlockedLaunch> {
+        let path = spool_path();
+        let Ok(raw) = std::fs::read_to_string(&path) else {
+            return Vec::new();
+        };
+        let _ = std::fs::write(&path, "");
+        raw.lines().filter_map(|l| serde_json::from_str(l).ok()).collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::BTreeSet;
+    use std::ffi::CString;
+    use std::os::fd::RawFd;
+    use std::sync::{Arc, Mutex};
+
+    use chrono::Utc;
+    use tracing::{info, warn};
+
+    use super::BlockedLaunch;
+
+    /// File systems that can't hold programs worth blocking.
+    const PSEUDO_FS: &[&str] = &[
+        "proc", "sysfs", "cgroup", "cgroup2", "devpts", "devtmpfs", "securityfs", "debugfs", "tracefs",
+        "pstore", "bpf", "mqueue", "hugetlbfs", "configfs", "fusectl", "binfmt_misc", "autofs", "efivarfs",
+        "rpc_pipefs", "nsfs",
+    ];
+
+    /// An fanotify group answering exec permission events on a thread.
+    pub struct Fanotify {
+        fd: RawFd,
+    }
+
+    impl Fanotify {
+        pub fn start(
+            blocked: Arc<Mutex<BTreeSet<String>>>,
+            attempts: Arc<Mutex<Vec<BlockedLaunch>>>,
+        ) -> anyhow::Result<Self> {
+            // SAFETY: plain syscall; the result is checked below
+            let fd = unsafe {
+                libc::fanotify_init(
+                    libc::FAN_CLASS_CONTENT | libc::FAN_CLOEXEC,
+                    (libc::O_RDONLY | libc::O_LARGEFILE | libc::O_CLOEXEC) as u32,
+                )
+            };
+            if fd < 0 {
+                anyhow::bail!("fanotify_init failed (needs root): {}", std::io::Error::last_os_error());
+            }
+            std::thread::Builder::new()
+                .name("launch-block".into())
+                .spawn(move || answer_events(fd, &blocked, &attempts))?;
+            Ok(Self { fd })
+        }
+
+        /// Watch exec on every mounted disk file system.
+        pub fn mark(&self) -> anyhow::Result<()> {
+            let mounts = std::fs::read_to_string("/proc/self/mounts")?;
+            let mut marked = 0;
+            for line in mounts.lines() {
+                let cols: Vec<&str> = line.split_whitespace().collect();
+                let (Some(dir), Some(fs)) = (cols.get(1), cols.get(2)) else {
+                    continue;
+                };
+                if PSEUDO_FS.contains(fs) {
+                    continue;
+                }
+                // /proc/self/mounts escapes spaces as \040
+                let Ok(path) = CString::new(dir.replace("\\040", " ")) else {
+                    continue;
+                };
+                // SAFETY: fd is our fanotify group, path a valid C string
+                let rc = unsafe {
+                    libc::fanotify_mark(
+                        self.fd,
+                        libc::FAN_MARK_ADD | libc::FAN_MARK_MOUNT,
+                        libc::FAN_OPEN_EXEC_PERM,
+                        libc::AT_FDCWD,
+                        path.as_ptr(),
+                    )
+                };
+                if rc == 0 {
+                    marked += 1;
+                }
+            }
+            if marked == 0 {
+                anyhow::bail!("fanotify_mark failed on every mount: {}", std::io::Error::last_os_error());
+            }
+            info!("Launch blocking watches {marked} mount(s)");
+            Ok(())
+        }
+
+        /// Stop watching (the group and its thread stay for the next mark).
+        pub fn unmark(&self) {
+            let root = CString::new("/").unwrap_or_default();
+            // SAFETY: fd is our fanotify group, root a valid C string
+            unsafe {
+                libc::fanotify_mark(self.fd, libc::FAN_MARK_FLUSH | libc::FAN_MARK_MOUNT, 0, libc::AT_FDCWD, root.as_ptr());
+            }
+        }
+    }
+
+    /// Answer every permission event. Must never stall: until we reply the
+    /// exec blocks (if the agent dies the kernel allows everything again).
+    fn answer_events(fd: RawFd, blocked: &Mutex<BTreeSet<String>>, attempts: &Mutex<Vec<BlockedLaunch>>) {
+        let own_pid = std::process::id() as i32;
+        let meta_len = std::mem::size_of::<libc::fanotify_event_metadata>();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            // SAFETY: buf is valid for buf.len() bytes
+            let n = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+            if n < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                warn!("Launch blocking stopped: {err}");
+                return;
+            }
+            let n = n as usize;
+            let mut offset = 0;
+            while offset + meta_len <= n {
+                // SAFETY: at least meta_len bytes from offset were read
+                let meta: libc::fanotify_event_metadata =
+                    unsafe { std::ptr::read_unaligned(buf.as_ptr().add(offset).cast()) };
+                if meta.vers != libc::FANOTIFY_METADATA_VERSION || meta.event_len == 0 {
+                    warn!("Launch blocking stopped: unexpected fanotify event format");
+                    return;
+                }
+                offset += meta.event_len as usize;
+                if meta.fd < 0 {
+                    continue;
+                }
+
+                let path = std::fs::read_link(format!("/proc/self/fd/{}", meta.fd))
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let file = path.rsplit('/').next().unwrap_or_default().to_lowercase();
+                let name = file.strip_suffix(".exe").unwrap_or(&file).to_string();
+                let deny = meta.pid != own_pid
+                    && !name.is_empty()
+                    && blocked.lock().unwrap_or_else(|e| e.into_inner()).contains(&name);
+
+                if meta.mask & libc::FAN_OPEN_EXEC_PERM != 0 {
+                    let response = libc::fanotify_response {
+                        fd: meta.fd,
+                        response: if deny { libc::FAN_DENY } else { libc::FAN_ALLOW },
+                    };
+                    // SAFETY: response is a plain struct of the expected size
+                    unsafe {
+                        libc::write(
+                            fd,
+                            (&response as *const libc::fanotify_response).cast(),
+                            std::mem::size_of::<libc::fanotify_response>(),
+                        );
+                    }
+                }
+                // SAFETY: the event fd is ours to close
+                unsafe {
+                    libc::close(meta.fd);
+                }
+                if deny {
+                    info!("⛔ Blocked launch of {path}");
+                    attempts
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push(BlockedLaunch { name, path, at: Utc::now() });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+// ─────────────────────────────────────────────────────────────────
+//  lockdown.rs — Shortcut and task-manager lockdown during exams
+//
+//  With `[exam] lockdown` the ways out of the exam window are closed
+//  while exam mode is on:
+//    Windows: DisableTaskMgr policy (HKCU, running Task Manager is
+//             closed) and a low-level keyboard hook in a PowerShell
+//             helper swallowing Win (so Win+Tab and Win+Ctrl+←/→
+//             virtual-desktop switching too), Alt+Tab, Alt+Esc,
+//             Ctrl+Esc and Ctrl+Shift+Esc. Ctrl+Alt+Del can't be
+//             hooked; the policy removes Task Manager from it.
+//    Linux:   GNOME / Mutter keybindings for the app and window
+//             switchers, workspace switching, the overview and the
+//             Super key are emptied
+//  macOS has no way to block Cmd+Tab or Mission Control without an
+//  accessibility event tap and isn't covered.
+//  Previous values are written to `<state_dir>/exam_lockdown.json`
+//  before anything is changed and the file is removed on restore, so
+//  a crashed agent's lockdown is undone at the next start (the
+//  watchdog restarts it right away). The hook helper exits by itself
+//  when the agent process is gone.
+// ─────────────────────────────────────────────────────────────────
+
+use std::path::{Path, PathBuf};
+use std::process::Child;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::monitor::silent_cmd;
+
+const STATE_FILE: &str = "exam_lockdown.json";
+
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+const WIN_POLICIES: &[(&str, &str)] =
+    &[(r"HKCU\Software\Microsoft\Windows\CurrentVersion\Policies\System", "DisableTaskMgr")];
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const GNOME_KEYS: &[(&str, &str, &str)] = &[
+    ("org.gnome.desktop.wm.keybindings", "switch-applications", "[]"),
+    ("org.gnome.desktop.wm.keybindings", "switch-applications-backward", "[]"),
+    ("org.gnome.desktop.wm.keybindings", "switch-windows", "[]"),
+    ("org.gnome.desktop.wm.keybindings", "switch-windows-backward", "[]"),
+    ("org.gnome.desktop.wm.keybindings", "switch-group", "[]"),
+    ("org.gnome.desktop.wm.keybindings", "switch-to-workspace-left", "[]"),
+    ("org.gnome.desktop.wm.keybindings", "switch-to-workspace-right", "[]"),
+    ("org.gnome.desktop.wm.keybindings", "switch-to-workspace-up", "[]"),
+    ("org.gnome.desktop.wm.keybindings", "switch-to-workspace-down", "[]"),
+    ("org.gnome.shell.keybindings", "toggle-overview", "[]"),
+    ("org.gnome.shell.keybindings", "toggle-application-view", "[]"),
+    ("org.gnome.mutter", "overlay-key", "''"),
+];
+
+/// A setting changed by the lockdown and the value it had before.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Setting {
+    /// A REG_DWORD; None = was unset.
+    Registry { key: String, name: String, old: Option<String> },
+    Gsettings { schema: String, key: String, old: String },
+}
+
+/// An engaged lockdown, undone by `release`.
+pub struct Lockdown {
+    settings: Vec<Setting>,
+    hook: Option<Child>,
+    file: PathBuf,
+}
+
+/// Undo a lockdown a previous run left behind. Blocking.
+pub fn recover(state_dir: &Path) {
+    let file = state_dir.join(STATE_FILE);
+    let Ok(raw) = std::fs::read_to_string(&file) else {
+        return;
+    };
+    match serde_json::from_str::<Vec<Setting>>(&raw) {
+        Ok(settings) => {
+            warn!("Exam lockdown of a previous run is still in place; restoring it");
+            restore(&settings);
+        }
+        Err(e) => warn!("Unreadable {}: {e}", file.display()),
+    }
+    let _ = std::fs::remove_file(&file);
+}
+
+/// Block the shortcuts and task manager. None where nothing could be
+/// locked down. Blocking.
+pub fn engage(state_dir: &Path) -> Option<Lockdown> {
+    let settings: Vec<Setting> = if cfg!(target_os = "windows") {
+        WIN_POLICIES
+            .iter()
+            .map(|(key, name)| Setting::Registry { key: key.to_string(), name: name.to_string(), old: reg_get(key, name) })
+            .collect()
+    } else if cfg!(target_os = "linux") {
+        GNOME_KEYS
+            .iter()
+            .filter_map(|(schema, key, _)| {
+                Some(Setting::Gsettings { schema: schema.to_string(), key: key.to_string(), old: gsettings_get(schema, key)? })
+            })
+            .collect()
+    } else {
+        warn!("Exam lockdown is not available on macOS");
+        return None;
+    };
+    if settings.is_empty() {
+        warn!("Exam lockdown: nothing to lock down on this desktop");
+        return None;
+    }
+
+    // Saved first, so a crash halfway through is still undone
+    let file = state_dir.join(STATE_FILE);
+    let _ = std::fs::create_dir_all(state_dir);
+    match serde_json::to_string(&settings) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&file, json) {
+                warn!("Could not save exam lockdown state to {}: {e}", file.display());
+            }
+        }
+        Err(e) => warn!("Exam lockdown state serialization error: {e}"),
+    }
+
+    let mut hook = None;
+    if cfg!(target_os = "windows") {
+        for (key, name) in WIN_POLICIES {
+            let _ = silent_cmd("reg").args(["add", key, "/v", name, "/t", "REG_DWORD", "/d", "1", "/f"]).output();
+        }
+        let _ = silent_cmd("taskkill").args(["/IM", "taskmgr.exe", "/F"]).output();
+        let script = KEYBOARD_HOOK.replace("{parent}", &std::process::id().to_string());
+        match silent_cmd("powershell").args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script]).spawn() {
+            Ok(child) => hook = Some(child),
+            Err(e) => warn!("Could not start the exam keyboard hook: {e}"),
+        }
+    } else {
+        for (schema, key, value) in GNOME_KEYS {
+            let _ = silent_cmd("gsettings").args(["set", schema, key, value]).status();
+        }
+    }
+    info!("🔐 Exam lockdown on");
+    Some(Lockdown { settings, hook, file })
+}
+
+/// Undo `engage`. Blocking.
+pub fn release(mut lockdown: Lockdown) {
+    if let Some(mut hook) = lockdown.hook.take() {
+        let _ = hook.kill();
+        let _ = hook.wait();
+    }
+    restore(&lockdown.settings);
+    let _ = std::fs::remove_file(&lockdown.file);
+    info!("🔓 Exam lockdown off");
+}
+
+fn restore(settings: &[Setting]) {
+    for setting in settings {
+        match setting {
+            Setting::Registry { key, name, old: Some(old) } => {
+                let _ = silent_cmd("reg").args(["add", key, "/v", name, "/t", "REG_DWORD", "/d", old, "/f"]).output();
+            }
+            Setting::Registry { key, name, old: None } => {
+                let _ = silent_cmd("reg").args(["delete", key, "/v", name, "/f"]).output();
+            }
+            Setting::Gsettings { schema, key, old } => {
+                let _ = silent_cmd("gsettings").args(["set", schema, key, old]).status();
+            }
+        }
+    }
+}
+
+fn reg_get(key: &str, name: &str) -> Option<String> {
+    let out = silent_cmd("reg").args(["query", key, "/v", name]).output().ok()?;
+    String::from_utf8_lossy(&out.stdout).lines().find_map(|l| {
+        let cols: Vec<&str> = l.split_whitespace().collect();
+        (cols.len() == 3 && cols[0] == name).then(|| cols[2].to_string())
+    })
+}
+
+/// None when the schema or key doesn't exist on this desktop.
+fn gsettings_get(schema: &str, key: &str) -> Option<String> {
+    let out = silent_cmd("gsettings").args(["get", schema, key]).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&out.stdout).trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// Returning 1 from a WH_KEYBOARD_LL hook swallows the key. The helper
+// polls for the agent and exits with it, which removes the hook.
+const KEYBOARD_HOOK: &str = r#"
+Add-Type -TypeDefinition @'
+using System;
+using System.Diagnostics;
+using System.Runtime.InteropServices;
+using System.Threading;
+public static class NishackKeys {
+    delegate IntPtr Proc(int code, IntPtr w, IntPtr l);
+    [StructLayout(LayoutKind.Sequential)] struct MSG { public IntPtr h; public uint msg; public IntPtr w; public IntPtr l; public uint t; public int x; public int y; }
+    [DllImport("user32.dll")] static extern IntPtr SetWindowsHookEx(int id, Proc p, IntPtr mod, uint tid);
+    [DllImport("user32.dll")] static extern IntPtr CallNextHookEx(IntPtr h, int code, IntPtr w, IntPtr l);
+    [DllImport("user32.dll")] static extern short GetAsyncKeyState(int key);
+    [DllImport("user32.dll")] static extern int GetMessage(out MSG m, IntPtr h, uint min, uint max);
+    [DllImport("kernel32.dll")] static extern IntPtr GetModuleHandle(string name);
+    static Proc proc = Hook;
+    static IntPtr hook;
+    static IntPtr Hook(int code, IntPtr w, IntPtr l) {
+        if (code >= 0) {
+            int vk = Marshal.ReadInt32(l);
+            bool alt = (Marshal.ReadInt32(l, 8) & 0x20) != 0;
+            bool ctrl = (GetAsyncKeyState(0x11) & 0x8000) != 0;
+            if (vk == 0x5B || vk == 0x5C || (alt && (vk == 0x09 || vk == 0x1B)) || (ctrl && vk == 0x1B))
+                return (IntPtr)1;
+        }
+        return CallNextHookEx(hook, code, w, l);
+    }
+    public static void Run(int parent) {
+        hook = SetWindowsHookEx(13, proc, GetModuleHandle(null), 0);
+        var watch = new Thread(() => {
+            while (true) {
+                Thread.Sleep(2000);
+                try { Process.GetProcessById(parent); } catch (ArgumentException) { Environment.Exit(0); }
+            }
+        });
+        watch.IsBackground = true;
+        watch.Start();
+        MSG m;
+        while (GetMessage(out m, IntPtr.Zero, 0, 0) > 0) { }
+    }
+}
+'@
+[NishackKeys]::Run({parent})
+"#;
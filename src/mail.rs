@@ -0,0 +1,39 @@
+// ─────────────────────────────────────────────────────────────────
+//  mail.rs — Outgoing email over SMTP (weekly reports, alerts)
+// ─────────────────────────────────────────────────────────────────
+
+use lettre::message::{MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::{SmtpConfig, SmtpTls};
+
+/// Body of an outgoing message.
+pub enum Body {
+    Text(String),
+    Multipart(MultiPart),
+}
+
+/// Send `body` to every recipient in `smtp.to`.
+pub async fn send(smtp: &SmtpConfig, subject: &str, body: Body) -> anyhow::Result<()> {
+    let mut builder = Message::builder().from(smtp.from.parse()?).subject(subject);
+    for to in &smtp.to {
+        builder = builder.to(to.parse()?);
+    }
+    let email = match body {
+        Body::Text(text) => builder.singlepart(SinglePart::plain(text))?,
+        Body::Multipart(parts) => builder.multipart(parts)?,
+    };
+
+    let mut transport = match smtp.tls {
+        SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?,
+        SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host)?,
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host),
+    }
+    .port(smtp.port);
+    if let (Some(user), Some(password)) = (&smtp.username, &smtp.password) {
+        transport = transport.credentials(Credentials::new(user.clone(), password.clone()));
+    }
+    transport.build().send(email).await?;
+    Ok(())
+}
@@ -1,24 +1,114 @@
 // Hide the console window on Windows so the agent runs silently in the background.
 #![windows_subsystem = "windows"]
 
+mod alerts;
 mod api;
+mod audio;
+mod audio_sessions;
+mod audit;
+mod bandwidth;
+mod battery;
+mod budget;
+mod blocker;
+mod browser;
+mod capabilities;
+mod categories;
+mod commands;
 mod config;
+mod config_watch;
+mod desktop;
+mod diagnostics;
+mod displays;
+mod doctor;
+mod documents;
+mod dns_cache;
+mod dns_sniffer;
+mod doh;
+mod downloads;
+#[cfg(any(feature = "screenshots", feature = "streaming"))]
+mod envelope;
+mod exam;
+mod focus;
+mod focus_mode;
+mod gpu;
+mod hosts;
+mod inbox;
+mod installed;
+mod launch_block;
+mod lockdown;
+mod mail;
 mod models;
 mod monitor;
+mod mqtt;
+mod netstat;
+mod network;
+mod notify;
+mod outbox;
+mod path_bans;
+mod pg_store;
+mod platform;
+mod profiles;
+#[cfg(feature = "streaming")]
+mod recording;
+mod remote_access;
+mod report;
+mod self_view;
+mod selfstat;
+mod shell;
+#[cfg(feature = "screenshots")]
+mod snapshot;
+mod softlock;
 mod store;
+mod store_metrics;
+mod screen_capture;
+mod schedule;
+#[cfg(feature = "screenshots")]
 mod screenshot;
+#[cfg(any(feature = "screenshots", feature = "streaming"))]
+mod screenshot_sink;
+mod tamper;
+mod timeseries;
+mod unlock;
+mod upstream_dns;
+mod violation_sinks;
+mod vpn;
+#[cfg(feature = "webcam")]
+mod webcam;
+#[cfg(feature = "streaming")]
 mod ws_stream;
 
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
 
+use chrono::Utc;
 use tokio::net::TcpListener;
 use tracing::{error, info, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 
+use crate::alerts::Alerter;
 use crate::api::{build_router, AppState};
+use crate::audit::Audit;
+use crate::bandwidth::BandwidthMonitor;
 use crate::config::AppConfig;
+use crate::desktop::Desktop;
+use crate::diagnostics::Diagnostics;
+use crate::doctor::ToolMatrix;
+use crate::exam::ExamMode;
+use crate::focus_mode::FocusMode;
+use crate::models::{HeartbeatExtras, Violation, ViolationKind};
 use crate::monitor::Monitor;
+use crate::mqtt::Mqtt;
+use crate::network::NetworkWatch;
+use crate::schedule::Schedule;
+use crate::selfstat::SelfMonitor;
+use crate::softlock::SoftLock;
 use crate::store::Store;
+use crate::tamper::Watchdog;
+use crate::unlock::UnlockCodes;
+use crate::violation_sinks::ViolationSinks;
 
 const BANNER: &str = r#"
   _   _ _     _   _            _
@@ -29,22 +119,41 @@ const BANNER: &str = r#"
   School PC Monitoring Agent
 "#;
 
+/// Longest the agent spends deregistering before it exits anyway.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // ── Logging ─────────────────────────────────────────────────
-    tracing_subscriber::fmt()
-        .with_env_filter(
+    // A second layer keeps the recent lines for diagnostics bundles
+    let diagnostics = Arc::new(Diagnostics::new());
+    tracing_subscriber::registry()
+        .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "nishack=info".into()),
         )
-        .compact()
+        .with(tracing_subscriber::fmt::layer().compact())
+        .with(tracing_subscriber::fmt::layer().compact().with_ansi(false).with_writer(diagnostics.log_writer()))
         .init();
 
+    // Started by Windows in place of a blocked program (see launch_block.rs)
+    if launch_block::run_stub_from_args() {
+        return Ok(());
+    }
+
+    // The same binary doubles as its own watchdog (see tamper.rs)
+    if let Some(watchdog) = Watchdog::from_args() {
+        return run_watchdog(watchdog).await;
+    }
+
     println!("{BANNER}");
 
     // ── Config ──────────────────────────────────────────────────
     let cfg = AppConfig::load(None)?;
     info!("Config loaded — scan every {}s, API on :{}", cfg.monitor.scan_interval, cfg.api.port);
+    if cfg.monitor.budget.lower_priority {
+        tokio::task::spawn_blocking(budget::lower_priority).await?;
+    }
 
     // ── Identity ────────────────────────────────────────────────
     let hostname = hostname::get()
@@ -66,53 +175,328 @@ async fn main() -> anyhow::Result<()> {
     info!("Host: {hostname} | IP: {ip} | User: {username}");
 
     // ── Redis store ─────────────────────────────────────────────
-    let store = Store::new(&cfg.redis)?;
-    info!("Redis client ready ({})", cfg.redis.url);
+    let store = Store::new(&cfg.redis, &cfg.storage, &cfg.screenshots, &cfg.tags)?;
+    info!("Redis client ready ({}, namespace {})", cfg.redis.url, store.namespace());
+    let mqtt = cfg.mqtt.enabled.then(|| Mqtt::start(&cfg.mqtt, &hostname, "agent")).transpose()?;
+    let sinks = ViolationSinks::new(&cfg.violation_sinks, store.clone(), mqtt.as_ref())?;
 
     // ── Shared state for the API ────────────────────────────────
+    let self_monitor = Arc::new(SelfMonitor::new(&cfg.self_report));
+    let bandwidth = Arc::new(BandwidthMonitor::new(cfg.monitor.bandwidth.top_n));
+    let exam = Arc::new(ExamMode::new(&cfg.exam, Path::new(&cfg.monitor.tamper.state_dir)));
+    let desktop = Arc::new(Desktop::new());
+    let soft_lock = Arc::new(SoftLock::new());
+    let focus_mode = Arc::new(FocusMode::new());
+    let unlock_codes = UnlockCodes::new(&cfg.lock.unlock_codes, hostname.clone(), store.clone()).map(Arc::new);
+    let schedule = Arc::new(Schedule::new(&cfg.schedule));
+    let network = Arc::new(NetworkWatch::new());
+    #[cfg(not(any(feature = "screenshots", feature = "streaming")))]
+    if cfg.encryption.key.is_some() || cfg.encryption.key_file.is_some() {
+        info!("[encryption] key is set but this build captures no screen images to encrypt");
+    }
+    #[cfg(any(feature = "screenshots", feature = "streaming"))]
+    let sealer = envelope::Sealer::from_config(&cfg.encryption)?.map(Arc::new);
+    #[cfg(any(feature = "screenshots", feature = "streaming"))]
+    let screenshot_sink = Arc::new(screenshot_sink::ScreenshotSink::new(
+        &cfg.screenshots,
+        sealer.clone().filter(|_| cfg.encryption.screenshots),
+    )?);
+    let audit = Audit::new(&cfg.audit, hostname.clone(), store.clone());
+    let alerter = Alerter::new(&cfg.alerts, hostname.clone(), store.clone());
+    audit.record("agent_started", "local", Some(format!("v{} as {username}", env!("CARGO_PKG_VERSION")))).await;
+    let capabilities = {
+        let cfg = cfg.clone();
+        Arc::new(tokio::task::spawn_blocking(move || capabilities::detect(&cfg)).await?)
+    };
+    let tools = Arc::new(tokio::task::spawn_blocking(ToolMatrix::probe).await?);
+    let monitor = Arc::new(Mutex::new(
+        Monitor::new(&cfg.monitor, hostname.clone(), username.clone(), Arc::clone(&bandwidth), &tools),
+    ));
     let state = AppState {
         store: store.clone(),
         config: cfg.clone(),
         hostname: hostname.clone(),
         ip: ip.clone(),
         start_time: std::time::Instant::now(),
+        self_monitor: Arc::clone(&self_monitor),
+        monitor: Arc::clone(&monitor),
+        audit: audit.clone(),
+        exam: Arc::clone(&exam),
+        desktop: Arc::clone(&desktop),
+        soft_lock: Arc::clone(&soft_lock),
+        focus_mode: Arc::clone(&focus_mode),
+        capabilities: Arc::clone(&capabilities),
+        tools: Arc::clone(&tools),
+        diagnostics: Arc::clone(&diagnostics),
+        unlock_codes: unlock_codes.clone(),
+        #[cfg(feature = "screenshots")]
+        screenshot_sink: Arc::clone(&screenshot_sink),
     };
 
     // ── Spawn: HTTP API ─────────────────────────────────────────
     let api_port = cfg.api.port;
     let router = build_router(state);
-    tokio::spawn(async move {
-        let addr = format!("0.0.0.0:{api_port}");
-        let listener = TcpListener::bind(&addr).await.expect("Failed to bind API port");
-        info!("API listening on http://{addr}");
-        axum::serve(listener, router).await.expect("API server crashed");
-    });
+    {
+        let router = router.clone();
+        tokio::spawn(async move {
+            let addr = format!("0.0.0.0:{api_port}");
+            let listener = TcpListener::bind(&addr).await.expect("Failed to bind API port");
+            info!("API listening on http://{addr}");
+            let app = router.into_make_service_with_connect_info::<std::net::SocketAddr>();
+            axum::serve(listener, app).await.expect("API server crashed");
+        });
+    }
 
-    // ── Spawn: Heartbeat loop ───────────────────────────────────
+    // ── Spawn: Redis command channels (host + location tags) ────
+    if cfg.commands.enabled {
+        let channels = store.command_channels(&hostname, &cfg.tags);
+        tokio::spawn(commands::run(store.clone(), router.clone(), channels, audit.clone()));
+    }
+
+    // ── Spawn: Message inbox ────────────────────────────────────
+    if cfg.commands.inbox {
+        let every = Duration::from_secs(cfg.commands.inbox_poll_secs.max(1));
+        tokio::spawn(inbox::run(store.clone(), router, audit.clone(), hostname.clone(), every));
+    }
+
+    // ── Spawn: Soft-lock re-enforcement ─────────────────────────
+    tokio::spawn(softlock::run(
+        cfg.lock.clone(),
+        Arc::clone(&soft_lock),
+        unlock_codes,
+        sinks.clone(),
+        audit.clone(),
+        hostname.clone(),
+        username.clone(),
+    ));
+
+    // ── Spawn: Focus-mode enforcement ───────────────────────────
+    tokio::spawn(focus_mode::run(
+        cfg.focus_mode.clone(),
+        Arc::clone(&focus_mode),
+        sinks.clone(),
+        audit.clone(),
+        hostname.clone(),
+        username.clone(),
+    ));
+
+    // ── Spawn: Network environment ──────────────────────────────
+    if cfg.monitor.network.enabled {
+        tokio::spawn(network::run(
+            cfg.monitor.network.clone(),
+            Arc::clone(&network),
+            Arc::clone(&schedule),
+            sinks.clone(),
+            hostname.clone(),
+            username.clone(),
+        ));
+    }
+
+    // ── Spawn: Exam display checks ──────────────────────────────
+    tokio::spawn(displays::run(
+        cfg.exam.clone(),
+        Arc::clone(&exam),
+        sinks.clone(),
+        hostname.clone(),
+        username.clone(),
+    ));
+
+    // ── Spawn: Weekly report ────────────────────────────────────
+    if cfg.report.enabled {
+        tokio::spawn(report::run(
+            cfg.report.clone(),
+            cfg.redis.heartbeat_interval,
+            store.clone(),
+            hostname.clone(),
+            audit.clone(),
+        ));
+    }
+
+    // ── Spawn: CPU / RAM time series ────────────────────────────
+    if cfg.timeseries.enabled {
+        tokio::spawn(timeseries::run(
+            cfg.timeseries.clone(),
+            store.clone(),
+            hostname.clone(),
+            ip.clone(),
+            username.clone(),
+        ));
+    }
+
+    // ── Spawn: Store metrics for the dashboard ──────────────────
+    if cfg.self_report.stats_secs > 0 {
+        let store = store.clone();
+        let hostname = hostname.clone();
+        let every = cfg.self_report.stats_secs;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(every));
+            loop {
+                interval.tick().await;
+                // Outlives a missed push, gone once the agent is
+                store.push_agent_stats(&hostname, every * 3).await;
+            }
+        });
+    }
+
+    // ── Spawn: Redis failback ───────────────────────────────────
+    if !cfg.redis.fallback_urls.is_empty() {
+        let store = store.clone();
+        let every = Duration::from_secs(cfg.redis.failback_secs.max(1));
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(every).await;
+                store.fail_back().await;
+            }
+        });
+    }
+
+    // ── Spawn: Violation retention ──────────────────────────────
+    if cfg.redis.violation_retention_days > 0 {
+        let store = store.clone();
+        let hostname = hostname.clone();
+        let days = cfg.redis.violation_retention_days;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600));
+            loop {
+                interval.tick().await;
+                let cutoff = Utc::now() - chrono::Duration::days(days.into());
+                let trimmed = store.trim_violations(&hostname, cutoff).await;
+                if trimmed > 0 {
+                    info!("🧹 Dropped {trimmed} violations older than {days} days");
+                }
+            }
+        });
+    }
+
+    // ── Spawn: Online announcement ──────────────────────────────
     {
+        let store = store.clone();
+        let hostname = hostname.clone();
+        let ip = ip.clone();
+        let username = username.clone();
+        let port = cfg.api.port;
+        let capabilities = Arc::clone(&capabilities);
+        tokio::spawn(async move {
+            // The teacher may not be up (or published) yet; heartbeats
+            // cover discovery if it never answers
+            let mut delay = Duration::from_secs(5);
+            for _ in 0..8 {
+                if store.announce_online(&hostname, &ip, port, &username, &capabilities).await {
+                    return;
+                }
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(Duration::from_secs(120));
+            }
+        });
+    }
+
+    // ── Spawn: Heartbeat loop ───────────────────────────────────
+    let heartbeat = {
         let store = store.clone();
         let hostname = hostname.clone();
         let ip = ip.clone();
         let username = username.clone();
         let port = cfg.api.port;
         let interval = Duration::from_secs(cfg.redis.heartbeat_interval);
+        #[cfg(feature = "screenshots")]
+        let shots = cfg.screenshots.clone();
+        #[cfg(feature = "screenshots")]
+        let sink = Arc::clone(&screenshot_sink);
+        let self_monitor = Arc::clone(&self_monitor);
+        let bandwidth = Arc::clone(&bandwidth);
+        let exam = Arc::clone(&exam);
+        let soft_lock = Arc::clone(&soft_lock);
+        let focus_mode = Arc::clone(&focus_mode);
+        let schedule = Arc::clone(&schedule);
+        let network = Arc::clone(&network);
+        let capabilities = Arc::clone(&capabilities);
+        let mqtt = mqtt.clone();
+        let unavailable = tools.unavailable();
+        let labels = cfg.labels.resolved(&cfg.tags);
+        let documents = cfg.monitor.documents.clone();
+        // Skipped rather than failing on every heartbeat
+        let documents_available = tools.available("documents");
+        let audio = cfg.monitor.audio.enabled && tools.available("audio");
+        #[cfg(feature = "webcam")]
+        let webcam = cfg.monitor.webcam.enabled && tools.available("webcam");
 
         tokio::spawn(async move {
             loop {
-                store.push_heartbeat(&hostname, &ip, port, &username).await;
-                store.register_agent(&hostname, &ip, port).await;
+                let profile = schedule.active();
+                let mut extras = HeartbeatExtras {
+                    exam_mode: exam.is_active(),
+                    soft_lock: soft_lock.is_active(),
+                    focus_app: focus_mode.app(),
+                    top_talkers: bandwidth.top_talkers(),
+                    profile: profile.name,
+                    network: network.current(),
+                    degraded: capabilities.degraded.clone(),
+                    unavailable: unavailable.clone(),
+                    labels: labels.clone(),
+                    redis_server: store.redis_server(),
+                    ..Default::default()
+                };
+                {
+                    let (mon, st) = (Arc::clone(&self_monitor), store.clone());
+                    extras.agent_resources =
+                        tokio::task::spawn_blocking(move || mon.sample(&st)).await.ok();
+                }
+                if documents.enabled && documents_available {
+                    let documents = documents.clone();
+                    let open = tokio::task::spawn_blocking(move || documents::open_documents(&documents));
+                    if let Ok(Ok(open)) = tokio::time::timeout(Duration::from_secs(5), open).await {
+                        extras.documents = open;
+                    }
+                }
+                if audio {
+                    let sessions = tokio::task::spawn_blocking(audio_sessions::active);
+                    if let Ok(Ok(sessions)) = tokio::time::timeout(Duration::from_secs(5), sessions).await {
+                        extras.audio = sessions;
+                    }
+                }
+                #[cfg(feature = "webcam")]
+                if webcam {
+                    let users = tokio::task::spawn_blocking(webcam::users);
+                    if let Ok(Ok(users)) = tokio::time::timeout(Duration::from_secs(5), users).await {
+                        extras.webcam = users;
+                    }
+                }
+                #[cfg(feature = "screenshots")]
+                if shots.heartbeat_thumbnail && profile.screenshots {
+                    let (quality, dim) = (shots.thumbnail_quality, shots.thumbnail_dimension);
+                    let capture = tokio::task::spawn_blocking(move || {
+                        crate::screenshot::try_capture_screenshot(quality, dim)
+                    });
+                    // Never let a hung display delay the heartbeat itself
+                    if let Ok(Ok(thumb)) = tokio::time::timeout(Duration::from_secs(5), capture).await {
+                        extras.thumbnail = thumb.and_then(|thumb| sink.thumbnail(thumb));
+                    }
+                }
+
+                let hb = store.push_heartbeat(&hostname, &ip, port, &username, extras).await;
+                if let (Some(mqtt), Some(hb)) = (&mqtt, hb) {
+                    mqtt.heartbeat(&hb);
+                }
+                store.register_agent(&hostname, &ip, port, &labels, &capabilities).await;
                 tokio::time::sleep(interval).await;
             }
-        });
-    }
+        })
+    };
 
     // ── Spawn: Screenshot capture loop ──────────────────────────
+    #[cfg(not(feature = "screenshots"))]
+    if cfg.screenshots.enabled {
+        warn!("Screenshots are enabled in config but this build has no capture code (feature \"screenshots\")");
+    }
+    #[cfg(feature = "screenshots")]
     if cfg.screenshots.enabled {
         let store = store.clone();
         let hostname = hostname.clone();
         let quality = cfg.screenshots.quality;
         let max_dimension = cfg.screenshots.max_dimension;
         let interval = Duration::from_secs(cfg.screenshots.interval);
+        let schedule = Arc::clone(&schedule);
+        let sink = Arc::clone(&screenshot_sink);
 
         info!("Screenshot capture enabled — every {}s", cfg.screenshots.interval);
 
@@ -120,6 +504,9 @@ async fn main() -> anyhow::Result<()> {
             let mut consecutive_failures: u32 = 0;
             loop {
                 tokio::time::sleep(interval).await;
+                if !schedule.active().screenshots {
+                    continue;
+                }
 
                 // Capture with a timeout — after sleep/wake the display
                 // driver may not be ready yet, so we don't want to hang.
@@ -132,7 +519,9 @@ async fn main() -> anyhow::Result<()> {
                 match screenshot_result {
                     Ok(Ok(Some(data))) => {
                         consecutive_failures = 0;
-                        store.push_screenshot(&hostname, &data).await;
+                        if let Some(fields) = sink.put(&hostname, &data).await {
+                            store.push_screenshot(&hostname, fields).await;
+                        }
                     }
                     Ok(Ok(None)) => {
                         // Capture failed (logged inside try_capture_screenshot)
@@ -165,17 +554,51 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // ── Spawn: Live screen streaming (WebSocket to teacher) ─────
+    // Redis overrides of [streaming] reach the streaming loop through this
+    #[cfg(feature = "streaming")]
+    let streaming_settings = cfg.streaming.enabled.then(|| tokio::sync::watch::channel(cfg.streaming.clone()).0);
+    #[cfg(not(feature = "streaming"))]
+    let streaming_settings = None;
+    #[cfg(not(feature = "streaming"))]
+    if cfg.streaming.enabled {
+        warn!("Streaming is enabled in config but this build has no capture code (feature \"streaming\")");
+    }
+    #[cfg(feature = "streaming")]
     if cfg.streaming.enabled {
-        let streaming_cfg = cfg.streaming.clone();
+        let streaming_cfg = streaming_settings.as_ref().expect("created while enabled").subscribe();
+        let streaming_store = store.clone();
         let streaming_hostname = hostname.clone();
+        let streaming_schedule = Arc::clone(&schedule);
+        let streaming_audit = audit.clone();
+        let streaming_capabilities = Arc::clone(&capabilities);
+        let streaming_sealer = sealer.clone().filter(|_| cfg.encryption.stream);
+        let streaming_recorder =
+            recording::Recorder::start(&cfg.streaming, Arc::clone(&screenshot_sink), store.clone(), hostname.clone());
+        let streaming_annotations = ws_stream::Annotations {
+            monitor: Arc::clone(&monitor),
+            exam: Arc::clone(&exam),
+            soft_lock: Arc::clone(&soft_lock),
+            focus_mode: Arc::clone(&focus_mode),
+        };
 
         info!(
             "Live streaming enabled — server: {}, interval: {}ms",
-            streaming_cfg.server_url, streaming_cfg.interval_ms
+            cfg.streaming.server_url, cfg.streaming.interval_ms
         );
 
         tokio::spawn(async move {
-            ws_stream::run_streaming_loop(streaming_cfg, streaming_hostname).await;
+            ws_stream::run_streaming_loop(
+                streaming_cfg,
+                streaming_store,
+                streaming_hostname,
+                streaming_schedule,
+                streaming_audit,
+                streaming_capabilities,
+                streaming_annotations,
+                streaming_sealer,
+                streaming_recorder,
+            )
+            .await;
         });
     } else {
         info!("Live screen streaming disabled in config");
@@ -185,54 +608,306 @@ async fn main() -> anyhow::Result<()> {
     let scan_interval = Duration::from_secs(cfg.monitor.scan_interval);
 
     info!("Monitor started — scanning every {}s", cfg.monitor.scan_interval);
-    let monitor = Arc::new(Mutex::new(
-        Monitor::new(&cfg.monitor, hostname.clone(), username),
+
+    // ── Spawn: Config change listener ───────────────────────────
+    let (config_changes, _) = tokio::sync::watch::channel(0u64);
+    if cfg.monitor.ban_sync.listen {
+        let channels = store.config_channels(&hostname, &cfg.monitor.categories);
+        tokio::spawn(config_watch::run(store.clone(), channels, config_changes.clone()));
+    }
+
+    // ── Spawn: Schedule / streaming overrides from Redis ────────
+    tokio::spawn(config_watch::sync_overrides(
+        store.clone(),
+        hostname.clone(),
+        Arc::clone(&schedule),
+        cfg.schedule.clone(),
+        cfg.streaming.clone(),
+        streaming_settings,
+        config_changes.subscribe(),
+        Duration::from_secs(cfg.monitor.ban_sync.interval),
     ));
 
     // ── Spawn: Ban config sync from Redis (teacher pushes updates) ─
     {
         let sync_store = store.clone();
         let sync_monitor = Arc::clone(&monitor);
+        let sync_hostname = hostname.clone();
+        let sync_cfg = cfg.monitor.ban_sync.clone();
+        let mut changes = config_changes.subscribe();
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(30));
+            let mut interval = tokio::time::interval(Duration::from_secs(sync_cfg.interval));
+            let mut applied = None;
+            let mut published = String::new();
             loop {
-                interval.tick().await;
-                if let Some((procs, domains)) = sync_store.fetch_ban_config().await {
-                    let mut guard = sync_monitor.lock().expect("Monitor mutex poisoned");
-                    guard.update_bans(procs, domains);
-                    info!("Ban config updated from Redis");
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    Ok(()) = changes.changed() => interval.reset(),
+                }
+                let Some(bans) = sync_store.fetch_ban_config(&sync_hostname).await else {
+                    continue;
+                };
+                if applied.as_ref() == Some(&bans) {
+                    continue;
+                }
+
+                // Dry-run first so the dashboard can see what is about to change
+                let mut diff = sync_monitor.lock().unwrap_or_else(PoisonError::into_inner).preview_bans(&bans);
+                let hold = sync_cfg.require_confirmation
+                    && diff.is_destructive()
+                    && !sync_store.ban_change_confirmed(&sync_hostname, &diff.fingerprint).await;
+                diff.pending_confirmation = hold;
+
+                if published != diff.fingerprint || !hold {
+                    sync_store.publish_ban_diff(&sync_hostname, &diff).await;
+                    published = diff.fingerprint.clone();
                 }
+                if hold {
+                    info!(
+                        "Ban config {} would kill {:?} — waiting for teacher confirmation",
+                        diff.fingerprint, diff.would_kill
+                    );
+                    continue;
+                }
+
+                sync_monitor.lock().unwrap_or_else(PoisonError::into_inner).update_bans(&bans);
+                info!(
+                    "Ban config {} applied (+{}/-{} processes, +{}/-{} domains)",
+                    diff.fingerprint,
+                    diff.added_processes.len(), diff.removed_processes.len(),
+                    diff.added_domains.len(), diff.removed_domains.len()
+                );
+                sync_store.ack_ban_config(&sync_hostname, &diff.fingerprint).await;
+                applied = Some(bans);
             }
         });
     }
 
-    loop {
-        // Run the blocking scan on a dedicated thread so we don't starve
-        // the async runtime.
-        let mon = Arc::clone(&monitor);
-        let violations = tokio::task::spawn_blocking(move || {
-            let mut guard = mon.lock().expect("Monitor mutex poisoned");
-            guard.full_scan()
-        })
-        .await;
-
-        match violations {
-            Ok(viols) => {
-                if !viols.is_empty() {
-                    info!("Detected {} violation(s) this cycle", viols.len());
-                    for v in &viols {
-                        store.record_violation(v).await;
-                        // Forward to teacher backend so it appears on the dashboard
-                        store.push_violation_to_teacher(v).await;
+    // ── Spawn: Installed-application inventory ──────────────────
+    if cfg.monitor.installed_apps.enabled {
+        tokio::spawn(installed::run(cfg.monitor.installed_apps.clone(), store.clone(), hostname.clone()));
+    }
+
+    // ── Spawn: Shared ban-list categories ───────────────────────
+    if !cfg.monitor.categories.is_empty() {
+        tokio::spawn(categories::run(
+            cfg.monitor.categories.clone(),
+            cfg.monitor.category_source.clone(),
+            store.clone(),
+            Arc::clone(&monitor),
+            config_changes.subscribe(),
+        ));
+    }
+
+    let max_per_cycle = cfg.monitor.dedup.max_per_cycle;
+    let dedup_cooldown = Duration::from_secs(cfg.monitor.dedup.cooldown_secs);
+    // Staggered, the detector groups run a fraction of the interval apart
+    let phases = if cfg.monitor.budget.stagger { Monitor::SCAN_PHASES } else { 1 };
+    let pause = scan_interval / phases as u32;
+    let mut shutdown = std::pin::pin!(shutdown_signal());
+    'scan: loop {
+        let mut viols = Vec::new();
+        for phase in 0..phases {
+            // Run the blocking scan on a dedicated thread so we don't starve
+            // the async runtime.
+            let mon = Arc::clone(&monitor);
+            let enforce = schedule.active().bans;
+            let scanned = tokio::task::spawn_blocking(move || {
+                let mut guard = mon.lock().unwrap_or_else(PoisonError::into_inner);
+                guard.set_enforcing(enforce);
+                let found = if phases == 1 { guard.full_scan() } else { guard.scan_phase(phase) };
+                (found, guard.take_detector_failures())
+            })
+            .await;
+
+            match scanned {
+                Ok((found, failures)) => {
+                    for f in &failures {
+                        store.record_detector_failure(f).await;
                     }
+                    viols.extend(found);
+                }
+                Err(e) => {
+                    error!("Monitor task panicked: {e}");
+                }
+            }
+            if phase + 1 < phases {
+                tokio::select! {
+                    _ = tokio::time::sleep(pause) => {}
+                    _ = &mut shutdown => break 'scan,
+                }
+            }
+        }
+
+        if !viols.is_empty() {
+            info!("Detected {} violation(s) this cycle", viols.len());
+            // A runaway ban list mustn't flood Redis and the teacher
+            let cap = if max_per_cycle == 0 { viols.len() } else { max_per_cycle.min(viols.len()) };
+            let (kept, over) = viols.split_at(cap);
+            let mut new = Vec::new();
+            for v in kept {
+                if v.occurrences > 1 {
+                    // A repeat within the dedup cooldown: bump the stored record only
+                    sinks.update(v).await;
+                } else {
+                    new.push(v.clone());
                 }
             }
-            Err(e) => {
-                error!("Monitor task panicked: {e}");
+            if !over.is_empty() {
+                warn!("{} violation(s) over the per-cycle cap suppressed", over.len());
+                new.push(suppressed_summary(over));
             }
+            // Redis, the teacher dashboard and whatever else is configured
+            sinks.deliver_all(&new).await;
+            for v in &viols {
+                if let Some((alerter, alert)) = alerter.as_ref().and_then(|a| Some((a, a.observe(v)?))) {
+                    tokio::spawn(alerter.clone().send(alert));
+                }
+            }
+        }
+        // Repeats past their cooldown go to the violation stream once
+        store.flush_repeats(dedup_cooldown).await;
+
+        tokio::select! {
+            _ = tokio::time::sleep(pause) => {}
+            _ = &mut shutdown => break,
         }
+    }
 
-        tokio::time::sleep(scan_interval).await;
+    info!("Shutting down — removing enforcement changes");
+    // Nothing may re-register the agent once it's gone
+    heartbeat.abort();
+    audit.record("agent_stopped", "local", None).await;
+    let mon = Arc::clone(&monitor);
+    let _ = tokio::task::spawn_blocking(move || {
+        mon.lock().unwrap_or_else(PoisonError::into_inner).shutdown();
+        exam.stop();
+        desktop.restore_all();
+    })
+    .await;
+
+    // Leave the dashboard as offline rather than waiting for the heartbeat to expire
+    let farewell = async {
+        store.flush().await;
+        store.deregister_agent(&hostname).await;
+        store.announce_offline(&hostname).await;
+        if let Some(mqtt) = &mqtt {
+            mqtt.close(Duration::from_secs(2)).await;
+        }
+    };
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, farewell).await.is_err() {
+        warn!("Deregistration didn't finish within {}s — the heartbeat will expire instead", SHUTDOWN_TIMEOUT.as_secs());
+    }
+    Ok(())
+}
+
+/// Ctrl-C, or what a service manager sends to stop the agent: SIGTERM on
+/// Unix, the shutdown / close events on Windows.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+            return;
+        }
+    }
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows::{ctrl_close, ctrl_shutdown};
+        if let (Ok(mut shutdown), Ok(mut close)) = (ctrl_shutdown(), ctrl_close()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = shutdown.recv() => {}
+                _ = close.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Watchdog mode: supervise the agent, and when it is killed or suspended
+/// restart it and report who was logged in as a tamper violation.
+async fn run_watchdog(watchdog: Watchdog) -> anyhow::Result<()> {
+    // Load up front: the config may be edited while the agent is down
+    let cfg = AppConfig::load(None)?;
+    let store = Store::new(&cfg.redis, &cfg.storage, &cfg.screenshots, &cfg.tags)?;
+    let hostname = hostname::get()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "unknown-pc".into());
+    let mqtt = cfg.mqtt.enabled.then(|| Mqtt::start(&cfg.mqtt, &hostname, "watchdog")).transpose()?;
+    let sinks = ViolationSinks::new(&cfg.violation_sinks, store.clone(), mqtt.as_ref())?;
+
+    let alerter = Alerter::new(&cfg.alerts, hostname.clone(), store.clone());
+
+    let Ok(Some(killed)) = tokio::task::spawn_blocking(move || watchdog.run()).await else {
+        return Ok(());
+    };
+    tamper::restart_agent();
+
+    let v = Violation {
+        hostname,
+        target: "nishack agent".into(),
+        kind: ViolationKind::Tamper,
+        action_taken: true,
+        username: killed.username.clone(),
+        timestamp: killed.at,
+        url: None,
+        visited_at: None,
+        detail: Some(killed.describe()),
+        process: None,
+        occurrences: 1,
+        last_seen: None,
+        usage: None,
+        exe: None,
+    };
+    // Don't linger if Redis / the teacher server is unreachable
+    let report = sinks.deliver(&v);
+    let alert = alerter.as_ref().and_then(|a| a.observe(&v));
+    if tokio::time::timeout(Duration::from_secs(30), report).await.is_err() {
+        warn!("Watchdog could not report the agent being stopped");
+    }
+    if let (Some(alerter), Some(alert)) = (alerter, alert) {
+        alerter.send(alert).await;
+    }
+    if let Some(mqtt) = mqtt {
+        mqtt.close(Duration::from_secs(5)).await;
+    }
+    Ok(())
+}
+
+/// One record standing for the violations over the per-cycle cap, with
+/// the most frequent (rule, target) pairs in its detail.
+fn suppressed_summary(over: &[Violation]) -> Violation {
+    let mut counts: HashMap<(&str, &str), usize> = HashMap::new();
+    for v in over {
+        *counts.entry((v.kind.rule(), &v.target)).or_default() += 1;
+    }
+    let mut top: Vec<_> = counts.into_iter().collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut detail: Vec<String> = top.iter().take(5).map(|((rule, target), n)| format!("{rule} {target} ×{n}")).collect();
+    if top.len() > 5 {
+        detail.push(format!("{} more", top.len() - 5));
+    }
+    Violation {
+        hostname: over[0].hostname.clone(),
+        target: format!("{} additional violations suppressed", over.len()),
+        kind: ViolationKind::Suppressed,
+        action_taken: false,
+        username: over[0].username.clone(),
+        timestamp: Utc::now(),
+        url: None,
+        visited_at: None,
+        detail: Some(detail.join(", ")),
+        process: None,
+        occurrences: 1,
+        last_seen: None,
+        usage: None,
+        exe: None,
     }
 }
 
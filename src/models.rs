@@ -1,5 +1,8 @@
-use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 // ── Violation record ────────────────────────────────────────────
 
@@ -16,6 +19,50 @@ pub struct Violation {
     /// Username of the logged-in Windows user
     pub username: String,
     pub timestamp: DateTime<Utc>,
+    /// Full URL, when the violation came from browser history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// When the page was visited (browser history only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visited_at: Option<DateTime<Utc>>,
+    /// Extra context (e.g. the exact DNS name that was queried)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Process involved, when the target itself isn't a process
+    /// (e.g. the browser holding a connection to a banned domain)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+    /// Times seen within the dedup cooldown; the record keeps the first
+    /// occurrence's fields and `timestamp`
+    #[serde(default = "first_occurrence")]
+    pub occurrences: u32,
+    /// Latest repeat, when `occurrences` > 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// What a banned process was using when caught
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ProcessUsage>,
+    /// Full path of the process's executable, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>,
+}
+
+fn first_occurrence() -> u32 { 1 }
+
+/// Resource use of a caught process, so a launcher idling in the tray
+/// can be told from a game being played.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    /// Share of the whole machine (0-100); 0 in the scan that first
+    /// sees the process.
+    pub cpu_percent: f32,
+    pub memory_mb: u64,
+    /// Seconds since the process started.
+    pub running_secs: u64,
+    /// None where per-process GPU usage isn't available (see `gpu.rs`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu_percent: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -23,6 +70,308 @@ pub struct Violation {
 pub enum ViolationKind {
     Process,
     Domain,
+    Extension,
+    DnsBypass,
+    BandwidthAbuse,
+    ResourceAbuse,
+    RemoteAccess,
+    ScreenShare,
+    VpnProxy,
+    Tamper,
+    FileDownload,
+    LockCircumvention,
+    Keyword,
+    ExtraDisplay,
+    Microphone,
+    Webcam,
+    FocusEscape,
+    ForeignNetwork,
+    /// Stands for the violations over `[monitor.dedup] max_per_cycle`.
+    Suppressed,
+}
+
+impl ViolationKind {
+    /// Rule id in the teacher-backend schema.
+    pub fn rule(&self) -> &'static str {
+        match self {
+            ViolationKind::Process           => "banned_process",
+            ViolationKind::Domain            => "banned_domain",
+            ViolationKind::Extension         => "banned_extension",
+            ViolationKind::DnsBypass         => "dns_bypass",
+            ViolationKind::BandwidthAbuse    => "bandwidth_abuse",
+            ViolationKind::ResourceAbuse     => "resource_abuse",
+            ViolationKind::RemoteAccess      => "remote_access",
+            ViolationKind::ScreenShare       => "screen_share",
+            ViolationKind::VpnProxy          => "vpn_proxy",
+            ViolationKind::Tamper            => "tamper",
+            ViolationKind::FileDownload      => "file_download",
+            ViolationKind::LockCircumvention => "lock_circumvention",
+            ViolationKind::Keyword           => "banned_keyword",
+            ViolationKind::ExtraDisplay      => "extra_display",
+            ViolationKind::Microphone        => "microphone_use",
+            ViolationKind::Webcam            => "webcam_use",
+            ViolationKind::FocusEscape       => "focus_escape",
+            ViolationKind::ForeignNetwork    => "foreign_network",
+            ViolationKind::Suppressed        => "violations_suppressed",
+        }
+    }
+
+    /// Severity shown on the teacher dashboard.
+    pub fn severity(&self) -> &'static str {
+        match self {
+            ViolationKind::Process           => "high",
+            ViolationKind::Domain            => "medium",
+            ViolationKind::Extension         => "medium",
+            ViolationKind::DnsBypass         => "high",
+            ViolationKind::BandwidthAbuse    => "medium",
+            ViolationKind::ResourceAbuse     => "high",
+            ViolationKind::RemoteAccess      => "high",
+            ViolationKind::ScreenShare       => "high",
+            ViolationKind::VpnProxy          => "high",
+            ViolationKind::Tamper            => "high",
+            ViolationKind::FileDownload      => "medium",
+            ViolationKind::LockCircumvention => "medium",
+            ViolationKind::Keyword           => "low",
+            ViolationKind::ExtraDisplay      => "high",
+            ViolationKind::Microphone        => "medium",
+            ViolationKind::Webcam            => "medium",
+            ViolationKind::FocusEscape       => "medium",
+            ViolationKind::ForeignNetwork    => "medium",
+            ViolationKind::Suppressed        => "low",
+        }
+    }
+
+    /// Human-readable label (the dashboard UI is in Russian).
+    pub fn label(&self) -> &'static str {
+        match self {
+            ViolationKind::Process           => "Запрещённый процесс",
+            ViolationKind::Domain            => "Запрещённый домен",
+            ViolationKind::Extension         => "Запрещённое расширение",
+            ViolationKind::DnsBypass         => "Обход DNS-фильтра",
+            ViolationKind::BandwidthAbuse    => "Чрезмерный сетевой трафик",
+            ViolationKind::ResourceAbuse     => "Подозрительная нагрузка CPU/GPU",
+            ViolationKind::RemoteAccess      => "Средство удалённого доступа",
+            ViolationKind::ScreenShare       => "Запись или трансляция экрана",
+            ViolationKind::VpnProxy          => "VPN или прокси",
+            ViolationKind::Tamper            => "Вмешательство в работу агента",
+            ViolationKind::FileDownload      => "Загрузка запрещённого файла",
+            ViolationKind::LockCircumvention => "Обход блокировки экрана",
+            ViolationKind::Keyword           => "Запрещённое слово в заголовке окна",
+            ViolationKind::ExtraDisplay      => "Второй экран во время экзамена",
+            ViolationKind::Microphone        => "Запрещённое приложение использует микрофон",
+            ViolationKind::Webcam            => "Запрещённое приложение использует камеру",
+            ViolationKind::FocusEscape       => "Выход из режима фокуса",
+            ViolationKind::ForeignNetwork    => "Подключение к сторонней сети",
+            ViolationKind::Suppressed        => "Нарушения сверх лимита за цикл",
+        }
+    }
+}
+
+// ── Centrally-managed ban config ────────────────────────────────
+
+/// One layer of ban config as published in Redis (global, room or host).
+///
+/// Layers are applied in order global → room → host. Each layer adds its
+/// `banned_*` entries and removes its `allowed_*` entries from what it
+/// inherited; `replace = true` discards the inherited lists first.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BanLayer {
+    #[serde(default)]
+    pub replace: bool,
+    #[serde(default)]
+    pub banned_processes: Vec<String>,
+    #[serde(default)]
+    pub banned_domains: Vec<String>,
+    #[serde(default)]
+    pub allowed_processes: Vec<String>,
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+}
+
+/// Effective ban lists after merging all layers. Sorted sets, so the same
+/// layers always produce the same result regardless of entry order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BanConfig {
+    pub banned_processes: BTreeSet<String>,
+    pub banned_domains: BTreeSet<String>,
+}
+
+impl BanConfig {
+    /// Merge `layers` (lowest precedence first) into one effective config.
+    pub fn merge<'a>(layers: impl IntoIterator<Item = &'a BanLayer>) -> Self {
+        let mut cfg = Self::default();
+        for layer in layers {
+            if layer.replace {
+                cfg = Self::default();
+            }
+            let lower = |v: &Vec<String>| v.iter().map(|s| s.trim().to_lowercase()).collect::<Vec<_>>();
+            cfg.banned_processes.extend(lower(&layer.banned_processes));
+            cfg.banned_domains.extend(lower(&layer.banned_domains));
+            for p in lower(&layer.allowed_processes) {
+                cfg.banned_processes.remove(&p);
+            }
+            for d in lower(&layer.allowed_domains) {
+                cfg.banned_domains.remove(&d);
+            }
+        }
+        cfg.banned_processes.remove("");
+        cfg.banned_domains.remove("");
+        cfg
+    }
+
+    /// Stable short hash identifying this exact set of lists. Teachers
+    /// confirm a pending change by echoing it back.
+    pub fn fingerprint(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        let digest = Sha256::digest(json.as_bytes());
+        format!("{digest:x}")[..16].to_string()
+    }
+}
+
+/// One shared ban-list category, as published at the category source.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CategoryList {
+    #[serde(default)]
+    pub processes: Vec<String>,
+    #[serde(default)]
+    pub domains: Vec<String>,
+}
+
+// ── Teacher discovery ───────────────────────────────────────────
+
+/// A teacher server as it publishes itself in Redis. Agents try the
+/// endpoints in `priority` order (lowest first) and fail over to the
+/// next when one doesn't answer.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TeacherEndpoint {
+    pub host: String,
+    #[serde(default = "teacher_default_port")]
+    pub port: u16,
+    /// Screen-streaming WebSocket URL; `ws://{host}:{port}/ws/screen`
+    /// when not given.
+    #[serde(default)]
+    pub ws_url: Option<String>,
+    /// Base URL of the REST API; `http://{host}:{port}` when not given.
+    #[serde(default)]
+    pub api_url: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+fn teacher_default_port() -> u16 { 8080 }
+
+impl TeacherEndpoint {
+    /// What servers that only publish their IP listen on.
+    pub fn legacy(ip: String) -> Self {
+        Self { host: ip, port: teacher_default_port(), ws_url: None, api_url: None, priority: 0 }
+    }
+
+    pub fn api_url(&self) -> String {
+        match &self.api_url {
+            Some(url) => url.trim_end_matches('/').to_owned(),
+            None => format!("http://{}:{}", self.host, self.port),
+        }
+    }
+
+    #[cfg_attr(not(feature = "streaming"), allow(dead_code))]
+    pub fn ws_url(&self) -> String {
+        self.ws_url.clone().unwrap_or_else(|| format!("ws://{}:{}/ws/screen", self.host, self.port))
+    }
+}
+
+/// What applying a new ban config would change on this machine.
+#[derive(Debug, Clone, Serialize)]
+pub struct BanDiff {
+    /// Fingerprint of the incoming config.
+    pub fingerprint: String,
+    pub added_processes: Vec<String>,
+    pub removed_processes: Vec<String>,
+    pub added_domains: Vec<String>,
+    pub removed_domains: Vec<String>,
+    /// Currently running processes the new config would kill.
+    pub would_kill: Vec<String>,
+    /// True while the agent waits for a teacher confirmation.
+    pub pending_confirmation: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl BanDiff {
+    /// Destructive == the change would kill something running right now.
+    pub fn is_destructive(&self) -> bool {
+        !self.would_kill.is_empty()
+    }
+}
+
+/// A detector that panicked during a scan. The scan carries on without it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectorFailure {
+    pub detector: String,
+    pub hostname: String,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Outcome of the last full scan (all its phases), for diagnostics.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanReport {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// False outside class hours, when only tamper checks ran.
+    pub enforcing: bool,
+    pub violations: Vec<Violation>,
+    /// Detectors that panicked during it.
+    pub failed_detectors: Vec<String>,
+    /// Expensive detectors skipped because the system was busy.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_detectors: Vec<String>,
+}
+
+/// One entry of the installed-application inventory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledApp {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+}
+
+/// An application that appeared in or left the inventory.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppChange {
+    pub hostname: String,
+    /// "installed" | "uninstalled"
+    pub action: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// One admin action, as written to the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub hostname: String,
+    /// e.g. "shell_opened", "shell_closed", "admin_auth_failed"
+    pub action: String,
+    /// Who did it (remote address of the API client).
+    pub actor: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// One entry of a host's merged history (`GET /timeline`).
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineEvent {
+    pub timestamp: DateTime<Utc>,
+    /// "violation" | "lock" | "session" | "audit" | "usage" | "screenshot" | "detector_failure"
+    pub source: &'static str,
+    pub summary: String,
+    /// The stored record itself (screenshots without their image data).
+    pub data: serde_json::Value,
 }
 
 // ── System info snapshot ────────────────────────────────────────
@@ -54,7 +403,278 @@ pub struct Heartbeat {
     pub cpu_usage: f32,
     pub ram_usage: f32,
     pub uptime_secs: u64,
+    /// Laptops only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery: Option<Battery>,
+    /// Free space per mounted disk.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<DiskSpace>,
+    /// Hottest CPU sensor, where the OS exposes one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_temp_c: Option<f32>,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub extras: HeartbeatExtras,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Battery {
+    /// Charge, 0-100.
+    pub percent: u8,
+    /// Not running on battery (charging, full or held on mains).
+    pub charging: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskSpace {
+    pub mount: String,
+    pub total_mb: u64,
+    pub free_mb: u64,
+}
+
+/// Optional heartbeat fields contributed by other subsystems.
+/// Flattened into the heartbeat JSON; absent fields are omitted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HeartbeatExtras {
+    /// Tiny base64 JPEG preview of the screen for the dashboard grid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    /// The agent's own CPU / memory / Redis footprint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_resources: Option<SelfStats>,
+    /// True while the teacher has exam mode on.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exam_mode: bool,
+    /// True while a soft lock is being held on.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub soft_lock: bool,
+    /// The only application allowed while focus mode is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_app: Option<String>,
+    /// Apps using the most network bandwidth, busiest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_talkers: Vec<AppTraffic>,
+    /// Documents open in office apps and editors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<OpenDocument>,
+    /// Programs playing or recording audio.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audio: Vec<AudioSession>,
+    /// Programs using the camera.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webcam: Vec<String>,
+    /// The network the machine is on and when it last changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkEnv>,
+    /// Active lesson-schedule block, or "off-hours"; absent without a schedule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// `Capabilities::degraded`, so the dashboard sees it on every poll.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub degraded: Vec<String>,
+    /// Features switched off for lack of an OS tool (see `doctor.rs`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unavailable: Vec<ToolCheck>,
+    /// `[labels]`: classroom, row, seat, inventory tag.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Redis server in use, with `[redis] fallback_urls`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redis_server: Option<String>,
+}
+
+/// Whether a feature found the external tool it shells out to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCheck {
+    pub feature: String,
+    /// "ok" | "unavailable"
+    pub status: String,
+    /// Any one of these will do, preferred first.
+    pub needs: Vec<String>,
+    /// The one found on PATH.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub using: Option<String>,
+    /// Monitor detectors that don't run while it is unavailable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disables: Vec<String>,
+}
+
+/// The network the machine is connected through (see `network.rs`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkEnv {
+    /// Wi-Fi network name; None on a wired connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssid: Option<String>,
+    /// Default gateway address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<String>,
+    /// Interface carrying the default route.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// Looks like a phone's hotspot or USB tethering.
+    #[serde(default)]
+    pub hotspot: bool,
+    /// Whether this is a `[monitor.network]` school network; None while
+    /// none are configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub school: Option<bool>,
+    /// When the machine joined this network (or the agent started).
+    pub since: DateTime<Utc>,
+    /// The network before the last change, as "ssid via gateway".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+}
+
+/// Network throughput of one app (all its processes) over the last sample.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppTraffic {
+    pub app: String,
+    pub sent_bytes_per_sec: u64,
+    pub recv_bytes_per_sec: u64,
+}
+
+/// A program with an active audio stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioSession {
+    pub process: String,
+    /// "playback" | "capture"
+    pub direction: String,
+}
+
+/// A document open in an office app, editor or online editor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenDocument {
+    pub app: String,
+    pub file: String,
+}
+
+impl OpenDocument {
+    /// "essay.docx (Word)", the key used in attendance and reports.
+    pub fn label(&self) -> String {
+        format!("{} ({})", self.file, self.app)
+    }
+}
+
+impl AppTraffic {
+    pub fn total_mbps(&self) -> f64 {
+        (self.sent_bytes_per_sec + self.recv_bytes_per_sec) as f64 * 8.0 / 1_000_000.0
+    }
+}
+
+/// One sample of the agent's own resource usage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfStats {
+    /// Share of total machine CPU (0-100) since the previous sample.
+    pub cpu_percent: f32,
+    pub rss_mb: u64,
+    /// Open handles (Windows) / file descriptors (Unix).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handles: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sockets: Option<u64>,
+    pub redis_bytes_last_hour: u64,
+    /// Budgets currently exceeded ("cpu", "rss", "handles", "redis_bytes").
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub over_budget: Vec<String>,
+}
+
+// ── Attendance & weekly report ──────────────────────────────────
+
+/// One student's presence on one day, built up from heartbeats.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttendanceDay {
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub heartbeats: u32,
+    #[serde(default)]
+    pub exam_heartbeats: u32,
+    /// Heartbeats per lesson-schedule block.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, u32>,
+    /// Heartbeats each document was open in, by `OpenDocument::label`.
+    #[serde(default)]
+    pub documents: BTreeMap<String, u32>,
+}
+
+/// Activity summary for every student seen on this PC during one week.
+#[derive(Debug, Clone, Serialize)]
+pub struct WeeklyReport {
+    pub hostname: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    pub students: Vec<StudentReport>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StudentReport {
+    pub username: String,
+    pub days_present: usize,
+    pub online_minutes: u64,
+    pub exam_minutes: u64,
+    /// Minutes per lesson-schedule block.
+    pub lesson_minutes: BTreeMap<String, u64>,
+    /// Minutes each document was open, by `OpenDocument::label`.
+    pub document_minutes: BTreeMap<String, u64>,
+    pub attendance: Vec<AttendanceEntry>,
+    pub violations_total: usize,
+    pub violations_by_rule: BTreeMap<String, usize>,
+    pub violations_by_severity: BTreeMap<String, usize>,
+    /// Newest first, capped.
+    pub recent_violations: Vec<ReportViolation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AttendanceEntry {
+    pub date: NaiveDate,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub minutes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportViolation {
     pub timestamp: DateTime<Utc>,
+    pub rule: String,
+    pub severity: String,
+    pub detail: String,
+}
+
+// ── Capabilities ────────────────────────────────────────────────
+
+/// Features available on this agent (see `capabilities.rs`).
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    /// `capabilities::PROTOCOL_VERSION`
+    pub protocol_version: u32,
+    /// `std::env::consts::OS`: "windows", "macos", "linux"
+    pub platform: &'static str,
+    /// Architecture the agent was built for
+    pub arch: &'static str,
+    /// Architecture of the OS (differs under WOW64 / x64 emulation)
+    pub native_arch: String,
+    pub version: &'static str,
+    /// Displays found at startup
+    pub monitors: usize,
+    pub multi_monitor: bool,
+    pub exam_mode: bool,
+    pub webcam: bool,
+    pub h264: bool,
+    pub ocr: bool,
+    pub screenshots: bool,
+    pub streaming: bool,
+    /// Remote shell (needs `api.admin_token`)
+    pub shell: bool,
+    /// Redis command actions accepted (empty when commands are disabled)
+    pub commands: Vec<&'static str>,
+    /// Cargo features the binary was built with
+    pub build_features: Vec<&'static str>,
+    /// Optional detectors and controls switched on in the config
+    pub features: Vec<&'static str>,
+    /// `AppConfig::source_hash`
+    pub config_hash: String,
+    /// Why features are missing or degraded here, also sent in heartbeats
+    pub degraded: Vec<String>,
 }
 
 // ── API responses ───────────────────────────────────────────────
@@ -65,6 +685,46 @@ pub struct HealthResponse {
     pub version: &'static str,
     pub hostname: String,
     pub uptime_secs: u64,
+    /// Redis calls are being skipped after repeated connection failures.
+    pub redis_circuit_open: bool,
+    /// `host:port` of the Redis server in use, with `[redis] fallback_urls`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis_server: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<SelfStats>,
+}
+
+/// A stored screenshot: its metadata and, when Redis holds the image,
+/// the JPEG.
+#[derive(Debug, Clone)]
+pub struct Screenshot {
+    pub meta: serde_json::Map<String, serde_json::Value>,
+    pub jpeg: Option<Vec<u8>>,
+}
+
+impl Screenshot {
+    /// From the JSON form with inline base64 `data`.
+    pub fn from_json(raw: &str) -> Option<Self> {
+        use base64::Engine;
+
+        let mut meta: serde_json::Map<String, serde_json::Value> = serde_json::from_str(raw).ok()?;
+        let jpeg = match meta.remove("data") {
+            Some(serde_json::Value::String(b64)) => base64::engine::general_purpose::STANDARD.decode(b64).ok(),
+            _ => None,
+        };
+        Some(Self { meta, jpeg })
+    }
+
+    /// The JSON form, with the image (if any) as base64 `data`.
+    pub fn to_json(&self) -> serde_json::Value {
+        use base64::Engine;
+
+        let mut meta = self.meta.clone();
+        if let Some(jpeg) = &self.jpeg {
+            meta.insert("data".into(), base64::engine::general_purpose::STANDARD.encode(jpeg).into());
+        }
+        serde_json::Value::Object(meta)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -72,3 +732,19 @@ pub struct ViolationsResponse {
     pub total: usize,
     pub violations: Vec<Violation>,
 }
+
+#[derive(Debug, Serialize)]
+pub struct SystemHistoryResponse {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub total: usize,
+    pub samples: Vec<SystemSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimelineResponse {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub total: usize,
+    pub events: Vec<TimelineEvent>,
+}
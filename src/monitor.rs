@@ -1,99 +1,905 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
 use sysinfo::System;
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 
-use crate::config::MonitorConfig;
-use crate::models::{Violation, ViolationKind};
+use crate::audio_sessions;
+use crate::bandwidth::BandwidthMonitor;
+use crate::blocker::FirewallBlocker;
+use crate::browser::{self, Visit};
+use crate::config::{
+    AudioActivityConfig, BanAction, BandwidthConfig, BrowserHistoryConfig, ConnectionScanConfig, DnsBypassConfig, DownloadsConfig,
+    ExtensionBanConfig, MonitorConfig, RemoteAccessConfig, ResourceAbuseConfig, ScanScope, ScreenShareConfig,
+    UserProfile, VpnProxyConfig, WebcamConfig,
+};
+use crate::dns_cache;
+use crate::doctor::ToolMatrix;
+use crate::documents;
+use crate::doh::{self, DohResolvers};
+use crate::downloads;
+use crate::gpu;
+use crate::dns_sniffer::{DnsSniffer, QuerySource};
+use crate::hosts::HostsBlocker;
+use crate::launch_block::LaunchBlocker;
+use crate::models::{BanConfig, BanDiff, DetectorFailure, ProcessUsage, ScanReport, Violation, ViolationKind};
+use crate::netstat;
+use crate::notify;
+use crate::path_bans::PathBans;
+use crate::profiles;
+use crate::remote_access;
+use crate::screen_capture;
+use crate::tamper::TamperGuard;
+use crate::upstream_dns::{self, DomainResolver};
+use crate::vpn;
+#[cfg(feature = "webcam")]
+use crate::webcam;
 
 /// Create a `Command` that will NOT pop up a console window on Windows.
+/// A 32-bit agent gets the native system tool (see `platform.rs`).
 #[cfg(target_os = "windows")]
-fn silent_cmd(program: &str) -> std::process::Command {
+pub(crate) fn silent_cmd(program: &str) -> std::process::Command {
     use std::os::windows::process::CommandExt;
-    let mut cmd = std::process::Command::new(program);
+    let mut cmd = match crate::platform::native_tool(program) {
+        Some(native) => std::process::Command::new(native),
+        None => std::process::Command::new(program),
+    };
     cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
     cmd
 }
 
 #[cfg(not(target_os = "windows"))]
-fn silent_cmd(program: &str) -> std::process::Command {
+pub(crate) fn silent_cmd(program: &str) -> std::process::Command {
     std::process::Command::new(program)
 }
 
+/// How often the console user is looked up for per-user profiles and
+/// `scan_scope = "session"`.
+const LOGIN_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Detector names, as used by `[monitor.detector_intervals]`, scan
+/// reports and detector failures.
+const DETECTORS: &[&str] = &[
+    "user_profile", "firewall", "tamper", "blocked_launches", "processes", "resource_abuse",
+    "remote_access", "screen_share", "microphone", "webcam", "vpn_proxy", "dns_sniffer", "dns_cache",
+    "window_titles", "connections", "dns_bypass", "bandwidth", "browser_history", "extensions", "downloads",
+];
+
+/// Early enough for a `[monitor.detector_intervals]` timer to count as up.
+const TIMER_SLACK: Duration = Duration::from_millis(500);
+
+/// How a kill went, as checked afterwards by PID and start time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KillOutcome {
+    /// Gone after the first signal.
+    Stopped,
+    /// Only gone after a force kill.
+    ForceKilled,
+    /// Still running after the force kill (or the kill call failed and
+    /// there was no check).
+    Survived,
+}
+
+impl KillOutcome {
+    fn stopped(self) -> bool {
+        self != Self::Survived
+    }
+
+    /// What to add to the violation's detail, if anything.
+    fn note(self) -> Option<&'static str> {
+        match self {
+            Self::Stopped => None,
+            Self::ForceKilled => Some("force-killed"),
+            Self::Survived => Some("still running after kill"),
+        }
+    }
+}
+
 /// Holds a system handle and the ban configuration.
 pub struct Monitor {
     sys: System,
+    /// Effective lists: `base_bans` with the current user's profile applied.
     banned_procs: HashSet<String>,
     banned_domains: HashSet<String>,
+    /// Lowercased `banned_keywords`, matched in window titles.
+    banned_keywords: Vec<String>,
+    /// Lists from config.toml or the teacher's layers, before user overrides.
+    base_bans: BanConfig,
+    /// Entries from the enabled shared categories, added to `base_bans`.
+    category_bans: BanConfig,
+    user_profiles: Vec<UserProfile>,
+    /// Console login, looked up every `LOGIN_CHECK_INTERVAL`.
+    login: Option<String>,
+    login_last_check: Option<Instant>,
+    scan_scope: ScanScope,
+    /// Owner of `login`'s processes, for `ScanScope::Session`. None when
+    /// the account couldn't be matched (everything is scanned then).
+    session_uid: Option<sysinfo::Uid>,
+    /// Actions set per entry in config.toml; other names get the list default.
+    proc_actions: HashMap<String, BanAction>,
+    domain_actions: HashMap<String, BanAction>,
+    default_proc_action: BanAction,
+    default_domain_action: BanAction,
+    warn_grace: Duration,
+    /// `[monitor.banned_paths]`, with its own warning grace.
+    path_bans: PathBans,
+    path_warn_grace: Duration,
+    /// Wait before checking a kill took (`kill_confirm_ms`); zero = don't.
+    kill_confirm: Duration,
+    /// `warn` processes the user was warned about, and when.
+    warned_procs: HashMap<sysinfo::Pid, Instant>,
+    /// `log` processes already reported.
+    logged_procs: HashSet<sysinfo::Pid>,
+    /// Processes checked and not banned (or out of scope): start time and
+    /// name when checked. Cleared whenever the effective lists change.
+    clean_procs: HashMap<sysinfo::Pid, (u64, String)>,
+    /// Hash of every (PID, start time, name) at the last process pass.
+    proc_generation: u64,
+    /// A warn / kill ban was still running after the last pass.
+    proc_pending: bool,
+    /// `warn` domains and when the user was last warned about each.
+    warned_domains: HashMap<String, Instant>,
     hostname: String,
     username: String,
+    history_cfg: BrowserHistoryConfig,
+    history_last_run: Option<Instant>,
+    /// Newest visit already reported, per history database.
+    history_watermarks: HashMap<PathBuf, chrono::DateTime<Utc>>,
+    /// Newest visit seen per browser, for the stream's metadata.
+    latest_visits: HashMap<&'static str, Visit>,
+    extension_cfg: ExtensionBanConfig,
+    extensions_last_run: Option<Instant>,
+    /// Extensions already reported (profile dir + id), so a kept extension
+    /// isn't re-reported every scan.
+    reported_extensions: HashSet<String>,
+    downloads_cfg: DownloadsConfig,
+    downloads_last_run: Option<Instant>,
+    /// Files changed before this (agent start) aren't new downloads.
+    downloads_since: chrono::DateTime<Utc>,
+    reported_downloads: HashSet<PathBuf>,
+    /// Managed hosts-file block, when hosts enforcement is enabled.
+    hosts: Option<HostsBlocker>,
+    /// Firewall block rules, when firewall enforcement is enabled.
+    firewall: Option<FirewallBlocker>,
+    /// OS launch blocks for banned programs, when enabled and supported.
+    launch_blocker: Option<LaunchBlocker>,
+    firewall_refresh: Duration,
+    /// None = rules need (re)syncing on the next scan.
+    firewall_last_sync: Option<Instant>,
+    /// Live DNS / SNI capture; replaces cache parsing when running.
+    sniffer: Option<DnsSniffer>,
+    bypass_cfg: DnsBypassConfig,
+    bypass_last_run: Option<Instant>,
+    /// Resolved lazily on the first bypass scan (DNS lookups block).
+    doh_resolvers: Option<DohResolvers>,
+    /// (pid, resolver) pairs and browsers already reported.
+    reported_bypass: HashSet<String>,
+    doh_policy_applied: bool,
+    conn_cfg: ConnectionScanConfig,
+    conn_last_run: Option<Instant>,
+    /// Banned-domain IPs → domain, and when that map was last requested
+    /// from `conn_resolver`.
+    banned_ips: HashMap<std::net::IpAddr, String>,
+    banned_ips_resolved: Option<Instant>,
+    conn_resolver: Option<DomainResolver>,
+    /// (pid, domain) pairs seen in the previous connection scan.
+    active_banned_conns: HashSet<(u32, String)>,
+    bandwidth: Arc<BandwidthMonitor>,
+    bandwidth_cfg: BandwidthConfig,
+    bandwidth_last_run: Option<Instant>,
+    /// Apps currently over the bandwidth limit, and since when.
+    over_limit_since: HashMap<String, Instant>,
+    /// Apps already reported during their current spell over the limit.
+    reported_bandwidth: HashSet<String>,
+    abuse_cfg: ResourceAbuseConfig,
+    abuse_last_run: Option<Instant>,
+    /// PIDs currently over a CPU / GPU limit, and since when.
+    hogging_since: HashMap<sysinfo::Pid, Instant>,
+    /// PIDs already reported during their current spell over a limit.
+    reported_hogs: HashSet<sysinfo::Pid>,
+    remote_cfg: RemoteAccessConfig,
+    remote_last_run: Option<Instant>,
+    /// Remote-access tools found present in the previous check.
+    reported_remote: HashSet<&'static str>,
+    share_cfg: ScreenShareConfig,
+    share_last_run: Option<Instant>,
+    /// Capturing programs found in the previous check.
+    reported_capturers: HashSet<String>,
+    audio_cfg: AudioActivityConfig,
+    audio_last_run: Option<Instant>,
+    /// Banned programs found on the microphone in the previous check.
+    reported_mic: HashSet<String>,
+    webcam_cfg: WebcamConfig,
+    webcam_last_run: Option<Instant>,
+    /// Banned programs found on the camera in the previous check.
+    reported_webcam: HashSet<String>,
+    vpn_cfg: VpnProxyConfig,
+    vpn_last_run: Option<Instant>,
+    /// VPN / proxy findings present in the previous check.
+    reported_vpn: HashSet<String>,
+    tamper: Option<TamperGuard>,
+    /// False outside class hours (see `schedule.rs`): only tamper checks run
+    /// and hosts / firewall blocks are lifted.
+    enforcing: bool,
+    /// When enforcement last resumed; history before it isn't reported.
+    enforcing_since: Option<chrono::DateTime<Utc>>,
+    /// Detector panics not yet collected by `take_detector_failures`.
+    detector_failures: Vec<DetectorFailure>,
+    last_scan: Option<ScanReport>,
+    /// The scan whose phases are running.
+    scan_in_progress: Option<ScanReport>,
+    /// System CPU % above which expensive detectors skip a round; 0 = never.
+    max_system_cpu: f32,
+    /// System was over `max_system_cpu` at the start of this phase.
+    busy: bool,
+    /// `[monitor.detector_intervals]`, and when each of those last ran.
+    detector_intervals: HashMap<String, Duration>,
+    detector_last_run: HashMap<String, Instant>,
+    /// Detectors whose OS tool is missing, never run (see `doctor.rs`).
+    unavailable_detectors: HashSet<String>,
+    /// nvidia-smi / the GPU counters and the service manager are there.
+    gpu_available: bool,
+    services_available: bool,
+    dedup_cooldown: Duration,
+    /// First occurrence of each recently reported violation, by dedup key.
+    recent_violations: HashMap<String, (Instant, Violation)>,
 }
 
 impl Monitor {
-    pub fn new(cfg: &MonitorConfig, hostname: String, username: String) -> Self {
+    pub fn new(
+        cfg: &MonitorConfig,
+        hostname: String,
+        username: String,
+        bandwidth: Arc<BandwidthMonitor>,
+        tools: &ToolMatrix,
+    ) -> Self {
         // Store everything lowercased for case-insensitive matching
-        let banned_procs = cfg
-            .banned_processes
-            .names
-            .iter()
-            .map(|n| n.to_lowercase())
-            .collect();
+        let proc_actions: HashMap<String, BanAction> = cfg.banned_processes.actions().collect();
+        let domain_actions: HashMap<String, BanAction> = cfg.banned_domains.actions().collect();
+        let base_bans = BanConfig {
+            banned_processes: proc_actions.keys().cloned().collect(),
+            banned_domains: domain_actions.keys().cloned().collect(),
+        };
+        let banned_procs = base_bans.banned_processes.iter().cloned().collect();
+        let banned_domains = base_bans.banned_domains.iter().cloned().collect();
 
-        let banned_domains = cfg
-            .banned_domains
-            .names
-            .iter()
-            .map(|d| d.to_lowercase())
-            .collect();
+        let hosts = cfg
+            .enforcement
+            .hosts_file
+            .then(|| HostsBlocker::new(cfg.enforcement.hosts_path.as_deref()));
+        let resolvers = upstream_dns::parse_servers(&cfg.enforcement.resolvers);
+
+        let tamper = cfg.tamper.enabled.then(|| TamperGuard::start(&cfg.tamper, &username));
 
-        Self {
+        let mut monitor = Self {
             sys: System::new_all(),
             banned_procs,
             banned_domains,
+            banned_keywords: cfg.banned_keywords.iter().map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty()).collect(),
+            base_bans,
+            category_bans: BanConfig::default(),
+            user_profiles: cfg.user_profiles.clone(),
+            login: None,
+            login_last_check: None,
+            scan_scope: cfg.scan_scope,
+            session_uid: None,
+            proc_actions,
+            domain_actions,
+            default_proc_action: cfg.banned_processes.action,
+            default_domain_action: cfg.banned_domains.action,
+            warn_grace: Duration::from_secs(cfg.banned_processes.warn_grace_secs),
+            path_bans: PathBans::new(&cfg.banned_paths),
+            path_warn_grace: Duration::from_secs(cfg.banned_paths.warn_grace_secs),
+            kill_confirm: Duration::from_millis(cfg.enforcement.kill_confirm_ms),
+            warned_procs: HashMap::new(),
+            logged_procs: HashSet::new(),
+            clean_procs: HashMap::new(),
+            proc_generation: 0,
+            proc_pending: false,
+            warned_domains: HashMap::new(),
             hostname,
             username,
+            history_cfg: cfg.browser_history.clone(),
+            history_last_run: None,
+            history_watermarks: HashMap::new(),
+            latest_visits: HashMap::new(),
+            extension_cfg: ExtensionBanConfig {
+                names: cfg.banned_extensions.names.iter().map(|n| n.to_lowercase()).collect(),
+                ..cfg.banned_extensions.clone()
+            },
+            extensions_last_run: None,
+            reported_extensions: HashSet::new(),
+            downloads_cfg: DownloadsConfig {
+                extensions: cfg.downloads.extensions.iter().map(|e| e.trim_start_matches('.').to_lowercase()).collect(),
+                names: cfg.downloads.names.iter().map(|n| n.to_lowercase()).collect(),
+                ..cfg.downloads.clone()
+            },
+            downloads_last_run: None,
+            downloads_since: Utc::now(),
+            reported_downloads: HashSet::new(),
+            hosts,
+            firewall: cfg.enforcement.firewall.then(|| FirewallBlocker::new(resolvers.clone())),
+            launch_blocker: cfg.enforcement.block_launch.then(LaunchBlocker::new).flatten(),
+            firewall_refresh: Duration::from_secs(cfg.enforcement.firewall_refresh_secs),
+            firewall_last_sync: None,
+            sniffer: cfg.dns_sniffer.enabled.then(|| DnsSniffer::start(&cfg.dns_sniffer)),
+            bypass_cfg: cfg.dns_bypass.clone(),
+            bypass_last_run: None,
+            doh_resolvers: None,
+            reported_bypass: HashSet::new(),
+            doh_policy_applied: false,
+            conn_cfg: cfg.connections.clone(),
+            conn_last_run: None,
+            banned_ips: HashMap::new(),
+            banned_ips_resolved: None,
+            conn_resolver: cfg.connections.enabled.then(|| DomainResolver::start(resolvers.clone())),
+            active_banned_conns: HashSet::new(),
+            bandwidth,
+            bandwidth_cfg: cfg.bandwidth.clone(),
+            bandwidth_last_run: None,
+            over_limit_since: HashMap::new(),
+            reported_bandwidth: HashSet::new(),
+            abuse_cfg: ResourceAbuseConfig {
+                allowed: cfg.resource_abuse.allowed.iter().map(|n| n.to_lowercase()).collect(),
+                ..cfg.resource_abuse.clone()
+            },
+            abuse_last_run: None,
+            hogging_since: HashMap::new(),
+            reported_hogs: HashSet::new(),
+            remote_cfg: RemoteAccessConfig {
+                allowed: cfg.remote_access.allowed.iter().map(|n| n.to_lowercase()).collect(),
+                ..cfg.remote_access.clone()
+            },
+            remote_last_run: None,
+            reported_remote: HashSet::new(),
+            share_cfg: ScreenShareConfig {
+                recorders: cfg.screen_share.recorders.iter().map(|n| n.to_lowercase()).collect(),
+                ..cfg.screen_share.clone()
+            },
+            share_last_run: None,
+            reported_capturers: HashSet::new(),
+            audio_cfg: AudioActivityConfig {
+                microphone_banned: cfg.audio.microphone_banned.iter().map(|n| n.to_lowercase()).collect(),
+                ..cfg.audio.clone()
+            },
+            audio_last_run: None,
+            reported_mic: HashSet::new(),
+            webcam_cfg: WebcamConfig {
+                enabled: cfg.webcam.enabled && cfg!(feature = "webcam"),
+                banned: cfg.webcam.banned.iter().map(|n| n.to_lowercase()).collect(),
+                ..cfg.webcam.clone()
+            },
+            webcam_last_run: None,
+            reported_webcam: HashSet::new(),
+            vpn_cfg: VpnProxyConfig {
+                processes: cfg.vpn_proxy.processes.iter().map(|n| n.to_lowercase()).collect(),
+                allowed_adapters: cfg.vpn_proxy.allowed_adapters.iter().map(|n| n.to_lowercase()).collect(),
+                ..cfg.vpn_proxy.clone()
+            },
+            vpn_last_run: None,
+            reported_vpn: HashSet::new(),
+            tamper,
+            enforcing: true,
+            enforcing_since: None,
+            detector_failures: Vec::new(),
+            last_scan: None,
+            scan_in_progress: None,
+            max_system_cpu: cfg.budget.max_system_cpu,
+            busy: false,
+            dedup_cooldown: Duration::from_secs(cfg.dedup.cooldown_secs),
+            recent_violations: HashMap::new(),
+            detector_intervals: cfg
+                .detector_intervals
+                .iter()
+                .filter(|(name, _)| {
+                    let known = DETECTORS.contains(&name.as_str());
+                    if !known {
+                        warn!("[monitor.detector_intervals]: no detector named {name:?} (known: {})", DETECTORS.join(", "));
+                    }
+                    known
+                })
+                .map(|(name, secs)| (name.clone(), Duration::from_secs(*secs)))
+                .collect(),
+            detector_last_run: HashMap::new(),
+            unavailable_detectors: tools.disabled_detectors().into_iter().collect(),
+            gpu_available: tools.available("gpu_usage"),
+            services_available: tools.available("remote_access_services"),
+        };
+        monitor.sync_hosts_file();
+        monitor.sync_launch_blocks();
+        monitor
+    }
+
+    /// Bring the managed hosts-file block in line with the current bans.
+    fn sync_hosts_file(&mut self) {
+        if !self.enforcing {
+            return;
+        }
+        let blocked = self.blocked_domains();
+        let Some(hosts) = self.hosts.as_mut() else {
+            return;
+        };
+        match hosts.apply(&blocked) {
+            Ok(_) => self.flush_dns(),
+            Err(e) => warn!("hosts-file blocking failed (agent needs admin rights): {e}"),
         }
     }
 
+    /// Bring the OS launch blocks in line with the current bans.
+    fn sync_launch_blocks(&mut self) {
+        if !self.enforcing {
+            return;
+        }
+        let blocked = self.blocked_processes();
+        let Some(launch) = self.launch_blocker.as_mut() else {
+            return;
+        };
+        if let Err(e) = launch.apply(&blocked) {
+            warn!("Launch blocking failed (agent needs admin rights): {e}");
+        }
+    }
+
+    /// Re-resolve banned domains when the ban list changed or the refresh
+    /// interval elapsed, and install the rules for the last lookup that
+    /// finished. Resolution runs on the blocker's own thread, so the
+    /// monitor lock is never held while waiting for DNS.
+    fn sync_firewall(&mut self) {
+        if !self.enforcing {
+            return;
+        }
+        let blocked = self.blocked_domains();
+        let Some(firewall) = self.firewall.as_mut() else {
+            return;
+        };
+        if self.firewall_last_sync.is_none_or(|t| t.elapsed() >= self.firewall_refresh) {
+            self.firewall_last_sync = Some(Instant::now());
+            firewall.request(&blocked);
+        }
+        if let Err(e) = firewall.poll() {
+            warn!("Firewall blocking failed (agent needs admin rights): {e}");
+        }
+    }
+
+    /// Switch ban enforcement on or off (lesson schedule). Turning it off
+    /// lifts the hosts / firewall blocks; turning it back on re-applies them.
+    pub fn set_enforcing(&mut self, on: bool) {
+        if self.enforcing == on {
+            return;
+        }
+        self.enforcing = on;
+        if on {
+            info!("📚 Class hours — enforcement resumed");
+            self.enforcing_since = Some(Utc::now());
+            self.firewall_last_sync = None;
+            self.sync_hosts_file();
+            self.sync_launch_blocks();
+        } else {
+            info!("☕ Outside class hours — enforcement relaxed");
+            self.lift_blocks();
+        }
+    }
+
+    fn lift_blocks(&mut self) {
+        if let Some(hosts) = self.hosts.as_mut() {
+            hosts.clear();
+            self.flush_dns();
+        }
+        if let Some(firewall) = self.firewall.as_mut() {
+            firewall.clear();
+        }
+        if let Some(launch) = self.launch_blocker.as_mut() {
+            launch.clear();
+        }
+    }
+
+    /// Undo enforcement side-effects before the agent exits.
+    pub fn shutdown(&mut self) {
+        self.lift_blocks();
+        if let Some(tamper) = self.tamper.as_mut() {
+            tamper.mark_clean_exit();
+        }
+    }
+
+    /// Build a violation for this machine with all optional details unset.
+    fn violation(&self, target: String, kind: ViolationKind, action_taken: bool) -> Violation {
+        Violation {
+            hostname: self.hostname.clone(),
+            target,
+            kind,
+            action_taken,
+            username: self.username.clone(),
+            timestamp: Utc::now(),
+            url: None,
+            visited_at: None,
+            detail: None,
+            process: None,
+            occurrences: 1,
+            last_seen: None,
+            usage: None,
+            exe: None,
+        }
+    }
+
+    /// Fold repeats of a violation reported within the dedup cooldown into
+    /// its first occurrence: they come back as that record with
+    /// `occurrences` bumped, for the caller to update instead of re-adding.
+    fn dedup(&mut self, found: Vec<Violation>) -> Vec<Violation> {
+        if self.dedup_cooldown.is_zero() {
+            return found;
+        }
+        let cooldown = self.dedup_cooldown;
+        self.recent_violations.retain(|_, (since, _)| since.elapsed() < cooldown);
+        found
+            .into_iter()
+            .map(|v| {
+                let key = format!("{:?}|{}|{:?}|{}", v.kind, v.target, v.detail, v.action_taken);
+                match self.recent_violations.get_mut(&key) {
+                    Some((_, first)) => {
+                        first.occurrences += 1;
+                        first.last_seen = Some(v.timestamp);
+                        first.clone()
+                    }
+                    None => {
+                        self.recent_violations.insert(key, (Instant::now(), v.clone()));
+                        v
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Dry-run: describe what `update_bans(bans)` would change, including
+    /// processes from the last scan that would be killed. Nothing is applied.
+    pub fn preview_bans(&self, bans: &BanConfig) -> BanDiff {
+        let diff = |new: &std::collections::BTreeSet<String>, old: &HashSet<String>| {
+            let mut v: Vec<String> = new.iter().filter(|n| !old.contains(*n)).cloned().collect();
+            v.sort();
+            v
+        };
+        let gone = |new: &std::collections::BTreeSet<String>, old: &HashSet<String>| {
+            let mut v: Vec<String> = old.iter().filter(|o| !new.contains(*o)).cloned().collect();
+            v.sort();
+            v
+        };
+
+        let fingerprint = bans.fingerprint();
+        let bans = &self.for_login(bans);
+        let added_processes = diff(&bans.banned_processes, &self.banned_procs);
+        let mut would_kill: Vec<String> = self
+            .sys
+            .processes()
+            .values()
+            .map(|p| p.name().to_string_lossy().to_lowercase())
+            .filter(|name| {
+                let clean = name.strip_suffix(".exe").unwrap_or(name);
+                added_processes
+                    .iter()
+                    .any(|a| (a == clean || a == name) && self.proc_action(a) != BanAction::Log)
+            })
+            .collect();
+        would_kill.sort();
+        would_kill.dedup();
+
+        BanDiff {
+            fingerprint,
+            added_processes,
+            removed_processes: gone(&bans.banned_processes, &self.banned_procs),
+            added_domains: diff(&bans.banned_domains, &self.banned_domains),
+            removed_domains: gone(&bans.banned_domains, &self.banned_domains),
+            would_kill,
+            pending_confirmation: false,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Kill processes by name (case-insensitive, `.exe` optional) or PID
+    /// on request, confirmed like banned ones. Returns (PID, name,
+    /// stopped) for each one found. Blocking.
+    pub fn kill_matching(&mut self, name: Option<&str>, pid: Option<u32>) -> Vec<(u32, String, bool)> {
+        self.sys.refresh_processes(sysinfo::ProcessesToUpdate::All);
+        let wanted = name.map(|n| {
+            let n = n.to_lowercase();
+            n.strip_suffix(".exe").unwrap_or(&n).to_string()
+        });
+        let own_pid = std::process::id();
+        let found: Vec<(sysinfo::Pid, u64, String)> = self
+            .sys
+            .processes()
+            .iter()
+            .filter(|(p, _)| p.as_u32() != own_pid)
+            .filter(|(p, proc)| {
+                let proc_name = proc.name().to_string_lossy().to_lowercase();
+                pid == Some(p.as_u32())
+                    || wanted.as_deref().is_some_and(|w| proc_name.strip_suffix(".exe").unwrap_or(&proc_name) == w)
+            })
+            .map(|(p, proc)| (*p, proc.start_time(), proc.name().to_string_lossy().to_string()))
+            .collect();
+        let targets: Vec<_> = found.iter().map(|(p, start, _)| (*p, *start)).collect();
+        let outcomes = self.kill_verified(&targets);
+        found.into_iter().map(|(p, _, name)| (p.as_u32(), name, outcomes[&p].stopped())).collect()
+    }
+
+    /// The active banned domain `url` points at, if any.
+    pub fn banned_domain_for(&self, url: &str) -> Option<String> {
+        let host = browser::url_host(url)?;
+        self.banned_domains.iter().find(|d| browser::host_matches(&host, d)).cloned()
+    }
+
     /// Hot-reload ban lists from centrally-managed config.
-    pub fn update_bans(&mut self, banned_procs: Vec<String>, banned_domains: Vec<String>) {
-        self.banned_procs = banned_procs.iter().map(|n| n.to_lowercase()).collect();
-        self.banned_domains = banned_domains.iter().map(|d| d.to_lowercase()).collect();
+    pub fn update_bans(&mut self, bans: &BanConfig) {
+        self.base_bans = bans.clone();
+        self.apply_bans();
         info!("🔄 Ban lists updated: {} processes, {} domains",
             self.banned_procs.len(), self.banned_domains.len());
     }
 
+    fn proc_action(&self, name: &str) -> BanAction {
+        self.proc_actions.get(name).copied().unwrap_or(self.default_proc_action)
+    }
+
+    fn domain_action(&self, domain: &str) -> BanAction {
+        self.domain_actions.get(domain).copied().unwrap_or(self.default_domain_action)
+    }
+
+    /// Banned domains with the `kill` action — the ones hosts / firewall block.
+    fn blocked_domains(&self) -> HashSet<String> {
+        self.banned_domains
+            .iter()
+            .filter(|d| self.domain_action(d) == BanAction::Kill)
+            .cloned()
+            .collect()
+    }
+
+    /// Banned processes with the `kill` action — the ones launch blocking stops.
+    fn blocked_processes(&self) -> HashSet<String> {
+        self.banned_procs
+            .iter()
+            .filter(|p| self.proc_action(p) == BanAction::Kill)
+            .cloned()
+            .collect()
+    }
+
+    /// Replace the entries contributed by shared categories.
+    pub fn set_category_bans(&mut self, bans: &BanConfig) {
+        self.category_bans = bans.clone();
+        self.apply_bans();
+    }
+
+    /// `bans` plus the category entries, as they apply to the current login.
+    fn for_login(&self, bans: &BanConfig) -> BanConfig {
+        let mut bans = bans.clone();
+        bans.banned_processes.extend(self.category_bans.banned_processes.iter().cloned());
+        bans.banned_domains.extend(self.category_bans.banned_domains.iter().cloned());
+        match self.login.as_deref().and_then(|l| profiles::matching(&self.user_profiles, l)) {
+            Some(profile) => profiles::apply(&bans, profile),
+            None => bans,
+        }
+    }
+
+    /// Recompute the effective lists and push them to hosts / firewall.
+    fn apply_bans(&mut self) {
+        let effective = self.for_login(&self.base_bans);
+        self.banned_procs = effective.banned_processes.into_iter().collect();
+        self.banned_domains = effective.banned_domains.into_iter().collect();
+        self.sync_hosts_file();
+        self.sync_launch_blocks();
+        self.clean_procs.clear();
+        self.proc_generation = 0;
+        self.firewall_last_sync = None;
+        self.banned_ips_resolved = None;
+    }
+
+    /// Look up the console user and switch to their profile when it changed.
+    fn refresh_login(&mut self) {
+        let needed = !self.user_profiles.is_empty() || self.scan_scope == ScanScope::Session;
+        if !needed || self.login_last_check.is_some_and(|t| t.elapsed() < LOGIN_CHECK_INTERVAL) {
+            return;
+        }
+        self.login_last_check = Some(Instant::now());
+        let login = profiles::logged_in_user();
+        if login == self.login {
+            return;
+        }
+        let profile = login.as_deref().and_then(|l| profiles::matching(&self.user_profiles, l));
+        info!(
+            "👤 Console user {} — {}",
+            login.as_deref().unwrap_or("(none)"),
+            profile.map_or("default rules".to_string(), |p| format!("profile {:?}", p.name))
+        );
+        self.login = login;
+        if self.scan_scope == ScanScope::Session {
+            self.session_uid = self.login.as_deref().and_then(profiles::account_id);
+            if self.login.is_some() && self.session_uid.is_none() {
+                warn!("Console user's account not found; scanning every user's processes");
+            }
+        }
+        self.apply_bans();
+    }
+
+    /// Whether the process detectors should look at `proc`.
+    fn in_scope(&self, proc: &sysinfo::Process) -> bool {
+        if self.scan_scope == ScanScope::All {
+            return true;
+        }
+        match (&self.login, &self.session_uid) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(_), Some(uid)) => proc.user_id() == Some(uid),
+        }
+    }
+
     // ── Process scanning ────────────────────────────────────────
 
-    /// Refresh process list, kill banned ones, return violations.
+    /// Report launches refused by the OS launch blocks since the last scan.
+    pub fn scan_blocked_launches(&mut self) -> Vec<Violation> {
+        let Some(launch) = &self.launch_blocker else {
+            return Vec::new();
+        };
+        launch
+            .drain()
+            .into_iter()
+            .map(|attempt| {
+                let mut v = self.violation(attempt.name, ViolationKind::Process, true);
+                v.detail = Some(format!("launch blocked: {}", attempt.path));
+                v.timestamp = attempt.at;
+                v
+            })
+            .collect()
+    }
+
+    /// Refresh process list, kill banned ones, return violations. Only
+    /// processes that started (or changed name) since the last pass are
+    /// matched, and an unchanged process table is skipped altogether
+    /// unless a warned or unkillable ban is still running.
     pub fn scan_processes(&mut self) -> Vec<Violation> {
-        self.sys.refresh_processes(sysinfo::ProcessesToUpdate::All);
+        // Like refresh_processes(), plus command lines (read once per
+        // process) for the proxy-flag check in scan_vpn_proxy.
+        self.sys.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::All,
+            sysinfo::ProcessRefreshKind::new()
+                .with_memory()
+                .with_cpu()
+                .with_disk_usage()
+                .with_exe(sysinfo::UpdateKind::OnlyIfNotSet)
+                .with_cmd(sysinfo::UpdateKind::OnlyIfNotSet)
+                .with_user(sysinfo::UpdateKind::OnlyIfNotSet),
+        );
+
+        // Order-independent, so the process map's iteration order doesn't matter
+        let generation = self.sys.processes().values().fold(0u64, |acc, p| {
+            let mut h = DefaultHasher::new();
+            (p.pid(), p.start_time(), p.name()).hash(&mut h);
+            acc.wrapping_add(h.finish())
+        });
+        if generation == self.proc_generation && !self.proc_pending {
+            return Vec::new();
+        }
+        self.proc_generation = generation;
+        self.proc_pending = false;
+        let processes = self.sys.processes();
+        self.clean_procs.retain(|pid, _| processes.contains_key(pid));
 
         let mut violations = Vec::new();
+        let mut seen = HashSet::new();
+        // (index in violations, PID, start time) to kill once matching is done
+        let mut kills = Vec::new();
+        let cores = self.sys.cpus().len().max(1) as f32;
+        // Only asked for once something banned turns up
+        let mut gpu_usage: Option<HashMap<u32, f32>> = None;
+        let gpu_available = self.gpu_available;
+        let mut usage = |proc: &sysinfo::Process| {
+            let gpu = gpu_usage.get_or_insert_with(|| if gpu_available { gpu::usage_by_pid() } else { HashMap::new() });
+            ProcessUsage {
+                pid: proc.pid().as_u32(),
+                cpu_percent: proc.cpu_usage() / cores,
+                memory_mb: proc.memory() / 1_048_576,
+                running_secs: proc.run_time(),
+                gpu_percent: (!gpu.is_empty()).then(|| gpu.get(&proc.pid().as_u32()).copied().unwrap_or(0.0)),
+            }
+        };
 
         for (pid, proc) in self.sys.processes() {
             let name = proc.name().to_string_lossy().to_lowercase();
+            if self.clean_procs.get(pid).is_some_and(|(start, n)| *start == proc.start_time() && *n == name) {
+                continue;
+            }
             // Strip .exe suffix for matching
             let name_clean = name.strip_suffix(".exe").unwrap_or(&name);
 
-            if self.banned_procs.contains(name_clean) || self.banned_procs.contains(&*name) {
-                info!("🚫 Banned process detected: {} (PID {})", name, pid);
+            // By name first, else by where it runs from
+            let hit = if !self.in_scope(proc) {
+                None
+            } else if self.banned_procs.contains(name_clean) {
+                Some((self.proc_action(name_clean), None))
+            } else if self.banned_procs.contains(&*name) {
+                Some((self.proc_action(&name), None))
+            } else {
+                proc.exe().and_then(|exe| self.path_bans.find(exe)).map(|(pattern, action)| (action, Some(pattern)))
+            };
+            let Some((action, path_rule)) = hit else {
+                self.clean_procs.insert(*pid, (proc.start_time(), name.clone()));
+                continue;
+            };
+            seen.insert(*pid);
 
-                let killed = proc.kill();
-                if killed {
-                    info!("   ✅ Killed PID {pid}");
-                } else {
-                    warn!("   ⚠️  Failed to kill PID {pid}");
+            let exe = proc.exe().map(|p| p.display().to_string());
+            let tag = |v: &mut Violation| {
+                v.exe = exe.clone();
+                if let Some(pattern) = path_rule {
+                    let why = format!("banned path {pattern}");
+                    v.detail = Some(match v.detail.take() {
+                        Some(detail) => format!("{why}, {detail}"),
+                        None => why,
+                    });
+                }
+            };
+            let warn_grace = if path_rule.is_some() { self.path_warn_grace } else { self.warn_grace };
+            let warned_at = self.warned_procs.get(pid).copied();
+            self.proc_pending |= action != BanAction::Log;
+            match action {
+                BanAction::Log => {
+                    if self.logged_procs.insert(*pid) {
+                        info!("👀 Banned process running (log only): {} (PID {})", name, pid);
+                        let mut v = self.violation(name.clone(), ViolationKind::Process, false);
+                        v.detail = Some("log only".into());
+                        v.usage = Some(usage(proc));
+                        tag(&mut v);
+                        violations.push(v);
+                    }
+                }
+                BanAction::Warn if warned_at.is_none() => {
+                    info!("⚠️  Banned process detected, warning the user: {} (PID {})", name, pid);
+                    let grace = warn_grace.as_secs();
+                    notify::warn_user(
+                        "nishack",
+                        &format!("Программа {name} запрещена на уроке и будет закрыта через {grace} с. Сохраните работу."),
+                        grace,
+                    );
+                    self.warned_procs.insert(*pid, Instant::now());
+                    let mut v = self.violation(name.clone(), ViolationKind::Process, false);
+                    v.detail = Some(format!("warned, closing in {grace}s"));
+                    v.usage = Some(usage(proc));
+                    tag(&mut v);
+                    violations.push(v);
+                }
+                BanAction::Warn if warned_at.is_some_and(|t| t.elapsed() < warn_grace) => {}
+                action => {
+                    info!("🚫 Banned process detected: {} (PID {})", name, pid);
+                    // action_taken is filled in once the kill is confirmed
+                    let mut v = self.violation(name.clone(), ViolationKind::Process, false);
+                    if action == BanAction::Warn {
+                        v.detail = Some("closed after warning".into());
+                    }
+                    v.usage = Some(usage(proc));
+                    tag(&mut v);
+                    kills.push((violations.len(), *pid, proc.start_time()));
+                    violations.push(v);
                 }
+            }
+        }
+        self.warned_procs.retain(|pid, _| seen.contains(pid));
+        self.logged_procs.retain(|pid| seen.contains(pid));
 
-                violations.push(Violation {
-                    hostname: self.hostname.clone(),
-                    target: name.clone(),
-                    kind: ViolationKind::Process,
-                    action_taken: killed,
-                    username: self.username.clone(),
-                    timestamp: Utc::now(),
+        let targets: Vec<_> = kills.iter().map(|&(_, pid, start)| (pid, start)).collect();
+        let outcomes = self.kill_verified(&targets);
+        for (index, pid, _) in kills {
+            let outcome = outcomes[&pid];
+            if outcome.stopped() {
+                info!("   ✅ Killed PID {pid}");
+            } else {
+                warn!("   ⚠️  Failed to kill PID {pid}");
+            }
+            let v = &mut violations[index];
+            v.action_taken = outcome.stopped();
+            if let Some(note) = outcome.note() {
+                v.detail = Some(match v.detail.take() {
+                    Some(detail) => format!("{detail}, {note}"),
+                    None => note.to_string(),
                 });
             }
         }
@@ -101,18 +907,431 @@ impl Monitor {
         violations
     }
 
+    /// Kill `targets` (PID and start time when matched) and, unless
+    /// `kill_confirm` is zero, make sure they died: after the wait, any
+    /// still running with the same start time is force-killed and
+    /// checked again. A PID that now has another start time was reused
+    /// by a new process — the original is gone and the new one is left
+    /// alone. Blocking for up to twice `kill_confirm`.
+    fn kill_verified(&mut self, targets: &[(sysinfo::Pid, u64)]) -> HashMap<sysinfo::Pid, KillOutcome> {
+        let mut outcomes = HashMap::new();
+        for &(pid, start) in targets {
+            let Some(proc) = self.sys.process(pid).filter(|p| p.start_time() == start) else {
+                outcomes.insert(pid, KillOutcome::Stopped);
+                continue;
+            };
+            // SIGTERM first where there is one; Windows only has TerminateProcess
+            let sent = proc.kill_with(sysinfo::Signal::Term).unwrap_or_else(|| proc.kill());
+            let outcome = if sent { KillOutcome::Stopped } else { KillOutcome::Survived };
+            outcomes.insert(pid, outcome);
+        }
+        if self.kill_confirm.is_zero() || outcomes.is_empty() {
+            return outcomes;
+        }
+
+        std::thread::sleep(self.kill_confirm);
+        let mut forced = Vec::new();
+        for &(pid, start) in targets {
+            if self.still_running(pid, start) {
+                warn!("   ⚠️  PID {pid} survived the kill — forcing");
+                self.force_kill(pid);
+                forced.push((pid, start));
+            }
+        }
+        if forced.is_empty() {
+            return outcomes;
+        }
+
+        std::thread::sleep(self.kill_confirm);
+        for (pid, start) in forced {
+            let outcome = if self.still_running(pid, start) { KillOutcome::Survived } else { KillOutcome::ForceKilled };
+            outcomes.insert(pid, outcome);
+        }
+        outcomes
+    }
+
+    /// Whether `pid` is still the process that started at `start`.
+    fn still_running(&mut self, pid: sysinfo::Pid, start: u64) -> bool {
+        let updated = self.sys.refresh_processes_specifics(sysinfo::ProcessesToUpdate::Some(&[pid]), sysinfo::ProcessRefreshKind::new());
+        updated > 0
+            && self
+                .sys
+                .process(pid)
+                .is_some_and(|p| p.start_time() == start && p.status() != sysinfo::ProcessStatus::Zombie)
+    }
+
+    fn force_kill(&self, pid: sysinfo::Pid) {
+        if cfg!(target_os = "windows") {
+            let _ = silent_cmd("taskkill").args(["/F", "/T", "/PID", &pid.to_string()]).output();
+        } else if let Some(proc) = self.sys.process(pid) {
+            proc.kill_with(sysinfo::Signal::Kill);
+        }
+    }
+
+    /// Warn the user about visits to `warn` domains, at most once per
+    /// grace period per domain.
+    fn warn_about_domains(&mut self, found: &[Violation]) {
+        for v in found.iter().filter(|v| v.kind == ViolationKind::Domain) {
+            if self.domain_action(&v.target) != BanAction::Warn
+                || self.warned_domains.get(&v.target).is_some_and(|t| t.elapsed() < self.warn_grace)
+            {
+                continue;
+            }
+            self.warned_domains.insert(v.target.clone(), Instant::now());
+            notify::warn_user(
+                "nishack",
+                &format!("Сайт {} запрещён на уроке. Закройте вкладку.", v.target),
+                self.warn_grace.as_secs(),
+            );
+        }
+    }
+
+    // ── CPU / GPU abuse ─────────────────────────────────────────
+
+    /// Flag processes that sustain high CPU or GPU usage and aren't
+    /// known-good — catches miners and games however they're named.
+    /// Relies on the process refresh done by `scan_processes`.
+    pub fn scan_resource_abuse(&mut self) -> Vec<Violation> {
+        if !self.abuse_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.abuse_cfg.interval);
+        if self.abuse_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.abuse_last_run = Some(Instant::now());
+
+        let cores = self.sys.cpus().len().max(1) as f32;
+        let gpu_usage = if self.gpu_available { gpu::usage_by_pid() } else { HashMap::new() };
+        let own_pid = sysinfo::Pid::from_u32(std::process::id());
+
+        let mut hogs = Vec::new();
+        for (pid, proc) in self.sys.processes() {
+            if *pid == own_pid || !self.in_scope(proc) {
+                continue;
+            }
+            let name = proc.name().to_string_lossy().to_lowercase();
+            let name_clean = name.strip_suffix(".exe").unwrap_or(&name);
+            if self.abuse_cfg.allowed.iter().any(|a| a == name_clean) {
+                continue;
+            }
+            let cpu = proc.cpu_usage() / cores;
+            let gpu = gpu_usage.get(&pid.as_u32()).copied().unwrap_or(0.0);
+            if cpu > self.abuse_cfg.cpu_percent || gpu > self.abuse_cfg.gpu_percent {
+                hogs.push((*pid, name, cpu, gpu));
+            }
+        }
+        self.hogging_since.retain(|pid, _| hogs.iter().any(|h| h.0 == *pid));
+        self.reported_hogs.retain(|pid| self.hogging_since.contains_key(pid));
+
+        let sustained = Duration::from_secs(self.abuse_cfg.sustained_secs);
+        let mut violations = Vec::new();
+        for (pid, name, cpu, gpu) in hogs {
+            let since = *self.hogging_since.entry(pid).or_insert_with(Instant::now);
+            if since.elapsed() < sustained || !self.reported_hogs.insert(pid) {
+                continue;
+            }
+            warn!("🔥 {name} (PID {pid}) sustaining CPU {cpu:.0}% / GPU {gpu:.0}%");
+            let mut v = self.violation(name, ViolationKind::ResourceAbuse, false);
+            v.detail = Some(format!(
+                "CPU {cpu:.0}%, GPU {gpu:.0}% for {}s+ (PID {pid})",
+                since.elapsed().as_secs()
+            ));
+            violations.push(v);
+        }
+        violations
+    }
+
+    // ── Remote-access tools ─────────────────────────────────────
+
+    /// Look for remote-access tools by process, running service and
+    /// listening port. A tool is reported when it shows up, not every scan.
+    pub fn scan_remote_access(&mut self) -> Vec<Violation> {
+        if !self.remote_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.remote_cfg.interval);
+        if self.remote_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.remote_last_run = Some(Instant::now());
+
+        let services = if self.services_available { remote_access::running_services() } else { HashSet::new() };
+        let listeners = netstat::listening_tcp();
+
+        let mut present = HashSet::new();
+        let mut violations = Vec::new();
+        for tool in remote_access::TOOLS {
+            if self.remote_cfg.allowed.contains(&tool.name.to_lowercase()) {
+                continue;
+            }
+
+            let mut evidence = Vec::new();
+            let mut pids = Vec::new();
+            for (pid, proc) in self.sys.processes().iter().filter(|(_, p)| self.in_scope(p)) {
+                let name = proc.name().to_string_lossy().to_lowercase();
+                let name_clean = name.strip_suffix(".exe").unwrap_or(&name);
+                if tool.processes.contains(&name_clean) {
+                    evidence.push(format!("process {name} (PID {pid})"));
+                    pids.push((*pid, proc.start_time()));
+                }
+            }
+            let process_found = !evidence.is_empty();
+            let killed = self.remote_cfg.kill && self.kill_verified(&pids).values().all(|o| o.stopped());
+            evidence.extend(
+                tool.services
+                    .iter()
+                    .filter(|s| services.contains(**s))
+                    .map(|s| format!("service {s}")),
+            );
+            evidence.extend(
+                listeners
+                    .iter()
+                    .filter(|l| tool.ports.contains(&l.local.port()))
+                    .map(|l| format!("listening on {} (PID {})", l.local.port(), l.pid)),
+            );
+            if evidence.is_empty() {
+                continue;
+            }
+
+            present.insert(tool.name);
+            if self.reported_remote.contains(tool.name) {
+                continue;
+            }
+            warn!("🖥️  Remote-access tool {} found: {}", tool.name, evidence.join(", "));
+            let mut v = self.violation(tool.name.to_string(), ViolationKind::RemoteAccess, killed && process_found);
+            v.detail = Some(evidence.join(", "));
+            violations.push(v);
+        }
+        self.reported_remote = present;
+
+        violations
+    }
+
+    // ── Screen recording / sharing ──────────────────────────────
+
+    /// Report known recorders and (Windows) programs capturing the screen
+    /// right now, optionally killing them. Reported when they start.
+    pub fn scan_screen_share(&mut self) -> Vec<Violation> {
+        if !self.share_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.share_cfg.interval);
+        if self.share_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.share_last_run = Some(Instant::now());
+
+        let capturing = screen_capture::active_capturers();
+        let own_pid = sysinfo::Pid::from_u32(std::process::id());
+
+        // name → (reason, PIDs and start times)
+        let mut found: HashMap<String, (&str, Vec<_>)> = HashMap::new();
+        for (pid, proc) in self.sys.processes() {
+            if *pid == own_pid || !self.in_scope(proc) {
+                continue;
+            }
+            let name = proc.name().to_string_lossy().to_lowercase();
+            let name_clean = name.strip_suffix(".exe").unwrap_or(&name).to_string();
+            let reason = if capturing.contains(&name_clean) {
+                "capturing the screen"
+            } else if self.share_cfg.recorders.contains(&name_clean) {
+                "screen recorder running"
+            } else {
+                continue;
+            };
+            found.entry(name_clean).or_insert((reason, Vec::new())).1.push((*pid, proc.start_time()));
+        }
+
+        let outcomes = if self.share_cfg.kill {
+            let targets: Vec<_> = found.values().flat_map(|(_, pids)| pids.iter().copied()).collect();
+            self.kill_verified(&targets)
+        } else {
+            HashMap::new()
+        };
+        let mut violations = Vec::new();
+        for (name, (reason, pids)) in &found {
+            if self.reported_capturers.contains(name) {
+                continue;
+            }
+            let pids: Vec<_> = pids.iter().map(|(pid, _)| *pid).collect();
+            warn!("📹 {name} {reason} (PIDs {pids:?})");
+            let killed = self.share_cfg.kill && pids.iter().all(|pid| outcomes[pid].stopped());
+            let mut v = self.violation(name.clone(), ViolationKind::ScreenShare, killed);
+            v.detail = Some(format!("{reason}, PID {}", pids.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ")));
+            violations.push(v);
+        }
+        self.reported_capturers = found.into_keys().collect();
+
+        violations
+    }
+
+    // ── Microphone use ──────────────────────────────────────────
+
+    /// Report banned programs (and `microphone_banned` ones) recording
+    /// from the microphone, each when it starts.
+    pub fn scan_microphone(&mut self) -> Vec<Violation> {
+        if !self.audio_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.audio_cfg.interval);
+        if self.audio_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.audio_last_run = Some(Instant::now());
+
+        let recording: HashSet<String> = audio_sessions::active()
+            .into_iter()
+            .filter(|s| s.direction == "capture")
+            .map(|s| s.process.strip_suffix(".exe").unwrap_or(&s.process).to_string())
+            .filter(|name| self.banned_procs.contains(name) || self.audio_cfg.microphone_banned.contains(name))
+            .collect();
+
+        let mut violations = Vec::new();
+        for name in recording.difference(&self.reported_mic) {
+            warn!("🎙️  {name} is using the microphone");
+            let mut v = self.violation(name.clone(), ViolationKind::Microphone, false);
+            v.detail = Some("recording from the microphone".into());
+            violations.push(v);
+        }
+        self.reported_mic = recording;
+        violations
+    }
+
+    // ── Webcam use ──────────────────────────────────────────────
+
+    /// Report banned programs (and `[monitor.webcam] banned` ones) using
+    /// the camera, each when it starts.
+    pub fn scan_webcam(&mut self) -> Vec<Violation> {
+        if !self.webcam_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.webcam_cfg.interval);
+        if self.webcam_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.webcam_last_run = Some(Instant::now());
+
+        #[cfg(feature = "webcam")]
+        let users = webcam::users();
+        #[cfg(not(feature = "webcam"))]
+        let users: Vec<String> = Vec::new();
+        let filming: HashSet<String> = users
+            .into_iter()
+            .filter(|name| self.banned_procs.contains(name) || self.webcam_cfg.banned.contains(name))
+            .collect();
+
+        let mut violations = Vec::new();
+        for name in filming.difference(&self.reported_webcam) {
+            warn!("📷 {name} is using the camera");
+            let mut v = self.violation(name.clone(), ViolationKind::Webcam, false);
+            v.detail = Some("using the camera".into());
+            violations.push(v);
+        }
+        self.reported_webcam = filming;
+        violations
+    }
+
+    // ── VPN / proxy detection ───────────────────────────────────
+
+    /// Report VPN adapters, proxy settings (system, Firefox, Chromium
+    /// `--proxy-server`) and VPN client processes, each when it appears.
+    /// Optionally switches the system proxy back off.
+    pub fn scan_vpn_proxy(&mut self) -> Vec<Violation> {
+        if !self.vpn_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.vpn_cfg.interval);
+        if self.vpn_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.vpn_last_run = Some(Instant::now());
+
+        // (target, detail, action_taken)
+        let mut findings: Vec<(String, String, bool)> = Vec::new();
+
+        for adapter in vpn::active_vpn_adapters() {
+            if self.vpn_cfg.allowed_adapters.contains(&adapter.name.to_lowercase()) {
+                continue;
+            }
+            findings.push((adapter.name, format!("VPN adapter up: {}", adapter.description), false));
+        }
+
+        let proxies = vpn::proxy_settings();
+        let system_proxy = proxies.iter().any(|p| p.starts_with("system"));
+        let reset = system_proxy && self.vpn_cfg.reset_proxy && vpn::reset_system_proxy();
+        if reset {
+            info!("   ✅ System proxy switched off");
+        }
+        for proxy in proxies {
+            let reset = reset && proxy.starts_with("system");
+            findings.push(("proxy".into(), proxy, reset));
+        }
+
+        for (pid, proc) in self.sys.processes().iter().filter(|(_, p)| self.in_scope(p)) {
+            let name = proc.name().to_string_lossy().to_lowercase();
+            let name_clean = name.strip_suffix(".exe").unwrap_or(&name);
+            if self.vpn_cfg.processes.iter().any(|p| p == name_clean) {
+                findings.push((name.clone(), format!("VPN client running (PID {pid})"), false));
+            }
+            // Chromium browsers take a proxy straight from the command line
+            if let Some(arg) = proc.cmd().iter().map(|a| a.to_string_lossy()).find(|a| {
+                a.starts_with("--proxy-server") || a.starts_with("--proxy-pac-url")
+            }) {
+                findings.push((name.clone(), format!("browser started with {arg} (PID {pid})"), false));
+            }
+        }
+
+        let mut present = HashSet::new();
+        let mut violations = Vec::new();
+        for (target, detail, action_taken) in findings {
+            let key = format!("{target}|{detail}");
+            if !present.insert(key.clone()) || self.reported_vpn.contains(&key) {
+                continue;
+            }
+            warn!("🕳️  {target}: {detail}");
+            let mut v = self.violation(target, ViolationKind::VpnProxy, action_taken);
+            v.detail = Some(detail);
+            violations.push(v);
+        }
+        self.reported_vpn = present;
+
+        violations
+    }
+
+    // ── Tamper detection ────────────────────────────────────────
+
+    /// Report unexpected agent restarts, changed agent files and a killed
+    /// watchdog (see `tamper.rs`).
+    pub fn scan_tamper(&mut self) -> Vec<Violation> {
+        let Some(tamper) = self.tamper.as_mut() else {
+            return Vec::new();
+        };
+        let findings = tamper.check();
+        findings
+            .into_iter()
+            .map(|detail| {
+                warn!("🛡️  Tamper: {detail}");
+                let mut v = self.violation("nishack agent".into(), ViolationKind::Tamper, false);
+                v.detail = Some(detail);
+                v
+            })
+            .collect()
+    }
+
     // ── DNS cache scanning (Cross-platform) ─────────────────────
 
     /// Parse DNS cache output for banned domains.
     /// Windows: ipconfig /displaydns
-    /// macOS/Linux: dscacheutil -cachedump or parse browser history/network logs
+    /// macOS: dscacheutil -cachedump
+    /// Linux: systemd-resolved / nscd caches (see `dns_cache.rs`)
     pub fn scan_dns_cache(&self) -> Vec<Violation> {
-        let output = if cfg!(target_os = "windows") {
+        let cache = if cfg!(target_os = "windows") {
             match silent_cmd("ipconfig")
                 .arg("/displaydns")
                 .output()
             {
-                Ok(o) => o,
+                Ok(o) => String::from_utf8_lossy(&o.stdout).into_owned(),
                 Err(e) => {
                     warn!("Could not run ipconfig /displaydns: {e}");
                     return Vec::new();
@@ -125,19 +1344,24 @@ impl Monitor {
                 .arg("-entries")
                 .output()
             {
-                Ok(o) => o,
+                Ok(o) => String::from_utf8_lossy(&o.stdout).into_owned(),
                 Err(e) => {
                     warn!("Could not run dscacheutil (macOS DNS cache): {e}");
                     return Vec::new();
                 }
             }
         } else {
-            // Linux - no standard DNS cache command, skip
-            warn!("DNS cache scanning not supported on this platform");
-            return Vec::new();
+            // Linux: systemd-resolved / nscd, whichever caches here
+            match dns_cache::dump() {
+                Some(dump) => dump,
+                None => {
+                    warn!("No readable DNS cache (systemd-resolved / nscd) on this machine");
+                    return Vec::new();
+                }
+            }
         };
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_lowercase();
+        let stdout = cache.to_lowercase();
         let mut violations = Vec::new();
         let mut seen = HashSet::new();
 
@@ -145,14 +1369,8 @@ impl Monitor {
             if stdout.contains(domain.as_str()) && seen.insert(domain.clone()) {
                 info!("🌐 Banned domain found in DNS cache: {domain}");
 
-                violations.push(Violation {
-                    hostname: self.hostname.clone(),
-                    target: domain.clone(),
-                    kind: ViolationKind::Domain,
-                    action_taken: false, // DNS flush happens below
-                    username: self.username.clone(),
-                    timestamp: Utc::now(),
-                });
+                // action_taken stays false — DNS flush happens below
+                violations.push(self.violation(domain.clone(), ViolationKind::Domain, false));
             }
         }
 
@@ -164,6 +1382,207 @@ impl Monitor {
         violations
     }
 
+    // ── Live DNS sniffing ───────────────────────────────────────
+
+    /// Match queries captured since the last scan against banned domains.
+    /// One violation per banned domain per scan, naming the exact query.
+    pub fn scan_sniffed_queries(&self) -> Vec<Violation> {
+        let Some(sniffer) = &self.sniffer else {
+            return Vec::new();
+        };
+
+        let mut violations = Vec::new();
+        let mut seen = HashSet::new();
+        for q in sniffer.drain() {
+            let Some(domain) = self
+                .banned_domains
+                .iter()
+                .find(|d| browser::host_matches(&q.name, d))
+            else {
+                continue;
+            };
+            if !seen.insert(domain.clone()) {
+                continue;
+            }
+
+            let how = match q.source {
+                QuerySource::Dns => "DNS query",
+                QuerySource::TlsSni => "TLS SNI",
+            };
+            info!("🕵️  Banned domain on the wire: {} ({how})", q.name);
+            let mut v = self.violation(domain.clone(), ViolationKind::Domain, false);
+            v.detail = Some(format!("{how}: {}", q.name));
+            v.timestamp = q.seen_at;
+            violations.push(v);
+        }
+        violations
+    }
+
+    // ── Active connection scanning ──────────────────────────────
+
+    /// Find processes with established connections to banned domains.
+    /// A connection is reported when it first appears, not every scan.
+    pub fn scan_connections(&mut self) -> Vec<Violation> {
+        if !self.conn_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.conn_cfg.interval);
+        if self.conn_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.conn_last_run = Some(Instant::now());
+
+        // Resolved off the scan thread; until the first lookup is done
+        // there's nothing to match against
+        let refresh = Duration::from_secs(self.conn_cfg.resolve_refresh_secs);
+        if let Some(resolver) = self.conn_resolver.as_mut() {
+            if let Some(ips) = resolver.finished() {
+                self.banned_ips = ips;
+            }
+            if self.banned_ips_resolved.is_none_or(|t| t.elapsed() >= refresh) {
+                resolver.request(&self.banned_domains);
+                self.banned_ips_resolved = Some(Instant::now());
+            }
+        }
+
+        let mut current = HashSet::new();
+        let mut violations = Vec::new();
+        for conn in netstat::established_tcp() {
+            let Some(domain) = self.banned_ips.get(&conn.remote.ip()).cloned() else {
+                continue;
+            };
+            // The map may predate the last ban change
+            if !self.banned_domains.contains(&domain) {
+                continue;
+            }
+            if !current.insert((conn.pid, domain.clone())) {
+                continue;
+            }
+            if self.active_banned_conns.contains(&(conn.pid, domain.clone())) {
+                continue;
+            }
+
+            let process = self
+                .sys
+                .process(sysinfo::Pid::from_u32(conn.pid))
+                .map(|p| p.name().to_string_lossy().to_string())
+                .unwrap_or_else(|| format!("pid {}", conn.pid));
+            info!("🔌 {process} (PID {}) connected to banned {domain} at {}", conn.pid, conn.remote);
+            let mut v = self.violation(domain, ViolationKind::Domain, false);
+            v.detail = Some(format!("{process} → {}", conn.remote));
+            v.process = Some(process);
+            violations.push(v);
+        }
+        self.active_banned_conns = current;
+
+        violations
+    }
+
+    // ── Per-process bandwidth ───────────────────────────────────
+
+    /// Sample per-app throughput and report apps that stay over the limit
+    /// for `sustained_secs`. Reported once per spell over the limit.
+    pub fn scan_bandwidth(&mut self) -> Vec<Violation> {
+        if !self.bandwidth_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.bandwidth_cfg.interval);
+        if self.bandwidth_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.bandwidth_last_run = Some(Instant::now());
+
+        let sustained = Duration::from_secs(self.bandwidth_cfg.sustained_secs);
+        let over: Vec<_> = self
+            .bandwidth
+            .sample()
+            .into_iter()
+            .filter(|a| a.total_mbps() > self.bandwidth_cfg.limit_mbps)
+            .collect();
+        self.over_limit_since.retain(|app, _| over.iter().any(|a| &a.app == app));
+        self.reported_bandwidth.retain(|app| self.over_limit_since.contains_key(app));
+
+        let mut violations = Vec::new();
+        for traffic in over {
+            let since = *self.over_limit_since.entry(traffic.app.clone()).or_insert_with(Instant::now);
+            if since.elapsed() < sustained || !self.reported_bandwidth.insert(traffic.app.clone()) {
+                continue;
+            }
+            let mbps = traffic.total_mbps();
+            warn!("📶 {} sustaining {mbps:.1} Mbit/s (limit {})", traffic.app, self.bandwidth_cfg.limit_mbps);
+            let mut v = self.violation(traffic.app.clone(), ViolationKind::BandwidthAbuse, false);
+            v.detail = Some(format!(
+                "{mbps:.1} Mbit/s for {}s+ (↓ {} KB/s, ↑ {} KB/s)",
+                since.elapsed().as_secs(),
+                traffic.recv_bytes_per_sec / 1024,
+                traffic.sent_bytes_per_sec / 1024,
+            ));
+            v.process = Some(traffic.app);
+            violations.push(v);
+        }
+        violations
+    }
+
+    // ── DoH / DoT bypass detection ──────────────────────────────
+
+    /// Report processes talking to encrypted-DNS resolvers and browsers with
+    /// secure DNS enabled. Each finding is reported once per agent run.
+    pub fn scan_dns_bypass(&mut self) -> Vec<Violation> {
+        if !self.bypass_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.bypass_cfg.interval);
+        if self.bypass_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.bypass_last_run = Some(Instant::now());
+
+        let resolvers = self
+            .doh_resolvers
+            .get_or_insert_with(|| DohResolvers::resolve(&self.bypass_cfg.endpoints));
+
+        let mut found = Vec::new();
+        for conn in netstat::established_tcp() {
+            let Some(resolver) = resolvers.classify(&conn) else {
+                continue;
+            };
+            let process = self
+                .sys
+                .process(sysinfo::Pid::from_u32(conn.pid))
+                .map(|p| p.name().to_string_lossy().to_string())
+                .unwrap_or_else(|| format!("pid {}", conn.pid));
+            found.push((format!("{}|{resolver}", conn.pid), process, resolver));
+        }
+
+        let mut violations = Vec::new();
+        for (key, process, resolver) in found {
+            if !self.reported_bypass.insert(key) {
+                continue;
+            }
+            info!("🔐 Encrypted DNS bypass: {process} → {resolver}");
+            let mut v = self.violation(process, ViolationKind::DnsBypass, false);
+            v.detail = Some(resolver);
+            violations.push(v);
+        }
+
+        let browsers: Vec<_> = doh::browsers_with_doh()
+            .into_iter()
+            .filter(|b| self.reported_bypass.insert(format!("browser|{b}")))
+            .collect();
+        // Policies are machine-wide — write them once, on first detection.
+        if !browsers.is_empty() && self.bypass_cfg.disable_browser_doh && !self.doh_policy_applied {
+            self.doh_policy_applied = doh::disable_browser_doh();
+        }
+        for browser in browsers {
+            info!("🔐 Secure DNS enabled in {browser} settings");
+            let mut v = self.violation(browser.to_string(), ViolationKind::DnsBypass, self.doh_policy_applied);
+            v.detail = Some("secure DNS enabled in browser settings".into());
+            violations.push(v);
+        }
+
+        violations
+    }
+
     /// Flush the DNS resolver cache (platform-specific).
     fn flush_dns(&self) {
         let result = if cfg!(target_os = "windows") {
@@ -184,9 +1603,13 @@ impl Monitor {
                 })
         } else {
             // Linux - depends on the resolver
-            std::process::Command::new("systemd-resolve")
-                .arg("--flush-caches")
+            if dns_cache::nscd_present() {
+                let _ = silent_cmd("nscd").args(["-i", "hosts"]).output();
+            }
+            silent_cmd("resolvectl")
+                .arg("flush-caches")
                 .output()
+                .or_else(|_| silent_cmd("systemd-resolve").arg("--flush-caches").output())
         };
 
         match result {
@@ -197,9 +1620,10 @@ impl Monitor {
 
     // ── Browser window title scanning (Cross-platform) ──────────
 
-    /// Enumerate window titles to catch banned sites.
+    /// Enumerate window titles to catch banned sites and keywords.
     /// Windows: PowerShell Get-Process
     /// macOS: AppleScript to query browser windows
+    /// Linux: wmctrl (see `documents.rs`)
     pub fn scan_window_titles(&self) -> Vec<Violation> {
         let output = if cfg!(target_os = "windows") {
             let ps_script = r#"Get-Process | Where-Object {$_.MainWindowTitle -ne ''} | Select-Object -ExpandProperty MainWindowTitle"#;
@@ -247,11 +1671,22 @@ impl Monitor {
                 }
             }
         } else {
-            warn!("Window title scanning not supported on this platform");
-            return Vec::new();
+            let titles: Vec<String> = documents::window_titles().into_iter().map(|(_, title)| title).collect();
+            return self.title_violations(&titles);
         };
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_lowercase();
+        // AppleScript's `return` is a carriage return
+        let titles: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .split(['\r', '\n'])
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(String::from)
+            .collect();
+        self.title_violations(&titles)
+    }
+
+    fn title_violations(&self, titles: &[String]) -> Vec<Violation> {
+        let stdout = titles.join("\n").to_lowercase();
         let mut violations = Vec::new();
         let mut seen = HashSet::new();
 
@@ -262,27 +1697,385 @@ impl Monitor {
                 && seen.insert(domain.clone())
             {
                 info!("🪟 Banned site detected in window title: {domain}");
-                violations.push(Violation {
-                    hostname: self.hostname.clone(),
-                    target: domain.clone(),
-                    kind: ViolationKind::Domain,
-                    action_taken: false,
-                    username: self.username.clone(),
-                    timestamp: Utc::now(),
-                });
+                violations.push(self.violation(domain.clone(), ViolationKind::Domain, false));
+            }
+        }
+
+        for keyword in &self.banned_keywords {
+            let Some(title) = titles.iter().find(|t| t.to_lowercase().contains(keyword.as_str())) else {
+                continue;
+            };
+            info!("🪟 Banned keyword in window title: {keyword}");
+            let mut v = self.violation(keyword.clone(), ViolationKind::Keyword, false);
+            v.detail = Some(format!("window title: {title}"));
+            violations.push(v);
+        }
+
+        violations
+    }
+
+    // ── Browser history scanning (Chrome / Edge / Firefox) ──────
+
+    /// Read new entries from every browser history database and report
+    /// visits to banned domains. Catches pages the DNS cache never saw
+    /// (DoH, cache already expired). Runs at most every `interval` seconds.
+    pub fn scan_browser_history(&mut self) -> Vec<Violation> {
+        if !self.history_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.history_cfg.interval);
+        if self.history_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.history_last_run = Some(Instant::now());
+
+        let first_scan_since = Utc::now() - chrono::Duration::minutes(self.history_cfg.lookback_mins);
+        let mut violations = Vec::new();
+
+        for profile in browser::discover_profiles() {
+            let db = profile.history_db();
+            let since = self.history_watermarks.get(&db).copied().unwrap_or(first_scan_since);
+            let since = self.enforcing_since.map_or(since, |t| since.max(t));
+
+            let visits = match browser::read_history_since(&profile, since) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!("Could not read {} history at {}: {e}", profile.browser, db.display());
+                    continue;
+                }
+            };
+
+            let mut seen = HashSet::new();
+            let mut newest = since;
+            for visit in visits {
+                newest = newest.max(visit.visited_at);
+                if self.latest_visits.get(profile.browser).is_none_or(|v| v.visited_at < visit.visited_at) {
+                    self.latest_visits.insert(profile.browser, visit.clone());
+                }
+                let Some(host) = browser::url_host(&visit.url) else {
+                    continue;
+                };
+                let Some(domain) = self
+                    .banned_domains
+                    .iter()
+                    .find(|d| browser::host_matches(&host, d))
+                else {
+                    continue;
+                };
+                if !seen.insert(visit.url.clone()) {
+                    continue;
+                }
+
+                info!("📜 Banned site in {} history: {}", profile.browser, visit.url);
+                let mut v = self.violation(domain.clone(), ViolationKind::Domain, false);
+                v.url = Some(visit.url);
+                v.visited_at = Some(visit.visited_at);
+                violations.push(v);
+            }
+            self.history_watermarks.insert(db, newest);
+        }
+
+        violations
+    }
+
+    // ── Browser extension scanning ──────────────────────────────
+
+    /// Enumerate installed browser extensions and report (optionally delete)
+    /// the banned ones. Runs at most every `interval` seconds.
+    pub fn scan_extensions(&mut self) -> Vec<Violation> {
+        if self.extension_cfg.names.is_empty() {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.extension_cfg.interval);
+        if self.extensions_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.extensions_last_run = Some(Instant::now());
+
+        let mut violations = Vec::new();
+
+        for profile in browser::discover_profiles() {
+            for ext in browser::list_extensions(&profile) {
+                let id = ext.id.to_lowercase();
+                let name = ext.name.to_lowercase();
+                let banned = self
+                    .extension_cfg
+                    .names
+                    .iter()
+                    .any(|b| *b == id || (!name.is_empty() && name.contains(b.as_str())));
+                if !banned {
+                    continue;
+                }
+
+                let key = format!("{}|{id}", profile.dir.display());
+                let removed = if self.extension_cfg.remove {
+                    match browser::remove_extension(&ext) {
+                        Ok(()) => {
+                            info!("   ✅ Removed extension {} from {}", ext.id, ext.path.display());
+                            true
+                        }
+                        Err(e) => {
+                            warn!("   ⚠️  Failed to remove extension {}: {e}", ext.id);
+                            false
+                        }
+                    }
+                } else {
+                    false
+                };
+
+                // Removed ones may be re-installed later, so report them again.
+                if !removed && !self.reported_extensions.insert(key) {
+                    continue;
+                }
+
+                info!("🧩 Banned {} extension: {} ({})", ext.browser, ext.name, ext.id);
+                let target = if ext.name.is_empty() { ext.id.clone() } else { format!("{} ({})", ext.name, ext.id) };
+                violations.push(self.violation(target, ViolationKind::Extension, removed));
             }
         }
 
         violations
     }
 
+    // ── Downloaded files ────────────────────────────────────────
+
+    /// Report (and optionally delete) new files in users' Downloads /
+    /// Desktop folders with a banned extension or name.
+    pub fn scan_downloads(&mut self) -> Vec<Violation> {
+        if !self.downloads_cfg.enabled {
+            return Vec::new();
+        }
+        let interval = Duration::from_secs(self.downloads_cfg.interval);
+        if self.downloads_last_run.is_some_and(|t| t.elapsed() < interval) {
+            return Vec::new();
+        }
+        self.downloads_last_run = Some(Instant::now());
+
+        // Nothing downloaded during a break is reported afterwards
+        let since = self.enforcing_since.map_or(self.downloads_since, |t| t.max(self.downloads_since));
+        let mut violations = Vec::new();
+        for dir in downloads::watched_dirs() {
+            for file in downloads::files_in(&dir) {
+                if chrono::DateTime::<Utc>::from(file.changed) < since || self.reported_downloads.contains(&file.path) {
+                    continue;
+                }
+                let Some(reason) = self.banned_download(&file.name) else {
+                    continue;
+                };
+                self.reported_downloads.insert(file.path.clone());
+
+                let deleted = self.downloads_cfg.delete
+                    && match std::fs::remove_file(&file.path) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!("   ⚠️  Failed to delete {}: {e}", file.path.display());
+                            false
+                        }
+                    };
+                info!("📥 Banned download: {} ({reason})", file.path.display());
+                let mut v = self.violation(file.path.display().to_string(), ViolationKind::FileDownload, deleted);
+                v.detail = Some(format!("{}, {reason}", downloads::human_size(file.size)));
+                violations.push(v);
+            }
+        }
+        violations
+    }
+
+    /// Why a file named `name` (lowercased) is banned, if it is.
+    fn banned_download(&self, name: &str) -> Option<String> {
+        if let Some(ext) = downloads::extension(name).filter(|e| self.downloads_cfg.extensions.iter().any(|b| b == e)) {
+            return Some(format!(".{ext} file"));
+        }
+        if let Some(fragment) = self.downloads_cfg.names.iter().find(|n| !n.is_empty() && name.contains(n.as_str())) {
+            return Some(format!("name matches \"{fragment}\""));
+        }
+        if self.downloads_cfg.match_banned_processes {
+            if let Some(process) = self.banned_procs.iter().find(|p| !p.is_empty() && name.starts_with(p.as_str())) {
+                return Some(format!("named after banned process {process}"));
+            }
+        }
+        None
+    }
+
     // ── Full scan (combines all methods) ────────────────────────
 
-    /// Run every detection method and return combined violations.
+    /// Detector groups of a scan, run in order by `scan_phase`.
+    pub const SCAN_PHASES: usize = 3;
+
+    /// Run every detector group back to back.
     pub fn full_scan(&mut self) -> Vec<Violation> {
-        let mut all = self.scan_processes();
-        all.extend(self.scan_dns_cache());
-        all.extend(self.scan_window_titles());
+        let mut all = Vec::new();
+        for phase in 0..Self::SCAN_PHASES {
+            all.extend(self.scan_phase(phase));
+        }
         all
     }
+
+    /// Run one detector group (`0..SCAN_PHASES`). With `[monitor.budget]
+    /// stagger` the main loop spreads them over the scan interval and the
+    /// monitor is free in between. Phase 0 starts the scan report, the
+    /// last phase publishes it.
+    pub fn scan_phase(&mut self, phase: usize) -> Vec<Violation> {
+        let started = Instant::now();
+        if phase == 0 || self.scan_in_progress.is_none() {
+            self.scan_in_progress = Some(ScanReport {
+                started_at: Utc::now(),
+                duration_ms: 0,
+                enforcing: self.enforcing,
+                violations: Vec::new(),
+                failed_detectors: Vec::new(),
+                skipped_detectors: Vec::new(),
+            });
+        }
+        self.check_load();
+        let failures_before = self.detector_failures.len();
+        let found = self.scan_group(phase);
+        if let Some(report) = self.scan_in_progress.as_mut() {
+            report.duration_ms += started.elapsed().as_millis() as u64;
+            report.violations.extend(found.iter().cloned());
+            report.failed_detectors.extend(self.detector_failures[failures_before..].iter().map(|f| f.detector.clone()));
+        }
+        if phase + 1 >= Self::SCAN_PHASES {
+            self.last_scan = self.scan_in_progress.take();
+        }
+        found
+    }
+
+    /// What the last full scan found and how long its phases took.
+    pub fn last_scan(&self) -> Option<ScanReport> {
+        self.last_scan.clone()
+    }
+
+    /// Newest page in `browser`'s history as of the last history scan.
+    #[cfg_attr(not(feature = "streaming"), allow(dead_code))]
+    pub fn latest_visit(&self, browser: &str) -> Option<Visit> {
+        self.latest_visits.get(browser).cloned()
+    }
+
+    /// Set `busy` from the system CPU load since the previous phase.
+    fn check_load(&mut self) {
+        if self.max_system_cpu <= 0.0 {
+            return;
+        }
+        self.sys.refresh_cpu_usage();
+        let load = self.sys.global_cpu_usage();
+        let busy = load > self.max_system_cpu;
+        if busy != self.busy {
+            if busy {
+                info!("🐢 System CPU at {load:.0}% — skipping expensive detectors");
+            } else {
+                info!("System CPU back at {load:.0}% — all detectors running");
+            }
+        }
+        self.busy = busy;
+    }
+
+    fn scan_group(&mut self, phase: usize) -> Vec<Violation> {
+        let mut all = Vec::new();
+        if phase == 0 {
+            self.run_detector("user_profile", |m| {
+                m.refresh_login();
+                Vec::new()
+            });
+            self.run_detector("firewall", |m| {
+                m.sync_firewall();
+                Vec::new()
+            });
+            all.extend(self.run_detector("tamper", Self::scan_tamper));
+        }
+        if !self.enforcing {
+            // Queries seen during the break must not be reported afterwards
+            if let Some(sniffer) = &self.sniffer {
+                sniffer.drain();
+            }
+            return self.dedup(all);
+        }
+        match phase {
+            0 => {
+                all.extend(self.run_detector("blocked_launches", Self::scan_blocked_launches));
+                all.extend(self.run_detector("processes", Self::scan_processes));
+                all.extend(self.run_detector("resource_abuse", Self::scan_resource_abuse));
+                all.extend(self.run_detector("remote_access", Self::scan_remote_access));
+                all.extend(self.run_expensive("screen_share", Self::scan_screen_share));
+            }
+            1 => {
+                all.extend(self.run_expensive("microphone", Self::scan_microphone));
+                all.extend(self.run_expensive("webcam", Self::scan_webcam));
+                all.extend(self.run_expensive("vpn_proxy", Self::scan_vpn_proxy));
+                // The sniffer sees every query, so the cache parse + flush (which
+                // slows legitimate browsing) is only needed without it — or
+                // while it gives no output (no capture tool, not started yet).
+                if self.sniffer.is_some() {
+                    all.extend(self.run_detector("dns_sniffer", |m| m.scan_sniffed_queries()));
+                }
+                if !self.sniffer.as_ref().is_some_and(DnsSniffer::is_alive) {
+                    all.extend(self.run_expensive("dns_cache", |m| m.scan_dns_cache()));
+                }
+                all.extend(self.run_expensive("window_titles", |m| m.scan_window_titles()));
+            }
+            _ => {
+                all.extend(self.run_expensive("connections", Self::scan_connections));
+                all.extend(self.run_expensive("dns_bypass", Self::scan_dns_bypass));
+                all.extend(self.run_expensive("bandwidth", Self::scan_bandwidth));
+                all.extend(self.run_expensive("browser_history", Self::scan_browser_history));
+                all.extend(self.run_expensive("extensions", Self::scan_extensions));
+                all.extend(self.run_expensive("downloads", Self::scan_downloads));
+            }
+        }
+        self.warn_about_domains(&all);
+        self.dedup(all)
+    }
+
+    /// `run_detector` for one that shells out or reads large files:
+    /// skipped while the system is busy.
+    fn run_expensive(&mut self, name: &str, detector: impl FnOnce(&mut Self) -> Vec<Violation>) -> Vec<Violation> {
+        if self.busy {
+            if let Some(report) = self.scan_in_progress.as_mut() {
+                report.skipped_detectors.push(name.to_string());
+            }
+            return Vec::new();
+        }
+        self.run_detector(name, detector)
+    }
+
+    /// Run one detector, catching a panic so the remaining detectors still
+    /// run and the monitor mutex is never poisoned. Detectors with a
+    /// `[monitor.detector_intervals]` entry are skipped until it's up, ones
+    /// without their OS tool always. A panicking detector yields no
+    /// violations and is recorded as a `DetectorFailure`.
+    fn run_detector(&mut self, name: &str, detector: impl FnOnce(&mut Self) -> Vec<Violation>) -> Vec<Violation> {
+        if self.unavailable_detectors.contains(name) {
+            return Vec::new();
+        }
+        if let Some(&every) = self.detector_intervals.get(name) {
+            // Scan cycles drift by a few ms; don't let that cost a whole cycle
+            if self.detector_last_run.get(name).is_some_and(|t| t.elapsed() + TIMER_SLACK < every) {
+                return Vec::new();
+            }
+            self.detector_last_run.insert(name.to_string(), Instant::now());
+        }
+        match panic::catch_unwind(AssertUnwindSafe(|| detector(self))) {
+            Ok(found) => found,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".into());
+                error!("💥 Detector {name} panicked: {message}");
+                self.detector_failures.push(DetectorFailure {
+                    detector: name.to_string(),
+                    hostname: self.hostname.clone(),
+                    message,
+                    timestamp: Utc::now(),
+                });
+                Vec::new()
+            }
+        }
+    }
+
+    /// Detector failures since the last call.
+    pub fn take_detector_failures(&mut self) -> Vec<DetectorFailure> {
+        std::mem::take(&mut self.detector_failures)
+    }
 }
@@ -0,0 +1,163 @@
+// ─────────────────────────────────────────────────────────────────
+//  mqtt.rs — Mirror of agent events to an MQTT broker
+//
+//  With `[mqtt] enabled`, heartbeats go to `heartbeat_topic` and a
+//  retained `{"status": "online"}` to `status_topic` at startup, with a
+//  retained `"offline"` registered as the last will so the broker
+//  publishes it when the agent drops off; on a clean shutdown the agent
+//  publishes it itself. Violations go out through a `type = "mqtt"`
+//  violation sink, to `violation_topic`. Payloads are the same JSON as
+//  in Redis (violations in the teacher-backend schema).
+//  Publishing never waits for the broker: rumqttc reconnects in the
+//  background, and what doesn't fit in its queue meanwhile is dropped.
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Outgoing, Packet, QoS, TlsConfiguration, Transport};
+use tracing::{debug, info, warn};
+
+use crate::config::MqttConfig;
+use crate::models::{Heartbeat, Violation};
+use crate::store::teacher_payload;
+
+/// Messages held while the broker is unreachable.
+const QUEUE: usize = 64;
+
+#[derive(Clone)]
+pub struct Mqtt {
+    client: AsyncClient,
+    qos: QoS,
+    heartbeat_topic: String,
+    violation_topic: String,
+    /// Where the agent's online / offline status goes; None for the
+    /// watchdog.
+    status_topic: Option<String>,
+    hostname: String,
+    /// Notified once the event loop has sent DISCONNECT (see `close`).
+    closed: Arc<tokio::sync::Notify>,
+}
+
+impl Mqtt {
+    /// Connect in the background. `role` tells the agent ("agent") from
+    /// its watchdog in the client id; only the agent publishes status.
+    pub fn start(cfg: &MqttConfig, hostname: &str, role: &str) -> anyhow::Result<Self> {
+        let url = url::Url::parse(&cfg.broker).map_err(|e| anyhow::anyhow!("[mqtt] broker {}: {e}", cfg.broker))?;
+        let tls = match url.scheme() {
+            "mqtt" => false,
+            "mqtts" => true,
+            other => anyhow::bail!("[mqtt] broker: unknown scheme \"{other}\" (expected mqtt or mqtts)"),
+        };
+        let Some(host) = url.host_str() else {
+            anyhow::bail!("[mqtt] broker {} has no host", cfg.broker);
+        };
+        let qos = match cfg.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            other => anyhow::bail!("[mqtt] qos {other} (expected 0, 1 or 2)"),
+        };
+        let topic = |t: &str| t.replace("{hostname}", hostname);
+        let status_topic = topic(&cfg.status_topic);
+        let with_status = role == "agent" && !status_topic.is_empty();
+
+        let port = url.port().unwrap_or(if tls { 8883 } else { 1883 });
+        let mut options = MqttOptions::new(format!("nishack-{role}-{hostname}"), host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if tls {
+            options.set_transport(Transport::Tls(TlsConfiguration::Native));
+        }
+        if let Some(username) = &cfg.username {
+            options.set_credentials(username, cfg.password.clone().unwrap_or_default());
+        }
+        if with_status {
+            let offline = serde_json::json!({ "status": "offline", "hostname": hostname }).to_string();
+            options.set_last_will(LastWill::new(&status_topic, offline, qos, true));
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, QUEUE);
+        let broker = cfg.broker.clone();
+        let closed = Arc::new(tokio::sync::Notify::new());
+        let notify = Arc::clone(&closed);
+        tokio::spawn(async move {
+            let mut connected = false;
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("📡 MQTT connected to {broker}");
+                        connected = true;
+                    }
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => {
+                        notify.notify_one();
+                        return;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        if std::mem::replace(&mut connected, false) {
+                            warn!("MQTT connection to {broker} lost (will retry): {e}");
+                        } else {
+                            debug!("MQTT connection to {broker} failed: {e}");
+                        }
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        let mqtt = Self {
+            client,
+            qos,
+            heartbeat_topic: topic(&cfg.heartbeat_topic),
+            violation_topic: topic(&cfg.violation_topic),
+            status_topic: with_status.then(|| status_topic.clone()),
+            hostname: hostname.to_owned(),
+            closed,
+        };
+        if with_status {
+            let online = serde_json::json!({
+                "status": "online",
+                "hostname": hostname,
+                "version": env!("CARGO_PKG_VERSION"),
+                "timestamp": Utc::now(),
+            });
+            mqtt.publish(&status_topic, online.to_string(), true);
+        }
+        Ok(mqtt)
+    }
+
+    /// Send what's queued and disconnect, waiting up to `timeout` — for
+    /// a process about to exit. A clean disconnect skips the last will, so
+    /// the agent publishes its offline status itself first.
+    pub async fn close(&self, timeout: Duration) {
+        if let Some(topic) = &self.status_topic {
+            let offline = serde_json::json!({ "status": "offline", "hostname": self.hostname, "timestamp": Utc::now() });
+            self.publish(topic, offline.to_string(), true);
+        }
+        if self.client.disconnect().await.is_ok() {
+            let _ = tokio::time::timeout(timeout, self.closed.notified()).await;
+        }
+    }
+
+    fn publish(&self, topic: &str, payload: String, retain: bool) {
+        if topic.is_empty() {
+            return;
+        }
+        if let Err(e) = self.client.try_publish(topic, self.qos, retain, payload) {
+            debug!("MQTT publish to {topic} dropped: {e}");
+        }
+    }
+
+    pub fn heartbeat(&self, hb: &Heartbeat) {
+        if let Ok(payload) = serde_json::to_string(hb) {
+            self.publish(&self.heartbeat_topic, payload, false);
+        }
+    }
+
+    pub fn violations(&self, vs: &[Violation]) {
+        for v in vs {
+            self.publish(&self.violation_topic, teacher_payload(v).to_string(), false);
+        }
+    }
+}
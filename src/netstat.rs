@@ -0,0 +1,218 @@
+// ─────────────────────────────────────────────────────────────────
+//  netstat.rs — Established and listening TCP sockets per process
+//
+//  Parses the OS's own tooling rather than pulling in a sockets
+//  crate:
+//    Windows: netstat -ano -p TCP (and TCPv6)
+//    Linux:   ss -Htnp state established / ss -Htlnp
+//    macOS:   lsof -nP -iTCP -sTCP:ESTABLISHED / -sTCP:LISTEN
+// ─────────────────────────────────────────────────────────────────
+
+use std::net::{IpAddr, SocketAddr};
+
+use tracing::warn;
+
+use crate::monitor::silent_cmd;
+
+/// One established outbound/inbound TCP connection.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TcpConnection {
+    pub pid: u32,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+}
+
+/// Enumerate established TCP connections. Empty on failure (logged).
+pub fn established_tcp() -> Vec<TcpConnection> {
+    let result = if cfg!(target_os = "windows") {
+        silent_cmd("netstat").args(["-ano", "-p", "TCP"]).output().map(|o| {
+            let mut conns = parse_windows(&String::from_utf8_lossy(&o.stdout));
+            if let Ok(v6) = silent_cmd("netstat").args(["-ano", "-p", "TCPv6"]).output() {
+                conns.extend(parse_windows(&String::from_utf8_lossy(&v6.stdout)));
+            }
+            conns
+        })
+    } else if cfg!(target_os = "macos") {
+        silent_cmd("lsof")
+            .args(["-nP", "-iTCP", "-sTCP:ESTABLISHED", "-F", "pn"])
+            .output()
+            .map(|o| parse_lsof(&String::from_utf8_lossy(&o.stdout)))
+    } else {
+        silent_cmd("ss")
+            .args(["-Htnp", "state", "established"])
+            .output()
+            .map(|o| parse_ss(&String::from_utf8_lossy(&o.stdout)))
+    };
+
+    match result {
+        Ok(conns) => conns,
+        Err(e) => {
+            warn!("Could not enumerate TCP connections: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// A listening TCP socket.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TcpListener {
+    pub pid: u32,
+    pub local: SocketAddr,
+}
+
+/// Enumerate listening TCP sockets. Empty on failure (logged).
+pub fn listening_tcp() -> Vec<TcpListener> {
+    let result = if cfg!(target_os = "windows") {
+        silent_cmd("netstat").args(["-ano", "-p", "TCP"]).output().map(|o| {
+            let mut socks = parse_windows_listening(&String::from_utf8_lossy(&o.stdout));
+            if let Ok(v6) = silent_cmd("netstat").args(["-ano", "-p", "TCPv6"]).output() {
+                socks.extend(parse_windows_listening(&String::from_utf8_lossy(&v6.stdout)));
+            }
+            socks
+        })
+    } else if cfg!(target_os = "macos") {
+        silent_cmd("lsof")
+            .args(["-nP", "-iTCP", "-sTCP:LISTEN", "-F", "pn"])
+            .output()
+            .map(|o| parse_lsof_listening(&String::from_utf8_lossy(&o.stdout)))
+    } else {
+        silent_cmd("ss")
+            .args(["-Htlnp"])
+            .output()
+            .map(|o| parse_ss_listening(&String::from_utf8_lossy(&o.stdout)))
+    };
+
+    match result {
+        Ok(socks) => socks,
+        Err(e) => {
+            warn!("Could not enumerate listening sockets: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// Parse `1.2.3.4:443`, `[::1]:443` and lsof's `1.2.3.4:443` forms.
+/// Wildcard listeners (`*:7070`) map to the unspecified address.
+fn parse_addr(raw: &str) -> Option<SocketAddr> {
+    if let Ok(a) = raw.parse::<SocketAddr>() {
+        return Some(a);
+    }
+    if let Some(port) = raw.strip_prefix("*:") {
+        return Some(SocketAddr::new(IpAddr::from([0, 0, 0, 0]), port.parse().ok()?));
+    }
+    // ss / lsof print bare IPv6 without brackets, e.g. "::ffff:1.2.3.4:443"
+    let (ip, port) = raw.rsplit_once(':')?;
+    let ip = ip.trim_start_matches('[').trim_end_matches(']');
+    // ss may append an interface scope ("%eth0")
+    let ip = ip.split('%').next()?;
+    Some(SocketAddr::new(ip.parse::<IpAddr>().ok()?, port.parse().ok()?))
+}
+
+//   TCP    10.0.0.5:51234    142.250.74.46:443    ESTABLISHED    1234
+fn parse_windows(out: &str) -> Vec<TcpConnection> {
+    out.lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 5 || cols[0] != "TCP" || cols[3] != "ESTABLISHED" {
+                return None;
+            }
+            Some(TcpConnection {
+                local: parse_addr(cols[1])?,
+                remote: parse_addr(cols[2])?,
+                pid: cols[4].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+//   0  0  10.0.0.5:51234  142.250.74.46:443  users:(("chrome",pid=1234,fd=42))
+fn parse_ss(out: &str) -> Vec<TcpConnection> {
+    out.lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 5 {
+                return None;
+            }
+            let pid = cols[4]
+                .split("pid=")
+                .nth(1)?
+                .split(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()?;
+            Some(TcpConnection {
+                local: parse_addr(cols[2])?,
+                remote: parse_addr(cols[3])?,
+                pid,
+            })
+        })
+        .collect()
+}
+
+// lsof -F pn prints one field per line: "p<pid>" then "n<local>-><remote>"
+fn parse_lsof(out: &str) -> Vec<TcpConnection> {
+    let mut conns = Vec::new();
+    let mut pid = None;
+    for line in out.lines() {
+        if let Some(p) = line.strip_prefix('p') {
+            pid = p.parse().ok();
+        } else if let (Some(n), Some(pid)) = (line.strip_prefix('n'), pid) {
+            let Some((local, remote)) = n.split_once("->") else {
+                continue;
+            };
+            if let (Some(local), Some(remote)) = (parse_addr(local), parse_addr(remote)) {
+                conns.push(TcpConnection { pid, local, remote });
+            }
+        }
+    }
+    conns
+}
+
+//   TCP    0.0.0.0:7070    0.0.0.0:0    LISTENING    1234
+fn parse_windows_listening(out: &str) -> Vec<TcpListener> {
+    out.lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 5 || cols[0] != "TCP" || cols[3] != "LISTENING" {
+                return None;
+            }
+            Some(TcpListener { local: parse_addr(cols[1])?, pid: cols[4].parse().ok()? })
+        })
+        .collect()
+}
+
+//   LISTEN 0  128  0.0.0.0:7070  0.0.0.0:*  users:(("anydesk",pid=1234,fd=7))
+fn parse_ss_listening(out: &str) -> Vec<TcpListener> {
+    out.lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 6 {
+                return None;
+            }
+            let pid = cols[5]
+                .split("pid=")
+                .nth(1)?
+                .split(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()?;
+            Some(TcpListener { local: parse_addr(cols[3])?, pid })
+        })
+        .collect()
+}
+
+// lsof -F pn: "p<pid>" then "n<local>" (no "->" for listeners)
+fn parse_lsof_listening(out: &str) -> Vec<TcpListener> {
+    let mut socks = Vec::new();
+    let mut pid = None;
+    for line in out.lines() {
+        if let Some(p) = line.strip_prefix('p') {
+            pid = p.parse().ok();
+        } else if let (Some(n), Some(pid)) = (line.strip_prefix('n'), pid) {
+            if let Some(local) = parse_addr(n) {
+                socks.push(TcpListener { pid, local });
+            }
+        }
+    }
+    socks
+}
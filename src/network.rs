@@ -0,0 +1,265 @@
+// ─────────────────────────────────────────────────────────────────
+//  network.rs — Which network the machine is on
+//
+//  A student who moves the laptop onto a phone hotspot leaves the
+//  school's filtering behind, so `[monitor.network]` watches the
+//  default route and the Wi-Fi name, logs every change and sends the
+//  current network with heartbeats:
+//    Windows: Get-NetRoute / Get-NetAdapter, netsh wlan
+//    macOS:   route -n get default, networksetup -getairportnetwork
+//    Linux:   ip route, iwgetid / nmcli
+//  A hotspot is recognised by its SSID (`hotspot_hints`), by the
+//  gateways phones hand out (iOS 172.20.10.1, Android 192.168.43.1,
+//  Windows Mobile Hotspot 192.168.137.1) or by a USB-tethering adapter
+//  (RNDIS, Apple Mobile Device Ethernet). With `flag_foreign` a
+//  network that isn't the school's is reported once per lesson.
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::config::NetworkConfig;
+use crate::models::{NetworkEnv, Violation, ViolationKind};
+use crate::monitor::silent_cmd;
+use crate::schedule::Schedule;
+use crate::violation_sinks::ViolationSinks;
+
+/// Gateways phone hotspots use by default.
+const HOTSPOT_GATEWAYS: &[&str] = &["172.20.10.1", "192.168.43.1", "192.168.137.1"];
+
+/// Adapter descriptions (Windows) and drivers (Linux) of USB tethering.
+const TETHERING_HINTS: &[&str] = &["remote ndis", "apple mobile device ethernet", "rndis_host", "ipheth"];
+
+/// What a check sees, before it's compared with the previous one.
+#[derive(Debug, Default)]
+struct Route {
+    ssid: Option<String>,
+    gateway: Option<String>,
+    interface: Option<String>,
+    /// Adapter description (Windows) or driver (Linux).
+    adapter: String,
+}
+
+impl Route {
+    fn describe(&self) -> String {
+        match (&self.ssid, &self.gateway) {
+            (Some(ssid), Some(gw)) => format!("{ssid} via {gw}"),
+            (Some(ssid), None) => ssid.clone(),
+            (None, Some(gw)) => format!("wired via {gw}"),
+            (None, None) => "offline".into(),
+        }
+    }
+}
+
+/// The current network, shared between the watch loop and heartbeats.
+#[derive(Default)]
+pub struct NetworkWatch {
+    current: Mutex<Option<NetworkEnv>>,
+}
+
+impl NetworkWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current(&self) -> Option<NetworkEnv> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Store this check's result; true when the network changed (not on
+    /// the first check).
+    fn update(&self, route: &Route, hotspot: bool, school: Option<bool>) -> bool {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let same = |env: &NetworkEnv| env.ssid == route.ssid && env.gateway == route.gateway;
+        if let Some(env) = current.as_mut().filter(|env| same(env)) {
+            env.interface = route.interface.clone();
+            env.hotspot = hotspot;
+            env.school = school;
+            return false;
+        }
+        let previous = current.as_ref().map(|env| {
+            Route { ssid: env.ssid.clone(), gateway: env.gateway.clone(), ..Default::default() }.describe()
+        });
+        let changed = previous.is_some();
+        *current = Some(NetworkEnv {
+            ssid: route.ssid.clone(),
+            gateway: route.gateway.clone(),
+            interface: route.interface.clone(),
+            hotspot,
+            school,
+            since: Utc::now(),
+            previous,
+        });
+        changed
+    }
+}
+
+/// Check the network every `interval`, reporting foreign networks in
+/// class hours when `flag_foreign` is on. Runs forever.
+pub async fn run(
+    cfg: NetworkConfig,
+    watch: Arc<NetworkWatch>,
+    schedule: Arc<Schedule>,
+    sinks: ViolationSinks,
+    hostname: String,
+    username: String,
+) {
+    let interval = Duration::from_secs(cfg.interval.max(1));
+    let hints: Vec<String> = cfg.hotspot_hints.iter().map(|h| h.to_lowercase()).collect();
+    let ssids: Vec<String> = cfg.school_ssids.iter().map(|s| s.to_lowercase()).collect();
+    if cfg.flag_foreign && ssids.is_empty() && cfg.school_gateways.is_empty() {
+        warn!("[monitor.network] flag_foreign is on but no school networks are configured; only hotspots are reported");
+    }
+    // Network reported in the current lesson
+    let mut reported: Option<String> = None;
+    loop {
+        let Ok(route) = tokio::task::spawn_blocking(route).await else {
+            tokio::time::sleep(interval).await;
+            continue;
+        };
+        let hotspot = is_hotspot(&route, &hints);
+        let school = if hotspot {
+            Some(false)
+        } else if ssids.is_empty() && cfg.school_gateways.is_empty() {
+            None
+        } else {
+            let ssid = route.ssid.as_ref().is_some_and(|s| ssids.contains(&s.to_lowercase()));
+            let gateway = route.gateway.as_ref().is_some_and(|g| cfg.school_gateways.contains(g));
+            Some(ssid || gateway)
+        };
+        let description = route.describe();
+        if watch.update(&route, hotspot, school) {
+            info!("📶 Network changed: now {description}{}", if hotspot { " (hotspot)" } else { "" });
+        }
+
+        let foreign = school == Some(false) && route.gateway.is_some();
+        if !cfg.flag_foreign || !foreign || !schedule.active().bans {
+            reported = None;
+        } else if reported.as_deref() != Some(description.as_str()) {
+            warn!("📶 On a non-school network during class: {description}");
+            let v = Violation {
+                hostname: hostname.clone(),
+                target: route.ssid.clone().or(route.gateway.clone()).unwrap_or_default(),
+                kind: ViolationKind::ForeignNetwork,
+                action_taken: false,
+                username: username.clone(),
+                timestamp: Utc::now(),
+                url: None,
+                visited_at: None,
+                detail: Some(format!("connected to {description}{}", if hotspot { " (mobile hotspot)" } else { "" })),
+                process: None,
+                occurrences: 1,
+                last_seen: None,
+                usage: None,
+                exe: None,
+            };
+            sinks.deliver(&v).await;
+            reported = Some(description);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+fn is_hotspot(route: &Route, hints: &[String]) -> bool {
+    let ssid = route.ssid.as_deref().unwrap_or_default().to_lowercase();
+    let adapter = route.adapter.to_lowercase();
+    hints.iter().any(|h| ssid.contains(h.as_str()))
+        || route.gateway.as_deref().is_some_and(|g| HOTSPOT_GATEWAYS.contains(&g))
+        || TETHERING_HINTS.iter().any(|h| adapter.contains(h))
+}
+
+/// The default route and Wi-Fi name. Blocking.
+fn route() -> Route {
+    if cfg!(target_os = "windows") {
+        windows_route()
+    } else if cfg!(target_os = "macos") {
+        mac_route()
+    } else {
+        linux_route()
+    }
+}
+
+const WIN_DEFAULT_ROUTE: &str = r#"
+$r = Get-NetRoute -DestinationPrefix '0.0.0.0/0' -ErrorAction SilentlyContinue | Sort-Object RouteMetric | Select-Object -First 1
+if ($r) { $a = Get-NetAdapter -InterfaceIndex $r.ifIndex; "$($r.NextHop)|$($a.Name)|$($a.InterfaceDescription)" }
+"#;
+
+fn windows_route() -> Route {
+    let mut route = Route::default();
+    if let Ok(out) = silent_cmd("powershell").args(["-NoProfile", "-Command", WIN_DEFAULT_ROUTE]).output() {
+        let text = String::from_utf8_lossy(&out.stdout);
+        let mut cols = text.trim().splitn(3, '|');
+        route.gateway = cols.next().filter(|g| !g.is_empty()).map(String::from);
+        route.interface = cols.next().map(String::from);
+        route.adapter = cols.next().unwrap_or_default().to_string();
+    }
+    //     SSID                   : SchoolNet
+    if let Ok(out) = silent_cmd("netsh").args(["wlan", "show", "interfaces"]).output() {
+        route.ssid = String::from_utf8_lossy(&out.stdout).lines().find_map(|l| {
+            let (key, value) = l.split_once(':')?;
+            (key.trim() == "SSID").then(|| value.trim().to_string()).filter(|v| !v.is_empty())
+        });
+    }
+    route
+}
+
+fn mac_route() -> Route {
+    let mut route = Route::default();
+    //     gateway: 192.168.1.1
+    //   interface: en0
+    if let Ok(out) = silent_cmd("route").args(["-n", "get", "default"]).output() {
+        for line in String::from_utf8_lossy(&out.stdout).lines() {
+            match line.trim().split_once(": ") {
+                Some(("gateway", gw)) => route.gateway = Some(gw.trim().to_string()),
+                Some(("interface", iface)) => route.interface = Some(iface.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    // Current Wi-Fi Network: SchoolNet
+    if let Some(iface) = &route.interface {
+        if let Ok(out) = silent_cmd("networksetup").args(["-getairportnetwork", iface]).output() {
+            route.ssid = String::from_utf8_lossy(&out.stdout)
+                .trim()
+                .strip_prefix("Current Wi-Fi Network: ")
+                .map(String::from);
+        }
+    }
+    route
+}
+
+fn linux_route() -> Route {
+    let mut route = Route::default();
+    // default via 192.168.1.1 dev wlp2s0 proto dhcp metric 600
+    if let Ok(out) = silent_cmd("ip").args(["route", "show", "default"]).output() {
+        let text = String::from_utf8_lossy(&out.stdout);
+        let fields: Vec<&str> = text.lines().next().unwrap_or_default().split_whitespace().collect();
+        let after = |key: &str| fields.iter().position(|f| *f == key).and_then(|i| fields.get(i + 1)).map(|v| v.to_string());
+        route.gateway = after("via");
+        route.interface = after("dev");
+    }
+    let Some(iface) = route.interface.clone() else {
+        return route;
+    };
+    if let Ok(driver) = std::fs::read_link(format!("/sys/class/net/{iface}/device/driver")) {
+        route.adapter = driver.file_name().map(|d| d.to_string_lossy().to_string()).unwrap_or_default();
+    }
+    route.ssid = silent_cmd("iwgetid")
+        .args(["-r", &iface])
+        .output()
+        .ok()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(|| {
+            // yes:SchoolNet
+            let out = silent_cmd("nmcli").args(["-t", "-f", "active,ssid", "dev", "wifi"]).output().ok()?;
+            String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .find_map(|l| l.strip_prefix("yes:").map(|s| s.replace("\\:", ":")))
+                .filter(|s| !s.is_empty())
+        });
+    route
+}
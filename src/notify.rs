@@ -0,0 +1,42 @@
+// ─────────────────────────────────────────────────────────────────
+//  notify.rs — Warning pop-ups for the student
+//
+//    Windows: msg * (message box in every session, also from SYSTEM)
+//    macOS:   osascript display notification
+//    Linux:   notify-send (needs the agent in the user's session)
+//
+//  Best effort: a missing tool is logged and otherwise ignored.
+// ─────────────────────────────────────────────────────────────────
+
+use tracing::warn;
+
+use crate::desktop::applescript_escape;
+use crate::monitor::silent_cmd;
+
+/// Show `text` to whoever is at the screen. Doesn't wait for the
+/// notification to be dismissed.
+pub fn warn_user(title: &str, text: &str, timeout_secs: u64) {
+    let spawned = if cfg!(target_os = "windows") {
+        silent_cmd("msg")
+            .args(["*", &format!("/TIME:{timeout_secs}"), &format!("{title}\n\n{text}")])
+            .spawn()
+    } else if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification \"{}\" with title \"{}\"",
+            applescript_escape(text),
+            applescript_escape(title)
+        );
+        silent_cmd("osascript").args(["-e", &script]).spawn()
+    } else {
+        silent_cmd("notify-send")
+            .args(["-u", "critical", "-t", &(timeout_secs * 1000).to_string(), title, text])
+            .spawn()
+    };
+    match spawned {
+        // Reap it in the background so it doesn't linger as a zombie
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => warn!("Could not show warning to the user: {e}"),
+    }
+}
@@ -0,0 +1,210 @@
+// ─────────────────────────────────────────────────────────────────
+//  outbox.rs — Redis writes kept on disk while Redis is unreachable
+//
+//  Heartbeats, violations and screenshots that can't be written are
+//  appended to `[redis] offline_queue` as one JSON line each: the
+//  Redis operations they stand for plus when they were queued. The
+//  next write that gets a connection replays the file in order first
+//  (see `Store::deliver`), so nothing overtakes what came before it.
+//  Values with a TTL (latest heartbeat / screenshot) that would already
+//  have expired are skipped on replay. Once the file reaches
+//  `offline_queue_max_mb` new entries are dropped, keeping the oldest.
+// ─────────────────────────────────────────────────────────────────
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// One Redis operation, as queued.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum QueuedOp {
+    /// `LPUSH`, then `LTRIM` to `keep` entries when set.
+    Lpush { key: String, value: String, keep: Option<isize> },
+    Incr { key: String, by: i64 },
+    SetEx { key: String, value: String, ttl_secs: u64 },
+    /// `XADD key MAXLEN ~ maxlen * hostname … data …`.
+    Xadd { key: String, hostname: String, data: String, maxlen: usize },
+    /// Binary values (screenshots), base64 in the queue file.
+    SetExBytes {
+        key: String,
+        #[serde(with = "base64_bytes")]
+        value: Vec<u8>,
+        ttl_secs: u64,
+    },
+    LpushBytes {
+        key: String,
+        #[serde(with = "base64_bytes")]
+        value: Vec<u8>,
+        keep: Option<isize>,
+    },
+    /// `DEL`, `HSET` of all `fields`, `EXPIRE`.
+    HsetEx { key: String, fields: Vec<(String, String)>, ttl_secs: u64 },
+}
+
+impl QueuedOp {
+    /// Payload bytes it writes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            Self::Lpush { value, .. } | Self::SetEx { value, .. } | Self::Xadd { data: value, .. } => value.len(),
+            Self::SetExBytes { value, .. } | Self::LpushBytes { value, .. } => value.len(),
+            Self::HsetEx { fields, .. } => fields.iter().map(|(f, v)| f.len() + v.len()).sum(),
+            Self::Incr { .. } => 0,
+        }
+    }
+
+    pub fn add_to(&self, pipe: &mut redis::Pipeline) {
+        match self {
+            Self::Lpush { key, value, keep } => {
+                pipe.lpush(key, value).ignore();
+                if let Some(keep) = keep {
+                    pipe.ltrim(key, 0, keep - 1).ignore();
+                }
+            }
+            Self::Incr { key, by } => {
+                pipe.incr(key, *by).ignore();
+            }
+            Self::SetEx { key, value, ttl_secs } => {
+                pipe.set_ex(key, value, *ttl_secs).ignore();
+            }
+            Self::Xadd { key, hostname, data, maxlen } => {
+                let fields = [("hostname", hostname), ("data", data)];
+                pipe.xadd_maxlen(key, redis::streams::StreamMaxlen::Approx(*maxlen), "*", &fields).ignore();
+            }
+            Self::SetExBytes { key, value, ttl_secs } => {
+                pipe.set_ex(key, value.as_slice(), *ttl_secs).ignore();
+            }
+            Self::LpushBytes { key, value, keep } => {
+                pipe.lpush(key, value.as_slice()).ignore();
+                if let Some(keep) = keep {
+                    pipe.ltrim(key, 0, keep - 1).ignore();
+                }
+            }
+            Self::HsetEx { key, fields, ttl_secs } => {
+                pipe.del(key).ignore();
+                pipe.hset_multiple(key, fields).ignore();
+                pipe.expire(key, *ttl_secs as i64).ignore();
+            }
+        }
+    }
+}
+
+mod base64_bytes {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(d)?;
+        base64::engine::general_purpose::STANDARD.decode(text).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A write that didn't reach Redis.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pending {
+    /// "heartbeat", "violations", "screenshot" — for the logs.
+    pub kind: String,
+    pub queued_at: DateTime<Utc>,
+    pub ops: Vec<QueuedOp>,
+}
+
+impl Pending {
+    /// The operations still worth sending now.
+    pub fn live_ops(&self) -> impl Iterator<Item = &QueuedOp> {
+        let age = (Utc::now() - self.queued_at).num_seconds().max(0) as u64;
+        self.ops.iter().filter(move |op| {
+            !matches!(op, QueuedOp::SetEx { ttl_secs, .. } | QueuedOp::SetExBytes { ttl_secs, .. } | QueuedOp::HsetEx { ttl_secs, .. } if *ttl_secs <= age)
+        })
+    }
+}
+
+/// The queue file. `Store` keeps it behind an async mutex that is held
+/// for the whole of a replay, so writes can't interleave with one.
+pub struct Outbox {
+    path: PathBuf,
+    max_bytes: u64,
+    /// Something may be queued (set at startup if the file exists).
+    pending: bool,
+    /// Warned that the file is full since it last drained.
+    full_warned: bool,
+}
+
+impl Outbox {
+    pub fn new(path: impl Into<PathBuf>, max_mb: u64) -> Self {
+        let path = path.into();
+        let pending = fs::metadata(&path).is_ok_and(|m| m.len() > 0);
+        if pending {
+            info!("📮 Offline queue {} has writes from a previous run — replaying when Redis is reachable", path.display());
+        }
+        Self {
+            path,
+            max_bytes: max_mb * 1_048_576,
+            pending,
+            full_warned: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.pending
+    }
+
+    /// Append `entry`, unless the file is full.
+    pub fn push(&mut self, entry: &Pending) {
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size >= self.max_bytes {
+            if !std::mem::replace(&mut self.full_warned, true) {
+                warn!("📮 Offline queue {} is full ({size} bytes) — dropping new writes until Redis is back", self.path.display());
+            }
+            return;
+        }
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+        let appended = self.path.parent().filter(|d| !d.as_os_str().is_empty()).map_or(Ok(()), fs::create_dir_all).and_then(|_| {
+            let mut f = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            writeln!(f, "{line}")
+        });
+        match appended {
+            Ok(()) => self.pending = true,
+            Err(e) => warn!("Failed to queue {} for Redis: {e}", entry.kind),
+        }
+    }
+
+    /// Everything queued, oldest first. Unreadable lines are skipped.
+    pub fn load(&self) -> Vec<Pending> {
+        match fs::read_to_string(&self.path) {
+            Ok(text) => text.lines().filter_map(|l| serde_json::from_str(l).ok()).collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                warn!("Failed to read offline queue {}: {e}", self.path.display());
+                Vec::new()
+            }
+        }
+    }
+
+    /// Replace the queue with `rest` (what's left after a partial replay).
+    pub fn keep(&mut self, rest: &[Pending]) {
+        if rest.is_empty() {
+            if let Err(e) = fs::remove_file(&self.path) {
+                if e.kind() != ErrorKind::NotFound {
+                    warn!("Failed to clear offline queue {}: {e}", self.path.display());
+                }
+            }
+            self.pending = false;
+            self.full_warned = false;
+            return;
+        }
+        let text: String = rest.iter().filter_map(|p| serde_json::to_string(p).ok()).map(|l| l + "\n").collect();
+        if let Err(e) = fs::write(&self.path, text) {
+            warn!("Failed to rewrite offline queue {}: {e}", self.path.display());
+        }
+    }
+}
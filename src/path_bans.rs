@@ -0,0 +1,134 @@
+// ─────────────────────────────────────────────────────────────────
+//  path_bans.rs — Bans on where a program runs from
+//
+//  Games copied to a network share or unpacked into a temp folder
+//  get renamed freely, so `[monitor.banned_paths]` matches the full
+//  executable path instead of the process name. Patterns are globs
+//  (`*` any run of characters, separators included; `?` one), case-
+//  insensitive, `/` and `\` interchangeable:
+//    \\*\games\*          any share's games folder
+//    %TEMP%\*             anything run from a user's temp folder
+//    *\Downloads\*.exe
+//  `%VAR%` is expanded from the environment, except the per-user
+//  folders (USERPROFILE, APPDATA, LOCALAPPDATA, TEMP, TMP): the agent
+//  runs as SYSTEM / root, so those are widened to every profile
+//  (C:\Users\*\AppData\Local\Temp, /home/*). A leading `~` is the same
+//  as %USERPROFILE%. Mapped drives show as their letter (Z:\...), so
+//  ban those by letter too.
+// ─────────────────────────────────────────────────────────────────
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::config::{BanAction, BanEntry, BanList};
+
+/// The `[monitor.banned_paths]` patterns, expanded.
+pub struct PathBans {
+    /// (pattern as configured, normalised expansion, action)
+    rules: Vec<(String, String, BanAction)>,
+}
+
+impl PathBans {
+    pub fn new(list: &BanList) -> Self {
+        // Not `BanList::actions`: environment variable names keep their case
+        let rules = list
+            .names
+            .iter()
+            .map(|entry| {
+                let (pattern, action) = match entry {
+                    BanEntry::Name(pattern) => (pattern, list.action),
+                    BanEntry::Rule { name, action } => (name, action.unwrap_or(list.action)),
+                };
+                (pattern.clone(), normalise(&expand(pattern)), action)
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// The first pattern `exe` matches, with its action.
+    pub fn find(&self, exe: &Path) -> Option<(&str, BanAction)> {
+        let exe = normalise(&exe.to_string_lossy());
+        self.rules
+            .iter()
+            .find(|(_, expanded, _)| glob_match(expanded.as_bytes(), exe.as_bytes()))
+            .map(|(pattern, _, action)| (pattern.as_str(), *action))
+    }
+}
+
+/// Lowercased, `\` separators.
+fn normalise(path: &str) -> String {
+    path.replace('/', "\\").to_lowercase()
+}
+
+/// Expand `%VAR%` and a leading `~`.
+fn expand(pattern: &str) -> String {
+    let pattern = match pattern.strip_prefix('~') {
+        Some(rest) => format!("%USERPROFILE%{rest}"),
+        None => pattern.to_string(),
+    };
+    let mut out = String::new();
+    let mut rest = pattern.as_str();
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else {
+            break;
+        };
+        let name = &rest[start + 1..start + 1 + len];
+        out.push_str(&rest[..start]);
+        match per_user(name).or_else(|| std::env::var(name).ok()) {
+            Some(value) => out.push_str(&value),
+            None => {
+                warn!("[monitor.banned_paths]: %{name}% is not set, kept as is in {pattern:?}");
+                out.push_str(&rest[start..start + len + 2]);
+            }
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Per-user folders, for any user.
+fn per_user(name: &str) -> Option<String> {
+    let home = if cfg!(target_os = "windows") {
+        format!("{}\\Users\\*", std::env::var("SystemDrive").unwrap_or_else(|_| "C:".into()))
+    } else if cfg!(target_os = "macos") {
+        "/Users/*".into()
+    } else {
+        "/home/*".into()
+    };
+    let windows = cfg!(target_os = "windows");
+    Some(match name.to_uppercase().as_str() {
+        "USERPROFILE" | "HOME" => home,
+        "APPDATA" if windows => format!("{home}\\AppData\\Roaming"),
+        "LOCALAPPDATA" if windows => format!("{home}\\AppData\\Local"),
+        "TEMP" | "TMP" if windows => format!("{home}\\AppData\\Local\\Temp"),
+        // macOS per-user temp dirs (/var is a link to /private/var)
+        "TEMP" | "TMP" if cfg!(target_os = "macos") => "*/var/folders/*".into(),
+        "TEMP" | "TMP" => "/tmp".into(),
+        _ => return None,
+    })
+}
+
+/// `*` / `?` glob match over bytes (case already folded).
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it is matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((after, matched)) = star {
+            p = after;
+            t = matched + 1;
+            star = Some((after, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
@@ -0,0 +1,339 @@
+// ─────────────────────────────────────────────────────────────────
+//  pg_store.rs — heartbeats, violations, usage and screenshots in
+//  PostgreSQL (`[storage] kind = "postgres"`)
+//
+//  `Store` hands these four to `PgStore` instead of Redis; everything
+//  else (commands, ban lists, attendance, audit mirror) stays on Redis.
+//  Rows carry the same JSON as the Redis values in a `data` column,
+//  keyed by the agent's namespace (`{prefix}:{site}:{room}`) and
+//  hostname. The tables are created on first use. Like Redis, usage
+//  samples and screenshots are trimmed to the newest `USAGE_HISTORY` /
+//  `[screenshots] history` per host, and the latest heartbeat /
+//  screenshot count as gone after the TTLs they'd have in Redis. Writes
+//  are not queued offline.
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::{DateTime, Utc};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use tracing::warn;
+
+use crate::config::StorageConfig;
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS heartbeats (
+        namespace TEXT NOT NULL,
+        hostname TEXT NOT NULL,
+        received_at TIMESTAMPTZ NOT NULL,
+        data JSONB NOT NULL,
+        PRIMARY KEY (namespace, hostname)
+    )",
+    "CREATE TABLE IF NOT EXISTS usage_samples (
+        id BIGSERIAL PRIMARY KEY,
+        namespace TEXT NOT NULL,
+        hostname TEXT NOT NULL,
+        recorded_at TIMESTAMPTZ NOT NULL,
+        data JSONB NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS usage_samples_host ON usage_samples (namespace, hostname, recorded_at DESC)",
+    "CREATE TABLE IF NOT EXISTS violations (
+        id BIGSERIAL PRIMARY KEY,
+        namespace TEXT NOT NULL,
+        hostname TEXT NOT NULL,
+        rule TEXT NOT NULL,
+        first_seen TIMESTAMPTZ NOT NULL,
+        data JSONB NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS violations_host ON violations (namespace, hostname, first_seen DESC)",
+    "CREATE TABLE IF NOT EXISTS screenshots (
+        id BIGSERIAL PRIMARY KEY,
+        namespace TEXT NOT NULL,
+        hostname TEXT NOT NULL,
+        taken_at TIMESTAMPTZ NOT NULL,
+        data JSONB NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS screenshots_host ON screenshots (namespace, hostname, taken_at DESC)",
+];
+
+#[derive(Clone)]
+pub struct PgStore {
+    pool: PgPool,
+    namespace: String,
+    /// Set once the tables exist (shared by all clones).
+    schema: std::sync::Arc<tokio::sync::OnceCell<()>>,
+}
+
+impl PgStore {
+    /// Create the pool (connections are opened on first use).
+    pub fn new(cfg: &StorageConfig, namespace: &str) -> anyhow::Result<Self> {
+        let url = std::env::var("NISHACK_POSTGRES_URL")
+            .ok()
+            .filter(|u| !u.is_empty())
+            .unwrap_or_else(|| cfg.postgres_url.clone());
+        if url.is_empty() {
+            anyhow::bail!("[storage] kind = \"postgres\" needs postgres_url (or NISHACK_POSTGRES_URL)");
+        }
+        let pool = PgPoolOptions::new()
+            .max_connections(cfg.max_connections)
+            .acquire_timeout(std::time::Duration::from_secs(5))
+            .connect_lazy(&url)?;
+        Ok(Self {
+            pool,
+            namespace: namespace.to_owned(),
+            schema: Default::default(),
+        })
+    }
+
+    /// The pool, once the tables exist. None (logged) when Postgres is
+    /// unreachable; creating the tables is retried next time.
+    async fn pool(&self, what: &str) -> Option<&PgPool> {
+        let created = self
+            .schema
+            .get_or_try_init(|| async {
+                for statement in SCHEMA {
+                    sqlx::query(statement).execute(&self.pool).await?;
+                }
+                Ok::<_, sqlx::Error>(())
+            })
+            .await;
+        match created {
+            Ok(()) => Some(&self.pool),
+            Err(e) => {
+                warn!("Postgres unavailable, {what} not stored / read (will retry): {e}");
+                None
+            }
+        }
+    }
+
+    /// Replace the host's latest heartbeat and add a usage sample, keeping
+    /// the newest `keep`.
+    pub async fn push_heartbeat(&self, hostname: &str, heartbeat: &str, sample: &str, keep: i64) -> bool {
+        let Some(pool) = self.pool("heartbeat").await else {
+            return false;
+        };
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            sqlx::query(
+                "INSERT INTO heartbeats (namespace, hostname, received_at, data) VALUES ($1, $2, now(), $3::jsonb)
+                 ON CONFLICT (namespace, hostname) DO UPDATE SET received_at = EXCLUDED.received_at, data = EXCLUDED.data",
+            )
+            .bind(&self.namespace)
+            .bind(hostname)
+            .bind(heartbeat)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+                "INSERT INTO usage_samples (namespace, hostname, recorded_at, data) VALUES ($1, $2, now(), $3::jsonb)",
+            )
+            .bind(&self.namespace)
+            .bind(hostname)
+            .bind(sample)
+            .execute(&mut *tx)
+            .await?;
+            self.trim(&mut tx, "usage_samples", "recorded_at", hostname, keep).await?;
+            tx.commit().await
+        }
+        .await;
+        if let Err(e) = &result {
+            warn!("Failed to store heartbeat in Postgres: {e}");
+        }
+        result.is_ok()
+    }
+
+    /// Delete all but the newest `keep` rows of `table` for `hostname`.
+    async fn trim(
+        &self,
+        tx: &mut sqlx::PgConnection,
+        table: &str,
+        time: &str,
+        hostname: &str,
+        keep: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            "DELETE FROM {table} WHERE id IN (
+                SELECT id FROM {table} WHERE namespace = $1 AND hostname = $2
+                ORDER BY {time} DESC, id DESC OFFSET $3
+            )"
+        ))
+        .bind(&self.namespace)
+        .bind(hostname)
+        .bind(keep)
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Insert violations: `(hostname, rule, first_seen, teacher-backend JSON)`.
+    pub async fn record_violations(&self, rows: &[(String, String, DateTime<Utc>, String)]) -> bool {
+        let Some(pool) = self.pool("violations").await else {
+            return false;
+        };
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            for (hostname, rule, first_seen, data) in rows {
+                sqlx::query(
+                    "INSERT INTO violations (namespace, hostname, rule, first_seen, data) VALUES ($1, $2, $3, $4, $5::jsonb)",
+                )
+                .bind(&self.namespace)
+                .bind(hostname)
+                .bind(rule)
+                .bind(first_seen)
+                .bind(data)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await
+        }
+        .await;
+        if let Err(e) = &result {
+            warn!("Failed to store violations in Postgres: {e}");
+        }
+        result.is_ok()
+    }
+
+    /// Rewrite the violation first seen at `first_seen` under `rule`,
+    /// inserting it if there's none.
+    pub async fn update_violation(&self, hostname: &str, rule: &str, first_seen: DateTime<Utc>, data: &str) {
+        let Some(pool) = self.pool("violation update").await else {
+            return;
+        };
+        let updated = sqlx::query(
+            "UPDATE violations SET data = $5::jsonb
+             WHERE namespace = $1 AND hostname = $2 AND rule = $3 AND first_seen = $4",
+        )
+        .bind(&self.namespace)
+        .bind(hostname)
+        .bind(rule)
+        .bind(first_seen)
+        .bind(data)
+        .execute(pool)
+        .await;
+        match updated {
+            Ok(r) if r.rows_affected() > 0 => {}
+            Ok(_) => {
+                self.record_violations(&[(hostname.to_owned(), rule.to_owned(), first_seen, data.to_owned())]).await;
+            }
+            Err(e) => warn!("Failed to update violation in Postgres: {e}"),
+        }
+    }
+
+    /// Add a screenshot, keeping the newest `keep` for the host.
+    #[cfg(feature = "screenshots")]
+    pub async fn push_screenshot(&self, hostname: &str, data: &str, keep: i64) -> bool {
+        let Some(pool) = self.pool("screenshot").await else {
+            return false;
+        };
+        let result: Result<(), sqlx::Error> = async {
+            let mut tx = pool.begin().await?;
+            sqlx::query("INSERT INTO screenshots (namespace, hostname, taken_at, data) VALUES ($1, $2, now(), $3::jsonb)")
+                .bind(&self.namespace)
+                .bind(hostname)
+                .bind(data)
+                .execute(&mut *tx)
+                .await?;
+            self.trim(&mut tx, "screenshots", "taken_at", hostname, keep).await?;
+            tx.commit().await
+        }
+        .await;
+        if let Err(e) = &result {
+            warn!("Failed to store screenshot in Postgres: {e}");
+        }
+        result.is_ok()
+    }
+
+    /// The newest `count` rows of `list` (as named in Redis: `violations`,
+    /// `usage` or `screenshot_history`) for `hostname`, newest first.
+    pub async fn read(&self, list: &str, hostname: &str, count: i64) -> Vec<String> {
+        let (table, time) = match list {
+            "violations" => ("violations", "first_seen"),
+            "usage" => ("usage_samples", "recorded_at"),
+            "screenshot_history" => ("screenshots", "taken_at"),
+            _ => return Vec::new(),
+        };
+        let Some(pool) = self.pool(list).await else {
+            return Vec::new();
+        };
+        sqlx::query_scalar(&format!(
+            "SELECT data::text FROM {table} WHERE namespace = $1 AND hostname = $2 ORDER BY {time} DESC, id DESC LIMIT $3"
+        ))
+        .bind(&self.namespace)
+        .bind(hostname)
+        .bind(count)
+        .fetch_all(pool)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to read {list} from Postgres: {e}");
+            Vec::new()
+        })
+    }
+
+    /// The host's latest screenshot, if taken in the last `live_secs`.
+    pub async fn latest_screenshot(&self, hostname: &str, live_secs: u64) -> Option<String> {
+        let pool = self.pool("screenshot").await?;
+        sqlx::query_scalar(
+            "SELECT data::text FROM screenshots
+             WHERE namespace = $1 AND hostname = $2 AND taken_at > now() - make_interval(secs => $3)
+             ORDER BY taken_at DESC, id DESC LIMIT 1",
+        )
+        .bind(&self.namespace)
+        .bind(hostname)
+        .bind(live_secs as f64)
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Heartbeats in this namespace received in the last `live_secs`, as
+    /// `(hostname, JSON)`, by hostname.
+    pub async fn room_heartbeats(&self, live_secs: u64) -> Vec<(String, String)> {
+        let Some(pool) = self.pool("heartbeats").await else {
+            return Vec::new();
+        };
+        sqlx::query_as(
+            "SELECT hostname, data::text FROM heartbeats
+             WHERE namespace = $1 AND received_at > now() - make_interval(secs => $2)
+             ORDER BY hostname",
+        )
+        .bind(&self.namespace)
+        .bind(live_secs as f64)
+        .fetch_all(pool)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to read heartbeats from Postgres: {e}");
+            Vec::new()
+        })
+    }
+
+    /// Delete the host's latest heartbeat (the agent is shutting down).
+    pub async fn remove_heartbeat(&self, hostname: &str) {
+        let Some(pool) = self.pool("heartbeat").await else {
+            return;
+        };
+        let deleted = sqlx::query("DELETE FROM heartbeats WHERE namespace = $1 AND hostname = $2")
+            .bind(&self.namespace)
+            .bind(hostname)
+            .execute(pool)
+            .await;
+        if let Err(e) = deleted {
+            warn!("Failed to remove heartbeat from Postgres: {e}");
+        }
+    }
+
+    /// Delete the host's violations first seen before `cutoff`.
+    pub async fn trim_violations(&self, hostname: &str, cutoff: DateTime<Utc>) -> u64 {
+        let Some(pool) = self.pool("violation retention").await else {
+            return 0;
+        };
+        sqlx::query("DELETE FROM violations WHERE namespace = $1 AND hostname = $2 AND first_seen < $3")
+            .bind(&self.namespace)
+            .bind(hostname)
+            .bind(cutoff)
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected())
+            .unwrap_or_else(|e| {
+                warn!("Failed to trim violations in Postgres: {e}");
+                0
+            })
+    }
+}
@@ -0,0 +1,68 @@
+// ─────────────────────────────────────────────────────────────────
+//  platform.rs — Which build is running on which Windows
+//
+//  School fleets mix x86_64 desktops, Windows-on-ARM laptops and old
+//  32-bit lab machines. Native builds exist for all three
+//  (x86_64 / aarch64 / i686-pc-windows-msvc); what matters at runtime
+//  is a build that doesn't match the OS:
+//    32-bit agent on 64-bit Windows (WOW64): System32 and the registry
+//      are redirected to their 32-bit views, so `silent_cmd` runs
+//      system tools from Sysnative instead
+//    x86_64 agent on ARM64: works under emulation, just slower
+//  Both are reported through the capabilities and the heartbeat.
+// ─────────────────────────────────────────────────────────────────
+
+/// True for a 32-bit process on 64-bit Windows (x86_64 or ARM64).
+pub fn is_wow64() -> bool {
+    cfg!(target_os = "windows") && std::env::var_os("PROCESSOR_ARCHITEW6432").is_some()
+}
+
+/// Architecture of the OS, which may differ from the agent's own.
+pub fn native_arch() -> String {
+    if !cfg!(target_os = "windows") {
+        return std::env::consts::ARCH.to_string();
+    }
+    // Emulated x64 processes see AMD64 in PROCESSOR_ARCHITECTURE, but the
+    // processor identifier still names the ARM CPU
+    let identifier = std::env::var("PROCESSOR_IDENTIFIER").unwrap_or_default();
+    if identifier.to_uppercase().contains("ARM") {
+        return "aarch64".into();
+    }
+    let arch = std::env::var("PROCESSOR_ARCHITEW6432")
+        .or_else(|_| std::env::var("PROCESSOR_ARCHITECTURE"))
+        .unwrap_or_default();
+    match arch.to_uppercase().as_str() {
+        "AMD64" => "x86_64".into(),
+        "ARM64" => "aarch64".into(),
+        "X86" => "x86".into(),
+        _ => std::env::consts::ARCH.to_string(),
+    }
+}
+
+/// Why this build runs worse than a native one would, if it does.
+pub fn build_mismatch() -> Option<String> {
+    let native = native_arch();
+    if native == std::env::consts::ARCH {
+        return None;
+    }
+    Some(if is_wow64() {
+        format!("32-bit agent on {native} Windows; install the {native} build")
+    } else {
+        format!("{} agent emulated on {native}; install the {native} build", std::env::consts::ARCH)
+    })
+}
+
+/// Path of `program` under Sysnative for a WOW64 agent (None otherwise,
+/// or when there's no native copy).
+#[cfg(target_os = "windows")]
+pub fn native_tool(program: &str) -> Option<std::path::PathBuf> {
+    if !is_wow64() {
+        return None;
+    }
+    let root = std::env::var_os("SystemRoot").unwrap_or_else(|| r"C:\Windows".into());
+    let sysnative = std::path::Path::new(&root).join("Sysnative");
+    let exe = format!("{program}.exe");
+    [sysnative.join(&exe), sysnative.join(r"WindowsPowerShell\v1.0").join(&exe)]
+        .into_iter()
+        .find(|p| p.is_file())
+}
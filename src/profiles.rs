@@ -0,0 +1,83 @@
+// ─────────────────────────────────────────────────────────────────
+//  profiles.rs — Per-user ban-list overrides
+//
+//  On shared PCs the agent outlives logins, so the interactive user
+//  is looked up at runtime rather than taken from the agent's own
+//  environment (it usually runs as SYSTEM / root):
+//    Windows: Win32_ComputerSystem.UserName (console session)
+//    macOS:   owner of /dev/console
+//    Linux:   loginctl session on seat0, else the first `who` entry
+// ─────────────────────────────────────────────────────────────────
+
+use crate::config::UserProfile;
+use crate::models::{BanConfig, BanLayer};
+use crate::monitor::silent_cmd;
+
+/// Login name of the user at the console, lowercased and without any
+/// `DOMAIN\` prefix. None when nobody is logged in or lookup fails.
+pub fn logged_in_user() -> Option<String> {
+    let raw = if cfg!(target_os = "windows") {
+        let out = silent_cmd("powershell")
+            .args(["-NoProfile", "-Command", "(Get-CimInstance Win32_ComputerSystem).UserName"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    } else if cfg!(target_os = "macos") {
+        let out = silent_cmd("stat").args(["-f", "%Su", "/dev/console"]).output().ok()?;
+        String::from_utf8_lossy(&out.stdout).trim().to_string()
+    } else {
+        seat_user().or_else(first_who_user)?
+    };
+
+    let name = raw.rsplit('\\').next().unwrap_or_default().trim().to_lowercase();
+    // macOS reports root while the login window is showing
+    (!name.is_empty() && name != "root").then_some(name)
+}
+
+//   2  1000 alice seat0 tty2
+fn seat_user() -> Option<String> {
+    let out = silent_cmd("loginctl").args(["list-sessions", "--no-legend"]).output().ok()?;
+    String::from_utf8_lossy(&out.stdout).lines().find_map(|line| {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        (cols.len() >= 4 && cols[3] == "seat0").then(|| cols[2].to_string())
+    })
+}
+
+//   alice  tty2  2024-05-02 08:31 (tty2)
+fn first_who_user() -> Option<String> {
+    let out = silent_cmd("who").output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    text.lines().next()?.split_whitespace().next().map(String::from)
+}
+
+/// The account id (uid / SID) owning `login`'s processes. Blocking.
+pub fn account_id(login: &str) -> Option<sysinfo::Uid> {
+    sysinfo::Users::new_with_refreshed_list()
+        .iter()
+        .find(|u| u.name().rsplit('\\').next().unwrap_or_default().to_lowercase() == login)
+        .map(|u| u.id().clone())
+}
+
+/// The first profile listing `login` (as returned by `logged_in_user`).
+pub fn matching<'a>(profiles: &'a [UserProfile], login: &str) -> Option<&'a UserProfile> {
+    profiles
+        .iter()
+        .find(|p| p.users.iter().any(|u| u.trim().to_lowercase() == login))
+}
+
+/// `base` with `profile` applied on top.
+pub fn apply(base: &BanConfig, profile: &UserProfile) -> BanConfig {
+    let inherited = BanLayer {
+        banned_processes: base.banned_processes.iter().cloned().collect(),
+        banned_domains: base.banned_domains.iter().cloned().collect(),
+        ..Default::default()
+    };
+    let overrides = BanLayer {
+        replace: profile.replace,
+        banned_processes: profile.banned_processes.clone(),
+        banned_domains: profile.banned_domains.clone(),
+        allowed_processes: profile.allowed_processes.clone(),
+        allowed_domains: profile.allowed_domains.clone(),
+    };
+    BanConfig::merge([&inherited, &overrides])
+}
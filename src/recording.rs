@@ -0,0 +1,78 @@
+// ─────────────────────────────────────────────────────────────────
+//  recording.rs — Archive of the live stream in object storage
+//
+//  With `[streaming] record` and the S3 screenshot sink, a streamed
+//  frame at most every `record_interval_secs` is uploaded to the
+//  bucket, grouped by stream (its start time, kept across handoffs),
+//  and its object key and metadata are added to
+//  `{prefix}:recordings:{hostname}`. Uploads run on their own task so
+//  a slow bucket never holds up the stream; frames arriving while it
+//  is still busy are skipped.
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::config::StreamingConfig;
+use crate::screenshot_sink::ScreenshotSink;
+use crate::store::Store;
+
+/// Frames waiting for upload.
+const QUEUE: usize = 2;
+
+struct Frame {
+    session: DateTime<Utc>,
+    number: u64,
+    jpeg: Vec<u8>,
+}
+
+pub struct Recorder {
+    tx: mpsc::Sender<Frame>,
+    every: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl Recorder {
+    /// Start the uploader; None when recording is off or the sink can't
+    /// take it.
+    pub fn start(cfg: &StreamingConfig, sink: Arc<ScreenshotSink>, store: Store, hostname: String) -> Option<Arc<Self>> {
+        if !cfg.record {
+            return None;
+        }
+        if !sink.is_s3() {
+            warn!("[streaming] record needs [screenshots] sink = \"s3\" — the stream isn't recorded");
+            return None;
+        }
+        info!("⏺️ Recording the stream to S3 — a frame every {}s", cfg.record_interval_secs);
+        let (tx, mut rx) = mpsc::channel::<Frame>(QUEUE);
+        let keep = cfg.record_keep;
+        tokio::spawn(async move {
+            while let Some(frame) = rx.recv().await {
+                if let Some(fields) = sink.record(&hostname, frame.session, frame.number, frame.jpeg).await {
+                    store.push_recording(&hostname, fields, keep).await;
+                }
+            }
+        });
+        Some(Arc::new(Self {
+            tx,
+            every: Duration::from_secs(cfg.record_interval_secs.max(1)),
+            last: Mutex::new(None),
+        }))
+    }
+
+    /// Offer frame `number` of the stream started at `session`; taken
+    /// when the last one taken is at least `record_interval_secs` old.
+    pub fn offer(&self, session: DateTime<Utc>, number: u64, jpeg: &[u8]) {
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        if last.is_some_and(|at| at.elapsed() < self.every) {
+            return;
+        }
+        if self.tx.try_send(Frame { session, number, jpeg: jpeg.to_vec() }).is_ok() {
+            *last = Some(Instant::now());
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// ─────────────────────────────────────────────────────────────────
+//  remote_access.rs — Built-in remote-access tool signatures
+//
+//  Remote desktop tools let someone outside the room do a student's
+//  exam, so they get their own detection category, independent of
+//  the teacher-managed ban list. A tool counts as present when any
+//  of its processes, running services or listening ports is found.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::HashSet;
+
+use crate::monitor::silent_cmd;
+
+/// Everything that gives a remote-access tool away.
+pub struct RemoteTool {
+    pub name: &'static str,
+    /// Process names, lowercase without `.exe`.
+    pub processes: &'static [&'static str],
+    /// Service names / launchd labels / systemd units, lowercase.
+    pub services: &'static [&'static str],
+    /// TCP ports the tool listens on for direct connections.
+    pub ports: &'static [u16],
+}
+
+pub const TOOLS: &[RemoteTool] = &[
+    RemoteTool {
+        name: "TeamViewer",
+        processes: &["teamviewer", "teamviewer_service", "teamviewerd", "tv_w32", "tv_x64"],
+        services: &["teamviewer", "teamviewerd", "com.teamviewer.service"],
+        ports: &[5938],
+    },
+    RemoteTool {
+        name: "AnyDesk",
+        processes: &["anydesk"],
+        services: &["anydesk", "com.philandro.anydesk.service"],
+        ports: &[7070],
+    },
+    RemoteTool {
+        name: "Chrome Remote Desktop",
+        processes: &["remoting_host", "remoting_desktop", "chrome-remote-desktop-host"],
+        services: &["chromoting", "chrome-remote-desktop", "org.chromium.chromoting"],
+        ports: &[],
+    },
+    RemoteTool {
+        name: "RustDesk",
+        processes: &["rustdesk"],
+        services: &["rustdesk", "com.carriez.rustdesk_service"],
+        ports: &[21118],
+    },
+];
+
+/// Names of currently running services, lowercase. Blocking.
+pub fn running_services() -> HashSet<String> {
+    let out = if cfg!(target_os = "windows") {
+        // `sc query` lists running services by default
+        silent_cmd("sc").arg("query").output()
+    } else if cfg!(target_os = "macos") {
+        silent_cmd("launchctl").arg("list").output()
+    } else {
+        silent_cmd("systemctl")
+            .args(["list-units", "--type=service", "--state=running", "--no-legend", "--plain"])
+            .output()
+    };
+    let Ok(out) = out else {
+        return HashSet::new();
+    };
+    let text = String::from_utf8_lossy(&out.stdout);
+
+    text.lines()
+        .filter_map(|line| {
+            if cfg!(target_os = "windows") {
+                // SERVICE_NAME: TeamViewer
+                line.strip_prefix("SERVICE_NAME:").map(str::trim)
+            } else if cfg!(target_os = "macos") {
+                // <pid>  <status>  <label> — a "-" pid means loaded, not running
+                let cols: Vec<&str> = line.split_whitespace().collect();
+                (cols.len() == 3 && cols[0] != "-").then(|| cols[2])
+            } else {
+                // teamviewerd.service loaded active running ...
+                // chrome-remote-desktop@bob.service ...
+                line.split_whitespace()
+                    .next()
+                    .map(|unit| unit.trim_end_matches(".service"))
+                    .map(|unit| unit.split('@').next().unwrap_or(unit))
+            }
+        })
+        .map(str::to_lowercase)
+        .collect()
+}
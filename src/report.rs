@@ -0,0 +1,229 @@
+// ─────────────────────────────────────────────────────────────────
+//  report.rs — Weekly per-student activity report
+//
+//  Built from the last seven days of attendance (folded from
+//  heartbeats) and violations, for teacher–parent conferences. The
+//  report is stored in Redis as JSON and HTML and, with
+//  `[report.smtp]` set, emailed. Generated on the configured weekday
+//  and time, or on demand via `POST /report/weekly`.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{Datelike, Local, NaiveDate, NaiveTime, Utc, Weekday};
+use lettre::message::{header::ContentType, Attachment, MultiPart, SinglePart};
+use tracing::{info, warn};
+
+use crate::audit::Audit;
+use crate::config::{ReportConfig, SmtpConfig};
+use crate::mail;
+use crate::models::{AttendanceEntry, ReportViolation, StudentReport, WeeklyReport};
+use crate::store::Store;
+
+/// Violations listed individually per student.
+const RECENT_VIOLATIONS: usize = 20;
+
+/// Username for violations recorded without one.
+const UNKNOWN_STUDENT: &str = "unknown";
+
+/// Build the report for the seven days up to now.
+pub async fn weekly(store: &Store, hostname: &str, heartbeat_secs: u64) -> WeeklyReport {
+    let to = Utc::now();
+    let from = to - chrono::Duration::days(7);
+    let today = Local::now().date_naive();
+    let dates: Vec<NaiveDate> = (0..7).rev().map(|d| today - chrono::Duration::days(d)).collect();
+    let minutes = |beats: u32| u64::from(beats) * heartbeat_secs / 60;
+
+    let mut students: BTreeMap<String, StudentReport> = BTreeMap::new();
+
+    for (date, users) in store.attendance(hostname, &dates).await {
+        for (user, day) in users {
+            let s = student(&mut students, &user);
+            s.days_present += 1;
+            s.online_minutes += minutes(day.heartbeats);
+            s.exam_minutes += minutes(day.exam_heartbeats);
+            for (lesson, beats) in &day.profiles {
+                *s.lesson_minutes.entry(lesson.clone()).or_default() += minutes(*beats);
+            }
+            for (document, beats) in &day.documents {
+                *s.document_minutes.entry(document.clone()).or_default() += minutes(*beats);
+            }
+            s.attendance.push(AttendanceEntry {
+                date,
+                first_seen: day.first_seen,
+                last_seen: day.last_seen,
+                minutes: minutes(day.heartbeats),
+            });
+        }
+    }
+
+    for v in store.violations_between(hostname, from, to).await {
+        let field = |name: &str| v.get(name).and_then(|f| f.as_str()).unwrap_or_default().to_string();
+        let user = v.get("username").and_then(|u| u.as_str()).unwrap_or(UNKNOWN_STUDENT);
+        let s = student(&mut students, user);
+        s.violations_total += 1;
+        *s.violations_by_rule.entry(field("rule")).or_default() += 1;
+        *s.violations_by_severity.entry(field("severity")).or_default() += 1;
+        if s.recent_violations.len() < RECENT_VIOLATIONS {
+            if let Ok(timestamp) = field("timestamp").parse() {
+                s.recent_violations.push(ReportViolation {
+                    timestamp,
+                    rule: field("rule"),
+                    severity: field("severity"),
+                    detail: field("detail"),
+                });
+            }
+        }
+    }
+
+    let mut students: Vec<StudentReport> = students.into_values().collect();
+    for s in &mut students {
+        s.attendance.sort_by_key(|a| a.date);
+    }
+    WeeklyReport { hostname: hostname.to_string(), from, to, generated_at: Utc::now(), students }
+}
+
+fn student<'a>(students: &'a mut BTreeMap<String, StudentReport>, name: &str) -> &'a mut StudentReport {
+    students
+        .entry(name.to_string())
+        .or_insert_with(|| StudentReport { username: name.to_string(), ..Default::default() })
+}
+
+/// Store `report` in Redis and email it when SMTP is configured.
+/// Returns whether it was emailed.
+pub async fn deliver(cfg: &ReportConfig, store: &Store, report: &WeeklyReport) -> anyhow::Result<bool> {
+    let html = html(report);
+    store.push_report(report, &html).await;
+    let Some(smtp) = &cfg.smtp else {
+        return Ok(false);
+    };
+    send_email(smtp, report, html).await?;
+    info!("📧 Weekly report emailed to {}", smtp.to.join(", "));
+    Ok(true)
+}
+
+async fn send_email(smtp: &SmtpConfig, report: &WeeklyReport, html: String) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(report)?;
+    let parts = MultiPart::mixed().singlepart(SinglePart::html(html)).singlepart(
+        Attachment::new(format!("report-{}.json", report.hostname))
+            .body(json, ContentType::parse("application/json")?),
+    );
+    let subject = format!("Недельный отчёт — {}", report.hostname);
+    mail::send(smtp, &subject, mail::Body::Multipart(parts)).await
+}
+
+/// Generate and deliver the report once a week at the configured time.
+/// Runs forever (returns at once if the day / time is invalid).
+pub async fn run(cfg: ReportConfig, heartbeat_secs: u64, store: Store, hostname: String, audit: Audit) {
+    let (Ok(day), Ok(time)) = (cfg.day.trim().parse::<Weekday>(), NaiveTime::parse_from_str(cfg.time.trim(), "%H:%M"))
+    else {
+        warn!("Weekly report disabled: bad day {:?} or time {:?} (expected e.g. \"fri\" and \"16:00\")", cfg.day, cfg.time);
+        return;
+    };
+    info!("Weekly report every {day} at {time}");
+
+    let mut last_run: Option<NaiveDate> = None;
+    loop {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        let now = Local::now();
+        let today = now.date_naive();
+        if now.weekday() != day || now.time() < time || last_run == Some(today) {
+            continue;
+        }
+        last_run = Some(today);
+        // Already generated today, before a restart
+        if store
+            .latest_report_time(&hostname)
+            .await
+            .is_some_and(|t| t.with_timezone(&Local).date_naive() == today)
+        {
+            continue;
+        }
+
+        let report = weekly(&store, &hostname, heartbeat_secs).await;
+        let emailed = match deliver(&cfg, &store, &report).await {
+            Ok(emailed) => emailed,
+            Err(e) => {
+                warn!("Failed to email weekly report: {e}");
+                false
+            }
+        };
+        info!("📊 Weekly report generated for {} student(s)", report.students.len());
+        let detail = format!("{} student(s), emailed: {emailed}", report.students.len());
+        audit.record("report_generated", "schedule", Some(detail)).await;
+    }
+}
+
+/// Self-contained HTML rendering of `report`.
+pub fn html(report: &WeeklyReport) -> String {
+    let local = |t: chrono::DateTime<Utc>| t.with_timezone(&Local).format("%d.%m.%Y %H:%M").to_string();
+    let mut out = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Отчёт — {host}</title>\
+         <style>body{{font-family:sans-serif}}table{{border-collapse:collapse;margin-bottom:1em}}\
+         td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}</style></head><body>\
+         <h1>Недельный отчёт: {host}</h1><p>{from} — {to}</p>",
+        host = escape(&report.hostname),
+        from = local(report.from),
+        to = local(report.to),
+    );
+    if report.students.is_empty() {
+        out.push_str("<p>Нет данных за эту неделю.</p>");
+    }
+    for s in &report.students {
+        out.push_str(&format!(
+            "<h2>{}</h2><p>Дней присутствия: {} · В сети: {} мин · Экзамен: {} мин · Нарушений: {}</p>",
+            escape(&s.username),
+            s.days_present,
+            s.online_minutes,
+            s.exam_minutes,
+            s.violations_total
+        ));
+        if !s.attendance.is_empty() {
+            out.push_str("<table><tr><th>День</th><th>Первый вход</th><th>Последний</th><th>Минут</th></tr>");
+            for a in &s.attendance {
+                out.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    a.date.format("%d.%m.%Y"),
+                    local(a.first_seen),
+                    local(a.last_seen),
+                    a.minutes
+                ));
+            }
+            out.push_str("</table>");
+        }
+        if !s.lesson_minutes.is_empty() {
+            out.push_str("<table><tr><th>Урок</th><th>Минут</th></tr>");
+            for (lesson, minutes) in &s.lesson_minutes {
+                out.push_str(&format!("<tr><td>{}</td><td>{minutes}</td></tr>", escape(lesson)));
+            }
+            out.push_str("</table>");
+        }
+        if !s.document_minutes.is_empty() {
+            out.push_str("<table><tr><th>Документ</th><th>Минут</th></tr>");
+            for (document, minutes) in &s.document_minutes {
+                out.push_str(&format!("<tr><td>{}</td><td>{minutes}</td></tr>", escape(document)));
+            }
+            out.push_str("</table>");
+        }
+        if !s.recent_violations.is_empty() {
+            out.push_str("<table><tr><th>Время</th><th>Правило</th><th>Уровень</th><th>Описание</th></tr>");
+            for v in &s.recent_violations {
+                out.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    local(v.timestamp),
+                    escape(&v.rule),
+                    escape(&v.severity),
+                    escape(&v.detail)
+                ));
+            }
+            out.push_str("</table>");
+        }
+    }
+    out.push_str(&format!("<p><small>Сформировано {}</small></p></body></html>", local(report.generated_at)));
+    out
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
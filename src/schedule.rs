@@ -0,0 +1,128 @@
+// ─────────────────────────────────────────────────────────────────
+//  schedule.rs — Lesson-schedule based rule activation
+//
+//  `[[schedule]]` blocks name the class hours. Inside a block its
+//  switches decide whether bans, screenshots and streaming run;
+//  outside every block (breaks, after school) they all relax. With
+//  no blocks configured everything is always on. A Redis-side
+//  override (`config_watch::sync_overrides`) swaps the blocks at
+//  runtime.
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::RwLock;
+
+use chrono::{Datelike, Local, NaiveTime, Weekday};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::ScheduleBlock;
+
+/// Name reported while no block is active.
+pub const OFF_HOURS: &str = "off-hours";
+
+/// What is enforced right now.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActiveProfile {
+    /// Block name, `OFF_HOURS`, or None when no schedule is configured.
+    pub name: Option<String>,
+    pub bans: bool,
+    pub screenshots: bool,
+    pub streaming: bool,
+}
+
+struct Block {
+    name: String,
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+    bans: bool,
+    screenshots: bool,
+    streaming: bool,
+}
+
+struct Parsed {
+    blocks: Vec<Block>,
+    configured: bool,
+}
+
+/// Parsed schedule; cheap to query, shared between the loops.
+pub struct Schedule {
+    parsed: RwLock<Parsed>,
+}
+
+impl Schedule {
+    /// Invalid blocks are logged and skipped.
+    pub fn new(blocks: &[ScheduleBlock]) -> Self {
+        Self { parsed: RwLock::new(parse(blocks)) }
+    }
+
+    /// Swap in `blocks` for the ones in force.
+    pub fn replace(&self, blocks: &[ScheduleBlock]) {
+        let parsed = parse(blocks);
+        *self.parsed.write().unwrap_or_else(|e| e.into_inner()) = parsed;
+    }
+
+    /// Profile for the current local time.
+    pub fn active(&self) -> ActiveProfile {
+        let parsed = self.parsed.read().unwrap_or_else(|e| e.into_inner());
+        if !parsed.configured {
+            return ActiveProfile { name: None, bans: true, screenshots: true, streaming: true };
+        }
+        let now = Local::now();
+        let (day, time) = (now.weekday(), now.time());
+        match parsed
+            .blocks
+            .iter()
+            .find(|b| b.days.contains(&day) && b.start <= time && time < b.end)
+        {
+            Some(b) => ActiveProfile {
+                name: Some(b.name.clone()),
+                bans: b.bans,
+                screenshots: b.screenshots,
+                streaming: b.streaming,
+            },
+            None => ActiveProfile {
+                name: Some(OFF_HOURS.into()),
+                bans: false,
+                screenshots: false,
+                streaming: false,
+            },
+        }
+    }
+}
+
+fn parse(blocks: &[ScheduleBlock]) -> Parsed {
+    let parsed = blocks
+        .iter()
+        .filter_map(|b| {
+            let parsed = parse_block(b);
+            if let Err(e) = &parsed {
+                warn!("Ignoring schedule block {:?}: {e}", b.name);
+            }
+            parsed.ok()
+        })
+        .collect();
+    Parsed { blocks: parsed, configured: !blocks.is_empty() }
+}
+
+fn parse_block(b: &ScheduleBlock) -> Result<Block, String> {
+    let time = |s: &str| NaiveTime::parse_from_str(s.trim(), "%H:%M").map_err(|_| format!("bad time {s:?}, expected HH:MM"));
+    let (start, end) = (time(&b.start)?, time(&b.end)?);
+    if end <= start {
+        return Err(format!("end {} is not after start {} (blocks can't cross midnight)", b.end, b.start));
+    }
+    let days = b
+        .days
+        .iter()
+        .map(|d| d.trim().parse::<Weekday>().map_err(|_| format!("bad weekday {d:?}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Block {
+        name: b.name.clone(),
+        days,
+        start,
+        end,
+        bans: b.bans,
+        screenshots: b.screenshots,
+        streaming: b.streaming,
+    })
+}
@@ -0,0 +1,80 @@
+// ─────────────────────────────────────────────────────────────────
+//  screen_capture.rs — Which programs are capturing the screen
+//
+//  Windows records every use of the graphics-capture APIs in the
+//  CapabilityAccessManager consent store; an entry whose
+//  LastUsedTimeStop is 0 is capturing right now. Other platforms
+//  have no equivalent, so there we rely on the known-recorder list
+//  alone (see `[monitor.screen_share] recorders`).
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::HashSet;
+
+use crate::monitor::silent_cmd;
+
+/// Consent-store capabilities that mean "capturing the screen".
+const CAPTURE_CAPABILITIES: &[&str] = &["graphicsCaptureProgrammatic", "graphicsCaptureWithoutBorder"];
+
+/// Executable names (lowercase, no `.exe`) with a screen capture in
+/// progress, where the OS exposes that. Blocking.
+pub fn active_capturers() -> HashSet<String> {
+    if !cfg!(target_os = "windows") {
+        return HashSet::new();
+    }
+
+    // The agent usually runs as SYSTEM, so look at every loaded user hive
+    // rather than HKCU.
+    let Ok(users) = silent_cmd("reg").args(["query", "HKU"]).output() else {
+        return HashSet::new();
+    };
+    let mut active = HashSet::new();
+    for hive in String::from_utf8_lossy(&users.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with("HKEY_USERS\\S-1-5-21-") && !l.ends_with("_Classes"))
+    {
+        for capability in CAPTURE_CAPABILITIES {
+            let key = format!(
+                r"{hive}\Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\{capability}"
+            );
+            if let Ok(out) = silent_cmd("reg").args(["query", &key, "/s"]).output() {
+                active.extend(parse_consent_store(&String::from_utf8_lossy(&out.stdout)));
+            }
+        }
+    }
+    active
+}
+
+//   HKEY_USERS\S-1-5-21-...\graphicsCaptureProgrammatic\NonPackaged\C:#Program Files#obs-studio#bin#64bit#obs64.exe
+//       LastUsedTimeStart    REG_QWORD    0x1d9f0c2a8e4b1c0
+//       LastUsedTimeStop    REG_QWORD    0x0
+fn parse_consent_store(out: &str) -> Vec<String> {
+    let mut active = Vec::new();
+    let mut app: Option<String> = None;
+    let mut started = false;
+    for line in out.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("HKEY_") {
+            // Packaged apps are keyed by package family name, non-packaged
+            // ones by their exe path with '\\' written as '#'.
+            app = trimmed
+                .rsplit('\\')
+                .next()
+                .and_then(|last| last.rsplit('#').next())
+                .map(|exe| exe.to_lowercase().trim_end_matches(".exe").to_string());
+            started = false;
+            continue;
+        }
+        let cols: Vec<&str> = trimmed.split_whitespace().collect();
+        match cols.as_slice() {
+            ["LastUsedTimeStart", _, value] => started = *value != "0x0",
+            ["LastUsedTimeStop", _, "0x0"] if started => {
+                if let Some(app) = app.take() {
+                    active.push(app);
+                }
+            }
+            _ => {}
+        }
+    }
+    active
+}
@@ -20,11 +20,11 @@ pub fn capture_screenshot(quality: u8, max_dimension: u32) -> Result<String> {
                             screen_capture = Some((img, s.display_info));
                             break;
                         }
-                        Err(e) => last_err = e.into(),
+                        Err(e) => last_err = e,
                     }
                 }
             }
-            Err(e) => last_err = e.into(),
+            Err(e) => last_err = e,
         }
         if attempt < 2 {
             std::thread::sleep(std::time::Duration::from_millis(500));
@@ -0,0 +1,476 @@
+// ─────────────────────────────────────────────────────────────────
+//  screenshot_sink.rs — Where screenshot images are kept
+//
+//  `[screenshots] sink` picks one; Redis always gets the metadata
+//  (`screenshot_meta:{hostname}` and its history), only the image moves:
+//    redis: base64 under `data`, which `Store` keeps in Redis as a
+//           binary value (the default)
+//    disk:  a file in `[screenshots.disk] dir`, the newest `keep`
+//           files kept; Redis stores the file name and
+//           `GET /screenshot` reads it back
+//    s3:    an object in an S3-compatible bucket (SigV4), Redis stores
+//           its `key` and a presigned download URL valid for
+//           `url_expiry_secs`; the API presigns a fresh one from the
+//           key when it reads one back, so history outlives the URLs
+//  With the s3 sink, `[streaming] record` archives streamed frames to
+//  the same bucket (see `record`).
+//  With `[encryption] screenshots`, the images of the Redis and disk
+//  sinks and the heartbeat thumbnails are sealed with AES-256-GCM (see
+//  envelope.rs) and the metadata says so (`encryption`, `key_id`); the
+//  API opens them again for its readers. Disk files written before
+//  that ("NSK1": ChaCha20 keystream ‖ HMAC-SHA256, keyed from
+//  `[screenshots.disk] key`) can still be read, never written.
+// ─────────────────────────────────────────────────────────────────
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::config::{ScreenshotConfig, ScreenshotDiskConfig, ScreenshotS3Config};
+use crate::envelope::{self, Sealer};
+use crate::unlock::hmac_sha256;
+
+/// Disk files of the former scheme, still read.
+const LEGACY_MAGIC: &[u8; 4] = b"NSK1";
+const NONCE_LEN: usize = 8;
+const TAG_LEN: usize = 32;
+/// Longest lifetime SigV4 allows for presigned URLs.
+const MAX_URL_EXPIRY_SECS: u64 = 7 * 24 * 3600;
+
+pub struct ScreenshotSink {
+    kind: SinkKind,
+    /// Seals images kept in Redis and thumbnails.
+    sealer: Option<Arc<Sealer>>,
+}
+
+enum SinkKind {
+    Redis,
+    Disk(DiskSink),
+    S3(S3Sink),
+}
+
+impl ScreenshotSink {
+    pub fn new(cfg: &ScreenshotConfig, sealer: Option<Arc<Sealer>>) -> anyhow::Result<Self> {
+        let kind = match cfg.sink.as_str() {
+            "redis" => SinkKind::Redis,
+            "disk" => {
+                let disk = DiskSink::new(&cfg.disk)?;
+                if sealer.is_none() {
+                    warn!("Screenshots are written to {} unencrypted (no [encryption] key)", disk.dir.display());
+                }
+                SinkKind::Disk(disk)
+            }
+            "s3" => SinkKind::S3(S3Sink::new(&cfg.s3)?),
+            other => bail!("unknown screenshots.sink \"{other}\" (expected redis, disk or s3)"),
+        };
+        Ok(Self { kind, sealer })
+    }
+
+    /// Store a capture and return the fields Redis keeps for it; None when
+    /// it couldn't be stored.
+    pub async fn put(&self, hostname: &str, data_b64: &str) -> Option<Map<String, Value>> {
+        let mut fields = Map::new();
+        match &self.kind {
+            SinkKind::Redis => match &self.sealer {
+                Some(sealer) => {
+                    let sealed = sealer.seal(&decode(data_b64)?);
+                    fields.insert("data".into(), base64::engine::general_purpose::STANDARD.encode(sealed).into());
+                    fields.insert("encryption".into(), envelope::ALGORITHM.into());
+                    fields.insert("key_id".into(), sealer.key_id().into());
+                }
+                None => {
+                    fields.insert("data".into(), data_b64.into());
+                }
+            },
+            SinkKind::Disk(disk) => {
+                let mut bytes = decode(data_b64)?;
+                if let Some(sealer) = &self.sealer {
+                    bytes = sealer.seal(&bytes);
+                }
+                let (dir, keep) = (disk.dir.clone(), disk.keep);
+                let name = file_name(hostname, Utc::now(), self.sealer.is_some());
+                let written = {
+                    let name = name.clone();
+                    tokio::task::spawn_blocking(move || write_rotated(&dir, &name, &bytes, keep)).await
+                };
+                if let Err(e) = written.map_err(anyhow::Error::from).and_then(|r| r) {
+                    warn!("Could not write screenshot {name}: {e:#}");
+                    return None;
+                }
+                fields.insert("file".into(), name.into());
+                if let Some(sealer) = &self.sealer {
+                    fields.insert("encryption".into(), envelope::ALGORITHM.into());
+                    fields.insert("key_id".into(), sealer.key_id().into());
+                }
+            }
+            SinkKind::S3(s3) => {
+                let jpeg = decode(data_b64)?;
+                let now = Utc::now();
+                let key = format!("{}{hostname}/{}.jpg", s3.cfg.prefix, now.format("%Y-%m-%d/%H%M%S%.3f"));
+                if let Err(e) = s3.put(&key, jpeg, now).await {
+                    warn!("Could not upload screenshot {key}: {e:#}");
+                    return None;
+                }
+                fields.insert("key".into(), key.into());
+                s3.refresh_url(&mut fields, now);
+            }
+        }
+        fields.insert("size".into(), data_b64.len().into());
+        Some(fields)
+    }
+
+    #[cfg(feature = "streaming")]
+    pub fn is_s3(&self) -> bool {
+        matches!(self.kind, SinkKind::S3(_))
+    }
+
+    #[cfg(feature = "streaming")]
+    /// Upload one frame of the live stream started at `session` to
+    /// `{prefix}{hostname}/recordings/{session}/{frame}.jpg` and return the
+    /// fields Redis keeps for it; None when it couldn't be stored or the
+    /// sink isn't S3.
+    pub async fn record(&self, hostname: &str, session: DateTime<Utc>, frame: u64, jpeg: Vec<u8>) -> Option<Map<String, Value>> {
+        let SinkKind::S3(s3) = &self.kind else {
+            return None;
+        };
+        let now = Utc::now();
+        let session = session.format("%Y%m%dT%H%M%SZ").to_string();
+        let key = format!("{}{hostname}/recordings/{session}/{frame:08}.jpg", s3.cfg.prefix);
+        let size = jpeg.len();
+        if let Err(e) = s3.put(&key, jpeg, now).await {
+            warn!("Could not upload stream frame {key}: {e:#}");
+            return None;
+        }
+        let mut fields = Map::new();
+        fields.insert("session".into(), session.into());
+        fields.insert("frame".into(), frame.into());
+        fields.insert("timestamp".into(), now.to_rfc3339().into());
+        fields.insert("size".into(), size.into());
+        fields.insert("key".into(), key.into());
+        Some(fields)
+    }
+
+    /// Read back and open the image of a screenshot kept on disk
+    /// (`file`), like `unseal`; None for other sinks. Blocking.
+    pub fn image(&self, meta: &mut Map<String, Value>) -> Option<Vec<u8>> {
+        let SinkKind::Disk(disk) = &self.kind else {
+            return None;
+        };
+        // Only a bare file name inside the directory
+        let name = Path::new(meta.get("file")?.as_str()?).file_name()?;
+        let path = disk.dir.join(name);
+        let bytes = std::fs::read(&path).map_err(|e| warn!("Could not read screenshot {}: {e}", path.display())).ok()?;
+        if !bytes.starts_with(LEGACY_MAGIC) {
+            return self.unseal(meta, bytes);
+        }
+        let Some(key) = &disk.legacy_key else {
+            warn!("Screenshot {} predates [encryption] and needs [screenshots.disk] key", path.display());
+            return None;
+        };
+        legacy_decrypt(key, &bytes).map_err(|e| warn!("Could not decrypt screenshot {}: {e:#}", path.display())).ok()
+    }
+
+    /// The plain image of one kept in Redis, dropping the encryption
+    /// fields from `meta` once it is opened; None when it can't be.
+    pub fn unseal(&self, meta: &mut Map<String, Value>, jpeg: Vec<u8>) -> Option<Vec<u8>> {
+        if !envelope::is_sealed(&jpeg) {
+            return Some(jpeg);
+        }
+        let Some(sealer) = &self.sealer else {
+            warn!("Screenshot is encrypted but no [encryption] key is set");
+            return None;
+        };
+        let jpeg = sealer.open(&jpeg).map_err(|e| warn!("Could not decrypt screenshot: {e:#}")).ok()?;
+        meta.remove("encryption");
+        meta.remove("key_id");
+        Some(jpeg)
+    }
+
+    /// Fill in `data` for a stored screenshot kept on disk, and decrypt
+    /// one kept in Redis, so API readers get the image as with the plain
+    /// Redis sink. Others are returned as they are. Blocking.
+    pub fn resolve(&self, mut shot: Value) -> Value {
+        let Some(meta) = shot.as_object_mut() else {
+            return shot;
+        };
+        self.refresh_url(meta);
+        let jpeg = match self.image(meta) {
+            Some(jpeg) => Some(jpeg),
+            None => match meta.get("data").and_then(|d| d.as_str()).and_then(decode) {
+                Some(data) if envelope::is_sealed(&data) => self.unseal(meta, data),
+                _ => return shot,
+            },
+        };
+        match jpeg {
+            Some(jpeg) => {
+                meta.insert("data".into(), base64::engine::general_purpose::STANDARD.encode(jpeg).into());
+            }
+            None => {
+                meta.remove("data");
+            }
+        }
+        shot
+    }
+
+    /// Replace the presigned `url` (and `expires_at`) of an object kept
+    /// in S3 with a fresh one, so links read back from Redis work however
+    /// old the entry is. Others are left alone.
+    pub fn refresh_url(&self, meta: &mut Map<String, Value>) {
+        if let SinkKind::S3(s3) = &self.kind {
+            s3.refresh_url(meta, Utc::now());
+        }
+    }
+
+    /// A base64 heartbeat thumbnail as it should be stored: sealed when
+    /// screenshots are encrypted.
+    pub fn thumbnail(&self, data_b64: String) -> Option<String> {
+        let Some(sealer) = &self.sealer else {
+            return Some(data_b64);
+        };
+        Some(base64::engine::general_purpose::STANDARD.encode(sealer.seal(&decode(&data_b64)?)))
+    }
+}
+
+fn decode(data_b64: &str) -> Option<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(data_b64)
+        .map_err(|e| warn!("Screenshot isn't valid base64: {e}"))
+        .ok()
+}
+
+// ── Disk ────────────────────────────────────────────────────────
+
+struct DiskSink {
+    dir: PathBuf,
+    /// Opens files written before they were sealed with `[encryption]`.
+    legacy_key: Option<Vec<u8>>,
+    keep: usize,
+}
+
+impl DiskSink {
+    fn new(cfg: &ScreenshotDiskConfig) -> anyhow::Result<Self> {
+        let dir = match &cfg.dir {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(|p| p.join("screenshots")))
+                .unwrap_or_else(|| PathBuf::from("screenshots")),
+        };
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating screenshot directory {}", dir.display()))?;
+        let legacy_key = cfg.key.as_deref().filter(|k| !k.is_empty()).map(|k| k.as_bytes().to_vec());
+        info!("Screenshots go to {} (keeping {})", dir.display(), cfg.keep.max(1));
+        Ok(Self { dir, legacy_key, keep: cfg.keep.max(1) })
+    }
+}
+
+/// `pc-07_20261014T093005.120.jpg.ngc`; sorts by time within one host.
+fn file_name(hostname: &str, at: DateTime<Utc>, sealed: bool) -> String {
+    let host: String = hostname.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    format!("{host}_{}.jpg{}", at.format("%Y%m%dT%H%M%S%.3f"), if sealed { ".ngc" } else { "" })
+}
+
+/// Write one screenshot and delete all but the newest `keep`. Blocking.
+fn write_rotated(dir: &Path, name: &str, bytes: &[u8], keep: usize) -> anyhow::Result<()> {
+    // Written under a temporary name so readers never see half a file
+    let tmp = dir.join(format!("{name}.tmp"));
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, dir.join(name))?;
+
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = std::fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            [".jpg", ".jpg.ngc", ".jpg.nsk"].iter().any(|ext| name.ends_with(ext))
+        })
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+    if files.len() > keep {
+        files.sort();
+        for (_, path) in &files[..files.len() - keep] {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("Could not delete old screenshot {}: {e}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Older agents encrypted disk files themselves, with a passphrase; this
+// only reproduces that scheme to read them back.
+
+fn legacy_mac(key: &[u8]) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length")
+}
+
+fn legacy_keys(secret: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let derive = |label: &[u8]| -> [u8; 32] {
+        let mut mac = legacy_mac(secret);
+        mac.update(label);
+        mac.finalize().into_bytes().into()
+    };
+    (derive(b"nishack screenshots: encryption"), derive(b"nishack screenshots: authentication"))
+}
+
+/// Open an "NSK1" file: "NSK1" ‖ 8-byte nonce ‖ ChaCha20 ciphertext ‖
+/// HMAC-SHA256 over everything before it.
+fn legacy_decrypt(secret: &[u8], bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if bytes.len() < LEGACY_MAGIC.len() + NONCE_LEN + TAG_LEN || !bytes.starts_with(LEGACY_MAGIC) {
+        bail!("not an encrypted screenshot");
+    }
+    let (enc_key, mac_key) = legacy_keys(secret);
+    let (signed, tag) = bytes.split_at(bytes.len() - TAG_LEN);
+    let mut mac = legacy_mac(&mac_key);
+    mac.update(signed);
+    if mac.verify_slice(tag).is_err() {
+        bail!("authentication failed (wrong key or damaged file)");
+    }
+    let nonce = u64::from_le_bytes(signed[LEGACY_MAGIC.len()..LEGACY_MAGIC.len() + NONCE_LEN].try_into()?);
+    let mut body = signed[LEGACY_MAGIC.len() + NONCE_LEN..].to_vec();
+    // The keystream those files were written with
+    let mut rng = ChaCha20Rng::from_seed(enc_key);
+    rng.set_stream(nonce);
+    let mut stream = vec![0u8; body.len()];
+    rng.fill_bytes(&mut stream);
+    body.iter_mut().zip(stream).for_each(|(b, k)| *b ^= k);
+    Ok(body)
+}
+
+// ── S3 ──────────────────────────────────────────────────────────
+
+struct S3Sink {
+    cfg: ScreenshotS3Config,
+    /// "https", and the host (with port) requests are signed for.
+    scheme: String,
+    host: String,
+    client: reqwest::Client,
+}
+
+impl S3Sink {
+    fn new(cfg: &ScreenshotS3Config) -> anyhow::Result<Self> {
+        if cfg.bucket.is_empty() || cfg.access_key.is_empty() || cfg.secret_key.is_empty() {
+            bail!("screenshots.sink = \"s3\" needs [screenshots.s3] bucket, access_key and secret_key");
+        }
+        let endpoint = url::Url::parse(&cfg.endpoint).context("invalid [screenshots.s3] endpoint")?;
+        let mut host = endpoint.host_str().context("[screenshots.s3] endpoint has no host")?.to_string();
+        if let Some(port) = endpoint.port() {
+            host = format!("{host}:{port}");
+        }
+        if !cfg.path_style {
+            host = format!("{}.{host}", cfg.bucket);
+        }
+        let client = reqwest::Client::builder().timeout(std::time::Duration::from_secs(30)).build()?;
+        info!("Screenshots go to s3://{} at {host}", cfg.bucket);
+        Ok(Self { cfg: cfg.clone(), scheme: endpoint.scheme().to_string(), host, client })
+    }
+
+    /// URI-encoded path of an object.
+    fn path(&self, key: &str) -> String {
+        let key = uri_encode(key, false);
+        if self.cfg.path_style {
+            format!("/{}/{key}", uri_encode(&self.cfg.bucket, true))
+        } else {
+            format!("/{key}")
+        }
+    }
+
+    fn scope(&self, now: DateTime<Utc>) -> String {
+        format!("{}/{}/s3/aws4_request", now.format("%Y%m%d"), self.cfg.region)
+    }
+
+    fn signature(&self, now: DateTime<Utc>, canonical_request: &str) -> String {
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            now.format("%Y%m%dT%H%M%SZ"),
+            self.scope(now),
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut key = hmac_sha256(format!("AWS4{}", self.cfg.secret_key).as_bytes(), now.format("%Y%m%d").to_string().as_bytes());
+        for part in [self.cfg.region.as_str(), "s3", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        hex(&hmac_sha256(&key, string_to_sign.as_bytes()))
+    }
+
+    async fn put(&self, key: &str, jpeg: Vec<u8>, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let path = self.path(key);
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex(&Sha256::digest(&jpeg));
+        let signed_headers = "content-type;host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "PUT\n{path}\n\ncontent-type:image/jpeg\nhost:{}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}",
+            self.host
+        );
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={signed_headers}, Signature={}",
+            self.cfg.access_key,
+            self.scope(now),
+            self.signature(now, &canonical_request)
+        );
+        let resp = self
+            .client
+            .put(format!("{}://{}{path}", self.scheme, self.host))
+            .header("content-type", "image/jpeg")
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization)
+            .body(jpeg)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("HTTP {status}: {}", body.chars().take(200).collect::<String>());
+        }
+        Ok(())
+    }
+
+    /// Set `url` and `expires_at` of the object under `key` in `meta`,
+    /// presigned at `now`.
+    fn refresh_url(&self, meta: &mut Map<String, Value>, now: DateTime<Utc>) {
+        let Some(key) = meta.get("key").and_then(|k| k.as_str()).map(str::to_owned) else {
+            return;
+        };
+        let expiry = self.cfg.url_expiry_secs.clamp(1, MAX_URL_EXPIRY_SECS);
+        meta.insert("url".into(), self.presign_get(&key, now, expiry).into());
+        meta.insert("expires_at".into(), (now + chrono::Duration::seconds(expiry as i64)).to_rfc3339().into());
+    }
+
+    /// A GET URL for `key` anyone can use for `expiry_secs`.
+    fn presign_get(&self, key: &str, now: DateTime<Utc>, expiry_secs: u64) -> String {
+        let path = self.path(key);
+        let credential = format!("{}/{}", self.cfg.access_key, self.scope(now));
+        // Already in the sorted order SigV4 wants
+        let query = format!(
+            "X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential={}&X-Amz-Date={}&X-Amz-Expires={expiry_secs}&X-Amz-SignedHeaders=host",
+            uri_encode(&credential, true),
+            now.format("%Y%m%dT%H%M%SZ")
+        );
+        let canonical_request = format!("GET\n{path}\n{query}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD", self.host);
+        format!("{}://{}{path}?{query}&X-Amz-Signature={}", self.scheme, self.host, self.signature(now, &canonical_request))
+    }
+}
+
+/// AWS URI encoding: everything but unreserved characters, and `/` too
+/// when `slash` is set.
+fn uri_encode(s: &str, slash: bool) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            b'/' if !slash => "/".to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,188 @@
+// ─────────────────────────────────────────────────────────────────
+//  self_view.rs — What the agent does, shown to the student
+//
+//  `GET /me` serves one self-contained, read-only HTML page (in the
+//  same Russian as the weekly report) that anyone at the machine can
+//  open in a browser (http://localhost:{port}/me). No token needed:
+//    monitored   the detectors and captures switched on in the config
+//                (as advertised in the capabilities) and the schedule
+//    now         exam mode, soft lock and focus mode
+//    actions     the last `[self_view] days` of the host's timeline —
+//                violations, locks, screen views, screenshots — minus
+//                the usage samples
+//    retention   `[self_view] retention` from the school plus how long
+//                the agent itself keeps each kind of record
+// ─────────────────────────────────────────────────────────────────
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::config::AppConfig;
+use crate::models::{Capabilities, TimelineEvent};
+use crate::report::escape;
+use crate::store::{ATTENDANCE_TTL_SECS, AUDIT_HISTORY, USAGE_HISTORY};
+
+/// Actions listed at most, newest first.
+const MAX_ACTIONS: usize = 100;
+
+/// What the page shows besides the config.
+pub struct Context {
+    pub hostname: String,
+    pub capabilities: Capabilities,
+    pub exam: bool,
+    pub soft_lock: bool,
+    pub focus_app: Option<String>,
+    /// The host's timeline over the last `[self_view] days`, oldest first.
+    pub events: Vec<TimelineEvent>,
+}
+
+/// Render the page.
+pub fn html(cfg: &AppConfig, ctx: &Context) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Что видит агент — {host}</title>\
+         <style>body{{font-family:sans-serif;max-width:60em}}table{{border-collapse:collapse;margin-bottom:1em}}\
+         td,th{{border:1px solid #ccc;padding:4px 8px;text-align:left}}</style></head><body>\
+         <h1>Что видит агент на компьютере {host}</h1>\
+         <p>Эта страница показывает, что школьный агент наблюдает на этом компьютере, \
+         что с ним делали и как долго хранятся данные.</p>",
+        host = escape(&ctx.hostname),
+    );
+
+    out.push_str("<h2>Что отслеживается</h2><ul>");
+    for item in monitored(cfg, &ctx.capabilities) {
+        out.push_str(&format!("<li>{}</li>", escape(&item)));
+    }
+    out.push_str("</ul>");
+
+    out.push_str("<h2>Сейчас</h2><ul>");
+    out.push_str(&format!("<li>Режим экзамена: {}</li>", on_off(ctx.exam)));
+    out.push_str(if ctx.soft_lock { "<li>Экран заблокирован</li>" } else { "<li>Экран не заблокирован</li>" });
+    match &ctx.focus_app {
+        Some(app) => out.push_str(&format!("<li>Режим фокуса: включён ({})</li>", escape(app))),
+        None => out.push_str("<li>Режим фокуса: выключен</li>"),
+    }
+    out.push_str("</ul>");
+
+    let actions: Vec<&TimelineEvent> =
+        ctx.events.iter().rev().filter(|e| e.source != "usage").take(MAX_ACTIONS).collect();
+    out.push_str(&format!("<h2>Действия за последние {} дн.</h2>", cfg.self_view.days));
+    if actions.is_empty() {
+        out.push_str("<p>Ничего не записано.</p>");
+    } else {
+        out.push_str("<table><tr><th>Время</th><th>Что</th><th>Подробности</th></tr>");
+        for e in actions {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                local(e.timestamp),
+                source_label(e.source),
+                escape(&e.summary),
+            ));
+        }
+        out.push_str("</table>");
+    }
+
+    out.push_str("<h2>Хранение данных</h2>");
+    if let Some(policy) = cfg.self_view.retention.as_deref().filter(|p| !p.trim().is_empty()) {
+        out.push_str(&format!("<p>{}</p>", escape(policy).replace('\n', "<br>")));
+    }
+    out.push_str("<ul>");
+    for item in retention(cfg) {
+        out.push_str(&format!("<li>{}</li>", escape(&item)));
+    }
+    out.push_str("</ul></body></html>");
+    out
+}
+
+/// One line per thing the agent looks at.
+fn monitored(cfg: &AppConfig, caps: &Capabilities) -> Vec<String> {
+    let mut items = vec!["Запущенные программы и заголовки окон".to_string()];
+    if caps.screenshots {
+        items.push(format!("Снимки экрана каждые {} с", cfg.screenshots.interval));
+    }
+    if cfg.screenshots.heartbeat_thumbnail && caps.screenshots {
+        items.push("Уменьшенная копия экрана для учителя".into());
+    }
+    if caps.streaming {
+        items.push("Трансляция экрана учителю по запросу".into());
+    }
+    if cfg.monitor.network.enabled {
+        items.push("Подключённая сеть (Wi-Fi, точка доступа)".into());
+    }
+    items.extend(caps.features.iter().filter_map(|f| feature_label(f)).map(String::from));
+    for block in &cfg.schedule {
+        items.push(format!("Расписание «{}»: {} {}–{}", block.name, block.days.join(", "), block.start, block.end));
+    }
+    items
+}
+
+fn feature_label(feature: &str) -> Option<&'static str> {
+    Some(match feature {
+        "browser_history" => "История браузера",
+        "downloads" => "Загруженные файлы",
+        "installed_apps" => "Установленные программы",
+        "documents" => "Открытые документы",
+        "banned_keywords" => "Запрещённые слова в заголовках окон",
+        "categories" => "Категории открытых сайтов",
+        "dns_sniffer" | "dns_bypass" => "Адреса открываемых сайтов (DNS)",
+        "connections" => "Сетевые подключения программ",
+        "bandwidth" => "Объём сетевого трафика",
+        "resource_abuse" => "Нагрузка на процессор и видеокарту",
+        "remote_access" => "Программы удалённого доступа",
+        "screen_share" => "Демонстрация экрана в других программах",
+        "audio" => "Воспроизведение звука",
+        "webcam_use" => "Использование веб-камеры",
+        "vpn_proxy" => "VPN и прокси",
+        "tamper" => "Попытки отключить агент",
+        "hosts_file" | "firewall" => "Блокировка запрещённых сайтов",
+        // Not monitoring
+        _ => return None,
+    })
+}
+
+/// How long each kind of record is kept by the agent and in Redis.
+fn retention(cfg: &AppConfig) -> Vec<String> {
+    let mut items = vec![
+        match cfg.redis.violation_retention_days {
+            0 => "Нарушения хранятся на школьном сервере, пока их не удалит администратор.".to_string(),
+            days => format!("Нарушения: {days} дней на школьном сервере."),
+        },
+        format!("Журнал действий администраторов: последние {AUDIT_HISTORY} записей на сервере, полностью — на этом компьютере."),
+        format!("Замеры нагрузки: последние {USAGE_HISTORY}."),
+        format!("Посещаемость: {} дней.", ATTENDANCE_TTL_SECS / 86_400),
+    ];
+    if cfg.screenshots.enabled {
+        items.push(match cfg.screenshots.sink.as_str() {
+            "disk" => format!("Снимки экрана: последние {} в папке на этом компьютере.", cfg.screenshots.disk.keep),
+            "s3" => "Снимки экрана: в хранилище школы, по его правилам хранения.".into(),
+            _ => format!("Снимки экрана: последние {} на сервере.", cfg.screenshots.history),
+        });
+        items.push(format!(
+            "Снимки класса по команде учителя: {} мин.",
+            cfg.screenshots.snapshot_ttl_secs / 60
+        ));
+    }
+    if cfg.streaming.enabled {
+        items.push("Трансляция экрана не записывается агентом.".into());
+    }
+    items
+}
+
+fn source_label(source: &str) -> &'static str {
+    match source {
+        "violation" => "Нарушение",
+        "lock" => "Блокировка",
+        "session" => "Запуск / остановка агента",
+        "screen_access" => "Просмотр экрана",
+        "screenshot" => "Снимок экрана",
+        "detector_failure" => "Сбой проверки",
+        "app" => "Программы",
+        _ => "Действие администратора",
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on { "включён" } else { "выключен" }
+}
+
+fn local(t: DateTime<Utc>) -> String {
+    t.with_timezone(&Local).format("%d.%m.%Y %H:%M").to_string()
+}
@@ -0,0 +1,171 @@
+// ─────────────────────────────────────────────────────────────────
+//  selfstat.rs — The agent's own resource usage
+//
+//  Admins need evidence the agent isn't what slows lab PCs down, so
+//  we sample our own CPU, memory, handle and socket counts, plus the
+//  bytes written to Redis in the last hour, and warn when any of
+//  them exceeds its configured budget. Outside Linux the handle and
+//  socket counts take a PowerShell / lsof run and a connection table
+//  walk, so those are refreshed every `COUNTS_EVERY` only.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sysinfo::{Pid, ProcessesToUpdate, System};
+use tracing::warn;
+
+use crate::config::SelfReportConfig;
+use crate::models::SelfStats;
+use crate::store::Store;
+
+/// Oldest shelled-out handle / socket counts a sample may reuse.
+const COUNTS_EVERY: Duration = Duration::from_secs(300);
+
+/// (open handles / fds, sockets).
+type Counts = (Option<u64>, Option<u64>);
+
+struct Inner {
+    sys: System,
+    /// (when, Store::bytes_written) samples covering the last hour.
+    redis_samples: VecDeque<(Instant, u64)>,
+    /// Last shelled-out counts and when they were taken (not on Linux).
+    counts: Option<(Instant, Counts)>,
+    latest: Option<SelfStats>,
+}
+
+/// Samples and remembers the agent's footprint. Shared between the
+/// heartbeat loop (which samples) and the API (which reads `latest`).
+pub struct SelfMonitor {
+    pid: Pid,
+    budget: SelfReportConfig,
+    inner: Mutex<Inner>,
+}
+
+impl SelfMonitor {
+    pub fn new(budget: &SelfReportConfig) -> Self {
+        Self {
+            pid: Pid::from_u32(std::process::id()),
+            budget: budget.clone(),
+            inner: Mutex::new(Inner {
+                sys: System::new(),
+                redis_samples: VecDeque::new(),
+                counts: None,
+                latest: None,
+            }),
+        }
+    }
+
+    /// Most recent sample, if any was taken yet.
+    pub fn latest(&self) -> Option<SelfStats> {
+        self.inner.lock().ok()?.latest.clone()
+    }
+
+    /// Take a fresh sample (blocking: may shell out for handle counts).
+    pub fn sample(&self, store: &Store) -> SelfStats {
+        let cached = self
+            .inner
+            .lock()
+            .expect("selfstat mutex poisoned")
+            .counts
+            .filter(|(at, _)| at.elapsed() < COUNTS_EVERY)
+            .map(|(_, counts)| counts);
+        let (handles, sockets) = cached.unwrap_or_else(|| handle_and_socket_counts(self.pid.as_u32()));
+        let mut inner = self.inner.lock().expect("selfstat mutex poisoned");
+        if cached.is_none() && !cfg!(target_os = "linux") {
+            inner.counts = Some((Instant::now(), (handles, sockets)));
+        }
+
+        inner.sys.refresh_processes(ProcessesToUpdate::Some(&[self.pid]));
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as f32;
+        let (cpu_percent, rss_mb) = inner
+            .sys
+            .process(self.pid)
+            .map(|p| (p.cpu_usage() / cores, p.memory() / 1_048_576))
+            .unwrap_or_default();
+
+        let now = Instant::now();
+        let written = store.bytes_written();
+        inner.redis_samples.push_back((now, written));
+        while inner
+            .redis_samples
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > Duration::from_secs(3600))
+        {
+            inner.redis_samples.pop_front();
+        }
+        let oldest = inner.redis_samples.front().map(|(_, b)| *b).unwrap_or(written);
+        let redis_bytes_last_hour = written - oldest;
+
+        let mut over_budget = Vec::new();
+        if cpu_percent > self.budget.max_cpu_percent {
+            over_budget.push("cpu".to_string());
+        }
+        if rss_mb > self.budget.max_rss_mb {
+            over_budget.push("rss".to_string());
+        }
+        if handles.is_some_and(|h| h > self.budget.max_handles) {
+            over_budget.push("handles".to_string());
+        }
+        if redis_bytes_last_hour > self.budget.max_redis_mb_per_hour * 1_048_576 {
+            over_budget.push("redis_bytes".to_string());
+        }
+        if !over_budget.is_empty() {
+            warn!(
+                "⚠️  Agent over resource budget {:?}: cpu {cpu_percent:.1}%, rss {rss_mb} MB, handles {:?}, redis {} KB/h",
+                over_budget, handles, redis_bytes_last_hour / 1024
+            );
+        }
+
+        let stats = SelfStats {
+            cpu_percent,
+            rss_mb,
+            handles,
+            sockets,
+            redis_bytes_last_hour,
+            over_budget,
+        };
+        inner.latest = Some(stats.clone());
+        stats
+    }
+}
+
+/// (open handles / fds, sockets) for `pid`: cheap from /proc on Linux,
+/// a command run elsewhere.
+fn handle_and_socket_counts(pid: u32) -> Counts {
+    if cfg!(target_os = "linux") {
+        let Ok(entries) = std::fs::read_dir("/proc/self/fd") else {
+            return (None, None);
+        };
+        let (mut fds, mut sockets) = (0, 0);
+        for e in entries.flatten() {
+            fds += 1;
+            if std::fs::read_link(e.path()).is_ok_and(|l| l.to_string_lossy().starts_with("socket:")) {
+                sockets += 1;
+            }
+        }
+        return (Some(fds), Some(sockets));
+    }
+
+    let sockets = crate::netstat::established_tcp()
+        .iter()
+        .filter(|c| c.pid == pid)
+        .count() as u64;
+
+    let handles = if cfg!(target_os = "windows") {
+        crate::monitor::silent_cmd("powershell")
+            .args(["-NoProfile", "-Command", &format!("(Get-Process -Id {pid}).HandleCount")])
+            .output()
+            .ok()
+            .and_then(|o| String::from_utf8_lossy(&o.stdout).trim().parse().ok())
+    } else {
+        crate::monitor::silent_cmd("lsof")
+            .args(["-p", &pid.to_string()])
+            .output()
+            .ok()
+            .map(|o| String::from_utf8_lossy(&o.stdout).lines().count().saturating_sub(1) as u64)
+    };
+
+    (handles, Some(sockets))
+}
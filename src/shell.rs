@@ -0,0 +1,157 @@
+// ─────────────────────────────────────────────────────────────────
+//  shell.rs — Admin remote terminal over WebSocket
+//
+//  Bridges a pseudo-terminal running the platform shell to the
+//  dashboard's terminal widget:
+//    client → agent: binary / text frames are keystrokes; a text
+//                    frame {"resize": {"cols": N, "rows": N}} resizes
+//    agent → client: binary frames of raw terminal output
+//  Every byte in both directions is recorded in an audit transcript.
+// ─────────────────────────────────────────────────────────────────
+
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket};
+use futures_util::{SinkExt, StreamExt};
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::audit::{Audit, Transcript};
+
+const INITIAL_COLS: u16 = 120;
+const INITIAL_ROWS: u16 = 32;
+
+#[derive(Deserialize)]
+struct Control {
+    resize: Resize,
+}
+
+#[derive(Deserialize)]
+struct Resize {
+    cols: u16,
+    rows: u16,
+}
+
+/// The shell a technician expects on this OS.
+fn default_shell() -> CommandBuilder {
+    if cfg!(target_os = "windows") {
+        CommandBuilder::new("powershell.exe")
+    } else {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into());
+        let mut cmd = CommandBuilder::new(shell);
+        cmd.arg("-l");
+        cmd.env("TERM", "xterm-256color");
+        cmd
+    }
+}
+
+/// Run one shell session until either side hangs up.
+pub async fn run_session(socket: WebSocket, audit: Audit, actor: String) {
+    let transcript = match audit.transcript("shell", INITIAL_COLS, INITIAL_ROWS) {
+        Ok(t) => Arc::new(t),
+        Err(e) => {
+            // No transcript, no session — unaudited shells aren't allowed.
+            warn!("Cannot start shell transcript: {e}");
+            return;
+        }
+    };
+    audit
+        .record("shell_opened", &actor, Some(transcript.path.display().to_string()))
+        .await;
+    let started = std::time::Instant::now();
+
+    let result = bridge(socket, Arc::clone(&transcript)).await;
+    let detail = match &result {
+        Ok(()) => format!("{}s, transcript {}", started.elapsed().as_secs(), transcript.path.display()),
+        Err(e) => format!("failed after {}s: {e}", started.elapsed().as_secs()),
+    };
+    info!("🖥️  Shell session for {actor} ended ({detail})");
+    audit.record("shell_closed", &actor, Some(detail)).await;
+}
+
+async fn bridge(socket: WebSocket, transcript: Arc<Transcript>) -> anyhow::Result<()> {
+    let pair = native_pty_system().openpty(PtySize {
+        rows: INITIAL_ROWS,
+        cols: INITIAL_COLS,
+        pixel_width: 0,
+        pixel_height: 0,
+    })?;
+    let mut child = pair.slave.spawn_command(default_shell())?;
+    drop(pair.slave);
+    let master = pair.master;
+    let mut reader = master.try_clone_reader()?;
+    let mut writer = master.take_writer()?;
+
+    // PTY reads block, so output is pumped on its own thread.
+    let (out_tx, mut out_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(64);
+    {
+        let transcript = Arc::clone(&transcript);
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(n @ 1..) = reader.read(&mut buf) {
+                transcript.output(&buf[..n]);
+                if out_tx.blocking_send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    // Likewise for writes.
+    let (in_tx, in_rx) = std::sync::mpsc::channel::<Vec<u8>>();
+    {
+        let transcript = Arc::clone(&transcript);
+        std::thread::spawn(move || {
+            for data in in_rx {
+                transcript.input(&data);
+                if writer.write_all(&data).and_then(|_| writer.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    loop {
+        tokio::select! {
+            out = out_rx.recv() => match out {
+                Some(data) => {
+                    if ws_tx.send(Message::Binary(data)).await.is_err() {
+                        break;
+                    }
+                }
+                // Shell exited
+                None => {
+                    let _ = ws_tx.send(Message::Close(None)).await;
+                    break;
+                }
+            },
+            msg = ws_rx.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    if let Ok(ctl) = serde_json::from_str::<Control>(&text) {
+                        let Resize { cols, rows } = ctl.resize;
+                        transcript.resize(cols, rows);
+                        let _ = master.resize(PtySize { rows, cols, pixel_width: 0, pixel_height: 0 });
+                    } else if in_tx.send(text.into_bytes()).is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Binary(data))) => {
+                    if in_tx.send(data).is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    // Hang-up from the dashboard: don't leave the shell running.
+    if child.try_wait().ok().flatten().is_none() {
+        let _ = child.kill();
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+// ─────────────────────────────────────────────────────────────────
+//  snapshot.rs — Room-wide screenshots taken at the same instant
+//
+//  The teacher publishes one `snapshot` command with a wall-clock
+//  time `at` to the room channel; every agent sleeps until then and
+//  captures, so the snapshots line up. Instants are read on the
+//  Redis server's clock (see `Store::clock_offset`), not the PC's,
+//  so a drifting lab clock doesn't spread the captures out. Each
+//  agent adds its screenshot to the Hash `snapshot:{id}` with the
+//  moment it was taken and its distance from `at` (`skew_ms`); the
+//  image itself goes through the screenshot sink like any other.
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+
+use crate::config::ScreenshotConfig;
+use crate::screenshot_sink::ScreenshotSink;
+use crate::store::Store;
+
+/// Furthest ahead a snapshot can be scheduled.
+const MAX_LEAD: Duration = Duration::from_secs(600);
+
+/// Schedule a capture at `at` (Redis clock) and return how many
+/// milliseconds away it is; Err when `at` is already past the
+/// tolerance or too far ahead.
+pub async fn schedule(
+    cfg: ScreenshotConfig,
+    store: Store,
+    sink: Arc<ScreenshotSink>,
+    hostname: String,
+    id: String,
+    at: DateTime<Utc>,
+) -> Result<i64, String> {
+    let offset = store.clock_offset().await.unwrap_or_else(|| {
+        warn!("Could not read the Redis clock; snapshot {id} uses the local clock");
+        chrono::Duration::zero()
+    });
+    let lead = at - (Utc::now() + offset);
+    if lead < -chrono::Duration::milliseconds(cfg.snapshot_tolerance_ms as i64) {
+        return Err(format!("snapshot time passed {} ms ago", -lead.num_milliseconds()));
+    }
+    if lead.to_std().is_ok_and(|l| l > MAX_LEAD) {
+        return Err(format!("snapshot time is more than {} s ahead", MAX_LEAD.as_secs()));
+    }
+
+    let wait = lead.to_std().unwrap_or_default();
+    info!("📸 Snapshot {id} scheduled in {} ms (clock offset {} ms)", wait.as_millis(), offset.num_milliseconds());
+    tokio::spawn(async move {
+        tokio::time::sleep(wait).await;
+        let taken_at = Utc::now() + offset;
+        let (quality, dim) = (cfg.quality, cfg.max_dimension);
+        let capture = tokio::task::spawn_blocking(move || crate::screenshot::try_capture_screenshot(quality, dim));
+        let Ok(Ok(Some(data))) = tokio::time::timeout(Duration::from_secs(15), capture).await else {
+            warn!("Snapshot {id}: capture failed");
+            return;
+        };
+        let Some(fields) = sink.put(&hostname, &data).await else {
+            return;
+        };
+        let mut payload = serde_json::json!({
+            "hostname": hostname,
+            "at": at,
+            "taken_at": taken_at,
+            "skew_ms": (taken_at - at).num_milliseconds(),
+        });
+        if let Some(m) = payload.as_object_mut() {
+            m.extend(fields);
+        }
+        if store.push_snapshot(&id, &hostname, &payload.to_string(), cfg.snapshot_ttl_secs).await {
+            info!("📸 Snapshot {id} stored ({:+} ms from target)", (taken_at - at).num_milliseconds());
+        }
+    });
+    Ok(lead.num_milliseconds().max(0))
+}
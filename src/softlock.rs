@@ -0,0 +1,310 @@
+// ─────────────────────────────────────────────────────────────────
+//  softlock.rs — Soft lock that stays on until the teacher lifts it
+//
+//  `POST /lock/soft` minimises every window; a single minimise is
+//  undone with one click, so the lock is a mode instead: every
+//  `[lock] soft_reassert_secs` the agent checks whether windows were
+//  brought back and minimises them again, until `POST /unlock`.
+//  Restores are reported as one `lock_circumvention` violation per
+//  lock, its occurrences counting the attempts. With unlock codes on,
+//  a prompt for one stays open on the screen (see `unlock.rs`) and is
+//  left out of the check:
+//    Windows: visible, non-minimised top-level windows (user32)
+//    macOS:   windows of visible apps whose AXMinimized is false
+//    Linux:   the window manager's "showing the desktop" mode is off
+// ─────────────────────────────────────────────────────────────────
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::audit::Audit;
+use crate::config::LockConfig;
+use crate::models::{Violation, ViolationKind};
+use crate::monitor::silent_cmd;
+use crate::notify;
+use crate::violation_sinks::ViolationSinks;
+use crate::unlock::{self, Redeemed, UnlockCodes};
+
+/// Pause before the code prompt is shown again after it was dismissed.
+const PROMPT_RETRY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SoftLockStatus {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    /// Times windows were found restored during the current lock.
+    pub circumventions: u32,
+}
+
+#[derive(Default)]
+struct Inner {
+    since: Option<DateTime<Utc>>,
+    /// The lock's circumvention violation, once one was reported.
+    violation: Option<Violation>,
+    /// PID of the unlock-code prompt while it is open.
+    prompt: Option<u32>,
+    /// When the prompt was last closed.
+    prompt_closed: Option<Instant>,
+}
+
+/// Shared soft-lock state (API, the re-enforcement loop and heartbeats).
+#[derive(Default)]
+pub struct SoftLock {
+    inner: Mutex<Inner>,
+}
+
+impl SoftLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.inner.lock().is_ok_and(|i| i.since.is_some())
+    }
+
+    pub fn status(&self) -> SoftLockStatus {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        SoftLockStatus {
+            active: inner.since.is_some(),
+            since: inner.since,
+            circumventions: inner.violation.as_ref().map_or(0, |v| v.occurrences),
+        }
+    }
+
+    /// Minimise all windows and keep them minimised until `stop`. Stays
+    /// off when the first minimise fails. Blocking.
+    pub fn start(&self) -> Option<SoftLockStatus> {
+        if !minimize_all() {
+            return None;
+        }
+        {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            if inner.since.is_none() {
+                info!("🔒 Soft lock on");
+                *inner = Inner { since: Some(Utc::now()), ..Default::default() };
+            }
+        }
+        Some(self.status())
+    }
+
+    /// Leave soft lock; windows are left as they are.
+    pub fn stop(&self) -> SoftLockStatus {
+        let status = self.status();
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.since.take().is_some() {
+            info!("🔓 Soft lock off ({} circumvention attempt(s))", status.circumventions);
+        }
+        status
+    }
+
+    fn prompt(&self) -> Option<u32> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).prompt
+    }
+
+    /// Whether the code prompt should be (re)opened now.
+    fn prompt_due(&self) -> bool {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.prompt.is_none() && inner.prompt_closed.is_none_or(|t| t.elapsed() >= PROMPT_RETRY)
+    }
+
+    fn set_prompt(&self, pid: Option<u32>) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if pid.is_none() {
+            inner.prompt_closed = Some(Instant::now());
+        }
+        inner.prompt = pid;
+    }
+
+    /// Count a restore during the current lock. Returns the lock's
+    /// violation, new (`occurrences == 1`) or with the count bumped;
+    /// None if the lock was lifted meanwhile.
+    fn circumvented(&self, hostname: &str, username: &str) -> Option<Violation> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let since = inner.since?;
+        let v = match inner.violation.as_mut() {
+            Some(v) => {
+                v.occurrences += 1;
+                v.last_seen = Some(Utc::now());
+                v
+            }
+            None => inner.violation.insert(Violation {
+                hostname: hostname.to_string(),
+                target: "soft_lock".into(),
+                kind: ViolationKind::LockCircumvention,
+                action_taken: true,
+                username: username.to_string(),
+                timestamp: Utc::now(),
+                url: None,
+                visited_at: None,
+                detail: Some(format!("windows restored during soft lock (locked since {})", since.to_rfc3339())),
+                process: None,
+                occurrences: 1,
+                last_seen: None,
+                usage: None,
+                exe: None,
+            }),
+        };
+        Some(v.clone())
+    }
+}
+
+/// Re-minimise while soft lock is on and report restores; keep the
+/// unlock-code prompt up when `codes` are configured. Runs forever.
+pub async fn run(
+    cfg: LockConfig,
+    lock: Arc<SoftLock>,
+    codes: Option<Arc<UnlockCodes>>,
+    sinks: ViolationSinks,
+    audit: Audit,
+    hostname: String,
+    username: String,
+) {
+    let interval = Duration::from_secs(cfg.soft_reassert_secs.max(1));
+    loop {
+        tokio::time::sleep(interval).await;
+        if !lock.is_active() {
+            continue;
+        }
+        if let Some(codes) = codes.as_ref().filter(|c| c.prompt_enabled() && lock.prompt_due()) {
+            open_prompt(&lock, codes, &audit);
+        }
+        let prompt = lock.prompt();
+        // Showing the desktop would hide the prompt too
+        if prompt.is_some() && cfg!(target_os = "linux") {
+            continue;
+        }
+        let restored = tokio::task::spawn_blocking(move || windows_restored(prompt)).await.ok().flatten();
+        if restored == Some(false) {
+            continue;
+        }
+        // Unknown (no check on this desktop) is re-minimised all the same
+        if !tokio::task::spawn_blocking(minimize_all).await.unwrap_or(false) {
+            warn!("Soft lock: could not minimise windows again");
+        }
+        if restored != Some(true) {
+            continue;
+        }
+        let Some(v) = lock.circumvented(&hostname, &username) else {
+            continue;
+        };
+        warn!("🔒 Soft lock circumvented (attempt {})", v.occurrences);
+        if v.occurrences > 1 {
+            sinks.update(&v).await;
+        } else {
+            sinks.deliver(&v).await;
+        }
+    }
+}
+
+/// Show the code prompt and handle what is typed into it in the background.
+fn open_prompt(lock: &Arc<SoftLock>, codes: &Arc<UnlockCodes>, audit: &Audit) {
+    let child = match unlock::spawn_prompt("Компьютер заблокирован. Введите код разблокировки от учителя:") {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Could not show the unlock-code prompt: {e}");
+            lock.set_prompt(None);
+            return;
+        }
+    };
+    lock.set_prompt(Some(child.id()));
+    let (lock, codes, audit) = (Arc::clone(lock), Arc::clone(codes), audit.clone());
+    tokio::spawn(async move {
+        let typed = tokio::task::spawn_blocking(move || child.wait_with_output())
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+            .unwrap_or_default();
+        lock.set_prompt(None);
+        if typed.is_empty() || !lock.is_active() {
+            return;
+        }
+        let outcome = codes.redeem(&typed, lock.status().since).await;
+        codes.audit_rejection(outcome, &audit, "student").await;
+        match outcome {
+            Redeemed::Accepted => {
+                let status = lock.stop();
+                let detail = format!("soft, by unlock code, {} circumvention attempt(s)", status.circumventions);
+                audit.record("unlock", "unlock code", Some(detail)).await;
+            }
+            Redeemed::Rejected { .. } => {
+                warn!("🔒 Wrong unlock code entered");
+                notify::warn_user("nishack", "Неверный или уже использованный код разблокировки.", 5);
+            }
+            Redeemed::LockedOut { retry_in_secs } => {
+                let message = format!("Слишком много неверных кодов. Повторите через {} мин.", retry_in_secs.div_ceil(60));
+                notify::warn_user("nishack", &message, 5);
+            }
+        }
+    });
+}
+
+/// Minimise all windows (Win: Shell.Application, macOS: AppleScript, Linux: wmctrl).
+pub fn minimize_all() -> bool {
+    let status = if cfg!(target_os = "windows") {
+        // Use the Shell.Application COM object to toggle desktop (minimise all)
+        silent_cmd("powershell")
+            .args(["-WindowStyle", "Hidden", "-Command", "(New-Object -ComObject Shell.Application).MinimizeAll()"])
+            .status()
+    } else if cfg!(target_os = "macos") {
+        silent_cmd("osascript")
+            .args(["-e", r#"tell application "System Events" to keystroke "m" using {command down, option down}"#])
+            .status()
+    } else {
+        silent_cmd("wmctrl").args(["-k", "on"]).status()
+    };
+    status.map(|s| s.success()).unwrap_or(false)
+}
+
+const WIN_RESTORED_SCRIPT: &str = r#"
+Add-Type -Namespace NisHack -Name Win -MemberDefinition '
+[DllImport("user32.dll")] public static extern bool IsWindowVisible(IntPtr h);
+[DllImport("user32.dll")] public static extern bool IsIconic(IntPtr h);'
+@(Get-Process | Where-Object {
+    $_.Id -ne {except} -and $_.MainWindowHandle -ne 0 -and
+    [NisHack.Win]::IsWindowVisible($_.MainWindowHandle) -and
+    -not [NisHack.Win]::IsIconic($_.MainWindowHandle)
+}).Count
+"#;
+
+const MAC_RESTORED_SCRIPT: &str = r#"
+set n to 0
+tell application "System Events"
+    repeat with p in (every process whose visible is true and background only is false and unix id is not {except})
+        try
+            set n to n + (count (windows of p whose value of attribute "AXMinimized" is false))
+        end try
+    end repeat
+end tell
+return n
+"#;
+
+/// Whether any application window other than process `except`'s is
+/// showing again. None when the desktop can't tell.
+fn windows_restored(except: Option<u32>) -> Option<bool> {
+    if cfg!(target_os = "linux") {
+        // Window manager's "showing the desktop" mode: ON
+        let out = silent_cmd("wmctrl").arg("-m").output().ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+        let mode = text.lines().find(|l| l.contains("showing the desktop"))?;
+        return Some(mode.trim_end().ends_with("OFF"));
+    }
+    let except = except.unwrap_or_default().to_string();
+    let out = if cfg!(target_os = "windows") {
+        let script = WIN_RESTORED_SCRIPT.replace("{except}", &except);
+        silent_cmd("powershell").args(["-NoProfile", "-Command", &script]).output().ok()?
+    } else {
+        let script = MAC_RESTORED_SCRIPT.replace("{except}", &except);
+        silent_cmd("osascript").args(["-e", &script]).output().ok()?
+    };
+    if !out.status.success() {
+        return None;
+    }
+    let count: u32 = String::from_utf8_lossy(&out.stdout).trim().parse().ok()?;
+    Some(count > 0)
+}
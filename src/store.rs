@@ -1,31 +1,149 @@
-use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use redis::AsyncCommands;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
+
+use crate::battery;
+use crate::config::{EventStorage, RedisConfig, RedisMode, ScreenshotConfig, StorageConfig, StorageKind, TagsConfig};
+use crate::models::{
+    AppChange, AttendanceDay, AuditEvent, BanConfig, BanDiff, BanLayer, Capabilities, CategoryList, DetectorFailure,
+    DiskSpace, Heartbeat, HeartbeatExtras, InstalledApp, OpenDocument, Screenshot, SystemSnapshot, TeacherEndpoint,
+    TimelineEvent, Violation, ViolationKind, WeeklyReport,
+};
+use crate::outbox::{Outbox, Pending, QueuedOp};
+use crate::pg_store::PgStore;
+use crate::store_metrics::StoreMetrics;
+
+/// Usage samples kept per host (a day at the default 30 s heartbeat).
+pub(crate) const USAGE_HISTORY: isize = 2880;
+
+/// Newest entries read from each list when building a timeline.
+const TIMELINE_SCAN: isize = 5000;
+
+/// Newest violations searched for the record a repeat belongs to.
+const DEDUP_SCAN: isize = 200;
+
+/// Violations read at a time from the old end of a list when trimming.
+const RETENTION_SCAN: isize = 500;
+
+/// Audit events mirrored per host.
+pub(crate) const AUDIT_HISTORY: isize = 500;
+
+/// Histories that `[redis] events` can move into room streams.
+const STREAMED: &[&str] = &["violations", "usage", "screenshot_history"];
 
-use crate::config::RedisConfig;
-use crate::models::{Heartbeat, Violation, ViolationKind};
+/// Days of attendance kept, enough for a monthly look back.
+pub(crate) const ATTENDANCE_TTL_SECS: i64 = 35 * 24 * 3600;
+
+/// A shared connection idle this long is checked before it's reused.
+const HEALTH_CHECK_AFTER: Duration = Duration::from_secs(10);
+
+/// Consumer group the agents read their message inbox with.
+const INBOX_GROUP: &str = "nishack";
+
+/// Longest wait for a new connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Failed connects / dropped connections in a row that open the circuit.
+const CIRCUIT_THRESHOLD: u32 = 3;
+
+/// How long an open circuit skips Redis: doubles from the first to the
+/// last with every failed retry.
+const CIRCUIT_BACKOFF: (Duration, Duration) = (Duration::from_secs(1), Duration::from_secs(60));
+
+/// Violations held for the teacher server while it can't be reached.
+const TEACHER_RETRY_MAX: usize = 1000;
+
+/// Wait between retries of held violations, doubling like `CIRCUIT_BACKOFF`.
+const TEACHER_RETRY_BACKOFF: (Duration, Duration) = (Duration::from_secs(5), Duration::from_secs(300));
 
 /// Thin async wrapper around a Redis connection.
 #[derive(Clone)]
 pub struct Store {
-    client: redis::Client,
+    backend: Backend,
+    /// The connection every operation shares (all clones too).
+    shared: Arc<tokio::sync::Mutex<SharedConn>>,
+    /// Mirrors `SharedConn::open_until.is_some()`, readable without
+    /// waiting out a connection attempt.
+    circuit_open: Arc<AtomicBool>,
+    /// Bare `key_prefix` — shared by every site and room.
     prefix: String,
+    /// `{prefix}:{site}:{room}` — this agent's namespace.
+    namespace: String,
+    /// Payload bytes written to Redis since startup (shared by all clones).
+    bytes_written: Arc<AtomicU64>,
+    /// Writes waiting for Redis (`[redis] offline_queue`), shared by all
+    /// clones. None when queueing is off.
+    outbox: Option<Arc<tokio::sync::Mutex<Outbox>>>,
+    events: EventStorage,
+    stream_maxlen: usize,
+    /// Heartbeats, violations, usage and screenshots go here instead of
+    /// Redis with `[storage] kind = "postgres"`.
+    pg: Option<PgStore>,
+    heartbeat_ttl_secs: u64,
+    screenshot_ttl_secs: u64,
+    /// Screenshots kept in `screenshot_history:{hostname}`.
+    screenshot_history: isize,
+    /// For the teacher server (kept-alive connections, shared by all clones).
+    http: reqwest::Client,
+    /// Violations the teacher server hasn't taken yet (shared by all clones).
+    teacher_retry: Arc<std::sync::Mutex<TeacherRetry>>,
+    /// Deduplicated repeats not yet appended to the violation stream, by
+    /// host, rule and first-seen time, with when they were last bumped
+    /// (shared by all clones).
+    repeats: Arc<std::sync::Mutex<HashMap<String, (Instant, Violation)>>>,
+    /// Counters and latencies of the operations (shared by all clones).
+    metrics: Arc<StoreMetrics>,
 }
 
 impl Store {
     /// Create a new store (does **not** open a connection yet).
-    pub fn new(cfg: &RedisConfig) -> anyhow::Result<Self> {
-        let client = redis::Client::open(cfg.url.as_str())?;
+    pub fn new(
+        cfg: &RedisConfig,
+        storage: &StorageConfig,
+        screenshots: &ScreenshotConfig,
+        tags: &TagsConfig,
+    ) -> anyhow::Result<Self> {
+        let backend = Backend::new(cfg)?;
+        let mut namespace = cfg.key_prefix.clone();
+        for segment in tags.segments() {
+            namespace.push(':');
+            namespace.push_str(&segment);
+        }
         Ok(Self {
-            client,
+            backend,
+            shared: Default::default(),
+            circuit_open: Default::default(),
             prefix: cfg.key_prefix.clone(),
+            bytes_written: Arc::new(AtomicU64::new(0)),
+            outbox: (!cfg.offline_queue.is_empty())
+                .then(|| Arc::new(tokio::sync::Mutex::new(Outbox::new(&cfg.offline_queue, cfg.offline_queue_max_mb)))),
+            events: cfg.events,
+            stream_maxlen: cfg.stream_maxlen,
+            pg: match storage.kind {
+                StorageKind::Redis => None,
+                StorageKind::Postgres => Some(PgStore::new(storage, &namespace)?),
+            },
+            namespace,
+            heartbeat_ttl_secs: cfg.heartbeat_ttl_secs,
+            screenshot_ttl_secs: screenshots.latest_ttl_secs,
+            screenshot_history: screenshots.history.max(1),
+            http: reqwest::Client::builder().timeout(Duration::from_secs(5)).build()?,
+            teacher_retry: Default::default(),
+            repeats: Default::default(),
+            metrics: Default::default(),
         })
     }
 
     // ── helpers ─────────────────────────────────────────────────
 
-    fn key(&self, parts: &[&str]) -> String {
-        let mut k = self.prefix.clone();
+    fn join(base: &str, parts: &[&str]) -> String {
+        let mut k = base.to_owned();
         for p in parts {
             k.push(':');
             k.push_str(p);
@@ -33,25 +151,333 @@ impl Store {
         k
     }
 
-    async fn conn(&self) -> Option<redis::aio::MultiplexedConnection> {
-        match self.client.get_multiplexed_async_connection().await {
-            Ok(c) => Some(c),
+    /// Key inside this agent's site/room namespace.
+    fn key(&self, parts: &[&str]) -> String {
+        Self::join(&self.namespace, parts)
+    }
+
+    /// Key directly under the bare prefix, shared by all sites and rooms.
+    fn global_key(&self, parts: &[&str]) -> String {
+        Self::join(&self.prefix, parts)
+    }
+
+    fn count_bytes(&self, n: usize) {
+        self.bytes_written.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// Total payload bytes written to Redis since startup.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// The store metrics in the Prometheus text format, with the bytes
+    /// written and the circuit breaker's state.
+    pub fn metrics_text(&self) -> String {
+        let mut out = self.metrics.prometheus();
+        out.push_str("# HELP nishack_store_bytes_written_total Payload bytes written to Redis.\n");
+        out.push_str("# TYPE nishack_store_bytes_written_total counter\n");
+        out.push_str(&format!("nishack_store_bytes_written_total {}\n", self.bytes_written()));
+        out.push_str("# HELP nishack_store_circuit_open Whether Redis calls are being skipped after repeated failures.\n");
+        out.push_str("# TYPE nishack_store_circuit_open gauge\n");
+        out.push_str(&format!("nishack_store_circuit_open {}\n", u8::from(self.circuit_open())));
+        out
+    }
+
+    /// Store the metrics as JSON at `{prefix}:agent_stats:{hostname}`,
+    /// expiring after `ttl_secs`.
+    pub async fn push_agent_stats(&self, hostname: &str, ttl_secs: u64) {
+        let mut stats = self.metrics.snapshot();
+        stats["hostname"] = hostname.into();
+        stats["bytes_written"] = self.bytes_written().into();
+        stats["circuit_open"] = self.circuit_open().into();
+        stats["timestamp"] = Utc::now().to_rfc3339().into();
+        let value = stats.to_string();
+        let Some(mut con) = self.conn("push_agent_stats").await else {
+            return;
+        };
+        self.count_bytes(value.len());
+        let stored: redis::RedisResult<()> = con.set_ex(self.key(&["agent_stats", hostname]), value, ttl_secs).await;
+        if let Err(e) = stored {
+            warn!("Failed to push agent stats: {e}");
+            self.drop_conn_on(&e).await;
+        }
+    }
+
+    /// This agent's namespace (`{prefix}:{site}:{room}`).
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// The shared connection for operation `op`, opened on first use.
+    /// One idle for `HEALTH_CHECK_AFTER` is checked first and replaced if
+    /// it's dead. None while the circuit is open (see `SharedConn`).
+    async fn conn(&self, op: &'static str) -> Option<Conn> {
+        match self.link().await {
+            Some(link) => Some(Conn { link, op, metrics: Arc::clone(&self.metrics) }),
+            None => {
+                self.metrics.unavailable(op);
+                None
+            }
+        }
+    }
+
+    async fn link(&self) -> Option<Link> {
+        let mut guard = self.shared.lock().await;
+        let shared = &mut *guard;
+        if let Some(con) = &mut shared.con {
+            if shared.checked.is_some_and(|t| t.elapsed() < HEALTH_CHECK_AFTER) {
+                return Some(con.clone());
+            }
+            match self.backend.check(con).await {
+                Ok(()) => {
+                    shared.checked = Some(Instant::now());
+                    return shared.con.clone();
+                }
+                Err(e) => {
+                    shared.failed(&format!("health check: {e}"));
+                    self.circuit_open.store(shared.open_until.is_some(), Ordering::Relaxed);
+                }
+            }
+        }
+        if shared.open_until.is_some_and(|at| Instant::now() < at) {
+            return None;
+        }
+
+        let connected = tokio::time::timeout(self.backend.connect_timeout(), self.backend.connect())
+            .await
+            .unwrap_or_else(|_| Err((redis::ErrorKind::IoError, "connection timed out").into()));
+        match connected {
+            Ok(con) => {
+                shared.connected(con.clone());
+                self.circuit_open.store(false, Ordering::Relaxed);
+                self.metrics.connected();
+                Some(con)
+            }
             Err(e) => {
-                warn!("Redis connection failed (will retry): {e}");
+                shared.failed(&e.to_string());
+                self.circuit_open.store(shared.open_until.is_some(), Ordering::Relaxed);
+                self.metrics.count("connect_failures", 1);
                 None
             }
         }
     }
 
-    // ── public API ──────────────────────────────────────────────
+    /// Count `e` against the circuit when it says the shared connection is
+    /// gone (or, after a Sentinel failover, no longer to the master), so
+    /// the next operation reconnects instead of waiting for the health
+    /// check.
+    async fn drop_conn_on(&self, e: &redis::RedisError) {
+        if is_transient(e) {
+            let mut shared = self.shared.lock().await;
+            shared.failed(&e.to_string());
+            self.circuit_open.store(shared.open_until.is_some(), Ordering::Relaxed);
+        }
+    }
 
-    /// Push a heartbeat. Key: `{prefix}:heartbeat:{hostname}`
-    /// The key auto-expires so stale agents disappear from the dashboard.
-    pub async fn push_heartbeat(&self, hostname: &str, ip: &str, port: u16, username: &str) {
-        let Some(mut con) = self.conn().await else {
+    /// Whether Redis calls are currently skipped.
+    pub fn circuit_open(&self) -> bool {
+        self.circuit_open.load(Ordering::Relaxed)
+    }
+
+    /// `host:port` of the server in use; None without `[redis] fallback_urls`.
+    pub fn redis_server(&self) -> Option<String> {
+        match &self.backend {
+            Backend::Single(servers) if servers.list.len() > 1 => Some(servers.current().0.clone()),
+            _ => None,
+        }
+    }
+
+    /// Told the index of the server in use whenever it changes; None
+    /// without `[redis] fallback_urls`. Pub/sub connections resubscribe on
+    /// it, since messages published on one server never reach another.
+    pub fn server_switches(&self) -> Option<tokio::sync::watch::Receiver<usize>> {
+        match &self.backend {
+            Backend::Single(servers) if servers.list.len() > 1 => Some(servers.active.subscribe()),
+            _ => None,
+        }
+    }
+
+    /// While on a fallback server, try the ones before it and move the
+    /// shared connection to the first that answers. Called every
+    /// `[redis] failback_secs`.
+    pub async fn fail_back(&self) {
+        let Backend::Single(servers) = &self.backend else {
+            return;
+        };
+        let active = *servers.active.borrow();
+        if active == 0 {
+            return;
+        }
+        let Ok((index, con)) = servers.connect(active).await else {
             return;
         };
+        let mut shared = self.shared.lock().await;
+        shared.connected(Link::Node(con));
+        self.metrics.connected();
+        self.circuit_open.store(false, Ordering::Relaxed);
+        servers.switched(index);
+    }
+
+    /// Append `data` for `hostname` to the room stream `{namespace}:stream:{name}`.
+    fn stream_op(&self, name: &str, hostname: &str, data: String) -> QueuedOp {
+        QueuedOp::Xadd { key: self.key(&["stream", name]), hostname: hostname.to_owned(), data, maxlen: self.stream_maxlen }
+    }
+
+    /// The newest `count` entries of `list` for `hostname`, newest first.
+    /// When the history only goes to the room stream, the host's are
+    /// looked for among its newest `TIMELINE_SCAN` (or `count`) entries.
+    async fn read_events(
+        &self,
+        con: &mut Conn,
+        list: &str,
+        hostname: &str,
+        count: isize,
+    ) -> Vec<String> {
+        if self.events.lists() || !STREAMED.contains(&list) {
+            return con.lrange(self.key(&[list, hostname]), 0, count - 1).await.unwrap_or_default();
+        }
+        let reply: redis::streams::StreamRangeReply =
+            con.xrevrange_count(self.key(&["stream", list]), "+", "-", count.max(TIMELINE_SCAN)).await.unwrap_or_default();
+        reply
+            .ids
+            .into_iter()
+            .filter(|entry| entry.get::<String>("hostname").as_deref() == Some(hostname))
+            .filter_map(|entry| entry.get("data"))
+            .take(count.max(0) as usize)
+            .collect()
+    }
+
+    /// `read_events` on a connection of its own, or from Postgres for the
+    /// histories kept there.
+    async fn events(&self, list: &str, hostname: &str, count: isize) -> Vec<String> {
+        if let Some(pg) = self.pg.as_ref().filter(|_| STREAMED.contains(&list)) {
+            return pg.read(list, hostname, count as i64).await;
+        }
+        let Some(mut con) = self.conn("events").await else {
+            return Vec::new();
+        };
+        self.read_events(&mut con, list, hostname, count).await
+    }
+
+    /// Send `ops` in one pipeline, after replaying anything queued before
+    /// them. When Redis can't be reached (or the connection fails during
+    /// the write) they are queued on disk instead — dropped without an
+    /// offline queue. A write Redis refuses (WRONGTYPE, NOPERM, …) would
+    /// be refused again, so it's logged and dropped, never queued.
+    /// Returns the connection if they went through.
+    async fn deliver(&self, kind: &'static str, ops: Vec<QueuedOp>) -> Option<Conn> {
+        let Some(outbox) = &self.outbox else {
+            let mut con = self.conn(kind).await?;
+            return match self.run_ops(&mut con, ops.iter()).await {
+                Ok(()) => Some(con),
+                Err(e) => {
+                    warn!("Failed to push {kind}: {e}");
+                    self.drop_conn_on(&e).await;
+                    None
+                }
+            };
+        };
+
+        // Held until this write is done, so nothing overtakes the replay
+        let mut outbox = outbox.lock().await;
+        let pending = Pending { kind: kind.to_string(), queued_at: Utc::now(), ops };
+        let Some(mut con) = self.conn(kind).await else {
+            outbox.push(&pending);
+            self.metrics.count("writes_queued", 1);
+            return None;
+        };
+        if !outbox.is_empty() && !self.replay(&mut outbox, &con).await {
+            outbox.push(&pending);
+            self.metrics.count("writes_queued", 1);
+            return None;
+        }
+        match self.run_ops(&mut con, pending.ops.iter()).await {
+            Ok(()) => Some(con),
+            Err(e) if is_transient(&e) => {
+                warn!("Failed to push {kind} (queued): {e}");
+                self.drop_conn_on(&e).await;
+                outbox.push(&pending);
+                self.metrics.count("writes_queued", 1);
+                None
+            }
+            Err(e) => {
+                warn!("Redis refused {kind}, dropped: {e}");
+                self.metrics.count("writes_rejected", 1);
+                None
+            }
+        }
+    }
+
+    async fn run_ops<'a>(
+        &self,
+        con: &mut Conn,
+        ops: impl Iterator<Item = &'a QueuedOp>,
+    ) -> redis::RedisResult<()> {
+        let mut pipe = redis::pipe();
+        let mut bytes = 0;
+        for op in ops {
+            op.add_to(&mut pipe);
+            bytes += op.bytes();
+        }
+        if pipe.cmd_iter().next().is_none() {
+            return Ok(());
+        }
+        pipe.query_async::<_, ()>(con).await?;
+        self.count_bytes(bytes);
+        Ok(())
+    }
+
+    /// Send everything in the offline queue, oldest first. False when the
+    /// connection failed part way; that write and the rest stay queued.
+    /// Writes Redis refuses are dropped so they can't block the queue.
+    async fn replay(&self, outbox: &mut Outbox, con: &Conn) -> bool {
+        let con = &mut Conn { op: "replay", ..con.clone() };
+        let queued = outbox.load();
+        let (mut replayed, mut rejected) = (0, 0);
+        for (i, pending) in queued.iter().enumerate() {
+            match self.run_ops(con, pending.live_ops()).await {
+                Ok(()) => replayed += 1,
+                Err(e) if is_transient(&e) => {
+                    warn!("Offline queue replay stopped after {i} of {} write(s): {e}", queued.len());
+                    self.drop_conn_on(&e).await;
+                    outbox.keep(&queued[i..]);
+                    self.count_replay(replayed, rejected);
+                    return false;
+                }
+                Err(e) => {
+                    warn!("Redis refused queued {} from {}, dropped: {e}", pending.kind, pending.queued_at);
+                    rejected += 1;
+                }
+            }
+        }
+        if replayed > 0 {
+            info!("📮 Replayed {replayed} queued write(s) to Redis");
+        }
+        self.count_replay(replayed, rejected);
+        outbox.keep(&[]);
+        true
+    }
 
+    fn count_replay(&self, replayed: u64, rejected: u64) {
+        self.metrics.count("writes_replayed", replayed);
+        self.metrics.count("writes_rejected", rejected);
+    }
+
+    // ── public API ──────────────────────────────────────────────
+
+    /// Push a heartbeat. Key: `{prefix}:heartbeat:{hostname}`
+    /// The key expires after `heartbeat_ttl_secs` so stale agents disappear
+    /// from the dashboard.
+    /// Queued while offline, except for the attendance update.
+    /// Returns the heartbeat sent, whether or not it got through.
+    pub async fn push_heartbeat(
+        &self,
+        hostname: &str,
+        ip: &str,
+        port: u16,
+        username: &str,
+        extras: HeartbeatExtras,
+    ) -> Option<Heartbeat> {
         // Gather live system metrics
         let mut sys = sysinfo::System::new();
         sys.refresh_cpu_all();
@@ -75,6 +501,13 @@ impl Store {
 
         let uptime_secs = sysinfo::System::uptime();
 
+        // Battery needs a subprocess on Windows / macOS
+        let hardware = tokio::task::spawn_blocking(|| (battery::status(), disk_space(), cpu_temperature()));
+        let (battery, disks, cpu_temp_c) = match tokio::time::timeout(std::time::Duration::from_secs(5), hardware).await {
+            Ok(Ok(hardware)) => hardware,
+            _ => (None, Vec::new(), None),
+        };
+
         let hb = Heartbeat {
             hostname: hostname.to_owned(),
             ip: ip.to_owned(),
@@ -85,243 +518,1827 @@ impl Store {
             cpu_usage,
             ram_usage,
             uptime_secs,
+            battery,
+            disks,
+            cpu_temp_c,
             timestamp: Utc::now(),
+            extras,
         };
 
+        // Compact copy kept as history for the timeline.
+        let sample = serde_json::json!({
+            "timestamp": hb.timestamp,
+            "username": hb.username,
+            "cpu_usage": hb.cpu_usage,
+            "ram_usage": hb.ram_usage,
+            "profile": hb.extras.profile,
+            "exam_mode": hb.extras.exam_mode,
+            "documents": hb.extras.documents,
+        })
+        .to_string();
+
         let key = self.key(&["heartbeat", hostname]);
         let payload = match serde_json::to_string(&hb) {
             Ok(p) => p,
             Err(e) => {
                 error!("Heartbeat serialization error: {e}");
-                return;
+                return None;
             }
         };
 
-        // SET with 90-second TTL (3× heartbeat interval)
-        let result: redis::RedisResult<()> = con.set_ex(&key, &payload, 90).await;
-        if let Err(e) = result {
-            warn!("Failed to push heartbeat: {e}");
-        } else {
-            info!("Heartbeat pushed → {key}");
+        if let Some(pg) = &self.pg {
+            if pg.push_heartbeat(hostname, &payload, &sample, USAGE_HISTORY as i64).await {
+                info!("Heartbeat stored in Postgres ({hostname})");
+                self.metrics.count("heartbeats_pushed", 1);
+            }
+            if let Some(mut con) = self.conn("push_heartbeat").await {
+                self.record_attendance(&mut con, &hb).await;
+            }
+            return Some(hb);
         }
-    }
 
-    /// Record a violation. Stored in a Redis list so we keep history.
-    /// Key: `{prefix}:violations:{hostname}`
-    ///
-    /// We serialise into the **teacher-backend** schema so the dashboard can
-    /// deserialise it directly:  { hostname, rule, detail, severity, timestamp }
-    pub async fn record_violation(&self, v: &Violation) {
-        let Some(mut con) = self.conn().await else {
-            return;
+        let mut ops = vec![QueuedOp::SetEx { key: key.clone(), value: payload, ttl_secs: self.heartbeat_ttl_secs }];
+        if self.events.streams() {
+            ops.push(self.stream_op("usage", hostname, sample.clone()));
+        }
+        if self.events.lists() {
+            ops.push(QueuedOp::Lpush { key: self.key(&["usage", hostname]), value: sample, keep: Some(USAGE_HISTORY) });
+        }
+        let Some(mut con) = self.deliver("heartbeat", ops).await else {
+            return Some(hb);
         };
+        info!("Heartbeat pushed → {key}");
+        self.metrics.count("heartbeats_pushed", 1);
 
-        // Map student model → teacher-compatible JSON
-        let rule = match v.kind {
-            ViolationKind::Process => "banned_process",
-            ViolationKind::Domain  => "banned_domain",
-        };
-        let severity = match v.kind {
-            ViolationKind::Process => "high",
-            ViolationKind::Domain  => "medium",
-        };
-        let detail = format!(
-            "{}: {} ({})",
-            match v.kind {
-                ViolationKind::Process => "Запрещённый процесс",
-                ViolationKind::Domain  => "Запрещённый домен",
-            },
-            v.target,
-            if v.action_taken { "заблокировано" } else { "не удалось заблокировать" }
-        );
+        self.record_attendance(&mut con, &hb).await;
+        Some(hb)
+    }
 
-        let payload = serde_json::json!({
-            "hostname": v.hostname,
-            "rule": rule,
-            "detail": detail,
-            "severity": severity,
-            "timestamp": v.timestamp.to_rfc3339(),
-        });
+    /// Fold one heartbeat into the student's attendance for today:
+    /// `{namespace}:attendance:{hostname}:{YYYY-MM-DD}`, one field per user.
+    async fn record_attendance(&self, con: &mut Conn, hb: &Heartbeat) {
+        let key = self.key(&["attendance", &hb.hostname, &Local::now().date_naive().to_string()]);
+        let existing: Option<String> = con.hget(&key, &hb.username).await.unwrap_or(None);
+        let mut day = existing
+            .and_then(|s| serde_json::from_str::<AttendanceDay>(&s).ok())
+            .unwrap_or(AttendanceDay {
+                first_seen: hb.timestamp,
+                last_seen: hb.timestamp,
+                heartbeats: 0,
+                exam_heartbeats: 0,
+                profiles: Default::default(),
+                documents: Default::default(),
+            });
+        day.last_seen = hb.timestamp;
+        day.heartbeats += 1;
+        if hb.extras.exam_mode {
+            day.exam_heartbeats += 1;
+        }
+        if let Some(profile) = &hb.extras.profile {
+            *day.profiles.entry(profile.clone()).or_default() += 1;
+        }
+        for doc in &hb.extras.documents {
+            *day.documents.entry(doc.label()).or_default() += 1;
+        }
 
-        let key = self.key(&["violations", &v.hostname]);
-        let result: redis::RedisResult<()> = con.lpush(&key, payload.to_string()).await;
+        let Ok(payload) = serde_json::to_string(&day) else {
+            return;
+        };
+        self.count_bytes(payload.len());
+        let result: redis::RedisResult<()> = redis::pipe()
+            .hset(&key, &hb.username, payload)
+            .expire(&key, ATTENDANCE_TTL_SECS)
+            .query_async(con)
+            .await;
         if let Err(e) = result {
-            warn!("Failed to record violation: {e}");
+            warn!("Failed to record attendance: {e}");
         }
-
-        // Also increment a quick counter for the dashboard
-        let counter_key = self.key(&["violation_count", &v.hostname]);
-        let _: redis::RedisResult<()> = con.incr(&counter_key, 1i64).await;
     }
 
-    /// Fetch the last `n` violations for a host.
-    pub async fn recent_violations(
+    /// Attendance per user for each of `dates`.
+    pub async fn attendance(
         &self,
         hostname: &str,
-        count: isize,
-    ) -> Vec<Violation> {
-        let Some(mut con) = self.conn().await else {
+        dates: &[NaiveDate],
+    ) -> Vec<(NaiveDate, HashMap<String, AttendanceDay>)> {
+        let Some(mut con) = self.conn("attendance").await else {
             return Vec::new();
         };
+        let mut pipe = redis::pipe();
+        for date in dates {
+            pipe.hgetall(self.key(&["attendance", hostname, &date.to_string()]));
+        }
+        let raw: Vec<HashMap<String, String>> = match pipe.query_async(&mut con).await {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Failed to read attendance: {e}");
+                return Vec::new();
+            }
+        };
+        dates
+            .iter()
+            .copied()
+            .zip(raw)
+            .map(|(date, users)| {
+                let users = users
+                    .into_iter()
+                    .filter_map(|(user, day)| Some((user, serde_json::from_str(&day).ok()?)))
+                    .collect();
+                (date, users)
+            })
+            .collect()
+    }
 
-        let key = self.key(&["violations", hostname]);
-        let raw: Vec<String> = con.lrange(&key, 0, count - 1).await.unwrap_or_default();
-
+    /// Violations (teacher-backend JSON) recorded between `from` and `to`,
+    /// newest first.
+    pub async fn violations_between(
+        &self,
+        hostname: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<serde_json::Value> {
+        let raw = self.events("violations", hostname, TIMELINE_SCAN).await;
         raw.iter()
-            .filter_map(|s| serde_json::from_str(s).ok())
+            .filter_map(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+            .filter(|v| {
+                v.get("timestamp")
+                    .and_then(|t| t.as_str()?.parse::<DateTime<Utc>>().ok())
+                    .is_some_and(|t| t >= from && t <= to)
+            })
             .collect()
     }
 
-    /// Register the machine's IP in a Redis set for easy discovery.
-    /// Key: `{prefix}:agents`
-    pub async fn register_agent(&self, hostname: &str, ip: &str, port: u16) {
-        let Some(mut con) = self.conn().await else {
+    /// Add a system sample to the Sorted Set `{namespace}:timeseries:{hostname}`,
+    /// scored by its time in ms, dropping those older than `window_secs`
+    /// and all but the newest `max`. The key expires once no sample is
+    /// young enough.
+    pub async fn push_timeseries(&self, snap: &SystemSnapshot, window_secs: u64, max: isize) {
+        let Some(mut con) = self.conn("push_timeseries").await else {
+            return;
+        };
+        let Ok(payload) = serde_json::to_string(snap) else {
             return;
         };
+        let key = self.key(&["timeseries", &snap.hostname]);
+        let at = snap.timestamp.timestamp_millis();
+        self.count_bytes(payload.len());
+        let result: redis::RedisResult<()> = redis::pipe()
+            .zadd(&key, payload, at)
+            .zrembyscore(&key, "-inf", at - window_secs as i64 * 1000)
+            .zremrangebyrank(&key, 0, -(max.max(1) + 1))
+            .expire(&key, window_secs as i64)
+            .query_async(&mut con)
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to store system sample: {e}");
+        }
+    }
 
-        let value = format!("{hostname}|{ip}|{port}");
-        let key = self.key(&["agents"]);
-        let _: redis::RedisResult<()> = con.sadd(&key, &value).await;
+    /// System samples for `hostname` taken between `from` and `to`, oldest first.
+    pub async fn timeseries(&self, hostname: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<SystemSnapshot> {
+        let Some(mut con) = self.conn("timeseries").await else {
+            return Vec::new();
+        };
+        let raw: Vec<String> = con
+            .zrangebyscore(self.key(&["timeseries", hostname]), from.timestamp_millis(), to.timestamp_millis())
+            .await
+            .unwrap_or_default();
+        raw.iter().filter_map(|r| serde_json::from_str(r).ok()).collect()
     }
 
-    /// Store a screenshot (base64-encoded) for a host.
-    /// Key: `{prefix}:screenshot:{hostname}` with metadata
-    /// Also pushes to a list for history: `{prefix}:screenshot_history:{hostname}`
-    pub async fn push_screenshot(&self, hostname: &str, screenshot_base64: &str) {
-        let Some(mut con) = self.conn().await else {
+    /// Store a weekly report: `{namespace}:report:{hostname}` (JSON) and
+    /// `{namespace}:report_html:{hostname}` hold the latest one,
+    /// `{namespace}:reports:{hostname}` the last 12 as JSON.
+    pub async fn push_report(&self, report: &WeeklyReport, html: &str) {
+        let Some(mut con) = self.conn("push_report").await else {
+            return;
+        };
+        let Ok(payload) = serde_json::to_string(report) else {
             return;
         };
+        let history_key = self.key(&["reports", &report.hostname]);
+        self.count_bytes(payload.len() * 2 + html.len());
+        let result: redis::RedisResult<()> = redis::pipe()
+            .set(self.key(&["report", &report.hostname]), &payload)
+            .set(self.key(&["report_html", &report.hostname]), html)
+            .lpush(&history_key, &payload)
+            .ltrim(&history_key, 0, 11)
+            .query_async(&mut con)
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to store weekly report: {e}");
+        }
+    }
 
-        let timestamp = Utc::now();
-        let metadata = serde_json::json!({
-            "hostname": hostname,
-            "timestamp": timestamp,
-            "data": screenshot_base64,
-            "size": screenshot_base64.len(),
-        });
+    /// When the latest stored weekly report was generated.
+    pub async fn latest_report_time(&self, hostname: &str) -> Option<DateTime<Utc>> {
+        let mut con = self.conn("latest_report_time").await?;
+        let raw: Option<String> = con.get(self.key(&["report", hostname])).await.ok()?;
+        let report: serde_json::Value = serde_json::from_str(&raw?).ok()?;
+        report.get("generated_at")?.as_str()?.parse().ok()
+    }
 
-        let payload = match serde_json::to_string(&metadata) {
-            Ok(p) => p,
-            Err(e) => {
-                error!("Screenshot serialization error: {e}");
-                return;
+    /// Claim the right to send alert `key` for the next `ttl_secs`:
+    /// `{namespace}:alert_sent:{hostname}:{key}`, shared by the agent and
+    /// its watchdog. None when Redis is unreachable.
+    pub async fn claim_alert(&self, hostname: &str, key: &str, ttl_secs: u64) -> Option<bool> {
+        let mut con = self.conn("claim_alert").await?;
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(self.key(&["alert_sent", hostname, key]))
+            .arg(Utc::now().to_rfc3339())
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs.max(1))
+            .query_async(&mut con)
+            .await
+            .ok()?;
+        Some(claimed.is_some())
+    }
+
+    /// Take `code` out of the teacher's one-time unlock codes for this host
+    /// (`{namespace}:unlock_codes:{hostname}`) or the room
+    /// (`{namespace}:unlock_codes`), both Sets. Some(true) when it was there
+    /// (and is now used up); None when Redis is unreachable.
+    pub async fn redeem_unlock_code(&self, hostname: &str, code: &str) -> Option<bool> {
+        let mut con = self.conn("redeem_unlock_code").await?;
+        for key in [self.key(&["unlock_codes", hostname]), self.key(&["unlock_codes"])] {
+            let removed: i64 = con.srem(&key, code).await.ok()?;
+            if removed > 0 {
+                return Some(true);
             }
-        };
+        }
+        Some(false)
+    }
 
-        // Store latest screenshot with TTL
-        let latest_key = self.key(&["screenshot", hostname]);
-        let result: redis::RedisResult<()> = con.set_ex(&latest_key, &payload, 120).await;
-        if let Err(e) = result {
-            warn!("Failed to push latest screenshot: {e}");
-        } else {
-            info!("Screenshot pushed → {latest_key}");
+    /// Mark derived unlock code `code` as used for `ttl_secs`
+    /// (`{namespace}:unlock_code_used:{hostname}:{code}`). Some(false) when
+    /// it was used already; None when Redis is unreachable.
+    pub async fn claim_unlock_code(&self, hostname: &str, code: &str, ttl_secs: u64) -> Option<bool> {
+        let mut con = self.conn("claim_unlock_code").await?;
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(self.key(&["unlock_code_used", hostname, code]))
+            .arg(Utc::now().to_rfc3339())
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs.max(1))
+            .query_async(&mut con)
+            .await
+            .ok()?;
+        Some(claimed.is_some())
+    }
+
+    /// Record violations (the `redis` violation sink), in one pipeline.
+    /// Stored in a Redis list so we keep history.
+    /// Key: `{prefix}:violations:{hostname}`
+    ///
+    /// We serialise into the **teacher-backend** schema so the dashboard can
+    /// deserialise it directly:  { hostname, rule, detail, severity, timestamp }
+    pub async fn record_violations(&self, vs: &[Violation]) {
+        if vs.is_empty() {
+            return;
+        }
+        if let Some(pg) = &self.pg {
+            let rows: Vec<_> = vs
+                .iter()
+                .map(|v| (v.hostname.clone(), v.kind.rule().to_string(), v.timestamp, teacher_payload(v).to_string()))
+                .collect();
+            pg.record_violations(&rows).await;
+            return;
         }
 
-        // Also store in history (keep last 10)
-        let history_key = self.key(&["screenshot_history", hostname]);
-        let _: redis::RedisResult<()> = con.lpush(&history_key, &payload).await;
-        let _: redis::RedisResult<()> = con.ltrim(&history_key, 0, 9).await;
+        let mut ops = Vec::new();
+        let mut counts: HashMap<&str, i64> = HashMap::new();
+        for v in vs {
+            let value = teacher_payload(v).to_string();
+            if self.events.streams() {
+                ops.push(self.stream_op("violations", &v.hostname, value.clone()));
+            }
+            if self.events.lists() {
+                ops.push(QueuedOp::Lpush { key: self.key(&["violations", &v.hostname]), value, keep: None });
+            }
+            *counts.entry(&v.hostname).or_default() += 1;
+        }
+        // Also increment a quick counter for the dashboard
+        for (hostname, by) in counts {
+            ops.push(QueuedOp::Incr { key: self.key(&["violation_count", hostname]), by });
+        }
+        if self.deliver("violations", ops).await.is_some() {
+            self.metrics.count("violations_recorded", vs.len() as u64);
+        }
     }
 
-    /// Fetch the latest screenshot for a host.
-    pub async fn latest_screenshot(&self, hostname: &str) -> Option<String> {
-        let Some(mut con) = self.conn().await else {
-            return None;
+    /// Rewrite the stored record of a deduplicated violation (see
+    /// `Violation::occurrences`), found by its first-seen timestamp among
+    /// the newest `DEDUP_SCAN` entries; pushed anew if it's no longer there.
+    /// Streams are append-only, so there the repeat waits for
+    /// `flush_repeats` to add its final count once.
+    pub async fn update_violation(&self, v: &Violation) {
+        let payload = teacher_payload(v);
+        if let Some(pg) = &self.pg {
+            pg.update_violation(&v.hostname, v.kind.rule(), v.timestamp, &payload.to_string()).await;
+            return;
+        }
+        if self.events.streams() {
+            let key = format!("{}|{}|{}", v.hostname, v.kind.rule(), v.timestamp.timestamp_millis());
+            self.repeats.lock().unwrap_or_else(PoisonError::into_inner).insert(key, (Instant::now(), v.clone()));
+        }
+        if !self.events.lists() {
+            return;
+        }
+        let Some(mut con) = self.conn("update_violation").await else {
+            return;
         };
 
-        let key = self.key(&["screenshot", hostname]);
-        con.get(&key).await.ok()
+        let key = self.key(&["violations", &v.hostname]);
+        let raw: Vec<String> = con.lrange(&key, 0, DEDUP_SCAN - 1).await.unwrap_or_default();
+        let index = raw.iter().position(|r| {
+            serde_json::from_str::<serde_json::Value>(r).is_ok_and(|old| {
+                old.get("timestamp") == payload.get("timestamp") && old.get("rule") == payload.get("rule")
+            })
+        });
+
+        let payload = payload.to_string();
+        self.count_bytes(payload.len());
+        let result: redis::RedisResult<()> = match index {
+            Some(i) => con.lset(&key, i as isize, payload).await,
+            None => con.lpush(&key, payload).await,
+        };
+        if let Err(e) = result {
+            warn!("Failed to update violation: {e}");
+        }
     }
 
-    /// Fetch the centrally-managed ban config from Redis.
-    /// Returns (banned_processes, banned_domains) or None if unavailable.
-    pub async fn fetch_ban_config(&self) -> Option<(Vec<String>, Vec<String>)> {
-        let mut con = self.conn().await?;
-        let key = self.key(&["ban_config"]);
-        let val: Option<String> = con.get(&key).await.ok()?;
-        let json: serde_json::Value = serde_json::from_str(&val?).ok()?;
-        let procs = json["banned_processes"]
-            .as_array()?
-            .iter()
-            .filter_map(|v| v.as_str().map(String::from))
-            .collect();
-        let domains = json["banned_domains"]
-            .as_array()?
+    /// Append the repeats not bumped for `quiet` (the dedup cooldown) to
+    /// the violation stream, each once with its final `occurrences`;
+    /// `Duration::ZERO` appends them all.
+    pub async fn flush_repeats(&self, quiet: Duration) {
+        let done: Vec<Violation> = {
+            let mut repeats = self.repeats.lock().unwrap_or_else(PoisonError::into_inner);
+            let keys: Vec<String> =
+                repeats.iter().filter(|(_, (at, _))| at.elapsed() >= quiet).map(|(key, _)| key.clone()).collect();
+            keys.iter().filter_map(|key| repeats.remove(key)).map(|(_, v)| v).collect()
+        };
+        if done.is_empty() {
+            return;
+        }
+        let ops = done
             .iter()
-            .filter_map(|v| v.as_str().map(String::from))
+            .map(|v| self.stream_op("violations", &v.hostname, teacher_payload(v).to_string()))
             .collect();
-        Some((procs, domains))
-    }
-
-    /// Discover the teacher server address from Redis.
-    /// Returns `Some("IP:PORT")` if the teacher has published its address.
-    pub async fn discover_teacher_address(&self) -> Option<String> {
-        let mut con = self.conn().await?;
-        // Teacher publishes its IP to {prefix}:server:ip (no port).
-        // Default teacher port is 8080.
-        let key = self.key(&["server", "ip"]);
-        let ip: Option<String> = con.get(&key).await.ok()?;
-        ip.map(|addr| format!("{addr}:8080"))
-    }
-
-    /// Forward a violation to the teacher backend via REST API.
-    /// This makes the violation appear on the teacher dashboard in real-time.
-    pub async fn push_violation_to_teacher(&self, v: &Violation) {
-        // Resolve teacher address
-        let address = match self.discover_teacher_address().await {
-            Some(addr) => addr,
-            None => {
-                warn!("Cannot forward violation to teacher — address not discovered");
-                return;
-            }
-        };
-
-        let url = format!("http://{address}/api/agent/violation");
-
-        // Map student violation model → teacher expected format
-        let payload = serde_json::json!({
-            "hostname": v.hostname,
-            "rule": match v.kind {
-                ViolationKind::Process => "banned_process",
-                ViolationKind::Domain => "banned_domain",
-            },
-            "detail": format!("{}: {} ({})",
-                match v.kind {
-                    ViolationKind::Process => "Запрещённый процесс",
-                    ViolationKind::Domain => "Запрещённый домен",
-                },
-                v.target,
-                if v.action_taken { "заблокировано" } else { "не удалось заблокировать" }
-            ),
-            "severity": match v.kind {
-                ViolationKind::Process => "high",
-                ViolationKind::Domain => "medium",
-            },
-            "timestamp": v.timestamp.to_rfc3339(),
-        });
+        self.deliver("violation_repeats", ops).await;
+    }
 
-        // Fire-and-forget HTTP POST
-        let client = match reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(5))
-            .build()
-        {
-            Ok(c) => c,
-            Err(e) => {
-                warn!("Failed to create HTTP client: {e}");
-                return;
-            }
+    /// Drop the host's violations first seen before `cutoff`
+    /// (`[redis] violation_retention_days`): the old end of its list, or
+    /// everything before `cutoff` in the room stream. Returns how many
+    /// list entries / rows went; stream trims aren't counted.
+    pub async fn trim_violations(&self, hostname: &str, cutoff: DateTime<Utc>) -> u64 {
+        if let Some(pg) = &self.pg {
+            return pg.trim_violations(hostname, cutoff).await;
+        }
+        let Some(mut con) = self.conn("trim_violations").await else {
+            return 0;
         };
 
-        match client.post(&url).json(&payload).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                info!("✅ Violation forwarded to teacher: {}", v.target);
+        if self.events.streams() {
+            let trimmed: redis::RedisResult<i64> = redis::cmd("XTRIM")
+                .arg(self.key(&["stream", "violations"]))
+                .arg("MINID")
+                .arg("~")
+                .arg(format!("{}-0", cutoff.timestamp_millis()))
+                .query_async(&mut con)
+                .await;
+            if let Err(e) = trimmed {
+                warn!("Failed to trim violation stream: {e}");
             }
-            Ok(resp) => {
-                warn!("Teacher API returned {}: {}", resp.status(), v.target);
+        }
+        if !self.events.lists() {
+            return 0;
+        }
+
+        // Newest first, so the old ones are a run at the tail
+        let key = self.key(&["violations", hostname]);
+        let is_old = |raw: &String| {
+            serde_json::from_str::<serde_json::Value>(raw).is_ok_and(|v| {
+                v.get("timestamp")
+                    .and_then(|t| t.as_str()?.parse::<DateTime<Utc>>().ok())
+                    .is_some_and(|t| t < cutoff)
+            })
+        };
+        let mut old: isize = 0;
+        loop {
+            let chunk: Vec<String> = match con.lrange(&key, -(old + RETENTION_SCAN), -(old + 1)).await {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    warn!("Failed to read violations for retention: {e}");
+                    return 0;
+                }
+            };
+            let run = chunk.iter().rev().take_while(|r| is_old(r)).count();
+            old += run as isize;
+            if run == 0 || run < chunk.len() {
+                break;
             }
+        }
+        if old == 0 {
+            return 0;
+        }
+        match con.ltrim::<_, ()>(&key, 0, -(old + 1)).await {
+            Ok(()) => old as u64,
             Err(e) => {
-                warn!("Failed to forward violation to teacher: {e}");
+                warn!("Failed to trim violations: {e}");
+                0
             }
         }
     }
+
+    /// Record a detector panic at `{namespace}:detector_failures:{hostname}`
+    /// (newest first, last 50 kept).
+    pub async fn record_detector_failure(&self, f: &DetectorFailure) {
+        let Some(mut con) = self.conn("record_detector_failure").await else {
+            return;
+        };
+        let Ok(payload) = serde_json::to_string(f) else {
+            return;
+        };
+        let key = self.key(&["detector_failures", &f.hostname]);
+        self.count_bytes(payload.len());
+        let result: redis::RedisResult<()> = redis::pipe()
+            .lpush(&key, payload)
+            .ltrim(&key, 0, 49)
+            .query_async(&mut con)
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to record detector failure: {e}");
+        }
+    }
+
+    /// Pub/sub channels this agent takes commands from:
+    /// `{prefix}:commands:{hostname}`, the broadcast `{prefix}:commands:all`
+    /// plus `{prefix}:commands:{site|room}:{tag}` for every location tag
+    /// it carries.
+    pub fn command_channels(&self, hostname: &str, tags: &TagsConfig) -> Vec<String> {
+        let mut channels = vec![self.global_key(&["commands", hostname]), self.global_key(&["commands", "all"])];
+        for (kind, tag) in tags.labeled() {
+            channels.push(self.global_key(&["commands", kind, &tag]));
+        }
+        channels
+    }
+
+    /// Pub/sub channels that say the teacher changed something the agent
+    /// syncs: the explicit `{prefix}:config_changed`, and the keyspace
+    /// notifications (`__keyspace@{db}__:{key}`) of the ban config layers,
+    /// their confirmations, the schedule and streaming overrides and the
+    /// Redis-hosted `categories`. The latter need `notify-keyspace-events`
+    /// on the server to include `K$g`.
+    pub fn config_channels(&self, hostname: &str, categories: &[String]) -> Vec<String> {
+        let mut keys = self.layer_keys("ban_config", hostname);
+        keys.extend(self.layer_keys("schedule", hostname));
+        keys.extend(self.layer_keys("streaming", hostname));
+        keys.push(self.key(&["ban_confirm"]));
+        keys.push(self.key(&["ban_confirm", hostname]));
+        keys.extend(categories.iter().map(|name| self.global_key(&["category", name])));
+
+        let db = self.backend.db();
+        let mut channels = vec![self.global_key(&["config_changed"])];
+        channels.extend(keys.iter().map(|key| format!("__keyspace@{db}__:{key}")));
+        channels
+    }
+
+    /// Open a pub/sub connection subscribed to `channels`.
+    /// Returns None if Redis is unreachable.
+    pub async fn subscribe(&self, channels: &[String]) -> Option<redis::aio::PubSub> {
+        let mut pubsub = match self.backend.pubsub().await {
+            Ok(p) => p,
+            Err(e) => {
+                warn!("Redis pub/sub connection failed (will retry): {e}");
+                return None;
+            }
+        };
+        for channel in channels {
+            if let Err(e) = pubsub.subscribe(channel).await {
+                warn!("Failed to subscribe to {channel}: {e}");
+                return None;
+            }
+        }
+        Some(pubsub)
+    }
+
+    /// Up to `count` messages from the inbox Stream
+    /// `{prefix}:messages:{hostname}` as (ID, JSON), oldest first: those
+    /// read before but never acknowledged, else new ones. The JSON is an
+    /// entry's `data` field, or an object of its fields without one. The
+    /// consumer group is created on first use (so, with `fallback_urls`,
+    /// on every server). None without a connection.
+    pub async fn read_inbox(&self, hostname: &str, count: usize) -> Option<Vec<(String, String)>> {
+        let mut con = self.conn("read_inbox").await?;
+        let key = self.global_key(&["messages", hostname]);
+
+        // "0" re-reads this consumer's unacknowledged entries, ">" new ones
+        let mut pending = Self::inbox_entries(&mut con, &key, hostname, "0", count).await;
+        if pending.as_ref().is_err_and(|e| e.code() == Some("NOGROUP")) {
+            info!("📬 Creating the message inbox group on {key}");
+            let created: redis::RedisResult<()> = con.xgroup_create_mkstream(&key, INBOX_GROUP, "0").await;
+            if let Some(e) = created.err().filter(|e| e.code() != Some("BUSYGROUP")) {
+                warn!("Failed to create the message inbox group: {e}");
+            }
+            pending = Self::inbox_entries(&mut con, &key, hostname, "0", count).await;
+        }
+        let entries = match pending {
+            Ok(pending) if pending.is_empty() => Self::inbox_entries(&mut con, &key, hostname, ">", count).await,
+            other => other,
+        };
+        match entries {
+            Ok(entries) => Some(entries),
+            Err(e) => {
+                warn!("Failed to read the message inbox: {e}");
+                self.drop_conn_on(&e).await;
+                None
+            }
+        }
+    }
+
+    async fn inbox_entries(
+        con: &mut Conn,
+        key: &str,
+        consumer: &str,
+        from: &str,
+        count: usize,
+    ) -> redis::RedisResult<Vec<(String, String)>> {
+        let opts = redis::streams::StreamReadOptions::default().group(INBOX_GROUP, consumer).count(count);
+        let reply: redis::streams::StreamReadReply = con.xread_options(&[key], &[from], &opts).await?;
+        Ok(reply
+            .keys
+            .into_iter()
+            .flat_map(|k| k.ids)
+            .map(|entry| {
+                let mut fields: serde_json::Map<String, serde_json::Value> = entry
+                    .map
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.clone(), redis::from_redis_value::<String>(v).ok()?.into())))
+                    .collect();
+                let message = match fields.remove("data") {
+                    Some(serde_json::Value::String(data)) => data,
+                    _ => serde_json::Value::Object(fields).to_string(),
+                };
+                (entry.id, message)
+            })
+            .collect())
+    }
+
+    /// Acknowledge inbox message `id` (see `read_inbox`), so it isn't
+    /// read again.
+    pub async fn ack_inbox(&self, hostname: &str, id: &str) -> bool {
+        let Some(mut con) = self.conn("ack_inbox").await else {
+            return false;
+        };
+        let acked: redis::RedisResult<i64> = con.xack(self.global_key(&["messages", hostname]), INBOX_GROUP, &[id]).await;
+        match acked {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Failed to acknowledge inbox message {id}: {e}");
+                self.drop_conn_on(&e).await;
+                false
+            }
+        }
+    }
+
+    /// Mirror an audit event to `{namespace}:audit:{hostname}`
+    /// (newest first, last `AUDIT_HISTORY` kept).
+    pub async fn record_audit(&self, e: &AuditEvent) {
+        let Some(mut con) = self.conn("record_audit").await else {
+            return;
+        };
+        let Ok(payload) = serde_json::to_string(e) else {
+            return;
+        };
+        let key = self.key(&["audit", &e.hostname]);
+        self.count_bytes(payload.len());
+        let result: redis::RedisResult<()> = redis::pipe()
+            .lpush(&key, payload)
+            .ltrim(&key, 0, AUDIT_HISTORY - 1)
+            .query_async(&mut con)
+            .await;
+        if let Err(e) = result {
+            warn!("Failed to record audit event: {e}");
+        }
+    }
+
+    /// The last recorded application inventory of a host, if any.
+    /// Key: `{prefix}:installed_apps:{hostname}`
+    pub async fn installed_apps(&self, hostname: &str) -> Option<Vec<InstalledApp>> {
+        let mut con = self.conn("installed_apps").await?;
+
+        let key = self.key(&["installed_apps", hostname]);
+        let raw: Option<String> = con.get(&key).await.ok()?;
+        serde_json::from_str(&raw?).ok()
+    }
+
+    /// Replace the stored inventory and push `changes` to
+    /// `{prefix}:app_events:{hostname}` (newest first, last 500 kept).
+    pub async fn push_installed_apps(&self, hostname: &str, apps: &[InstalledApp], changes: &[AppChange]) {
+        let Some(mut con) = self.conn("push_installed_apps").await else {
+            return;
+        };
+        let Ok(inventory) = serde_json::to_string(apps) else {
+            return;
+        };
+
+        let events_key = self.key(&["app_events", hostname]);
+        let mut pipe = redis::pipe();
+        self.count_bytes(inventory.len());
+        pipe.set(self.key(&["installed_apps", hostname]), inventory);
+        for change in changes {
+            let payload = serde_json::to_string(change).unwrap_or_default();
+            self.count_bytes(payload.len());
+            pipe.lpush(&events_key, payload);
+        }
+        pipe.ltrim(&events_key, 0, 499);
+        let result: redis::RedisResult<()> = pipe.query_async(&mut con).await;
+        if let Err(e) = result {
+            warn!("Failed to record application inventory: {e}");
+        }
+    }
+
+    /// Fetch the last `n` violations for a host.
+    pub async fn recent_violations(
+        &self,
+        hostname: &str,
+        count: isize,
+    ) -> Vec<Violation> {
+        let raw = self.events("violations", hostname, count).await;
+
+        raw.iter()
+            .filter_map(|s| serde_json::from_str(s).ok())
+            .collect()
+    }
+
+    /// Everything recorded about a host between `from` and `to`, oldest
+    /// first: violations, audit events (lock actions and agent sessions
+    /// among them), usage samples, screenshots, detector failures and
+    /// application installs / uninstalls.
+    pub async fn timeline(
+        &self,
+        hostname: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<TimelineEvent> {
+        let lists = ["violations", "audit", "usage", "screenshot_history", "detector_failures", "app_events"];
+        let mut raw = Vec::new();
+        for list in lists {
+            raw.push(self.events(list, hostname, TIMELINE_SCAN).await);
+        }
+
+        let mut events: Vec<TimelineEvent> = lists
+            .iter()
+            .zip(raw)
+            .flat_map(|(list, entries)| entries.into_iter().map(move |e| (*list, e)))
+            .filter_map(|(list, entry)| timeline_event(list, &entry))
+            .filter(|e| e.timestamp >= from && e.timestamp <= to)
+            .collect();
+        events.sort_by_key(|e| e.timestamp);
+        events
+    }
+
+    /// Register the machine for discovery in the Hash `{prefix}:agents`,
+    /// field `hostname`, as JSON with its address and labels, plus the
+    /// advertised capabilities as JSON at `{prefix}:capabilities:{hostname}`.
+    /// Replaces the Set older agents kept there.
+    pub async fn register_agent(
+        &self,
+        hostname: &str,
+        ip: &str,
+        port: u16,
+        labels: &BTreeMap<String, String>,
+        capabilities: &Capabilities,
+    ) {
+        let Some(mut con) = self.conn("register_agent").await else {
+            return;
+        };
+
+        let value = serde_json::json!({
+            "ip": ip,
+            "port": port,
+            "labels": labels,
+            "updated_at": Utc::now(),
+        })
+        .to_string();
+        let key = self.key(&["agents"]);
+        let mut registered: redis::RedisResult<()> = con.hset(&key, hostname, &value).await;
+        if registered.as_ref().is_err_and(|e| e.code() == Some("WRONGTYPE")) {
+            info!("Replacing the agents Set at {key} with a Hash");
+            let _: redis::RedisResult<()> = con.del(&key).await;
+            registered = con.hset(&key, hostname, &value).await;
+        }
+        if let Err(e) = registered {
+            debug!("Failed to register agent: {e}");
+        }
+
+        if let Ok(json) = serde_json::to_string(capabilities) {
+            let key = self.key(&["capabilities", hostname]);
+            let _: redis::RedisResult<()> = con.set(&key, json).await;
+        }
+    }
+
+    /// Take the agent off `{prefix}:agents` and delete its heartbeat, so it
+    /// shows as offline at once (on shutdown).
+    pub async fn deregister_agent(&self, hostname: &str) {
+        if let Some(pg) = &self.pg {
+            pg.remove_heartbeat(hostname).await;
+        }
+        let Some(mut con) = self.conn("deregister_agent").await else {
+            return;
+        };
+        let result: redis::RedisResult<()> = redis::pipe()
+            .hdel(self.key(&["agents"]), hostname)
+            .del(self.key(&["heartbeat", hostname]))
+            .query_async(&mut con)
+            .await;
+        match result {
+            Ok(()) => info!("👋 Deregistered from Redis"),
+            Err(e) => warn!("Failed to deregister agent: {e}"),
+        }
+    }
+
+    /// Before exit: append the pending repeats, replay the offline queue
+    /// while Redis is reachable (what isn't sent stays queued for the next
+    /// start) and give the violations held for the teacher server a last
+    /// try.
+    pub async fn flush(&self) {
+        self.flush_repeats(Duration::ZERO).await;
+        if let Some(outbox) = &self.outbox {
+            let mut outbox = outbox.lock().await;
+            if !outbox.is_empty() {
+                if let Some(con) = self.conn("flush").await {
+                    self.replay(&mut outbox, &con).await;
+                }
+            }
+        }
+
+        let held: Vec<serde_json::Value> =
+            self.teacher_retry.lock().unwrap_or_else(PoisonError::into_inner).queue.iter().cloned().collect();
+        if held.is_empty() {
+            return;
+        }
+        let sent = self.send_violations_to_teacher(&held).await;
+        let mut retry = self.teacher_retry.lock().unwrap_or_else(PoisonError::into_inner);
+        let sent = sent.min(retry.queue.len());
+        retry.queue.drain(..sent);
+        if !retry.queue.is_empty() {
+            warn!("{} violation(s) never reached the teacher server", retry.queue.len());
+        }
+    }
+
+    /// Store a screenshot for a host. The JPEG (`data` from the Redis
+    /// screenshot sink) goes in binary at `{prefix}:screenshot_jpeg:{hostname}`,
+    /// the rest in the Hash `{prefix}:screenshot_meta:{hostname}`, both
+    /// expiring after `[screenshots] latest_ttl_secs`. History: metadata JSON in
+    /// `{prefix}:screenshot_history:{hostname}` and the image (empty if kept
+    /// elsewhere) at the same index of `screenshot_history_jpeg:{hostname}`.
+    /// `fields` come from the screenshot sink: the inline `data`, or a
+    /// reference to where the image was put.
+    /// Queued while offline.
+    #[cfg(feature = "screenshots")]
+    pub async fn push_screenshot(&self, hostname: &str, mut fields: serde_json::Map<String, serde_json::Value>) {
+        let timestamp = Utc::now();
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("hostname".into(), hostname.into());
+        metadata.insert("timestamp".into(), timestamp.to_rfc3339().into());
+
+        if let Some(pg) = &self.pg {
+            metadata.extend(fields);
+            let payload = serde_json::Value::Object(metadata).to_string();
+            if pg.push_screenshot(hostname, &payload, self.screenshot_history as i64).await {
+                info!("Screenshot stored in Postgres ({hostname})");
+                self.metrics.count("screenshots_stored", 1);
+            }
+            return;
+        }
+
+        let jpeg = match fields.remove("data") {
+            Some(serde_json::Value::String(b64)) => match base64::engine::general_purpose::STANDARD.decode(b64) {
+                Ok(jpeg) => Some(jpeg),
+                Err(e) => {
+                    error!("Screenshot isn't valid base64: {e}");
+                    return;
+                }
+            },
+            _ => None,
+        };
+        metadata.extend(fields);
+        if jpeg.is_some() {
+            metadata.insert("image".into(), "redis".into());
+        }
+        let payload = serde_json::Value::Object(metadata.clone()).to_string();
+        let hash = metadata
+            .iter()
+            .map(|(k, v)| (k.clone(), v.as_str().map_or_else(|| v.to_string(), str::to_owned)))
+            .collect();
+
+        let meta_key = self.key(&["screenshot_meta", hostname]);
+        let mut ops = vec![QueuedOp::HsetEx { key: meta_key.clone(), fields: hash, ttl_secs: self.screenshot_ttl_secs }];
+        if let Some(jpeg) = &jpeg {
+            ops.push(QueuedOp::SetExBytes { key: self.key(&["screenshot_jpeg", hostname]), value: jpeg.clone(), ttl_secs: self.screenshot_ttl_secs });
+        }
+        if self.events.streams() {
+            ops.push(self.stream_op("screenshot_history", hostname, payload.clone()));
+        }
+        if self.events.lists() {
+            let key = self.key(&["screenshot_history", hostname]);
+            ops.push(QueuedOp::Lpush { key, value: payload, keep: Some(self.screenshot_history) });
+            let key = self.key(&["screenshot_history_jpeg", hostname]);
+            ops.push(QueuedOp::LpushBytes { key, value: jpeg.unwrap_or_default(), keep: Some(self.screenshot_history) });
+        }
+        if self.deliver("screenshot", ops).await.is_some() {
+            info!("Screenshot pushed → {meta_key}");
+            self.metrics.count("screenshots_stored", 1);
+        }
+    }
+
+    /// Append a recorded stream frame (`fields` from the screenshot sink:
+    /// its object key and metadata) to the List
+    /// `{prefix}:recordings:{hostname}`, newest first, `keep` entries.
+    /// Queued while offline.
+    #[cfg(feature = "streaming")]
+    pub async fn push_recording(&self, hostname: &str, fields: serde_json::Map<String, serde_json::Value>, keep: isize) {
+        let key = self.key(&["recordings", hostname]);
+        let value = serde_json::Value::Object(fields).to_string();
+        self.deliver("recording", vec![QueuedOp::Lpush { key, value, keep: Some(keep.max(1)) }]).await;
+    }
+
+    /// Store this host's part of a room-wide snapshot in the Hash
+    /// `{prefix}:snapshot:{id}` (field per hostname), kept `ttl_secs`.
+    #[cfg(feature = "screenshots")]
+    pub async fn push_snapshot(&self, id: &str, hostname: &str, payload: &str, ttl_secs: u64) -> bool {
+        let Some(mut con) = self.conn("push_snapshot").await else {
+            return false;
+        };
+        self.count_bytes(payload.len());
+        let key = self.key(&["snapshot", id]);
+        let stored: redis::RedisResult<()> = redis::pipe()
+            .hset(&key, hostname, payload)
+            .ignore()
+            .expire(&key, ttl_secs as i64)
+            .ignore()
+            .query_async(&mut con)
+            .await;
+        match stored {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Failed to push snapshot {id}: {e}");
+                false
+            }
+        }
+    }
+
+    /// How far the Redis server's clock is ahead of ours, from `TIME`
+    /// with half the round trip allowed for. Agents that add it share
+    /// one clock however far their own ones drifted.
+    #[cfg(feature = "screenshots")]
+    pub async fn clock_offset(&self) -> Option<chrono::Duration> {
+        let mut con = self.conn("clock_offset").await?;
+        let sent = Utc::now();
+        let (secs, micros): (i64, u32) = redis::cmd("TIME").query_async(&mut con).await.ok()?;
+        let received = Utc::now();
+        let server = DateTime::from_timestamp(secs, micros * 1000)?;
+        Some(server - (sent + (received - sent) / 2))
+    }
+
+    /// Fetch the latest screenshot for a host. Falls back to the JSON
+    /// (inline base64 `data`) that agents before binary storage wrote to
+    /// `{prefix}:screenshot:{hostname}`, which is also what Postgres keeps.
+    pub async fn latest_screenshot(&self, hostname: &str) -> Option<Screenshot> {
+        if let Some(pg) = &self.pg {
+            return Screenshot::from_json(&pg.latest_screenshot(hostname, self.screenshot_ttl_secs).await?);
+        }
+        let mut con = self.conn("latest_screenshot").await?;
+
+        let hash: HashMap<String, String> = con.hgetall(self.key(&["screenshot_meta", hostname])).await.ok()?;
+        if hash.is_empty() {
+            let legacy: Option<String> = con.get(self.key(&["screenshot", hostname])).await.ok()?;
+            return Screenshot::from_json(&legacy?);
+        }
+        let meta: serde_json::Map<String, serde_json::Value> = hash
+            .into_iter()
+            .map(|(k, v)| {
+                // Strings were stored bare, everything else as JSON
+                let value = serde_json::from_str(&v).ok().filter(|v: &serde_json::Value| !v.is_string());
+                (k, value.unwrap_or(serde_json::Value::String(v)))
+            })
+            .collect();
+        let jpeg = if meta.get("image").and_then(|v| v.as_str()) == Some("redis") {
+            con.get::<_, Option<Vec<u8>>>(self.key(&["screenshot_jpeg", hostname])).await.ok()?
+        } else {
+            None
+        };
+        Some(Screenshot { meta, jpeg })
+    }
+
+    /// The stored screenshot history for a host, newest first, with
+    /// images kept in Redis inlined as base64 `data`.
+    pub async fn screenshot_history(&self, hostname: &str) -> Vec<serde_json::Value> {
+        let raw = self.events("screenshot_history", hostname, self.screenshot_history).await;
+        let mut shots: Vec<serde_json::Value> = raw.iter().filter_map(|r| serde_json::from_str(r).ok()).collect();
+        if self.pg.is_some() || !self.events.lists() {
+            return shots;
+        }
+        let Some(mut con) = self.conn("screenshot_history").await else {
+            return shots;
+        };
+        let key = self.key(&["screenshot_history_jpeg", hostname]);
+        let images: Vec<Vec<u8>> = con.lrange(key, 0, self.screenshot_history - 1).await.unwrap_or_default();
+        for (shot, jpeg) in shots.iter_mut().zip(images) {
+            if shot.get("image").and_then(|v| v.as_str()) == Some("redis") && !jpeg.is_empty() {
+                shot["data"] = base64::engine::general_purpose::STANDARD.encode(jpeg).into();
+            }
+        }
+        shots
+    }
+
+    /// Fetch the shared ban-list category `name` from `{prefix}:category:{name}`.
+    /// None if Redis is unreachable, the key is missing or it's malformed.
+    pub async fn fetch_category(&self, name: &str) -> Option<CategoryList> {
+        let mut con = self.conn("fetch_category").await?;
+
+        let key = self.global_key(&["category", name]);
+        let raw: Option<String> = con.get(&key).await.ok()?;
+        serde_json::from_str(&raw?).map_err(|e| warn!("Ignoring malformed category at {key}: {e}")).ok()
+    }
+
+    /// Fetch and merge the centrally-managed ban config layers:
+    /// `{prefix}:ban_config` (global), `{namespace}:ban_config` (room) and
+    /// `{namespace}:ban_config:{hostname}` (host), in that order.
+    /// Returns None if Redis is unreachable, no layer is published or one
+    /// of them doesn't parse: merging the others would silently lift the
+    /// bans of the broken one, so the config applied last stays.
+    pub async fn fetch_ban_config(&self, hostname: &str) -> Option<BanConfig> {
+        let mut con = self.conn("fetch_ban_config").await?;
+
+        let mut layers = Vec::new();
+        for key in self.layer_keys("ban_config", hostname) {
+            let raw: Option<String> = con.get(&key).await.ok()?;
+            let Some(raw) = raw else { continue };
+            match serde_json::from_str::<BanLayer>(&raw) {
+                Ok(layer) => layers.push(layer),
+                Err(e) => {
+                    warn!("Malformed ban config at {key}, keeping the current one: {e}");
+                    return None;
+                }
+            }
+        }
+
+        if layers.is_empty() {
+            return None;
+        }
+        Some(BanConfig::merge(&layers))
+    }
+
+    /// `{prefix}:{name}`, `{namespace}:{name}` (when tags make it a
+    /// different key) and `{namespace}:{name}:{hostname}`: the layers of a
+    /// teacher-set config, least specific first.
+    fn layer_keys(&self, name: &str, hostname: &str) -> Vec<String> {
+        let mut keys = vec![self.global_key(&[name])];
+        if self.namespace != self.prefix {
+            keys.push(self.key(&[name]));
+        }
+        keys.push(self.key(&[name, hostname]));
+        keys
+    }
+
+    /// Redis-side overrides of a config.toml section (`schedule`,
+    /// `streaming`): the JSON layers at `layer_keys` that exist, least
+    /// specific first. None when Redis can't be reached or a layer isn't
+    /// JSON, so the override in force stays.
+    pub async fn fetch_overrides(&self, name: &str, hostname: &str) -> Option<Vec<serde_json::Value>> {
+        let mut con = self.conn("fetch_overrides").await?;
+        let mut layers = Vec::new();
+        for key in self.layer_keys(name, hostname) {
+            let raw: Option<String> = con.get(&key).await.ok()?;
+            let Some(raw) = raw else { continue };
+            match serde_json::from_str(&raw) {
+                Ok(layer) => layers.push(layer),
+                Err(e) => {
+                    warn!("Malformed {name} override at {key}, keeping the current one: {e}");
+                    return None;
+                }
+            }
+        }
+        Some(layers)
+    }
+
+    /// Publish the dry-run diff of an incoming ban config.
+    /// Key: `{namespace}:ban_diff:{hostname}` (expires after an hour)
+    pub async fn publish_ban_diff(&self, hostname: &str, diff: &BanDiff) {
+        let Some(mut con) = self.conn("publish_ban_diff").await else {
+            return;
+        };
+        let Ok(payload) = serde_json::to_string(diff) else {
+            return;
+        };
+        let key = self.key(&["ban_diff", hostname]);
+        self.count_bytes(payload.len());
+        let result: redis::RedisResult<()> = con.set_ex(&key, payload, 3600).await;
+        if let Err(e) = result {
+            warn!("Failed to publish ban diff: {e}");
+        }
+    }
+
+    /// Acknowledge that the config with this fingerprint is in force.
+    /// Key: `{namespace}:ban_applied:{hostname}` (JSON `fingerprint`, `applied_at`)
+    pub async fn ack_ban_config(&self, hostname: &str, fingerprint: &str) {
+        let Some(mut con) = self.conn("ack_ban_config").await else {
+            return;
+        };
+        let payload = serde_json::json!({ "fingerprint": fingerprint, "applied_at": Utc::now() }).to_string();
+        self.count_bytes(payload.len());
+        let result: redis::RedisResult<()> = con.set(self.key(&["ban_applied", hostname]), payload).await;
+        if let Err(e) = result {
+            warn!("Failed to acknowledge ban config: {e}");
+        }
+    }
+
+    /// Has the teacher confirmed the config with this fingerprint?
+    /// Confirmation is written to `{namespace}:ban_confirm:{hostname}` or,
+    /// for the whole room, `{namespace}:ban_confirm`.
+    pub async fn ban_change_confirmed(&self, hostname: &str, fingerprint: &str) -> bool {
+        let Some(mut con) = self.conn("ban_change_confirmed").await else {
+            return false;
+        };
+        for key in [self.key(&["ban_confirm", hostname]), self.key(&["ban_confirm"])] {
+            let val: Option<String> = con.get(&key).await.unwrap_or(None);
+            if val.as_deref() == Some(fingerprint) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The teacher servers published in Redis, preferred first: the JSON
+    /// records in the Hash `{namespace}:server:endpoints` (one field per
+    /// server) by `priority`, or a site-wide server's under the bare
+    /// prefix. Failing those, the bare IP older servers publish to
+    /// `server:ip`, on port 8080. Empty if Redis is unreachable.
+    pub async fn discover_teachers(&self) -> Vec<TeacherEndpoint> {
+        let Some(mut con) = self.conn("discover_teachers").await else {
+            return Vec::new();
+        };
+        let mut scopes = vec![self.key(&["server"])];
+        if self.namespace != self.prefix {
+            scopes.push(self.global_key(&["server"]));
+        }
+
+        for scope in &scopes {
+            let key = Self::join(scope, &["endpoints"]);
+            let records: HashMap<String, String> = con.hgetall(&key).await.unwrap_or_default();
+            let mut endpoints: Vec<TeacherEndpoint> = records
+                .iter()
+                .filter_map(|(id, raw)| {
+                    serde_json::from_str(raw)
+                        .map_err(|e| warn!("Ignoring malformed teacher endpoint {id} at {key}: {e}"))
+                        .ok()
+                })
+                .collect();
+            if !endpoints.is_empty() {
+                endpoints.sort_by(|a, b| a.priority.cmp(&b.priority).then_with(|| a.host.cmp(&b.host)));
+                return endpoints;
+            }
+        }
+        for scope in &scopes {
+            let ip: Option<String> = con.get(Self::join(scope, &["ip"])).await.unwrap_or(None);
+            if let Some(ip) = ip {
+                return vec![TeacherEndpoint::legacy(ip)];
+            }
+        }
+        Vec::new()
+    }
+
+    /// POST `body` to `path` on the first of `endpoints` that answers.
+    /// Returns its index and the response; None when none did.
+    async fn post_to_teacher(
+        &self,
+        endpoints: &[TeacherEndpoint],
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Option<(usize, reqwest::Response)> {
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            let url = format!("{}{path}", endpoint.api_url());
+            match self.http.post(&url).json(body).send().await {
+                Ok(resp) => return Some((i, resp)),
+                Err(e) => warn!("Teacher server at {url} unreachable: {e}"),
+            }
+        }
+        None
+    }
+
+    // ── per-room enumeration ────────────────────────────────────
+
+    /// Hostnames with a live heartbeat in this agent's namespace.
+    /// Only exact namespace matches count — a room's keys never leak into
+    /// a sibling room whose name shares a prefix.
+    pub async fn room_hosts(&self) -> Vec<String> {
+        if let Some(pg) = &self.pg {
+            return pg.room_heartbeats(self.heartbeat_ttl_secs).await.into_iter().map(|(host, _)| host).collect();
+        }
+        let Some(mut con) = self.conn("room_hosts").await else {
+            return Vec::new();
+        };
+
+        let base = self.key(&["heartbeat", ""]);
+        let pattern = format!("{base}*");
+        let mut hosts = Vec::new();
+        let Ok(mut iter) = con.scan_match::<_, String>(&pattern).await else {
+            return hosts;
+        };
+        while let Some(key) = iter.next_item().await {
+            if let Some(host) = key.strip_prefix(&base) {
+                if !host.contains(':') {
+                    hosts.push(host.to_owned());
+                }
+            }
+        }
+        hosts.sort();
+        hosts
+    }
+
+    /// Latest heartbeat of every host in this agent's room.
+    pub async fn room_heartbeats(&self) -> Vec<serde_json::Value> {
+        if let Some(pg) = &self.pg {
+            return pg.room_heartbeats(self.heartbeat_ttl_secs).await.iter().filter_map(|(_, hb)| serde_json::from_str(hb).ok()).collect();
+        }
+        let hosts = self.room_hosts().await;
+        let Some(mut con) = self.conn("room_heartbeats").await else {
+            return Vec::new();
+        };
+
+        let mut out = Vec::with_capacity(hosts.len());
+        for host in hosts {
+            let raw: Option<String> = con.get(self.key(&["heartbeat", &host])).await.unwrap_or(None);
+            if let Some(hb) = raw.and_then(|r| serde_json::from_str(&r).ok()) {
+                out.push(hb);
+            }
+        }
+        out
+    }
+
+    /// POST an agent-online event to the teacher server
+    /// (`/api/agent/online`), so a new or reimaged machine shows up on the
+    /// dashboard right away rather than when its heartbeat is found.
+    /// Returns whether the teacher accepted it.
+    pub async fn announce_online(
+        &self,
+        hostname: &str,
+        ip: &str,
+        port: u16,
+        username: &str,
+        capabilities: &Capabilities,
+    ) -> bool {
+        let endpoints = self.discover_teachers().await;
+        if endpoints.is_empty() {
+            return false;
+        }
+        let payload = serde_json::json!({
+            "hostname": hostname,
+            "ip": ip,
+            "port": port,
+            "username": username,
+            "namespace": self.namespace,
+            "version": capabilities.version,
+            "config_hash": capabilities.config_hash,
+            "capabilities": capabilities,
+            "timestamp": Utc::now(),
+        });
+
+        match self.post_to_teacher(&endpoints, "/api/agent/online", &payload).await {
+            Some((i, resp)) if resp.status().is_success() => {
+                info!("📣 Announced to teacher at {}", endpoints[i].api_url());
+                true
+            }
+            Some((_, resp)) => {
+                warn!("Teacher API returned {} for the online event", resp.status());
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// POST an agent-offline event to the teacher server
+    /// (`/api/agent/offline`) on shutdown.
+    pub async fn announce_offline(&self, hostname: &str) {
+        let endpoints = self.discover_teachers().await;
+        if endpoints.is_empty() {
+            return;
+        }
+        let payload = serde_json::json!({
+            "hostname": hostname,
+            "namespace": self.namespace,
+            "timestamp": Utc::now(),
+        });
+        match self.post_to_teacher(&endpoints, "/api/agent/offline", &payload).await {
+            Some((_, resp)) if resp.status().is_success() => {}
+            Some((_, resp)) => warn!("Teacher API returned {} for the offline event", resp.status()),
+            None => {}
+        }
+    }
+
+    /// Forward violations to the teacher backend via REST API (the
+    /// `teacher` violation sink), so they appear on the teacher dashboard
+    /// in real-time. What the server doesn't take (unreachable, 5xx) is
+    /// held, up to `TEACHER_RETRY_MAX`, and retried in the background with
+    /// backoff; while anything is held, new violations queue behind it so
+    /// they arrive in order.
+    pub async fn push_violations_to_teacher(&self, vs: &[Violation]) {
+        if vs.is_empty() {
+            return;
+        }
+        let payloads: Vec<serde_json::Value> = vs.iter().map(teacher_payload).collect();
+        if self.teacher_retry.lock().unwrap_or_else(PoisonError::into_inner).queue_behind(&payloads) {
+            return;
+        }
+        let sent = self.send_violations_to_teacher(&payloads).await;
+        if sent < payloads.len() && self.teacher_retry.lock().unwrap_or_else(PoisonError::into_inner).hold(&payloads[sent..])
+        {
+            tokio::spawn(self.clone().retry_teacher());
+        }
+    }
+
+    /// POST `payloads` in order until one fails. One endpoint lookup; once
+    /// a server has answered, the rest go to it. Returns how many are
+    /// done with (accepted, or refused for good with a 4xx).
+    async fn send_violations_to_teacher(&self, payloads: &[serde_json::Value]) -> usize {
+        let endpoints = self.discover_teachers().await;
+        if endpoints.is_empty() {
+            warn!("Cannot forward violation to teacher — address not discovered");
+            return 0;
+        }
+        let mut endpoints = endpoints.as_slice();
+        for (sent, payload) in payloads.iter().enumerate() {
+            let target = payload.get("rule").and_then(|d| d.as_str()).unwrap_or_default();
+            match self.post_to_teacher(endpoints, "/api/agent/violation", payload).await {
+                Some((i, resp)) => {
+                    endpoints = &endpoints[i..];
+                    if resp.status().is_success() {
+                        info!("✅ Violation forwarded to teacher: {target}");
+                    } else if resp.status().is_server_error() {
+                        warn!("Teacher API returned {}: {target} (will retry)", resp.status());
+                        return sent;
+                    } else {
+                        warn!("Teacher API returned {}: {target}", resp.status());
+                    }
+                }
+                None => {
+                    warn!("Failed to forward violation to teacher: no server reachable (will retry)");
+                    // The rest would only time out the same way
+                    return sent;
+                }
+            }
+        }
+        payloads.len()
+    }
+
+    /// Resend held violations until none are left, backing off from the
+    /// first to the last of `TEACHER_RETRY_BACKOFF` while they fail.
+    async fn retry_teacher(self) {
+        let (mut delay, max) = TEACHER_RETRY_BACKOFF;
+        loop {
+            tokio::time::sleep(delay).await;
+            let held: Vec<serde_json::Value> =
+                self.teacher_retry.lock().unwrap_or_else(PoisonError::into_inner).queue.iter().cloned().collect();
+            let sent = self.send_violations_to_teacher(&held).await;
+            let mut retry = self.teacher_retry.lock().unwrap_or_else(PoisonError::into_inner);
+            // `flush` may have sent some meanwhile
+            let sent = sent.min(retry.queue.len());
+            retry.queue.drain(..sent);
+            if retry.queue.is_empty() {
+                info!("📨 Held violations delivered to the teacher server");
+                *retry = TeacherRetry::default();
+                return;
+            }
+            if sent > 0 {
+                delay = TEACHER_RETRY_BACKOFF.0;
+            } else {
+                delay = (delay * 2).min(max);
+            }
+            debug!("{} violation(s) still held for the teacher server, retrying in {delay:?}", retry.queue.len());
+        }
+    }
+}
+
+/// Violations waiting for the teacher server, oldest first. New ones are
+/// dropped once `TEACHER_RETRY_MAX` are held, so the queue keeps the
+/// oldest (as the Redis offline queue does).
+#[derive(Default)]
+struct TeacherRetry {
+    queue: std::collections::VecDeque<serde_json::Value>,
+    /// A `retry_teacher` task is running.
+    retrying: bool,
+    /// Warned that the queue is full since it last drained.
+    full_warned: bool,
+}
+
+impl TeacherRetry {
+    /// Queue `payloads` if a retry is under way. Returns whether it was.
+    fn queue_behind(&mut self, payloads: &[serde_json::Value]) -> bool {
+        if self.retrying {
+            self.push(payloads);
+        }
+        self.retrying
+    }
+
+    /// Queue `payloads` that just failed. Returns whether a retry task
+    /// needs starting.
+    fn hold(&mut self, payloads: &[serde_json::Value]) -> bool {
+        self.push(payloads);
+        !std::mem::replace(&mut self.retrying, true)
+    }
+
+    fn push(&mut self, payloads: &[serde_json::Value]) {
+        let room = TEACHER_RETRY_MAX.saturating_sub(self.queue.len());
+        if payloads.len() > room && !std::mem::replace(&mut self.full_warned, true) {
+            warn!("{TEACHER_RETRY_MAX} violations held for the teacher server — dropping new ones until it's back");
+        }
+        self.queue.extend(payloads.iter().take(room).cloned());
+    }
+}
+
+/// `Store`'s shared connection, behind a circuit breaker: after
+/// `CIRCUIT_THRESHOLD` failures in a row the circuit opens and Redis calls
+/// return at once (nothing to connect to, offline queue for writes)
+/// until a retry is due; each failed retry doubles the wait. Opening and
+/// closing are logged once each, the failures in between only at debug.
+#[derive(Default)]
+struct SharedConn {
+    con: Option<Link>,
+    /// Last time `con` was opened or passed a health check.
+    checked: Option<Instant>,
+    /// Connection failures in a row.
+    failures: u32,
+    /// Set while the circuit is open: no connection attempt before then.
+    open_until: Option<Instant>,
+}
+
+impl SharedConn {
+    fn connected(&mut self, con: Link) {
+        if self.open_until.is_some() {
+            info!("🔌 Redis reachable again after {} failed attempt(s) — circuit closed", self.failures);
+        }
+        *self = Self { con: Some(con), checked: Some(Instant::now()), failures: 0, open_until: None };
+    }
+
+    fn failed(&mut self, error: &str) {
+        self.con = None;
+        self.failures += 1;
+        if self.failures < CIRCUIT_THRESHOLD {
+            debug!("Redis connection failed ({}/{CIRCUIT_THRESHOLD}): {error}", self.failures);
+            return;
+        }
+        let (first, max) = CIRCUIT_BACKOFF;
+        let wait = first.saturating_mul(1 << (self.failures - CIRCUIT_THRESHOLD).min(16)).min(max);
+        if self.open_until.is_none() {
+            warn!("🔌 Redis unreachable ({error}) — circuit open, skipping Redis calls and retrying with backoff");
+        } else {
+            debug!("Redis still unreachable, next try in {}s: {error}", wait.as_secs());
+        }
+        self.open_until = Some(Instant::now() + wait);
+    }
+}
+
+/// Where `Store` connects. A Sentinel failover is followed by the next
+/// reconnect (the health check notices the old master stepped down); a
+/// cluster connection follows slot moves and failovers by itself.
+#[derive(Clone)]
+enum Backend {
+    Single(Servers),
+    Sentinel {
+        sentinel: Arc<tokio::sync::Mutex<redis::sentinel::Sentinel>>,
+        master_name: String,
+        /// TLS and credentials for the master.
+        node: redis::sentinel::SentinelNodeConnectionInfo,
+    },
+    Cluster {
+        client: redis::cluster::ClusterClient,
+        /// Seed nodes, tried in order for pub/sub.
+        nodes: Vec<redis::ConnectionInfo>,
+    },
+}
+
+impl Backend {
+    fn new(cfg: &RedisConfig) -> anyhow::Result<Self> {
+        Ok(match cfg.mode {
+            RedisMode::Single => {
+                let mut list = Vec::new();
+                for url in std::iter::once(&cfg.url).chain(&cfg.fallback_urls) {
+                    let info = connection_info(cfg, url)?;
+                    list.push((info.addr.to_string(), redis::Client::open(info)?));
+                }
+                Self::Single(Servers { list, active: Arc::new(tokio::sync::watch::Sender::new(0)) })
+            }
+            _ if !cfg.fallback_urls.is_empty() => anyhow::bail!("[redis] fallback_urls needs mode = \"single\""),
+            RedisMode::Sentinel => {
+                if cfg.master_name.is_empty() {
+                    anyhow::bail!("[redis] mode = \"sentinel\" needs master_name");
+                }
+                let master = connection_info(cfg, &cfg.url)?;
+                let tls_mode = match master.addr {
+                    redis::ConnectionAddr::TcpTls { insecure: true, .. } => Some(redis::TlsMode::Insecure),
+                    redis::ConnectionAddr::TcpTls { .. } => Some(redis::TlsMode::Secure),
+                    _ => None,
+                };
+                Self::Sentinel {
+                    sentinel: Arc::new(tokio::sync::Mutex::new(redis::sentinel::Sentinel::build(cfg.sentinels.clone())?)),
+                    master_name: cfg.master_name.clone(),
+                    node: redis::sentinel::SentinelNodeConnectionInfo {
+                        tls_mode,
+                        redis_connection_info: Some(master.redis),
+                    },
+                }
+            }
+            RedisMode::Cluster => {
+                let nodes = cfg.cluster_nodes.iter().map(|url| connection_info(cfg, url)).collect::<anyhow::Result<Vec<_>>>()?;
+                Self::Cluster { client: redis::cluster::ClusterClient::new(nodes.clone())?, nodes }
+            }
+        })
+    }
+
+    /// Database number, for keyspace notification channels.
+    fn db(&self) -> i64 {
+        match self {
+            Self::Single(servers) => servers.current().1.get_connection_info().redis.db,
+            Self::Sentinel { node, .. } => node.redis_connection_info.as_ref().map_or(0, |info| info.db),
+            Self::Cluster { .. } => 0,
+        }
+    }
+
+    /// Longest a `connect` may take: every server in turn.
+    fn connect_timeout(&self) -> Duration {
+        match self {
+            Self::Single(servers) => CONNECT_TIMEOUT * servers.list.len() as u32,
+            _ => CONNECT_TIMEOUT,
+        }
+    }
+
+    async fn connect(&self) -> redis::RedisResult<Link> {
+        match self {
+            Self::Single(servers) => {
+                let (index, con) = servers.connect(servers.list.len()).await?;
+                servers.switched(index);
+                Ok(Link::Node(con))
+            }
+            Self::Sentinel { .. } => self.master().await?.get_multiplexed_async_connection().await.map(Link::Node),
+            Self::Cluster { client, .. } => client.get_async_connection().await.map(Link::Cluster),
+        }
+    }
+
+    /// PING the connection, or in sentinel mode check it's still to the
+    /// master.
+    async fn check(&self, con: &mut Link) -> redis::RedisResult<()> {
+        if !matches!(self, Self::Sentinel { .. }) {
+            return redis::cmd("PING").query_async(con).await;
+        }
+        let role: Vec<redis::Value> = redis::cmd("ROLE").query_async(con).await?;
+        match role.first() {
+            Some(redis::Value::Data(r)) if r.as_slice() == b"master" => Ok(()),
+            _ => Err((redis::ErrorKind::ReadOnly, "no longer the master").into()),
+        }
+    }
+
+    /// The current master, as the sentinels see it (the server at `url`
+    /// outside sentinel mode).
+    async fn master(&self) -> redis::RedisResult<redis::Client> {
+        match self {
+            Self::Sentinel { sentinel, master_name, node } => {
+                sentinel.lock().await.async_master_for(master_name, Some(node)).await
+            }
+            Self::Single(servers) => Ok(servers.current().1.clone()),
+            Self::Cluster { .. } => Err((redis::ErrorKind::InvalidClientConfig, "no single master in cluster mode").into()),
+        }
+    }
+
+    /// Messages published anywhere in a cluster reach every node, so
+    /// subscribing to the first seed that answers is enough.
+    async fn pubsub(&self) -> redis::RedisResult<redis::aio::PubSub> {
+        match self {
+            Self::Single(servers) => servers.current().1.get_async_pubsub().await,
+            Self::Sentinel { .. } => self.master().await?.get_async_pubsub().await,
+            Self::Cluster { nodes, .. } => {
+                let mut last_err = None;
+                for node in nodes {
+                    match redis::Client::open(node.clone())?.get_async_pubsub().await {
+                        Ok(pubsub) => return Ok(pubsub),
+                        Err(e) => last_err = Some(e),
+                    }
+                }
+                Err(last_err.unwrap_or_else(|| (redis::ErrorKind::InvalidClientConfig, "no cluster nodes").into()))
+            }
+        }
+    }
+}
+
+/// `[redis] url` and `fallback_urls`, in the order they are preferred.
+/// Every connect starts from the first, so a reconnect after a dropped
+/// connection already fails back.
+#[derive(Clone)]
+struct Servers {
+    /// `host:port` (for the logs and heartbeats) and client of each.
+    list: Vec<(String, redis::Client)>,
+    /// Index of the server last connected to (shared by all clones).
+    active: Arc<tokio::sync::watch::Sender<usize>>,
+}
+
+impl Servers {
+    fn current(&self) -> &(String, redis::Client) {
+        &self.list[*self.active.borrow()]
+    }
+
+    /// Connect to the first of the first `before` servers that answers.
+    async fn connect(&self, before: usize) -> redis::RedisResult<(usize, redis::aio::MultiplexedConnection)> {
+        let mut last_err = None;
+        for (index, (name, client)) in self.list.iter().enumerate().take(before) {
+            let connected = tokio::time::timeout(CONNECT_TIMEOUT, client.get_multiplexed_async_connection())
+                .await
+                .unwrap_or_else(|_| Err((redis::ErrorKind::IoError, "connection timed out").into()));
+            match connected {
+                Ok(con) => return Ok((index, con)),
+                Err(e) => {
+                    debug!("Redis server {name} unreachable: {e}");
+                    last_err = Some(e);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| (redis::ErrorKind::InvalidClientConfig, "no Redis server to try").into()))
+    }
+
+    /// Note that the shared connection is now to server `index`.
+    fn switched(&self, index: usize) {
+        let previous = self.active.send_replace(index);
+        if previous == index {
+            return;
+        }
+        let name = &self.list[index].0;
+        if index < previous {
+            info!("🔌 Redis: back on {name}");
+        } else {
+            warn!("🔌 Redis: {} unreachable — failed over to {name}", self.list[previous].0);
+        }
+    }
+}
+
+/// A connection from `Backend::connect`.
+#[derive(Clone)]
+enum Link {
+    Node(redis::aio::MultiplexedConnection),
+    Cluster(redis::cluster_async::ClusterConnection),
+}
+
+/// The shared connection as handed to one operation: every round trip
+/// on it is timed under the operation's name (see `StoreMetrics`).
+#[derive(Clone)]
+struct Conn {
+    link: Link,
+    op: &'static str,
+    metrics: Arc<StoreMetrics>,
+}
+
+impl redis::aio::ConnectionLike for Conn {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a redis::Cmd) -> redis::RedisFuture<'a, redis::Value> {
+        let started = Instant::now();
+        Box::pin(async move {
+            let result = self.link.req_packed_command(cmd).await;
+            self.metrics.record(self.op, started.elapsed(), result.is_ok());
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        pipeline: &'a redis::Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+        let started = Instant::now();
+        Box::pin(async move {
+            let result = self.link.req_packed_commands(pipeline, offset, count).await;
+            self.metrics.record(self.op, started.elapsed(), result.is_ok());
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.link.get_db()
+    }
+}
+
+impl redis::aio::ConnectionLike for Link {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a redis::Cmd) -> redis::RedisFuture<'a, redis::Value> {
+        match self {
+            Self::Node(con) => con.req_packed_command(cmd),
+            Self::Cluster(con) => con.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        pipeline: &'a redis::Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+        match self {
+            Self::Node(con) => con.req_packed_commands(pipeline, offset, count),
+            // A cluster refuses pipelines whose keys sit in different
+            // slots, which ours nearly always do — send them one by one
+            // (MULTI/EXEC blocks go through as they are)
+            Self::Cluster(con) => {
+                if offset > 0 {
+                    return con.req_packed_commands(pipeline, offset, count);
+                }
+                Box::pin(async move {
+                    let mut values = Vec::with_capacity(count);
+                    for cmd in pipeline.cmd_iter() {
+                        values.push(con.req_packed_command(cmd).await?);
+                    }
+                    Ok(values)
+                })
+            }
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Self::Node(con) => con.get_db(),
+            Self::Cluster(con) => con.get_db(),
+        }
+    }
+}
+
+/// Whether `e` is the connection's fault rather than the command's (or
+/// a server that is loading, failing over or read-only for now), so the
+/// same write may go through later. The open circuit never gets this
+/// far: there's no connection to write on.
+fn is_transient(e: &redis::RedisError) -> bool {
+    use redis::ErrorKind::*;
+
+    e.is_io_error()
+        || e.is_connection_dropped()
+        || e.is_timeout()
+        || matches!(e.kind(), ReadOnly | BusyLoadingError | TryAgain | ClusterDown | MasterDown)
+}
+
+/// `url` with `tls_insecure` and the ACL credentials applied.
+fn connection_info(cfg: &RedisConfig, url: &str) -> anyhow::Result<redis::ConnectionInfo> {
+    use redis::IntoConnectionInfo;
+
+    let mut info = url.into_connection_info()?;
+    if cfg.tls_insecure {
+        if let redis::ConnectionAddr::TcpTls { insecure, .. } = &mut info.addr {
+            *insecure = true;
+        }
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Secrets {
+        username: Option<String>,
+        password: Option<String>,
+    }
+    let secrets = match &cfg.secrets_file {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Redis secrets file {path}: {e}"))?;
+            toml::from_str(&text).map_err(|e| anyhow::anyhow!("Redis secrets file {path}: {e}"))?
+        }
+        None => Secrets { username: None, password: None },
+    };
+    let from_env = |var: &str| std::env::var(var).ok().filter(|v| !v.is_empty());
+    if let Some(username) = from_env("NISHACK_REDIS_USERNAME").or(secrets.username) {
+        info.redis.username = Some(username);
+    }
+    if let Some(password) = from_env("NISHACK_REDIS_PASSWORD").or(secrets.password) {
+        info.redis.password = Some(password);
+    }
+    Ok(info)
+}
+
+/// Mounted disks with their free space; snap / squashfs images (always
+/// full) are left out. Blocking.
+fn disk_space() -> Vec<DiskSpace> {
+    let mut disks: Vec<DiskSpace> = sysinfo::Disks::new_with_refreshed_list()
+        .iter()
+        .filter(|d| d.total_space() > 0 && d.file_system() != "squashfs")
+        .map(|d| DiskSpace {
+            mount: d.mount_point().to_string_lossy().into_owned(),
+            total_mb: d.total_space() / 1_048_576,
+            free_mb: d.available_space() / 1_048_576,
+        })
+        .collect();
+    disks.sort_by(|a, b| a.mount.cmp(&b.mount));
+    disks.dedup_by(|a, b| a.mount == b.mount);
+    disks
+}
+
+/// The hottest CPU sensor (package, Tctl, cores). Blocking.
+fn cpu_temperature() -> Option<f32> {
+    sysinfo::Components::new_with_refreshed_list()
+        .iter()
+        .filter(|c| {
+            let label = c.label().to_lowercase();
+            ["cpu", "package", "core", "tctl", "tdie", "k10temp", "coretemp"].iter().any(|k| label.contains(k))
+        })
+        .map(|c| c.temperature())
+        .filter(|t| t.is_finite() && *t > 0.0)
+        .reduce(f32::max)
+}
+
+/// Map the student-side violation model onto the **teacher-backend** schema:
+/// `{ hostname, rule, detail, severity, timestamp }` plus optional extras.
+pub(crate) fn teacher_payload(v: &Violation) -> serde_json::Value {
+    let mut detail = format!("{}: {}", v.kind.label(), v.url.as_deref().unwrap_or(&v.target));
+    // A summary of suppressed records has no outcome of its own
+    if v.kind != ViolationKind::Suppressed {
+        detail.push_str(if v.action_taken { " (заблокировано)" } else { " (не удалось заблокировать)" });
+    }
+    if let Some(extra) = &v.detail {
+        detail.push_str(&format!(" — {extra}"));
+    }
+
+    let mut payload = serde_json::json!({
+        "hostname": v.hostname,
+        "rule": v.kind.rule(),
+        "detail": detail,
+        "severity": v.kind.severity(),
+        "timestamp": v.timestamp.to_rfc3339(),
+    });
+    if let Some(url) = &v.url {
+        payload["url"] = url.clone().into();
+    }
+    if let Some(visited_at) = v.visited_at {
+        payload["visited_at"] = visited_at.to_rfc3339().into();
+    }
+    if let Some(process) = &v.process {
+        payload["process"] = process.clone().into();
+    }
+    if let Some(exe) = &v.exe {
+        payload["exe"] = exe.clone().into();
+    }
+    if !v.username.is_empty() {
+        payload["username"] = v.username.clone().into();
+    }
+    if v.occurrences > 1 {
+        payload["occurrences"] = v.occurrences.into();
+    }
+    if let Some(last_seen) = v.last_seen {
+        payload["last_seen"] = last_seen.to_rfc3339().into();
+    }
+    payload
+}
+
+/// Turn one raw list entry into a timeline event. Screenshots lose their
+/// image data; audit events are split into lock actions, agent sessions
+/// and everything else.
+fn timeline_event(list: &str, entry: &str) -> Option<TimelineEvent> {
+    let mut data: serde_json::Value = serde_json::from_str(entry).ok()?;
+    let timestamp = data.get("timestamp")?.as_str()?.parse::<DateTime<Utc>>().ok()?;
+    let field = |name: &str| data.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+
+    let (source, summary) = match list {
+        "violations" => ("violation", field("detail")),
+        "audit" => {
+            let action = field("action");
+            let mut summary = format!("{action} by {}", field("actor"));
+            if let Some(detail) = data.get("detail").and_then(|d| d.as_str()) {
+                summary.push_str(&format!(": {detail}"));
+            }
+            let source = match action.as_str() {
+                "lock" | "unlock" | "unlock_code_rejected" | "focus_started" | "focus_stopped" => "lock",
+                "agent_started" | "agent_stopped" => "session",
+                "screenshot_viewed" | "screenshot_history_viewed" | "room_viewed" | "snapshot_scheduled" | "stream_started"
+                | "stream_stopped" | "stream_auth_failed" | "stream_handoff" => "screen_access",
+                _ => "audit",
+            };
+            (source, summary)
+        }
+        "usage" => {
+            let pct = |name: &str| data.get(name).and_then(|v| v.as_f64()).unwrap_or_default();
+            let mut summary = format!(
+                "{}: CPU {:.0}%, RAM {:.0}%",
+                field("username"),
+                pct("cpu_usage"),
+                pct("ram_usage")
+            );
+            let documents: Vec<OpenDocument> =
+                data.get("documents").and_then(|d| serde_json::from_value(d.clone()).ok()).unwrap_or_default();
+            if !documents.is_empty() {
+                let labels: Vec<String> = documents.iter().map(OpenDocument::label).collect();
+                summary.push_str(&format!(", open: {}", labels.join(", ")));
+            }
+            ("usage", summary)
+        }
+        "screenshot_history" => {
+            if let Some(obj) = data.as_object_mut() {
+                obj.remove("data");
+            }
+            let size = data.get("size").and_then(|v| v.as_u64()).unwrap_or_default();
+            ("screenshot", format!("screenshot ({} KB)", size / 1024))
+        }
+        "detector_failures" => ("detector_failure", format!("{} failed: {}", field("detector"), field("message"))),
+        "app_events" => ("app", format!("{} {} {}", field("action"), field("name"), field("version")).trim_end().to_string()),
+        _ => return None,
+    };
+    Some(TimelineEvent { timestamp, source, summary, data })
 }
@@ -0,0 +1,139 @@
+// ─────────────────────────────────────────────────────────────────
+//  store_metrics.rs — Counters and latencies of Store operations
+//
+//  Every Redis round trip a `Store` operation makes is timed under the
+//  operation's name (`push_heartbeat`, `fetch_ban_config`, …) and
+//  failed ones are counted apart; an operation skipped because Redis
+//  can't be reached counts as `unavailable`. Next to those: connects
+//  and reconnects, failed connection attempts, and what the agent got
+//  written (heartbeats, violations, screenshots, writes queued offline
+//  and replayed). `GET /metrics` serves them in the Prometheus text
+//  format; every `[self_report] stats_secs` they also go to
+//  `{prefix}:agent_stats:{hostname}` as JSON for the dashboard.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// Upper bounds of the latency buckets, in seconds.
+const BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0, 5.0];
+
+/// Round trips of one operation.
+#[derive(Debug, Clone, Default)]
+pub struct OpStats {
+    pub calls: u64,
+    pub failures: u64,
+    /// Times it was skipped: no connection, or the circuit was open.
+    pub unavailable: u64,
+    pub total_secs: f64,
+    pub max_secs: f64,
+    /// Calls per latency bucket (not cumulative); the last is for
+    /// anything slower than the last bound.
+    buckets: [u64; BUCKETS.len() + 1],
+}
+
+impl OpStats {
+    fn mean_ms(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.total_secs * 1000.0 / self.calls as f64
+        }
+    }
+}
+
+/// Shared by all clones of a `Store`.
+#[derive(Default)]
+pub struct StoreMetrics {
+    ops: Mutex<BTreeMap<&'static str, OpStats>>,
+    counters: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl StoreMetrics {
+    /// One round trip of `op` that took `elapsed`.
+    pub fn record(&self, op: &'static str, elapsed: Duration, ok: bool) {
+        let secs = elapsed.as_secs_f64();
+        let mut ops = self.ops.lock().unwrap_or_else(PoisonError::into_inner);
+        let stats = ops.entry(op).or_default();
+        stats.calls += 1;
+        stats.failures += u64::from(!ok);
+        stats.total_secs += secs;
+        stats.max_secs = stats.max_secs.max(secs);
+        stats.buckets[BUCKETS.iter().position(|&le| secs <= le).unwrap_or(BUCKETS.len())] += 1;
+    }
+
+    /// `op` was skipped for lack of a connection.
+    pub fn unavailable(&self, op: &'static str) {
+        self.ops.lock().unwrap_or_else(PoisonError::into_inner).entry(op).or_default().unavailable += 1;
+    }
+
+    pub fn count(&self, name: &'static str, by: u64) {
+        *self.counters.lock().unwrap_or_else(PoisonError::into_inner).entry(name).or_default() += by;
+    }
+
+    /// A new connection is up: the first one or a reconnect.
+    pub fn connected(&self) {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        let name = if counters.contains_key("connects") { "reconnects" } else { "connects" };
+        *counters.entry(name).or_default() += 1;
+    }
+
+    /// Everything so far, for `agent_stats`.
+    pub fn snapshot(&self) -> serde_json::Value {
+        let ops = self.ops.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let ops: BTreeMap<_, _> = ops
+            .into_iter()
+            .map(|(op, stats)| {
+                let mean_ms = stats.mean_ms();
+                (op, serde_json::json!({
+                    "calls": stats.calls,
+                    "failures": stats.failures,
+                    "unavailable": stats.unavailable,
+                    "mean_ms": (mean_ms * 100.0).round() / 100.0,
+                    "max_ms": (stats.max_secs * 100_000.0).round() / 100.0,
+                }))
+            })
+            .collect();
+        serde_json::json!({ "counters": counters, "ops": ops })
+    }
+
+    /// The Prometheus text exposition of everything so far.
+    pub fn prometheus(&self) -> String {
+        let ops = self.ops.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let mut out = String::new();
+
+        let name = "nishack_store_op_failures_total";
+        let _ = writeln!(out, "# HELP {name} Redis round trips of a Store operation that failed.\n# TYPE {name} counter");
+        for (op, stats) in &ops {
+            let _ = writeln!(out, "{name}{{op=\"{op}\"}} {}", stats.failures);
+        }
+        let name = "nishack_store_op_unavailable_total";
+        let _ = writeln!(out, "# HELP {name} Store operations skipped for lack of a Redis connection.\n# TYPE {name} counter");
+        for (op, stats) in &ops {
+            let _ = writeln!(out, "{name}{{op=\"{op}\"}} {}", stats.unavailable);
+        }
+
+        let name = "nishack_store_op_duration_seconds";
+        let _ = writeln!(out, "# HELP {name} Latency of the Redis round trips of a Store operation.\n# TYPE {name} histogram");
+        for (op, stats) in &ops {
+            let mut cumulative = 0;
+            for (le, n) in BUCKETS.iter().zip(&stats.buckets) {
+                cumulative += n;
+                let _ = writeln!(out, "{name}_bucket{{op=\"{op}\",le=\"{le}\"}} {cumulative}");
+            }
+            let _ = writeln!(out, "{name}_bucket{{op=\"{op}\",le=\"+Inf\"}} {}", stats.calls);
+            let _ = writeln!(out, "{name}_sum{{op=\"{op}\"}} {}", stats.total_secs);
+            let _ = writeln!(out, "{name}_count{{op=\"{op}\"}} {}", stats.calls);
+        }
+
+        for (counter, value) in counters {
+            let name = format!("nishack_store_{counter}_total");
+            let _ = writeln!(out, "# TYPE {name} counter\n{name} {value}");
+        }
+        out
+    }
+}
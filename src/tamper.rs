@@ -0,0 +1,417 @@
+// ─────────────────────────────────────────────────────────────────
+//  tamper.rs — Tamper detection and agent self-protection
+//
+//  Students end the agent from Task Manager, suspend it, or edit
+//  config.toml to empty the ban lists. We guard against that with:
+//    • a watchdog process (`nishack --watchdog <pid> <dir>`) that
+//      notices the agent dying or freezing, reports who was logged in
+//      to Redis, and starts it again; the agent in turn restarts a
+//      killed watchdog
+//    • a state file (agent.json) marking clean shutdowns, so the next
+//      start knows whether the previous run was ended behind our back
+//    • SHA-256 of the executable and config, compared across runs and
+//      re-checked while running
+//    • an ACL on config.toml that leaves students read-only access
+// ─────────────────────────────────────────────────────────────────
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sysinfo::{Pid, ProcessStatus, ProcessesToUpdate, System};
+use tracing::{info, warn};
+
+use crate::config::{AppConfig, TamperConfig};
+use crate::monitor::silent_cmd;
+
+/// Command-line flag that starts the binary in watchdog mode.
+pub const WATCHDOG_FLAG: &str = "--watchdog";
+/// How often the agent touches its liveness file.
+const ALIVE_EVERY: Duration = Duration::from_secs(5);
+/// A liveness file older than this means the agent is frozen/suspended.
+const STALL_AFTER: Duration = Duration::from_secs(60);
+/// Watchdog polling period.
+const WATCHDOG_POLL: Duration = Duration::from_secs(2);
+/// This many unclean restarts within `CRASH_LOOP_WINDOW` is a crash loop.
+const CRASH_LOOP_STARTS: usize = 3;
+const CRASH_LOOP_WINDOW: chrono::Duration = chrono::Duration::minutes(10);
+/// Start of the finding reported for a crash loop.
+pub const CRASH_LOOP: &str = "agent is crash-looping";
+
+/// What one agent run leaves behind in `<state_dir>/agent.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AgentState {
+    pid: u32,
+    #[serde(default)]
+    watchdog_pid: Option<u32>,
+    clean_exit: bool,
+    #[serde(default)]
+    exe_hash: Option<String>,
+    #[serde(default)]
+    config_hash: Option<String>,
+    started_at: DateTime<Utc>,
+    #[serde(default)]
+    username: String,
+    /// Set by the watchdog once it has reported the agent ending or freezing.
+    #[serde(default)]
+    note: Option<String>,
+    /// Recent starts that followed an unclean exit, for crash-loop detection.
+    #[serde(default)]
+    unclean_starts: Vec<DateTime<Utc>>,
+}
+
+/// The running agent's half of tamper protection.
+pub struct TamperGuard {
+    cfg: TamperConfig,
+    dir: PathBuf,
+    exe: Option<PathBuf>,
+    config_path: PathBuf,
+    state: AgentState,
+    /// Findings from startup, reported on the first check.
+    pending: Vec<String>,
+    last_check: Option<Instant>,
+    /// Kept so a killed watchdog is reaped (and noticed) via `try_wait`.
+    watchdog: Option<Child>,
+    /// Stops the liveness thread so a clean exit isn't read as a freeze.
+    stop_alive: Arc<AtomicBool>,
+}
+
+impl TamperGuard {
+    /// Compare against the previous run, protect the config, and start the
+    /// liveness thread and watchdog.
+    pub fn start(cfg: &TamperConfig, username: &str) -> Self {
+        let dir = absolute(Path::new(&cfg.state_dir));
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            warn!("Could not create tamper state dir {}: {e}", dir.display());
+        }
+        let exe = std::env::current_exe().ok();
+        let config_path = absolute(&AppConfig::path(None));
+
+        let exe_hash = exe.as_deref().and_then(file_hash);
+        let config_hash = file_hash(&config_path);
+        let mut pending = Vec::new();
+        let now = Utc::now();
+        let mut unclean_starts = Vec::new();
+
+        if let Some(prev) = read_state(&dir) {
+            // The watchdog reports kills itself; a run from before the last
+            // boot just ended with a reboot or power loss.
+            let booted = DateTime::from_timestamp(System::boot_time() as i64, 0).unwrap_or_default();
+            if !prev.clean_exit && prev.note.is_none() && prev.started_at > booted {
+                pending.push(format!(
+                    "agent restarted unexpectedly: previous run (PID {}, started {}) stopped without a clean shutdown",
+                    prev.pid,
+                    prev.started_at.format("%Y-%m-%d %H:%M:%S")
+                ));
+                unclean_starts = prev.unclean_starts.into_iter().filter(|t| now - *t < CRASH_LOOP_WINDOW).collect();
+                unclean_starts.push(now);
+                if unclean_starts.len() >= CRASH_LOOP_STARTS {
+                    pending.push(format!(
+                        "{CRASH_LOOP}: {} unexpected restarts in the last {} minutes",
+                        unclean_starts.len(),
+                        CRASH_LOOP_WINDOW.num_minutes()
+                    ));
+                }
+            }
+            if prev.exe_hash.is_some() && exe_hash.is_some() && prev.exe_hash != exe_hash {
+                pending.push("agent executable changed since the previous run".into());
+            }
+            if prev.config_hash.is_some() && prev.config_hash != config_hash {
+                pending.push(format!("{} changed since the previous run", config_path.display()));
+            }
+        }
+
+        if cfg.protect_config {
+            protect_file(&config_path);
+        }
+
+        let mut guard = Self {
+            cfg: cfg.clone(),
+            dir,
+            exe,
+            config_path,
+            state: AgentState {
+                pid: std::process::id(),
+                watchdog_pid: None,
+                clean_exit: false,
+                exe_hash,
+                config_hash,
+                started_at: now,
+                username: username.to_string(),
+                note: None,
+                unclean_starts,
+            },
+            pending,
+            last_check: None,
+            watchdog: None,
+            stop_alive: Arc::new(AtomicBool::new(false)),
+        };
+        guard.write_state();
+        guard.spawn_alive_thread();
+        if guard.cfg.watchdog {
+            guard.spawn_watchdog();
+        }
+        guard
+    }
+
+    /// Return tamper findings: startup ones first, then (every
+    /// `check_interval`) file-hash changes and a killed watchdog.
+    pub fn check(&mut self) -> Vec<String> {
+        let mut findings = std::mem::take(&mut self.pending);
+        let interval = Duration::from_secs(self.cfg.check_interval);
+        if self.last_check.is_some_and(|t| t.elapsed() < interval) {
+            return findings;
+        }
+        self.last_check = Some(Instant::now());
+
+        let mut changed = false;
+        let config_hash = file_hash(&self.config_path);
+        if config_hash != self.state.config_hash {
+            findings.push(format!("{} was modified while the agent was running", self.config_path.display()));
+            self.state.config_hash = config_hash;
+            changed = true;
+        }
+        let exe_hash = self.exe.as_deref().and_then(file_hash);
+        if exe_hash.is_some() && exe_hash != self.state.exe_hash {
+            findings.push("agent executable was replaced while the agent was running".into());
+            self.state.exe_hash = exe_hash;
+            changed = true;
+        }
+
+        if self.cfg.watchdog {
+            let exited = self.watchdog.as_mut().is_some_and(|c| !matches!(c.try_wait(), Ok(None)));
+            if exited {
+                let pid = self.watchdog.take().map(|c| c.id()).unwrap_or_default();
+                findings.push(format!("watchdog process (PID {pid}) was terminated"));
+                self.spawn_watchdog();
+            }
+        }
+
+        if changed {
+            self.write_state();
+        }
+        findings
+    }
+
+    /// Record a graceful shutdown so neither the watchdog nor the next
+    /// start treats this exit as tampering.
+    pub fn mark_clean_exit(&mut self) {
+        self.stop_alive.store(true, Ordering::Relaxed);
+        self.state.clean_exit = true;
+        self.write_state();
+    }
+
+    fn write_state(&self) {
+        if let Err(e) = write_state(&self.dir, &self.state) {
+            warn!("Could not write tamper state: {e}");
+        }
+    }
+
+    fn spawn_alive_thread(&self) {
+        let path = self.dir.join("alive");
+        let stop = Arc::clone(&self.stop_alive);
+        let spawned = std::thread::Builder::new().name("tamper-alive".into()).spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let _ = std::fs::write(&path, Utc::now().to_rfc3339());
+                std::thread::sleep(ALIVE_EVERY);
+            }
+        });
+        if let Err(e) = spawned {
+            warn!("Could not start tamper liveness thread: {e}");
+        }
+    }
+
+    fn spawn_watchdog(&mut self) {
+        let Some(exe) = &self.exe else {
+            return;
+        };
+        match silent_cmd(&exe.to_string_lossy())
+            .arg(WATCHDOG_FLAG)
+            .arg(self.state.pid.to_string())
+            .arg(&self.dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => {
+                info!("🛡️  Watchdog started (PID {})", child.id());
+                self.state.watchdog_pid = Some(child.id());
+                self.watchdog = Some(child);
+                self.write_state();
+            }
+            Err(e) => warn!("Could not start watchdog: {e}"),
+        }
+    }
+}
+
+// ── Watchdog mode ───────────────────────────────────────────────
+
+/// The agent ended or froze without a clean shutdown.
+#[derive(Debug, Clone)]
+pub struct AgentKilled {
+    pub pid: u32,
+    /// User logged in when it happened.
+    pub username: String,
+    pub suspended: bool,
+    pub at: DateTime<Utc>,
+}
+
+impl AgentKilled {
+    pub fn describe(&self) -> String {
+        let how = if self.suspended { "suspended" } else { "killed" };
+        format!(
+            "agent (PID {}) {how} by user {} at {}; watchdog restarted it",
+            self.pid,
+            self.username,
+            self.at.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
+}
+
+/// The supervising half, started as `nishack --watchdog <pid> <state_dir>`.
+pub struct Watchdog {
+    agent_pid: u32,
+    dir: PathBuf,
+}
+
+impl Watchdog {
+    /// Some if this process was started in watchdog mode.
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        if args.get(1).map(String::as_str) != Some(WATCHDOG_FLAG) {
+            return None;
+        }
+        let agent_pid = args.get(2).and_then(|p| p.parse().ok()).unwrap_or_default();
+        let dir = args.get(3).map(PathBuf::from).unwrap_or_default();
+        if agent_pid == 0 || dir.as_os_str().is_empty() {
+            warn!("usage: {WATCHDOG_FLAG} <agent pid> <state dir>");
+        }
+        Some(Self { agent_pid, dir })
+    }
+
+    /// Poll the agent until it exits cleanly (None) or disappears / freezes
+    /// without one. In the latter case a frozen agent is killed, the reason
+    /// is noted in its state file and the event returned; the caller reports
+    /// it and calls `restart_agent`. Blocking.
+    pub fn run(self) -> Option<AgentKilled> {
+        let pid = Pid::from_u32(self.agent_pid);
+        let mut sys = System::new();
+        loop {
+            std::thread::sleep(WATCHDOG_POLL);
+            let mut state = read_state(&self.dir)?;
+            // A clean exit, or a newer agent with its own watchdog
+            if state.clean_exit || state.pid != self.agent_pid {
+                return None;
+            }
+
+            sys.refresh_processes(ProcessesToUpdate::Some(&[pid]));
+            let suspended = match sys.process(pid) {
+                None => false,
+                Some(proc) if proc.status() == ProcessStatus::Zombie => false,
+                Some(proc) if alive_age(&self.dir).is_some_and(|age| age > STALL_AFTER) => {
+                    // Suspended (e.g. from Task Manager or Resource Monitor)
+                    proc.kill();
+                    true
+                }
+                Some(_) => continue,
+            };
+
+            // Re-read: the agent may have exited cleanly in the meantime
+            if read_state(&self.dir).is_some_and(|s| s.clean_exit) {
+                return None;
+            }
+            let killed = AgentKilled {
+                pid: self.agent_pid,
+                username: state.username.clone(),
+                suspended,
+                at: Utc::now(),
+            };
+            warn!("🛡️  {}", killed.describe());
+            // Tells the next agent this was already reported
+            state.note = Some(killed.describe());
+            let _ = write_state(&self.dir, &state);
+            return Some(killed);
+        }
+    }
+}
+
+/// Start a fresh agent from the same executable.
+pub fn restart_agent() {
+    let Ok(exe) = std::env::current_exe() else {
+        return;
+    };
+    if let Err(e) = silent_cmd(&exe.to_string_lossy())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        warn!("Watchdog could not restart the agent: {e}");
+    }
+}
+
+// ── Helpers ─────────────────────────────────────────────────────
+
+fn read_state(dir: &Path) -> Option<AgentState> {
+    let raw = std::fs::read_to_string(dir.join("agent.json")).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+fn write_state(dir: &Path, state: &AgentState) -> std::io::Result<()> {
+    // Write-then-rename so the watchdog never reads half a file
+    let tmp = dir.join("agent.json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(state)?)?;
+    std::fs::rename(tmp, dir.join("agent.json"))
+}
+
+fn alive_age(dir: &Path) -> Option<Duration> {
+    let modified = std::fs::metadata(dir.join("alive")).and_then(|m| m.modified()).ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+fn file_hash(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    Some(format!("{:x}", Sha256::digest(&bytes)))
+}
+
+/// The watchdog may run from a different working directory, so keep
+/// every path we hand it absolute.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Make `path` writable only by administrators / root.
+fn protect_file(path: &Path) {
+    let ok = if cfg!(target_os = "windows") {
+        // SYSTEM and Administrators full control, Users read-only, no inherited ACEs
+        silent_cmd("icacls")
+            .arg(path)
+            .args(["/inheritance:r", "/grant:r", "*S-1-5-18:F", "*S-1-5-32-544:F", "*S-1-5-32-545:R"])
+            .output()
+            .is_ok_and(|o| o.status.success())
+    } else {
+        set_unix_mode(path)
+    };
+    if ok {
+        info!("🛡️  {} protected against modification", path.display());
+    } else {
+        warn!("Could not protect {} (agent needs admin rights)", path.display());
+    }
+}
+
+#[cfg(unix)]
+fn set_unix_mode(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o644)).is_ok()
+}
+
+#[cfg(not(unix))]
+fn set_unix_mode(_path: &Path) -> bool {
+    false
+}
@@ -0,0 +1,68 @@
+// ─────────────────────────────────────────────────────────────────
+//  timeseries.rs — CPU / RAM samples over a lesson
+//
+//  Every `[timeseries] interval` seconds a `SystemSnapshot` is taken and
+//  added to the Sorted Set `{namespace}:timeseries:{hostname}`, scored
+//  by its time in ms, so the dashboard can graph the lesson instead of
+//  only seeing the heartbeat's instant. Each write drops the samples
+//  older than `window_secs` and any beyond the newest `max_samples`.
+//  GET /info/history reads a time range back.
+// ─────────────────────────────────────────────────────────────────
+
+use std::time::Duration;
+
+use chrono::Utc;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+use tracing::warn;
+
+use crate::config::TimeseriesConfig;
+use crate::models::SystemSnapshot;
+use crate::store::Store;
+
+/// Sample every `interval` and store it. Runs forever.
+pub async fn run(cfg: TimeseriesConfig, store: Store, hostname: String, ip: String, username: String) {
+    // Kept between samples so CPU usage covers the whole interval
+    let mut sys = System::new();
+    sys.refresh_cpu_usage();
+    let os = System::long_os_version().unwrap_or_default();
+    loop {
+        tokio::time::sleep(Duration::from_secs(cfg.interval.max(1))).await;
+        let (hostname, ip, username, os) = (hostname.clone(), ip.clone(), username.clone(), os.clone());
+        let sampled = tokio::task::spawn_blocking(move || {
+            let snap = sample(&mut sys, hostname, ip, username, os);
+            (sys, snap)
+        })
+        .await;
+        let snap = match sampled {
+            Ok((returned, snap)) => {
+                sys = returned;
+                snap
+            }
+            Err(e) => {
+                warn!("System sample task failed: {e}");
+                return;
+            }
+        };
+        store.push_timeseries(&snap, cfg.window_secs, cfg.max_samples).await;
+    }
+}
+
+/// Blocking.
+fn sample(sys: &mut System, hostname: String, ip: String, username: String, os: String) -> SystemSnapshot {
+    sys.refresh_cpu_usage();
+    sys.refresh_memory();
+    // Counted only: no per-process CPU / memory needed
+    sys.refresh_processes_specifics(ProcessesToUpdate::All, ProcessRefreshKind::new());
+    SystemSnapshot {
+        hostname,
+        ip,
+        os,
+        username,
+        cpu_usage: sys.global_cpu_usage(),
+        total_memory_mb: sys.total_memory() / 1_048_576,
+        used_memory_mb: sys.used_memory() / 1_048_576,
+        uptime_secs: System::uptime(),
+        process_count: sys.processes().len(),
+        timestamp: Utc::now(),
+    }
+}
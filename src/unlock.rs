@@ -0,0 +1,212 @@
+// ─────────────────────────────────────────────────────────────────
+//  unlock.rs — One-time codes that lift a soft lock
+//
+//  The teacher reads a code out and the student types it into the
+//  prompt shown during the lock, instead of the teacher unlocking
+//  each PC from the dashboard. Two sources, either or both:
+//    derived: HMAC-SHA256(`secret`, "{hostname}:{period}") as six
+//             digits, valid this period and the previous one; the
+//             dashboard computes it with the same secret (or reads
+//             `GET /unlock-code`). Each is accepted once.
+//    Redis:   codes the teacher SADDs to `unlock_codes:{hostname}` or
+//             the room's `unlock_codes`; redeeming removes them.
+//  Six digits are quick to guess, so after `max_attempts` wrong codes
+//  during one lock nothing is accepted for `lockout_secs`.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::warn;
+
+use crate::audit::Audit;
+use crate::config::UnlockCodesConfig;
+use crate::desktop::applescript_escape;
+use crate::monitor::silent_cmd;
+use crate::store::Store;
+
+/// Digits in a derived code.
+const CODE_DIGITS: u32 = 6;
+
+pub struct UnlockCodes {
+    cfg: UnlockCodesConfig,
+    hostname: String,
+    store: Store,
+    /// Derived codes already redeemed, for when Redis is down.
+    used: Mutex<HashSet<(u64, String)>>,
+    attempts: Mutex<Attempts>,
+}
+
+/// Wrong codes entered during one soft lock.
+#[derive(Default)]
+struct Attempts {
+    /// Start of the lock they were entered during.
+    lock: Option<DateTime<Utc>>,
+    failures: u32,
+    locked_out_until: Option<Instant>,
+}
+
+/// What became of a code entered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redeemed {
+    Accepted,
+    /// Wrong or used; at 0 left, the lockout has just begun.
+    Rejected { attempts_left: u32 },
+    /// Not checked: too many wrong codes during this lock.
+    LockedOut { retry_in_secs: u64 },
+}
+
+impl UnlockCodes {
+    /// None unless unlock codes are enabled.
+    pub fn new(cfg: &UnlockCodesConfig, hostname: String, store: Store) -> Option<Self> {
+        cfg.enabled.then(|| Self {
+            cfg: cfg.clone(),
+            hostname,
+            store,
+            used: Mutex::default(),
+            attempts: Mutex::default(),
+        })
+    }
+
+    /// Show the code prompt during soft locks.
+    pub fn prompt_enabled(&self) -> bool {
+        self.cfg.prompt
+    }
+
+    fn period(&self) -> u64 {
+        self.cfg.period_secs.max(60)
+    }
+
+    /// The derived code for this machine right now and seconds until it
+    /// rolls over. None without a secret.
+    pub fn current(&self) -> Option<(String, u64)> {
+        let now = Utc::now().timestamp().max(0) as u64;
+        let window = now / self.period();
+        let code = self.derive(window)?;
+        Some((code, (window + 1) * self.period() - now))
+    }
+
+    fn derive(&self, window: u64) -> Option<String> {
+        let secret = self.cfg.secret.as_deref().filter(|s| !s.is_empty())?;
+        let mac = hmac_sha256(secret.as_bytes(), format!("{}:{window}", self.hostname).as_bytes());
+        // RFC 4226 dynamic truncation
+        let offset = usize::from(mac[31] & 0x0f);
+        let bin = u32::from_be_bytes([mac[offset] & 0x7f, mac[offset + 1], mac[offset + 2], mac[offset + 3]]);
+        Some(format!("{:0width$}", bin % 10u32.pow(CODE_DIGITS), width = CODE_DIGITS as usize))
+    }
+
+    /// Check `code` against the soft lock started at `lock`, using it up
+    /// if it is valid; wrong codes count towards the lockout.
+    pub async fn redeem(&self, code: &str, lock: Option<DateTime<Utc>>) -> Redeemed {
+        {
+            let mut attempts = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
+            if attempts.lock != lock {
+                *attempts = Attempts { lock, ..Default::default() };
+            }
+            if let Some(until) = attempts.locked_out_until {
+                let left = until.saturating_duration_since(Instant::now());
+                if !left.is_zero() {
+                    return Redeemed::LockedOut { retry_in_secs: left.as_secs().max(1) };
+                }
+                attempts.locked_out_until = None;
+                attempts.failures = 0;
+            }
+        }
+        if self.check(code).await {
+            *self.attempts.lock().unwrap_or_else(|e| e.into_inner()) = Attempts::default();
+            return Redeemed::Accepted;
+        }
+        let mut attempts = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
+        attempts.failures += 1;
+        let attempts_left = self.cfg.max_attempts.max(1).saturating_sub(attempts.failures);
+        if attempts_left == 0 {
+            attempts.locked_out_until = Some(Instant::now() + Duration::from_secs(self.cfg.lockout_secs));
+        }
+        Redeemed::Rejected { attempts_left }
+    }
+
+    /// Audit a code that wasn't accepted, entered by `actor`.
+    pub async fn audit_rejection(&self, outcome: Redeemed, audit: &Audit, actor: &str) {
+        match outcome {
+            Redeemed::Accepted => {}
+            Redeemed::Rejected { attempts_left } => {
+                audit.record("unlock_code_rejected", actor, Some(format!("{attempts_left} attempt(s) left"))).await;
+                if attempts_left == 0 {
+                    warn!("🔒 Too many wrong unlock codes, none accepted for {}s", self.cfg.lockout_secs);
+                    let detail = format!("{} wrong code(s), locked out for {}s", self.cfg.max_attempts.max(1), self.cfg.lockout_secs);
+                    audit.record("unlock_code_lockout", actor, Some(detail)).await;
+                }
+            }
+            Redeemed::LockedOut { retry_in_secs } => {
+                audit.record("unlock_code_rejected", actor, Some(format!("locked out, {retry_in_secs}s left"))).await;
+            }
+        }
+    }
+
+    /// Whether `code` is valid, using it up if so.
+    async fn check(&self, code: &str) -> bool {
+        let code: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+        if code.is_empty() {
+            return false;
+        }
+        let window = Utc::now().timestamp().max(0) as u64 / self.period();
+        for w in [window, window.saturating_sub(1)] {
+            if self.derive(w).is_some_and(|derived| crate::api::constant_time_eq(derived.as_bytes(), code.as_bytes())) {
+                return self.claim(w, &code).await;
+            }
+        }
+        self.store.redeem_unlock_code(&self.hostname, &code).await.unwrap_or(false)
+    }
+
+    async fn claim(&self, window: u64, code: &str) -> bool {
+        {
+            let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+            used.retain(|(w, _)| *w + 1 >= window);
+            if !used.insert((window, code.to_string())) {
+                return false;
+            }
+        }
+        // Shared through Redis so a restarted agent doesn't accept it again
+        self.store.claim_unlock_code(&self.hostname, code, 2 * self.period()).await.unwrap_or(true)
+    }
+}
+
+/// HMAC-SHA256 of `msg` under `key`.
+pub(crate) fn hmac_sha256(key: &[u8], msg: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(msg);
+    mac.finalize().into_bytes().into()
+}
+
+/// Start the code prompt on the student's screen; its stdout is the code
+/// typed (empty when cancelled).
+///    Windows: VisualBasic InputBox, macOS: display dialog, Linux: zenity
+pub fn spawn_prompt(message: &str) -> std::io::Result<std::process::Child> {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let script = format!(
+            "Add-Type -AssemblyName Microsoft.VisualBasic; \
+             [Microsoft.VisualBasic.Interaction]::InputBox('{}', 'nishack')",
+            message.replace('\'', "''")
+        );
+        let mut cmd = silent_cmd("powershell");
+        cmd.args(["-NoProfile", "-Command", &script]);
+        cmd
+    } else if cfg!(target_os = "macos") {
+        let script = format!(
+            "text returned of (display dialog \"{}\" default answer \"\" with title \"nishack\")",
+            applescript_escape(message)
+        );
+        let mut cmd = silent_cmd("osascript");
+        cmd.args(["-e", &script]);
+        cmd
+    } else {
+        let mut cmd = silent_cmd("zenity");
+        cmd.args(["--entry", "--title=nishack", &format!("--text={message}")]);
+        cmd
+    };
+    cmd.stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::null()).spawn()
+}
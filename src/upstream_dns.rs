@@ -0,0 +1,302 @@
+// ─────────────────────────────────────────────────────────────────
+//  upstream_dns.rs — Look up banned domains past the local resolver
+//
+//  With hosts-file enforcement on, the OS resolver answers our own
+//  0.0.0.0 entries for every banned domain, so firewall rules and
+//  connection matching would never see the real addresses. Banned
+//  domains are therefore asked of DNS servers directly — plain DNS over
+//  UDP port 53, one A and one AAAA query per name:
+//    `[monitor.enforcement] resolvers` when set, otherwise the servers
+//    the OS itself uses (resolv.conf / systemd-resolved's upstreams,
+//    Get-DnsClientServerAddress), never a third party by default.
+//  A server that doesn't answer is skipped for the rest of the round
+//  (networks that block outbound port 53 cost one timeout, not one per
+//  name); names no server answered go to the OS resolver. Lookups run
+//  on their own thread (`DomainResolver`), so the scan never waits on
+//  DNS, and a round stops after `ROUND_BUDGET`.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+#[cfg(target_os = "windows")]
+use crate::monitor::silent_cmd;
+
+/// Longest one round of lookups may take; the names left over wait for
+/// the next round.
+const ROUND_BUDGET: Duration = Duration::from_secs(30);
+
+/// Per-query wait for an upstream answer.
+const TIMEOUT: Duration = Duration::from_secs(2);
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+/// Parse `1.1.1.1`, `1.1.1.1:5353`, `2606:4700::1111` or
+/// `[2606:4700::1111]:53`; unparsable entries are logged and skipped.
+pub fn parse_servers(servers: &[String]) -> Vec<SocketAddr> {
+    servers
+        .iter()
+        .filter_map(|s| {
+            let addr = s.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)).or_else(|_| s.parse());
+            if addr.is_err() {
+                warn!("Ignoring DNS resolver {s:?}: expected an IP address");
+            }
+            addr.ok()
+        })
+        .collect()
+}
+
+/// Banned domains resolved on a background thread. Only the newest
+/// request counts: older ones still queued are skipped, and results of
+/// requests made before the last `request` / `discard` are dropped.
+pub struct DomainResolver {
+    requests: Sender<(u64, Vec<String>)>,
+    results: Receiver<(u64, HashMap<IpAddr, String>)>,
+    generation: u64,
+}
+
+impl DomainResolver {
+    /// Spawn the lookup thread, asking `servers` (the OS's own DNS
+    /// servers when empty).
+    pub fn start(servers: Vec<SocketAddr>) -> Self {
+        let (requests, queue) = mpsc::channel::<(u64, Vec<String>)>();
+        let (done, results) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("domain-resolver".into())
+            .spawn(move || {
+                let servers = if servers.is_empty() { system_servers() } else { servers };
+                info!("🔎 Resolving banned domains through {servers:?}");
+                while let Ok(mut next) = queue.recv() {
+                    // Only the newest ban list is worth resolving
+                    while let Ok(newer) = queue.try_recv() {
+                        next = newer;
+                    }
+                    let (generation, domains) = next;
+                    if done.send((generation, resolve_all(&domains, &servers))).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("failed to spawn domain-resolver thread");
+        Self { requests, results, generation: 0 }
+    }
+
+    /// Start resolving `domains` (plus their `www.` variants).
+    pub fn request<'a>(&mut self, domains: impl IntoIterator<Item = &'a String>) {
+        self.generation += 1;
+        let _ = self.requests.send((self.generation, domains.into_iter().cloned().collect()));
+    }
+
+    /// Forget the request in flight; its result won't be returned.
+    pub fn discard(&mut self) {
+        self.generation += 1;
+    }
+
+    /// IP → banned domain from the latest request, once it's done. Never blocks.
+    pub fn finished(&mut self) -> Option<HashMap<IpAddr, String>> {
+        let current = self.generation;
+        self.results.try_iter().filter(|(generation, _)| *generation == current).last().map(|(_, ips)| ips)
+    }
+}
+
+/// Resolve `domains` (plus their `www.` variants), mapping each IP back
+/// to the banned domain it came from. Sinkhole answers (0.0.0.0 /
+/// loopback) are skipped.
+fn resolve_all(domains: &[String], servers: &[SocketAddr]) -> HashMap<IpAddr, String> {
+    let deadline = Instant::now() + ROUND_BUDGET;
+    let mut servers = servers.to_vec();
+    let mut map = HashMap::new();
+    for domain in domains {
+        if Instant::now() >= deadline {
+            warn!("Resolving banned domains took over {}s, the rest waits for the next round", ROUND_BUDGET.as_secs());
+            break;
+        }
+        let mut names = vec![domain.clone()];
+        if !domain.starts_with("www.") {
+            names.push(format!("www.{domain}"));
+        }
+        for name in names {
+            let ips = match lookup(&name, &mut servers) {
+                Some(ips) => ips,
+                None => match (name.as_str(), 443).to_socket_addrs() {
+                    Ok(addrs) => addrs.map(|a| a.ip()).collect(),
+                    Err(_) => continue,
+                },
+            };
+            for ip in ips {
+                if !ip.is_unspecified() && !ip.is_loopback() {
+                    map.entry(ip).or_insert_with(|| domain.clone());
+                }
+            }
+        }
+    }
+    map
+}
+
+/// Addresses of `name` according to the first server that answers;
+/// servers that don't are dropped from `servers`. `None` when none did.
+fn lookup(name: &str, servers: &mut Vec<SocketAddr>) -> Option<Vec<IpAddr>> {
+    while let Some(&server) = servers.first() {
+        match query(server, name, TYPE_A) {
+            Some(mut ips) => {
+                // A v4-only upstream path shouldn't discard the A answer
+                ips.extend(query(server, name, TYPE_AAAA).unwrap_or_default());
+                return Some(ips);
+            }
+            None => {
+                warn!("DNS server {server} did not answer, skipping it this round");
+                servers.remove(0);
+            }
+        }
+    }
+    None
+}
+
+/// DNS servers the OS is configured with, minus local stubs (which
+/// read the hosts file themselves).
+fn system_servers() -> Vec<SocketAddr> {
+    let mut servers: Vec<SocketAddr> = Vec::new();
+    for ip in configured_addresses() {
+        // fec0:0:0:ffff::1-3 are Windows' placeholder entries
+        let placeholder = matches!(ip, IpAddr::V6(v6) if v6.segments()[0] == 0xfec0);
+        let server = SocketAddr::new(ip, 53);
+        if !ip.is_loopback() && !ip.is_unspecified() && !placeholder && !servers.contains(&server) {
+            servers.push(server);
+        }
+    }
+    if servers.is_empty() {
+        warn!("No DNS server found in the OS settings, banned domains go through the OS resolver");
+    }
+    servers
+}
+
+#[cfg(target_os = "windows")]
+fn configured_addresses() -> Vec<IpAddr> {
+    let Ok(out) = silent_cmd("powershell")
+        .args([
+            "-NoProfile", "-NonInteractive", "-Command",
+            "Get-DnsClientServerAddress | ForEach-Object { $_.ServerAddresses }",
+        ])
+        .output()
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&out.stdout).lines().filter_map(|l| l.trim().parse().ok()).collect()
+}
+
+#[cfg(not(target_os = "windows"))]
+fn configured_addresses() -> Vec<IpAddr> {
+    // systemd-resolved's stub (127.0.0.53) is in /etc/resolv.conf; its
+    // upstreams are listed here
+    ["/run/systemd/resolve/resolv.conf", "/etc/resolv.conf"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .map(|conf| {
+            conf.lines()
+                .filter_map(|l| l.trim().strip_prefix("nameserver"))
+                .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+                .collect::<Vec<_>>()
+        })
+        .find(|ips| ips.iter().any(|ip| !ip.is_loopback()))
+        .unwrap_or_default()
+}
+
+/// One question to one server. `None` on timeout, socket error or a
+/// malformed / failed (non-NOERROR, non-NXDOMAIN) response.
+fn query(server: SocketAddr, name: &str, qtype: u16) -> Option<Vec<IpAddr>> {
+    let local: SocketAddr = if server.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).ok()?;
+    socket.set_read_timeout(Some(TIMEOUT)).ok()?;
+    socket.connect(server).ok()?;
+
+    let id = rand::random::<u16>();
+    socket.send(&encode(id, name, qtype)?).ok()?;
+    let mut buf = [0u8; 1500];
+    // Skip stray datagrams that don't answer this question
+    loop {
+        let len = socket.recv(&mut buf).ok()?;
+        if len >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == id {
+            return decode(&buf[..len], qtype);
+        }
+    }
+}
+
+/// Standard query with recursion desired.
+fn encode(id: u16, name: &str, qtype: u16) -> Option<Vec<u8>> {
+    let mut msg = Vec::with_capacity(name.len() + 18);
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&qtype.to_be_bytes());
+    msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Some(msg)
+}
+
+/// Records of `qtype` in the answer section; CNAMEs are followed by the
+/// upstream, so their targets' addresses are already in there.
+fn decode(msg: &[u8], qtype: u16) -> Option<Vec<IpAddr>> {
+    let header = msg.get(..12)?;
+    if header[2] & 0x80 == 0 {
+        return None;
+    }
+    match header[3] & 0x0f {
+        0 => {}
+        3 => return Some(Vec::new()), // NXDOMAIN
+        _ => return None,
+    }
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let answers = u16::from_be_bytes([header[6], header[7]]);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(msg, pos)? + 4;
+    }
+    let mut ips = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(msg, pos)?;
+        let fixed = msg.get(pos..pos + 10)?;
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let class = u16::from_be_bytes([fixed[2], fixed[3]]);
+        let len = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        let data = msg.get(pos + 10..pos + 10 + len)?;
+        pos += 10 + len;
+        if rtype != qtype || class != CLASS_IN {
+            continue;
+        }
+        match data.len() {
+            4 => ips.push(IpAddr::from(<[u8; 4]>::try_from(data).ok()?)),
+            16 => ips.push(IpAddr::from(<[u8; 16]>::try_from(data).ok()?)),
+            _ => {}
+        }
+    }
+    Some(ips)
+}
+
+/// Position just past the (possibly compressed) name at `pos`.
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // Compression pointer: two bytes, and the name ends here
+            l if l & 0xc0 == 0xc0 => return Some(pos + 2),
+            l => pos += 1 + l as usize,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+// ─────────────────────────────────────────────────────────────────
+//  violation_sinks.rs — Where violations are delivered
+//
+//  `[[violation_sinks]]` is an ordered list; each violation goes to
+//  every sink whose `min_severity` / `kinds` filter lets it through:
+//    redis:   `violations:{hostname}` list plus counter (see Store)
+//    teacher: POST /api/agent/violation on the first discovered teacher
+//             server that answers
+//    webhook: POST of the same JSON to `url`
+//    syslog:  RFC 5424 datagram to `address` (UDP), JSON as the message
+//    file:    one JSON line per violation appended to `path`
+//    mqtt:    published to `[mqtt] violation_topic` (see mqtt.rs)
+//  All use the teacher-backend schema (`store::teacher_payload`).
+//  Repeats folded by deduplication (`occurrences > 1`) only rewrite the
+//  Redis record, as before; the other sinks saw the first occurrence.
+// ─────────────────────────────────────────────────────────────────
+
+use std::io::Write;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use tokio::net::UdpSocket;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+use crate::config::ViolationSinkConfig;
+use crate::models::Violation;
+use crate::mqtt::Mqtt;
+use crate::store::{teacher_payload, Store};
+
+/// Syslog facility local0.
+const SYSLOG_FACILITY: u8 = 16;
+
+enum SinkKind {
+    Redis,
+    Teacher,
+    Webhook { url: String, client: reqwest::Client },
+    /// The socket is bound and connected on first use, for the address
+    /// family `address` resolves to.
+    Syslog { address: String, socket: OnceCell<UdpSocket> },
+    File { path: String },
+    Mqtt(Mqtt),
+}
+
+struct Sink {
+    kind: SinkKind,
+    min_severity: u8,
+    /// Rule ids; empty = all.
+    kinds: Vec<String>,
+}
+
+impl Sink {
+    fn accepts(&self, v: &Violation) -> bool {
+        severity_rank(v.kind.severity()) >= self.min_severity
+            && (self.kinds.is_empty() || self.kinds.iter().any(|k| k == v.kind.rule()))
+    }
+}
+
+/// The configured sinks, cheap to clone into every loop that reports.
+#[derive(Clone)]
+pub struct ViolationSinks {
+    sinks: Arc<Vec<Sink>>,
+    store: Store,
+}
+
+impl ViolationSinks {
+    pub fn new(cfgs: &[ViolationSinkConfig], store: Store, mqtt: Option<&Mqtt>) -> anyhow::Result<Self> {
+        let mut sinks = Vec::new();
+        for cfg in cfgs {
+            let kind = match cfg.kind.as_str() {
+                "redis" => SinkKind::Redis,
+                "teacher" => SinkKind::Teacher,
+                "webhook" => {
+                    let Some(url) = cfg.url.clone() else {
+                        bail!("violation sink \"webhook\" needs a url");
+                    };
+                    let client = reqwest::Client::builder().timeout(Duration::from_secs(5)).build()?;
+                    SinkKind::Webhook { url, client }
+                }
+                "syslog" => {
+                    let Some(address) = cfg.address.clone() else {
+                        bail!("violation sink \"syslog\" needs an address");
+                    };
+                    SinkKind::Syslog { address, socket: OnceCell::new() }
+                }
+                "file" => {
+                    let Some(path) = cfg.path.clone() else {
+                        bail!("violation sink \"file\" needs a path");
+                    };
+                    SinkKind::File { path }
+                }
+                "mqtt" => {
+                    let Some(mqtt) = mqtt else {
+                        bail!("violation sink \"mqtt\" needs [mqtt] enabled");
+                    };
+                    SinkKind::Mqtt(mqtt.clone())
+                }
+                other => bail!("unknown violation sink \"{other}\" (expected redis, teacher, webhook, syslog, file or mqtt)"),
+            };
+            let min_severity = match cfg.min_severity.as_str() {
+                s @ ("low" | "medium" | "high") => severity_rank(s),
+                other => bail!("unknown min_severity \"{other}\" (expected low, medium or high)"),
+            };
+            sinks.push(Sink { kind, min_severity, kinds: cfg.kinds.clone() });
+        }
+        if sinks.is_empty() {
+            warn!("No violation sinks configured — violations are only logged");
+        }
+        Ok(Self { sinks: Arc::new(sinks), store })
+    }
+
+    /// Deliver a violation to every sink that takes it.
+    pub async fn deliver(&self, v: &Violation) {
+        self.deliver_all(std::slice::from_ref(v)).await;
+    }
+
+    /// `deliver` for a whole scan cycle, batched per sink.
+    pub async fn deliver_all(&self, vs: &[Violation]) {
+        for sink in self.sinks.iter() {
+            let selected: Vec<Violation> = vs.iter().filter(|v| sink.accepts(v)).cloned().collect();
+            if selected.is_empty() {
+                continue;
+            }
+            match &sink.kind {
+                SinkKind::Redis => self.store.record_violations(&selected).await,
+                SinkKind::Teacher => self.store.push_violations_to_teacher(&selected).await,
+                SinkKind::Webhook { url, client } => post_webhook(client, url, &selected).await,
+                SinkKind::Syslog { address, socket } => send_syslog(address, socket, &selected).await,
+                SinkKind::File { path } => append_file(path, &selected).await,
+                SinkKind::Mqtt(mqtt) => mqtt.violations(&selected),
+            }
+        }
+    }
+
+    /// Pass a repeat (`occurrences > 1`) on: the Redis record is rewritten,
+    /// the other sinks already have the first occurrence.
+    pub async fn update(&self, v: &Violation) {
+        if self.sinks.iter().any(|s| matches!(s.kind, SinkKind::Redis) && s.accepts(v)) {
+            self.store.update_violation(v).await;
+        }
+    }
+}
+
+fn severity_rank(severity: &str) -> u8 {
+    match severity {
+        "high" => 2,
+        "medium" => 1,
+        _ => 0,
+    }
+}
+
+async fn post_webhook(client: &reqwest::Client, url: &str, vs: &[Violation]) {
+    for v in vs {
+        match client.post(url).json(&teacher_payload(v)).send().await {
+            Ok(resp) if resp.status().is_success() => {
+                info!("✅ Violation sent to webhook: {}", v.target);
+            }
+            Ok(resp) => warn!("Violation webhook returned {}: {}", resp.status(), v.target),
+            Err(e) => {
+                warn!("Failed to send violation to webhook: {e}");
+                // The rest would only time out the same way
+                return;
+            }
+        }
+    }
+}
+
+/// `<PRI>1 TIMESTAMP HOSTNAME nishack PID violation - MSG`
+async fn send_syslog(address: &str, socket: &OnceCell<UdpSocket>, vs: &[Violation]) {
+    // Retried on the next batch if it failed (e.g. the name didn't resolve yet)
+    let socket = match socket.get_or_try_init(|| syslog_socket(address)).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("Syslog socket error for {address}: {e}");
+            return;
+        }
+    };
+    for v in vs {
+        // err / warning / notice
+        let level = match v.kind.severity() {
+            "high" => 3,
+            "medium" => 4,
+            _ => 5,
+        };
+        let line = format!(
+            "<{}>1 {} {} nishack {} violation - {}",
+            SYSLOG_FACILITY * 8 + level,
+            v.timestamp.to_rfc3339(),
+            v.hostname,
+            std::process::id(),
+            teacher_payload(v),
+        );
+        if let Err(e) = socket.send(line.as_bytes()).await {
+            warn!("Failed to send violation to syslog at {address}: {e}");
+            return;
+        }
+    }
+}
+
+/// A UDP socket of the same family as `address` (IPv4 or IPv6),
+/// connected to it.
+async fn syslog_socket(address: &str) -> std::io::Result<UdpSocket> {
+    let Some(target) = tokio::net::lookup_host(address).await?.next() else {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "address did not resolve"));
+    };
+    let local: SocketAddr = match target {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(target).await?;
+    Ok(socket)
+}
+
+async fn append_file(path: &str, vs: &[Violation]) {
+    let lines: String = vs.iter().map(|v| format!("{}\n", teacher_payload(v))).collect();
+    let path = path.to_string();
+    let written = tokio::task::spawn_blocking(move || {
+        let path = std::path::Path::new(&path);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(lines.as_bytes())
+    })
+    .await;
+    match written {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("Failed to append violations to file: {e}"),
+        Err(e) => warn!("Violation file task failed: {e}"),
+    }
+}
@@ -0,0 +1,191 @@
+// ─────────────────────────────────────────────────────────────────
+//  vpn.rs — VPN adapters and proxy settings
+//
+//  Tunnels and proxies route traffic past every domain ban, so we
+//  look for them at the OS level:
+//    • VPN / tunnel adapters that are up
+//         Windows: Get-NetAdapter    Linux: ip link    macOS: scutil --nc
+//    • system proxy settings
+//         Windows: Internet Settings (HKCU)
+//         macOS:   scutil --proxy
+//         Linux:   GNOME proxy mode
+//    • Firefox's own proxy settings (it ignores the system ones)
+//  System proxies can be reset; Firefox keeps prefs.js open, so its
+//  settings are only reported.
+// ─────────────────────────────────────────────────────────────────
+
+use crate::browser::{self, BrowserFamily};
+use crate::monitor::silent_cmd;
+
+/// Words in an adapter's name / description that mark it as a tunnel.
+const VPN_ADAPTER_HINTS: &[&str] = &[
+    "tap-", "wintun", "wireguard", "openvpn", "nordlynx", "protonvpn",
+    "anyconnect", "pangp", "fortinet", "forticlient", "windscribe", "expressvpn",
+    "surfshark", "cyberghost", "hotspot shield", "tailscale", "zerotier", "ppp", "ipsec",
+];
+
+/// Linux interface-name prefixes used by tunnels.
+const TUNNEL_PREFIXES: &[&str] = &["tun", "tap", "wg", "ppp", "nordlynx", "proton", "tailscale", "zt"];
+
+/// A network adapter that looks like a VPN tunnel.
+#[derive(Debug, Clone)]
+pub struct VpnAdapter {
+    pub name: String,
+    pub description: String,
+}
+
+/// VPN / tunnel adapters that are currently up. Blocking.
+pub fn active_vpn_adapters() -> Vec<VpnAdapter> {
+    if cfg!(target_os = "windows") {
+        let script = r#"Get-NetAdapter | Where-Object Status -eq 'Up' | ForEach-Object { "$($_.Name)|$($_.InterfaceDescription)" }"#;
+        let Ok(out) = silent_cmd("powershell").args(["-NoProfile", "-Command", script]).output() else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .filter_map(|l| l.trim().split_once('|'))
+            .filter(|(name, desc)| {
+                let both = format!("{name} {desc}").to_lowercase();
+                VPN_ADAPTER_HINTS.iter().any(|h| both.contains(h))
+            })
+            .map(|(name, desc)| VpnAdapter { name: name.into(), description: desc.into() })
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        // utun interfaces exist even without a VPN (iCloud, Continuity), so
+        // ask the network configuration for connected VPN services instead.
+        //   * (Connected)   1A2B...  PPP --> L2TP   "School VPN"   [PPP:L2TP]
+        let Ok(out) = silent_cmd("scutil").args(["--nc", "list"]).output() else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .filter(|l| l.contains("(Connected)"))
+            .map(|l| VpnAdapter {
+                name: l.split('"').nth(1).unwrap_or("VPN").to_string(),
+                description: l.trim().to_string(),
+            })
+            .collect()
+    } else {
+        //   5: wg0: <POINTOPOINT,NOARP,UP,LOWER_UP> mtu 1420 ...
+        let Ok(out) = silent_cmd("ip").args(["-o", "link", "show", "up"]).output() else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .filter_map(|l| l.split(':').nth(1).map(|n| n.trim().split('@').next().unwrap_or("").to_string()))
+            .filter(|name| TUNNEL_PREFIXES.iter().any(|p| name.starts_with(p)))
+            .map(|name| VpnAdapter { description: format!("tunnel interface {name}"), name })
+            .collect()
+    }
+}
+
+/// Human-readable descriptions of every proxy currently configured in
+/// the OS or in Firefox. Blocking.
+pub fn proxy_settings() -> Vec<String> {
+    let mut found = system_proxy();
+    found.extend(firefox_proxies());
+    found
+}
+
+fn system_proxy() -> Vec<String> {
+    if cfg!(target_os = "windows") {
+        let key = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings";
+        let Ok(out) = silent_cmd("reg").args(["query", key]).output() else {
+            return Vec::new();
+        };
+        let text = String::from_utf8_lossy(&out.stdout);
+        let value = |name: &str| {
+            text.lines().find_map(|l| {
+                let cols: Vec<&str> = l.split_whitespace().collect();
+                (cols.first() == Some(&name) && cols.len() >= 3).then(|| cols[2..].join(" "))
+            })
+        };
+        let mut found = Vec::new();
+        if value("ProxyEnable").as_deref() == Some("0x1") {
+            found.push(format!("system proxy {}", value("ProxyServer").unwrap_or_default()));
+        }
+        if let Some(pac) = value("AutoConfigURL") {
+            found.push(format!("system proxy script {pac}"));
+        }
+        found
+    } else if cfg!(target_os = "macos") {
+        //   HTTPEnable : 1
+        //   HTTPProxy : 10.0.0.1
+        let Ok(out) = silent_cmd("scutil").arg("--proxy").output() else {
+            return Vec::new();
+        };
+        let text = String::from_utf8_lossy(&out.stdout);
+        let value = |name: &str| {
+            text.lines()
+                .find_map(|l| l.trim().strip_prefix(name)?.trim().strip_prefix(':').map(|v| v.trim().to_string()))
+        };
+        [("HTTP", "HTTPProxy"), ("HTTPS", "HTTPSProxy"), ("SOCKS", "SOCKSProxy"), ("ProxyAutoConfig", "ProxyAutoConfigURLString")]
+            .into_iter()
+            .filter(|(kind, _)| value(&format!("{kind}Enable")).as_deref() == Some("1"))
+            .map(|(kind, host)| format!("system {kind} proxy {}", value(host).unwrap_or_default()))
+            .collect()
+    } else {
+        let Ok(out) = silent_cmd("gsettings").args(["get", "org.gnome.system.proxy", "mode"]).output() else {
+            return Vec::new();
+        };
+        let mode = String::from_utf8_lossy(&out.stdout).trim().trim_matches('\'').to_string();
+        if mode == "manual" || mode == "auto" {
+            vec![format!("system proxy ({mode})")]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Firefox: network.proxy.type 1 = manual, 2 = PAC script.
+fn firefox_proxies() -> Vec<String> {
+    browser::discover_profiles()
+        .into_iter()
+        .filter(|p| p.family == BrowserFamily::Firefox)
+        .filter_map(|p| {
+            let prefs = std::fs::read_to_string(p.dir.join("prefs.js")).ok()?;
+            let manual = prefs.lines().any(|l| l.contains("\"network.proxy.type\"") && l.contains(", 1)"));
+            let pac = prefs.lines().any(|l| l.contains("\"network.proxy.type\"") && l.contains(", 2)"));
+            let profile = p.dir.file_name()?.to_string_lossy().to_string();
+            match (manual, pac) {
+                (true, _) => Some(format!("Firefox manual proxy (profile {profile})")),
+                (_, true) => Some(format!("Firefox proxy script (profile {profile})")),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Switch the system proxy off. Returns true on success.
+pub fn reset_system_proxy() -> bool {
+    if cfg!(target_os = "windows") {
+        let key = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings";
+        let disabled = silent_cmd("reg")
+            .args(["add", key, "/v", "ProxyEnable", "/t", "REG_DWORD", "/d", "0", "/f"])
+            .output()
+            .is_ok_and(|o| o.status.success());
+        // Missing value is fine
+        let _ = silent_cmd("reg").args(["delete", key, "/v", "AutoConfigURL", "/f"]).output();
+        disabled
+    } else if cfg!(target_os = "macos") {
+        let Ok(out) = silent_cmd("networksetup").arg("-listallnetworkservices").output() else {
+            return false;
+        };
+        let mut ok = true;
+        // First line is an explanatory note; disabled services start with '*'
+        for service in String::from_utf8_lossy(&out.stdout).lines().skip(1).filter(|l| !l.starts_with('*')) {
+            for flag in ["-setwebproxystate", "-setsecurewebproxystate", "-setsocksfirewallproxystate", "-setautoproxystate"] {
+                ok &= silent_cmd("networksetup")
+                    .args([flag, service, "off"])
+                    .status()
+                    .is_ok_and(|s| s.success());
+            }
+        }
+        ok
+    } else {
+        silent_cmd("gsettings")
+            .args(["set", "org.gnome.system.proxy", "mode", "none"])
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+}
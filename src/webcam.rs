@@ -0,0 +1,77 @@
+// ─────────────────────────────────────────────────────────────────
+//  webcam.rs — Which programs are using the camera
+//
+//  A video call during a test shows up as the camera being held, so
+//  `[monitor.webcam]` sends the programs with heartbeats and flags
+//  banned ones:
+//    Windows: CapabilityAccessManager consent store, every user hive;
+//             an app whose LastUsedTimeStop is 0 has the camera open
+//    Linux:   processes with a /dev/video* device open (/proc/*/fd;
+//             other users' processes need the agent to run as root)
+//  macOS attributes camera use only inside its privacy indicator and
+//  isn't covered.
+// ─────────────────────────────────────────────────────────────────
+
+use std::collections::BTreeSet;
+
+use crate::monitor::silent_cmd;
+
+/// Lower-case names of the programs holding a camera right now,
+/// without `.exe`. Blocking.
+pub fn users() -> Vec<String> {
+    let names: BTreeSet<String> = if cfg!(target_os = "windows") {
+        windows_users()
+    } else if cfg!(target_os = "linux") {
+        linux_users()
+    } else {
+        BTreeSet::new()
+    };
+    names.into_iter().collect()
+}
+
+// Desktop apps are keys like `NonPackaged\C:#Program Files#Zoom#bin#Zoom.exe`,
+// store apps `Microsoft.WindowsCamera_8wekyb3d8bbwe`
+const CONSENT_STORE: &str = r#"
+Get-ChildItem 'Registry::HKEY_USERS\*\Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\webcam' -Recurse -ErrorAction SilentlyContinue |
+    Where-Object { $_.GetValue('LastUsedTimeStart') -gt 0 -and $_.GetValue('LastUsedTimeStop') -eq 0 } |
+    ForEach-Object { $_.PSChildName }
+"#;
+
+fn windows_users() -> BTreeSet<String> {
+    let Ok(out) = silent_cmd("powershell").args(["-NoProfile", "-Command", CONSENT_STORE]).output() else {
+        return BTreeSet::new();
+    };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|key| {
+            let name = match key.rsplit_once('#') {
+                Some((_, exe)) => exe,
+                // Package family name: publisher.App_hash
+                None => key.split('_').next().unwrap_or(key).rsplit('.').next().unwrap_or(key),
+            };
+            let name = name.to_lowercase();
+            name.strip_suffix(".exe").unwrap_or(&name).to_string()
+        })
+        .collect()
+}
+
+fn linux_users() -> BTreeSet<String> {
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return BTreeSet::new();
+    };
+    procs
+        .filter_map(Result::ok)
+        .filter(|p| p.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()))
+        .filter(|p| {
+            std::fs::read_dir(p.path().join("fd")).is_ok_and(|fds| {
+                fds.filter_map(Result::ok).any(|fd| {
+                    std::fs::read_link(fd.path()).is_ok_and(|l| l.to_string_lossy().starts_with("/dev/video"))
+                })
+            })
+        })
+        .filter_map(|p| std::fs::read_to_string(p.path().join("comm")).ok())
+        .map(|comm| comm.trim().to_lowercase())
+        .collect()
+}
@@ -2,54 +2,284 @@
 //  ws_stream.rs — Live screen streaming over WebSocket
 //
 //  Connects to the teacher server's /ws/screen endpoint,
-//  sends a JSON handshake, then streams JPEG frames.
-//  Automatically reconnects on disconnect.
+//  sends a JSON handshake (with the agent's capabilities), then
+//  streams JPEG frames.
+//  Reconnects on disconnect with exponential backoff and jitter
+//  (`reconnect_secs` doubling up to `reconnect_max_secs`), so a
+//  room of agents doesn't hit a rebooting server in lockstep. An
+//  auth rejection (HTTP 401/403) stops the loop instead. Pings go
+//  out every `ping_secs`; a connection the server hasn't answered on
+//  for `pong_timeout_secs` is dead (sleeping laptop, AP roam) and is
+//  dropped, even while unchanged frames aren't being sent. Every
+//  connection is audited as `stream_started` / `stream_stopped`.
+//  The server can hand the stream to another one (a substitute
+//  teacher's laptop) with `{"type":"handoff","url":"wss://…"}`: the
+//  new connection is opened and handshaken while frames still go to
+//  the old one, then swapped in, so at most one frame interval is
+//  lost. Its handshake carries the frame count and start of the
+//  stream, and reconnects go to the new URL until the settings change.
+//  With `discover`, the URL comes from the teacher endpoints published
+//  in Redis (see `Store::discover_teachers`) instead, the next one in
+//  priority order tried after each failed connection.
+//  Every `metadata_secs` the foreground app and window title, the
+//  browser's latest URL, exam / soft-lock / focus state and the rules
+//  the last scan flagged are sampled; when they changed, they go out
+//  as a `{"type":"meta",…}` text message tagged with the frame count,
+//  so the viewer can overlay them without polling each agent's API.
+//  `{"type":"magnify","x":…,"y":…,"w":…,"h":…,"secs":N}` (fractions
+//  of the screen) zooms into a region for N seconds, capped at
+//  `magnifier_max_secs`: each captured frame's crop of it goes out at
+//  full resolution as a binary message `ZOOM` + u32 BE header length
+//  + JSON header + JPEG, next to the normal frames (which start with
+//  the JPEG marker, so viewers tell them apart). Unchanged crops are
+//  skipped; `magnify_ended` follows when it runs out or `secs` is 0.
+//  Capture faults are told apart (no display, failed capture, an
+//  all-black frame — what a driver reset or sleep/wake leaves behind)
+//  and each drops the chosen display so the next try re-enumerates,
+//  backing off up to 5 s; the viewer gets `capture_lost` and
+//  `capture_restored`. A changed display layout (projector plugged in,
+//  resolution switch) re-picks the display and sends
+//  `display_changed` followed by a full frame. A screen still black
+//  after a few re-picks is believed and streamed.
+//  With `[encryption] stream`, every binary message (frames and ZOOM
+//  messages alike) is sealed with AES-256-GCM (see envelope.rs) and the
+//  handshake says so in `encryption`; text messages stay plain.
+//  With `record`, frames are also handed to the recorder (see
+//  recording.rs) before they are sealed.
 // ─────────────────────────────────────────────────────────────────
 
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeSet;
+use std::hash::BuildHasher;
 use std::io::Cursor;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
 use futures_util::{SinkExt, StreamExt};
 use image::codecs::jpeg::JpegEncoder;
 use image::DynamicImage;
 use sha2::{Digest, Sha256};
+use tokio::sync::watch;
 use tokio::time::sleep;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 use tracing::{error, info, warn};
 
+use crate::audit::Audit;
+use crate::browser;
 use crate::config::StreamingConfig;
+use crate::envelope::{self, Sealer};
+use crate::exam::ExamMode;
+use crate::focus_mode::{self, FocusMode};
+use crate::models::Capabilities;
+use crate::monitor::Monitor;
+use crate::recording::Recorder;
+use crate::schedule::Schedule;
+use crate::softlock::SoftLock;
+use crate::store::Store;
 
-/// Capture the primary screen using xcap and return a DynamicImage.
-/// Re-enumerates monitors every call so we recover after sleep/wake.
-fn capture_screen() -> anyhow::Result<DynamicImage> {
-    // Retry up to 3 times with a short pause — the GPU driver may not be
-    // ready immediately after waking from sleep.
-    let mut last_err = anyhow::anyhow!("No monitors found");
-    for attempt in 0..3 {
-        match xcap::Monitor::all() {
-            Ok(monitors) => {
-                if let Some(monitor) = monitors
-                    .into_iter()
-                    .find(|m| m.is_primary())
-                    .or_else(|| xcap::Monitor::all().ok().and_then(|m| m.into_iter().next()))
-                {
-                    match monitor.capture_image() {
-                        Ok(raw) => return Ok(DynamicImage::ImageRgba8(raw)),
-                        Err(e) => {
-                            last_err = e.into();
+/// A connection that stayed up this long resets the backoff.
+const STABLE_CONNECTION: Duration = Duration::from_secs(60);
+
+/// Longest a handoff target gets to accept the connection and handshake.
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Prefix of magnifier messages; normal frames start with 0xFF 0xD8.
+const ZOOM_MAGIC: &[u8; 4] = b"ZOOM";
+
+type Ws = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Counters that carry over when the stream is handed off.
+struct StreamStats {
+    frames: u64,
+    since: DateTime<Utc>,
+}
+
+/// Where the metadata messages are sampled from.
+#[derive(Clone)]
+pub struct Annotations {
+    pub monitor: Arc<Mutex<Monitor>>,
+    pub exam: Arc<ExamMode>,
+    pub soft_lock: Arc<SoftLock>,
+    pub focus_mode: Arc<FocusMode>,
+}
+
+impl Annotations {
+    /// The current metadata, without `at` and `frame`. Blocking (asks
+    /// the OS for the foreground window).
+    fn sample(&self) -> serde_json::Value {
+        let front = focus_mode::foreground();
+        let (violations, visit) = {
+            let monitor = self.monitor.lock().unwrap_or_else(PoisonError::into_inner);
+            let violations: BTreeSet<&'static str> =
+                monitor.last_scan().iter().flat_map(|scan| scan.violations.iter().map(|v| v.kind.rule())).collect();
+            let visit = front.as_ref().and_then(|f| browser::browser_of_process(&f.name)).and_then(|b| monitor.latest_visit(b));
+            (violations, visit)
+        };
+        serde_json::json!({
+            "type": "meta",
+            "app": front.as_ref().map(|f| f.name.as_str()),
+            "title": front.as_ref().map(|f| f.title.as_str()).filter(|t| !t.is_empty()),
+            "url": visit.as_ref().map(|v| v.url.as_str()),
+            "url_visited_at": visit.as_ref().map(|v| v.visited_at),
+            "exam": self.exam.is_active(),
+            "soft_lock": self.soft_lock.is_active(),
+            "focus_app": self.focus_mode.app(),
+            "violations": violations,
+        })
+    }
+}
+
+/// Aborts the task when dropped, so a sampler ends with its connection.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Sample `annotations` every `every` on a background task; the
+/// receiver sees a new value only when something in it changed.
+fn start_sampler(annotations: Annotations, every: Duration) -> (AbortOnDrop, watch::Receiver<serde_json::Value>) {
+    let (tx, rx) = watch::channel(serde_json::Value::Null);
+    let task = tokio::spawn(async move {
+        loop {
+            let annotations = annotations.clone();
+            match tokio::task::spawn_blocking(move || annotations.sample()).await {
+                Ok(meta) => {
+                    tx.send_if_modified(|current| {
+                        let changed = *current != meta;
+                        if changed {
+                            *current = meta;
                         }
-                    }
+                        changed
+                    });
                 }
+                Err(e) => warn!("Stream metadata sampling panicked: {e}"),
             }
-            Err(e) => {
-                last_err = e.into();
+            sleep(every).await;
+        }
+    });
+    (AbortOnDrop(task), rx)
+}
+
+/// The metadata message as sent, stamped with the time and frame count.
+fn meta_message(meta: &serde_json::Value, frames: u64) -> Message {
+    let mut meta = meta.clone();
+    meta["at"] = serde_json::json!(Utc::now());
+    meta["frame"] = serde_json::json!(frames);
+    Message::Text(meta.to_string())
+}
+
+/// Why a frame couldn't be captured.
+#[derive(Debug)]
+enum CaptureFault {
+    /// Displays couldn't be listed, or there are none (driver reset,
+    /// asleep, unplugged).
+    NoDisplay(String),
+    /// The display was there but capturing it failed.
+    Capture(String),
+    /// The frame came back all black — a capture handle that outlived a
+    /// driver reset or sleep.
+    Blank,
+}
+
+impl CaptureFault {
+    fn describe(&self) -> String {
+        match self {
+            Self::NoDisplay(e) => format!("no display ({e})"),
+            Self::Capture(e) => format!("capture failed ({e})"),
+            Self::Blank => "black frame".into(),
+        }
+    }
+}
+
+/// Position, size and primary flag of every display, to notice when the
+/// layout changes (projector plugged in, resolution switched).
+type DisplayLayout = Vec<(u32, i32, i32, u32, u32, bool)>;
+
+/// The display being streamed, carried across frames. xcap monitor
+/// handles can't move between threads, so displays are listed every
+/// frame and this keeps which one is used, dropping it on any fault so
+/// the next frame picks afresh.
+#[derive(Default)]
+struct Capturer {
+    display: Option<u32>,
+    layout: DisplayLayout,
+    /// Black frames retried since the last normal one; a screen that is
+    /// still black after `BLANK_RETRIES` is taken as really black.
+    blank_retries: u32,
+    /// Why the display was last re-picked, for the viewer.
+    reset: Option<String>,
+}
+
+/// Re-picks of the display on black frames before believing them.
+const BLANK_RETRIES: u32 = 3;
+
+impl Capturer {
+    /// Capture the streamed display (the primary one by default). Blocking.
+    fn capture(&mut self) -> Result<DynamicImage, CaptureFault> {
+        let monitors = xcap::Monitor::all().map_err(|e| CaptureFault::NoDisplay(e.to_string()))?;
+        if monitors.is_empty() {
+            self.display = None;
+            return Err(CaptureFault::NoDisplay("none attached".into()));
+        }
+        let layout: DisplayLayout =
+            monitors.iter().map(|m| (m.id(), m.x(), m.y(), m.width(), m.height(), m.is_primary())).collect();
+        if layout != self.layout {
+            if !self.layout.is_empty() {
+                info!("🖥️  Display layout changed ({} display(s)) — re-picking the streamed one", layout.len());
+                self.reset = Some("display layout changed".into());
             }
+            self.layout = layout;
+            self.display = None;
         }
-        if attempt < 2 {
-            std::thread::sleep(std::time::Duration::from_millis(500));
+
+        let monitor = monitors
+            .iter()
+            .find(|m| Some(m.id()) == self.display)
+            .or_else(|| monitors.iter().find(|m| m.is_primary()))
+            .unwrap_or(&monitors[0]);
+        let raw = match monitor.capture_image() {
+            Ok(raw) => raw,
+            Err(e) => {
+                self.display = None;
+                return Err(CaptureFault::Capture(e.to_string()));
+            }
+        };
+        if is_blank(&raw) {
+            if self.blank_retries < BLANK_RETRIES {
+                self.blank_retries += 1;
+                self.display = None;
+                return Err(CaptureFault::Blank);
+            }
+        } else {
+            self.blank_retries = 0;
         }
+        self.display = Some(monitor.id());
+        Ok(DynamicImage::ImageRgba8(raw))
+    }
+
+    /// Why the display was re-picked since the last call, if it was.
+    fn take_reset(&mut self) -> Option<String> {
+        self.reset.take()
+    }
+}
+
+/// Whether every pixel of a sparse grid is (nearly) black.
+fn is_blank(img: &image::RgbaImage) -> bool {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return true;
     }
-    Err(last_err)
+    let step_x = (width / 32).max(1);
+    let step_y = (height / 32).max(1);
+    (0..height)
+        .step_by(step_y as usize)
+        .all(|y| (0..width).step_by(step_x as usize).all(|x| img.get_pixel(x, y).0[..3].iter().all(|&c| c <= 4)))
 }
 
 /// Compress a DynamicImage to JPEG bytes in memory, optionally resizing.
@@ -78,87 +308,500 @@ fn sha256_hash(data: &[u8]) -> String {
 }
 
 /// Spawn the screen-streaming loop as a background task.
-/// This function runs forever — it reconnects automatically on failure.
-pub async fn run_streaming_loop(cfg: StreamingConfig, hostname: String) {
+/// This function runs until the server rejects the agent's credentials —
+/// it reconnects with backoff on any other failure.
+/// Streams only while the lesson schedule allows it. With `discover`,
+/// each attempt goes to the teacher endpoints published in Redis, moving
+/// down the list with every failure in a row. New `settings` (a Redis
+/// override) close the stream and reconnect with them.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_streaming_loop(
+    mut settings: watch::Receiver<StreamingConfig>,
+    store: Store,
+    hostname: String,
+    schedule: Arc<Schedule>,
+    audit: Audit,
+    capabilities: Arc<Capabilities>,
+    annotations: Annotations,
+    sealer: Option<Arc<Sealer>>,
+    recorder: Option<Arc<Recorder>>,
+) {
+    let mut cfg = settings.borrow_and_update().clone();
     info!(
         "🎬 Screen streaming enabled — server: {}, interval: {}ms, quality: {}",
         cfg.server_url, cfg.interval_ms, cfg.quality
     );
 
+    // Where the stream goes; a handoff moves it until the settings change
+    let mut url = cfg.server_url.clone();
+    let mut handed_off = false;
+    let mut failures: u32 = 0;
+    let mut reload = false;
     loop {
+        if reload || settings.has_changed().unwrap_or(false) {
+            cfg = settings.borrow_and_update().clone();
+            info!(
+                "🎬 Streaming settings changed — server: {}, interval: {}ms, quality: {}",
+                cfg.server_url, cfg.interval_ms, cfg.quality
+            );
+            url = cfg.server_url.clone();
+            handed_off = false;
+            failures = 0;
+            reload = false;
+        }
+        if !schedule.active().streaming {
+            failures = 0;
+            reload = tokio::select! {
+                _ = sleep(Duration::from_secs(cfg.reconnect_secs)) => false,
+                Ok(()) = settings.changed() => true,
+            };
+            continue;
+        }
+        let handshake = serde_json::json!({
+            "role": "student",
+            "hostname": hostname,
+            "capabilities": capabilities.as_ref(),
+            "metadata_secs": cfg.metadata_secs,
+            "magnifier_max_secs": cfg.magnifier_max_secs,
+            "encryption": sealer.as_ref().map(|s| serde_json::json!({ "alg": envelope::ALGORITHM, "key_id": s.key_id() })),
+        });
+        if cfg.discover && !handed_off {
+            let endpoints = store.discover_teachers().await;
+            url = match endpoints.get(failures as usize % endpoints.len().max(1)) {
+                Some(endpoint) => endpoint.ws_url(),
+                None => cfg.server_url.clone(),
+            };
+        }
         info!("Connecting to teacher server for screen streaming...");
 
-        match connect_and_stream(&cfg, &hostname).await {
+        let started = Instant::now();
+        let target = url.clone();
+        let result = connect_and_stream(
+            &cfg,
+            &settings,
+            &mut url,
+            &handshake,
+            &schedule,
+            &audit,
+            &annotations,
+            sealer.as_deref(),
+            recorder.as_deref(),
+        )
+        .await;
+        handed_off |= url != target;
+        if started.elapsed() >= STABLE_CONNECTION {
+            failures = 0;
+        }
+        let delay = backoff(&cfg, failures);
+        failures = failures.saturating_add(1);
+        match result {
+            Ok(()) if settings.has_changed().unwrap_or(false) => continue,
             Ok(()) => {
-                warn!("Screen stream connection closed gracefully. Reconnecting in {}s...", cfg.reconnect_secs);
+                warn!("Screen stream connection closed gracefully. Reconnecting in {:.1}s...", delay.as_secs_f64());
+            }
+            Err(e) if auth_rejected(&e) => {
+                audit.record("stream_auth_failed", &url, Some(e.to_string())).await;
+                if handed_off {
+                    warn!("Screen stream rejected by handoff target {url}: {e}. Back to {}", cfg.server_url);
+                    url = cfg.server_url.clone();
+                    handed_off = false;
+                    continue;
+                }
+                error!("Screen stream rejected by {url}: {e}. Not retrying until the agent restarts");
+                return;
             }
             Err(e) => {
-                error!("Screen stream error: {e}. Reconnecting in {}s...", cfg.reconnect_secs);
+                error!("Screen stream error: {e}. Reconnecting in {:.1}s...", delay.as_secs_f64());
             }
         }
 
-        sleep(Duration::from_secs(cfg.reconnect_secs)).await;
+        reload = tokio::select! {
+            _ = sleep(delay) => false,
+            Ok(()) = settings.changed() => true,
+        };
     }
 }
 
-/// Establish a WebSocket connection, send handshake, then stream frames.
-async fn connect_and_stream(cfg: &StreamingConfig, hostname: &str) -> anyhow::Result<()> {
-    let (ws_stream, _response) = connect_async(&cfg.server_url).await?;
-    info!("✅ WebSocket connected to {}", cfg.server_url);
+/// Wait before reconnect attempt `failures` (0-based): `reconnect_secs`
+/// doubled per failure up to `reconnect_max_secs`, then a random point
+/// in its upper half.
+fn backoff(cfg: &StreamingConfig, failures: u32) -> Duration {
+    let base = cfg.reconnect_secs.max(1) as f64;
+    let cap = cfg.reconnect_max_secs.max(cfg.reconnect_secs.max(1)) as f64;
+    let delay = (base * 2f64.powi(failures.min(16) as i32)).min(cap);
+    // RandomState is seeded randomly per instance
+    let unit = RandomState::new().hash_one(failures) as f64 / u64::MAX as f64;
+    Duration::from_secs_f64(delay * (0.5 + unit / 2.0))
+}
+
+/// Whether the server refused the handshake as unauthorised (401/403),
+/// which retrying won't fix.
+fn auth_rejected(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<tungstenite::Error>(),
+        Some(tungstenite::Error::Http(resp)) if matches!(resp.status().as_u16(), 401 | 403)
+    )
+}
 
-    let (mut write, _read) = ws_stream.split();
+/// Establish a WebSocket connection and stream frames over it, auditing
+/// when the teacher server starts and stops receiving this screen.
+/// `url` follows handoffs.
+#[allow(clippy::too_many_arguments)]
+async fn connect_and_stream(
+    cfg: &StreamingConfig,
+    settings: &watch::Receiver<StreamingConfig>,
+    url: &mut String,
+    handshake: &serde_json::Value,
+    schedule: &Schedule,
+    audit: &Audit,
+    annotations: &Annotations,
+    sealer: Option<&Sealer>,
+    recorder: Option<&Recorder>,
+) -> anyhow::Result<()> {
+    let (mut ws_stream, _response) = connect_async(url.as_str()).await?;
+    info!("✅ WebSocket connected to {url}");
+    audit.record("stream_started", url, None).await;
 
     // ── Step 1: JSON handshake ──────────────────────────────
-    let handshake = serde_json::json!({
-        "role": "student",
-        "hostname": hostname,
-    });
-    write
-        .send(Message::Text(handshake.to_string().into()))
-        .await?;
+    ws_stream.send(Message::Text(handshake.to_string())).await?;
     info!("Handshake sent: {handshake}");
 
+    let started = Instant::now();
+    let mut stats = StreamStats { frames: 0, since: Utc::now() };
+    let result = stream(ws_stream, cfg, settings, handshake, schedule, audit, annotations, sealer, recorder, url, &mut stats).await;
+    let reason = match &result {
+        Ok(()) => "closed".to_string(),
+        Err(e) => e.to_string(),
+    };
+    let detail = format!("after {}s, {} frame(s): {reason}", started.elapsed().as_secs(), stats.frames);
+    audit.record("stream_stopped", url, Some(detail)).await;
+    result
+}
+
+/// Open `url` and send it the handshake, marked as a handoff of the
+/// stream so far.
+fn start_handoff(url: String, handshake: &serde_json::Value, from: &str, stats: &StreamStats) -> JoinHandle<anyhow::Result<Ws>> {
+    let mut handshake = handshake.clone();
+    handshake["handoff"] = serde_json::json!({
+        "from": from,
+        "frames": stats.frames,
+        "since": stats.since,
+    });
+    tokio::spawn(async move {
+        let connect = async {
+            let (mut ws, _response) = connect_async(url.as_str()).await?;
+            ws.send(Message::Text(handshake.to_string())).await?;
+            anyhow::Ok(ws)
+        };
+        tokio::time::timeout(HANDOFF_TIMEOUT, connect).await.map_err(|_| anyhow::anyhow!("timed out"))?
+    })
+}
+
+/// The target of a `{"type":"handoff","url":…}` control message.
+fn handoff_target(text: &str) -> Option<Result<String, String>> {
+    let msg: serde_json::Value = serde_json::from_str(text).ok()?;
+    if msg.get("type").and_then(|t| t.as_str()) != Some("handoff") {
+        return None;
+    }
+    let url = msg.get("url").and_then(|u| u.as_str()).unwrap_or_default();
+    Some(match url::Url::parse(url) {
+        Ok(u) if matches!(u.scheme(), "ws" | "wss") => Ok(url.to_string()),
+        _ => Err(format!("not a ws:// or wss:// URL: {url:?}")),
+    })
+}
+
+/// A `{"type":"magnify",…}` request: a region as fractions of the
+/// screen and how long to send it for.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+struct Magnify {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    secs: u64,
+}
+
+/// A magnifier request being served.
+struct Zoom {
+    region: Magnify,
+    until: Instant,
+    last_hash: String,
+}
+
+/// The request in a magnify control message, validated.
+fn magnify_request(text: &str) -> Option<Result<Magnify, String>> {
+    let msg: serde_json::Value = serde_json::from_str(text).ok()?;
+    if msg.get("type").and_then(|t| t.as_str()) != Some("magnify") {
+        return None;
+    }
+    Some(match serde_json::from_value::<Magnify>(msg) {
+        Ok(m) if m.secs == 0 => Ok(m),
+        Ok(m) if (0.0..1.0).contains(&m.x) && (0.0..1.0).contains(&m.y) && m.w > 0.0 && m.h > 0.0 => {
+            Ok(Magnify { w: m.w.min(1.0 - m.x), h: m.h.min(1.0 - m.y), ..m })
+        }
+        Ok(m) => Err(format!("region {}x{} at ({}, {}) is outside the screen", m.w, m.h, m.x, m.y)),
+        Err(e) => Err(e.to_string()),
+    })
+}
+
+/// The magnifier message for `region` of `img`: `ZOOM`, header length,
+/// JSON header and the crop as a full-resolution JPEG. None when the
+/// crop is empty or doesn't encode.
+fn zoom_message(img: &DynamicImage, region: &Magnify, quality: u8, frame: u64) -> Option<(Vec<u8>, String)> {
+    let (width, height) = (img.width(), img.height());
+    let x = ((region.x * width as f64) as u32).min(width.saturating_sub(1));
+    let y = ((region.y * height as f64) as u32).min(height.saturating_sub(1));
+    let w = ((region.w * width as f64).round() as u32).clamp(1, width - x);
+    let h = ((region.h * height as f64).round() as u32).clamp(1, height - y);
+    let jpeg = compress_to_jpeg(&img.crop_imm(x, y, w, h), quality, u32::MAX)
+        .inspect_err(|e| warn!("Magnifier JPEG compression failed: {e}"))
+        .ok()?;
+    let hash = sha256_hash(&jpeg);
+    let header = serde_json::json!({
+        "frame": frame,
+        "x": x,
+        "y": y,
+        "width": w,
+        "height": h,
+        "screen_width": width,
+        "screen_height": height,
+    })
+    .to_string();
+    let mut msg = Vec::with_capacity(8 + header.len() + jpeg.len());
+    msg.extend_from_slice(ZOOM_MAGIC);
+    msg.extend_from_slice(&(header.len() as u32).to_be_bytes());
+    msg.extend_from_slice(header.as_bytes());
+    msg.extend_from_slice(&jpeg);
+    Some((msg, hash))
+}
+
+/// Stream JPEG frames and metadata until the connection drops, streaming
+/// hours end or the settings change, moving to another server when told to.
+#[allow(clippy::too_many_arguments)]
+async fn stream(
+    ws_stream: Ws,
+    cfg: &StreamingConfig,
+    settings: &watch::Receiver<StreamingConfig>,
+    handshake: &serde_json::Value,
+    schedule: &Schedule,
+    audit: &Audit,
+    annotations: &Annotations,
+    sealer: Option<&Sealer>,
+    recorder: Option<&Recorder>,
+    url: &mut String,
+    stats: &mut StreamStats,
+) -> anyhow::Result<()> {
+    let (mut write, mut read) = ws_stream.split();
+
     // ── Step 2: Stream JPEG frames ──────────────────────────
     let mut last_hash = String::new();
     let frame_interval = Duration::from_millis(cfg.interval_ms);
     let quality = cfg.quality;
     let max_dim = cfg.max_dimension;
     let mut consecutive_capture_fails: u32 = 0;
+    let capturer = Arc::new(Mutex::new(Capturer::default()));
+    // When capture started failing, while it is
+    let mut capture_lost: Option<Instant> = None;
+    let ping_interval = Duration::from_secs(cfg.ping_secs.max(1));
+    let pong_timeout = Duration::from_secs(cfg.pong_timeout_secs.max(cfg.ping_secs.max(1) + 1));
+    let mut last_ping = Instant::now();
+    // Pongs and anything else from the server prove the connection is alive
+    let mut last_heard = Instant::now();
+    // Connection being opened to the handoff target
+    let mut handoff: Option<(String, JoinHandle<anyhow::Result<Ws>>)> = None;
+    // Magnifier request being served
+    let mut zoom: Option<Zoom> = None;
+    // Metadata sampler, stopped when the connection ends
+    let mut metadata = (cfg.metadata_secs > 0)
+        .then(|| start_sampler(annotations.clone(), Duration::from_secs(cfg.metadata_secs)));
 
     loop {
-        sleep(frame_interval).await;
+        tokio::select! {
+            _ = sleep(frame_interval) => {}
+            changed = async { metadata.as_mut().expect("guarded").1.changed().await }, if metadata.is_some() => {
+                if changed.is_err() {
+                    metadata = None;
+                    continue;
+                }
+                let msg = meta_message(&metadata.as_mut().expect("guarded").1.borrow_and_update(), stats.frames);
+                match tokio::time::timeout(Duration::from_secs(10), write.send(msg)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => return Err(anyhow::anyhow!("metadata send failed: {e}")),
+                    Err(_) => return Err(anyhow::anyhow!("metadata send timeout")),
+                }
+                continue;
+            }
+            msg = read.next() => {
+                match msg {
+                    Some(Ok(Message::Close(_))) | None => {
+                        info!("Teacher server closed the screen stream");
+                        return Ok(());
+                    }
+                    Some(Ok(Message::Text(text))) => {
+                        last_heard = Instant::now();
+                        match handoff_target(&text) {
+                            Some(Ok(target)) if handoff.is_none() => {
+                                info!("Screen stream handoff requested: {url} → {target}");
+                                handoff = Some((target.clone(), start_handoff(target, handshake, url, stats)));
+                            }
+                            Some(Ok(_)) => warn!("Screen stream handoff already in progress; request ignored"),
+                            Some(Err(e)) => warn!("Ignoring screen stream handoff: {e}"),
+                            None => {}
+                        }
+                        let reply = match magnify_request(&text) {
+                            Some(Ok(m)) if m.secs == 0 => zoom.take().map(|_| serde_json::json!({ "type": "magnify_ended" })),
+                            Some(Ok(_)) if cfg.magnifier_max_secs == 0 => {
+                                Some(serde_json::json!({ "type": "magnify_failed", "error": "magnifier disabled" }))
+                            }
+                            Some(Ok(m)) => {
+                                let secs = m.secs.min(cfg.magnifier_max_secs);
+                                let detail = format!("region {}x{} at ({}, {}) for {secs}s", m.w, m.h, m.x, m.y);
+                                info!("🔍 Magnifier on {detail}");
+                                audit.record("stream_magnify", url, Some(detail)).await;
+                                zoom = Some(Zoom { region: m, until: Instant::now() + Duration::from_secs(secs), last_hash: String::new() });
+                                None
+                            }
+                            Some(Err(e)) => {
+                                warn!("Ignoring magnifier request: {e}");
+                                Some(serde_json::json!({ "type": "magnify_failed", "error": e }))
+                            }
+                            None => None,
+                        };
+                        if let Some(reply) = reply {
+                            let _ = tokio::time::timeout(Duration::from_secs(2), write.send(Message::Text(reply.to_string()))).await;
+                        }
+                    }
+                    Some(Ok(_)) => last_heard = Instant::now(),
+                    Some(Err(e)) => return Err(e.into()),
+                }
+                continue;
+            }
+            joined = async { (&mut handoff.as_mut().expect("guarded").1).await }, if handoff.is_some() => {
+                let (target, _) = handoff.take().expect("guarded");
+                match joined.map_err(anyhow::Error::from).and_then(|r| r) {
+                    Ok(ws) => {
+                        let _ = tokio::time::timeout(Duration::from_secs(2), write.send(Message::Close(None))).await;
+                        (write, read) = ws.split();
+                        info!("✅ Screen stream handed off to {target} after {} frame(s)", stats.frames);
+                        audit.record("stream_handoff", &target, Some(format!("from {url}, {} frame(s) so far", stats.frames))).await;
+                        *url = target;
+                        // The new viewer needs a full frame and the metadata right
+                        // away; it didn't ask for the magnifier
+                        last_hash.clear();
+                        zoom = None;
+                        if let Some((_, rx)) = &metadata {
+                            let meta = rx.borrow().clone();
+                            if !meta.is_null() {
+                                let _ = tokio::time::timeout(Duration::from_secs(10), write.send(meta_message(&meta, stats.frames))).await;
+                            }
+                        }
+                        last_heard = Instant::now();
+                        last_ping = Instant::now();
+                    }
+                    Err(e) => {
+                        warn!("Screen stream handoff to {target} failed: {e}; staying on {url}");
+                        let reply = serde_json::json!({ "type": "handoff_failed", "url": target, "error": e.to_string() });
+                        let _ = tokio::time::timeout(Duration::from_secs(2), write.send(Message::Text(reply.to_string()))).await;
+                    }
+                }
+                continue;
+            }
+        }
+
+        if last_heard.elapsed() >= pong_timeout {
+            error!("No pong from the teacher server for {}s — connection dead", last_heard.elapsed().as_secs());
+            return Err(anyhow::anyhow!("pong timeout")); // Triggers reconnection
+        }
+        if last_ping.elapsed() >= ping_interval {
+            last_ping = Instant::now();
+            match tokio::time::timeout(Duration::from_secs(10), write.send(Message::Ping(Vec::new()))).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => return Err(anyhow::anyhow!("ping failed: {e}")),
+                Err(_) => return Err(anyhow::anyhow!("ping timeout")),
+            }
+        }
+
+        if !schedule.active().streaming {
+            info!("Outside streaming hours — closing the screen stream");
+            let _ = write.send(Message::Close(None)).await;
+            return Ok(());
+        }
+        if settings.has_changed().unwrap_or(false) {
+            info!("Streaming settings changed — reconnecting the screen stream");
+            let _ = write.send(Message::Close(None)).await;
+            return Ok(());
+        }
 
         // Capture screen on a blocking thread (with timeout for sleep/wake)
+        let task_capturer = Arc::clone(&capturer);
         let capture_result = tokio::time::timeout(
             Duration::from_secs(10),
-            tokio::task::spawn_blocking(move || capture_screen()),
+            tokio::task::spawn_blocking(move || task_capturer.lock().unwrap_or_else(PoisonError::into_inner).capture()),
         )
         .await;
 
-        let img = match capture_result {
-            Ok(Ok(Ok(img))) => {
+        let fault = match capture_result {
+            Ok(Ok(Ok(img))) => Ok(img),
+            Ok(Ok(Err(fault))) => Err(fault.describe()),
+            Ok(Err(e)) => Err(format!("capture task panicked ({e})")),
+            Err(_) => Err("capture timed out (display waking up?)".to_string()),
+        };
+        let img = match fault {
+            Ok(img) => {
+                if let Some(since) = capture_lost.take() {
+                    info!("✅ Screen capture recovered after {}s", since.elapsed().as_secs());
+                    let restored = serde_json::json!({ "type": "capture_restored", "after_secs": since.elapsed().as_secs() });
+                    let _ = tokio::time::timeout(Duration::from_secs(2), write.send(Message::Text(restored.to_string()))).await;
+                }
                 consecutive_capture_fails = 0;
                 img
             }
-            Ok(Ok(Err(e))) => {
+            Err(reason) => {
                 consecutive_capture_fails += 1;
-                warn!("Screen capture failed ({consecutive_capture_fails}x): {e}");
-                if consecutive_capture_fails > 5 {
-                    sleep(Duration::from_secs(5)).await;
+                warn!("Screen capture failed ({consecutive_capture_fails}x): {reason} — re-enumerating displays");
+                if capture_lost.is_none() {
+                    capture_lost = Some(Instant::now());
+                    let lost = serde_json::json!({ "type": "capture_lost", "reason": reason });
+                    let _ = tokio::time::timeout(Duration::from_secs(2), write.send(Message::Text(lost.to_string()))).await;
                 }
-                continue;
-            }
-            Ok(Err(e)) => {
-                warn!("Screen capture task panicked: {e}");
-                continue;
-            }
-            Err(_) => {
-                consecutive_capture_fails += 1;
-                warn!("Screen capture timed out (display waking up?)");
-                sleep(Duration::from_secs(3)).await;
+                // 0.5 s, 1 s, 2 s … up to 5 s while the driver comes back
+                let backoff = Duration::from_millis(500 << consecutive_capture_fails.min(5).saturating_sub(1));
+                sleep(backoff.min(Duration::from_secs(5))).await;
                 continue;
             }
         };
+        let reset = capturer.lock().unwrap_or_else(PoisonError::into_inner).take_reset();
+        if let Some(reason) = reset {
+            // Different display or size: the viewer needs a full frame
+            last_hash.clear();
+            let reset = serde_json::json!({ "type": "display_changed", "reason": reason, "width": img.width(), "height": img.height() });
+            let _ = tokio::time::timeout(Duration::from_secs(2), write.send(Message::Text(reset.to_string()))).await;
+        }
+
+        // Magnified region, at full resolution, before the frame is scaled down
+        if zoom.as_ref().is_some_and(|z| Instant::now() >= z.until) {
+            zoom = None;
+            info!("🔍 Magnifier ended");
+            let ended = serde_json::json!({ "type": "magnify_ended" });
+            let _ = tokio::time::timeout(Duration::from_secs(2), write.send(Message::Text(ended.to_string()))).await;
+        }
+        if let Some(z) = zoom.as_mut() {
+            match zoom_message(&img, &z.region, cfg.magnifier_quality, stats.frames) {
+                Some((msg, hash)) if hash != z.last_hash => {
+                    z.last_hash = hash;
+                    let msg = match sealer {
+                        Some(sealer) => sealer.seal(&msg),
+                        None => msg,
+                    };
+                    match tokio::time::timeout(Duration::from_secs(10), write.send(Message::Binary(msg))).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => return Err(anyhow::anyhow!("magnifier send failed: {e}")),
+                        Err(_) => return Err(anyhow::anyhow!("magnifier send timeout")),
+                    }
+                }
+                _ => {}
+            }
+        }
 
         // Compress to JPEG
         let jpeg_bytes = match compress_to_jpeg(&img, quality, max_dim) {
@@ -176,16 +819,25 @@ async fn connect_and_stream(cfg: &StreamingConfig, hostname: &str) -> anyhow::Re
         }
         last_hash = hash;
 
+        if let Some(recorder) = recorder {
+            recorder.offer(stats.since, stats.frames, &jpeg_bytes);
+        }
+
         // Send binary frame (timeout so we don't hang on a dead socket)
+        let jpeg_bytes = match sealer {
+            Some(sealer) => sealer.seal(&jpeg_bytes),
+            None => jpeg_bytes,
+        };
         let size_kb = jpeg_bytes.len() as f64 / 1024.0;
         let send_result = tokio::time::timeout(
             Duration::from_secs(10),
-            write.send(Message::Binary(jpeg_bytes.into())),
+            write.send(Message::Binary(jpeg_bytes)),
         )
         .await;
 
         match send_result {
             Ok(Ok(())) => {
+                stats.frames += 1;
                 info!("📸 Frame sent: {size_kb:.1} KB");
             }
             Ok(Err(e)) => {